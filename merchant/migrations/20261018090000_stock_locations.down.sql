@@ -0,0 +1,5 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS stock_level_variation_index;
+DROP INDEX IF EXISTS stock_location_account_index;
+DROP TABLE IF EXISTS stock_levels;
+DROP TABLE IF EXISTS stock_locations;
@@ -0,0 +1,21 @@
+CREATE TABLE IF NOT EXISTS stock_locations
+(
+    id INT PRIMARY KEY NOT NULL,
+    account VARCHAR(30) NOT NULL,
+    version TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    location_data JSONB NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS stock_levels
+(
+    account VARCHAR(30) NOT NULL,
+    location_id INT NOT NULL,
+    variation_id INT NOT NULL,
+    units INT DEFAULT 0 NOT NULL,
+    version TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    PRIMARY KEY (account, location_id, variation_id)
+);
+
+CREATE INDEX IF NOT EXISTS stock_location_account_index ON stock_locations (account);
+CREATE INDEX IF NOT EXISTS stock_level_variation_index ON stock_levels (account, variation_id);
@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS stock_alert_sequence_index;
+DROP INDEX IF EXISTS stock_alert_account_index;
+DROP TABLE IF EXISTS stock_alerts;
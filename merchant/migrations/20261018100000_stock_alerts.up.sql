@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS stock_alerts
+(
+    id INT PRIMARY KEY NOT NULL,
+    account VARCHAR(30) NOT NULL,
+    variation_id INT NOT NULL,
+    available_units INT NOT NULL,
+    reorder_threshold INT NOT NULL,
+    -- the change of the log that ran the variation low
+    sequence INTEGER NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS stock_alert_account_index ON stock_alerts (account, created_at);
+CREATE INDEX IF NOT EXISTS stock_alert_sequence_index ON stock_alerts (account, sequence);
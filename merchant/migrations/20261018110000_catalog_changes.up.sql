@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS catalog_changes
+(
+    account VARCHAR(30) NOT NULL,
+    sequence INTEGER NOT NULL,
+    object_id INT NOT NULL,
+    operation VARCHAR(20) NOT NULL,
+    document JSONB NOT NULL,
+    cmd JSONB DEFAULT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    PRIMARY KEY (account, sequence)
+);
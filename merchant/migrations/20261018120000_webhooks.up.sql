@@ -0,0 +1,39 @@
+CREATE TABLE IF NOT EXISTS webhook_subscriptions
+(
+    id INT PRIMARY KEY NOT NULL,
+    account VARCHAR(30) NOT NULL,
+    url TEXT NOT NULL,
+    events JSONB NOT NULL,
+    secret TEXT NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    version TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS webhook_subscriptions_account ON webhook_subscriptions (account);
+
+CREATE TABLE IF NOT EXISTS webhook_deliveries
+(
+    id INT PRIMARY KEY NOT NULL,
+    account VARCHAR(30) NOT NULL,
+    subscription_id INT NOT NULL,
+    event VARCHAR(30) NOT NULL,
+    payload JSONB NOT NULL,
+    status VARCHAR(20) NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    response_status INTEGER DEFAULT NULL,
+    error TEXT DEFAULT NULL,
+    next_attempt_at TIMESTAMP DEFAULT NULL,
+    version TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS webhook_deliveries_due ON webhook_deliveries (status, next_attempt_at);
+CREATE INDEX IF NOT EXISTS webhook_deliveries_subscription ON webhook_deliveries (account, subscription_id);
+
+-- the last change of the log queued for the webhooks of each account, the worker goes on from it
+CREATE TABLE IF NOT EXISTS webhook_cursors
+(
+    account VARCHAR(30) PRIMARY KEY NOT NULL,
+    sequence INTEGER NOT NULL
+);
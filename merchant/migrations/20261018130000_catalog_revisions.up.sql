@@ -0,0 +1,26 @@
+CREATE TABLE IF NOT EXISTS catalog_revisions
+(
+    account VARCHAR(30) NOT NULL,
+    object_id INT NOT NULL,
+    revision INTEGER NOT NULL,
+    document JSONB NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    PRIMARY KEY (object_id, revision)
+);
+
+-- the objects written before the history existed start with their current state
+INSERT INTO catalog_revisions (account, object_id, revision, document, created_at)
+SELECT
+    account,
+    id,
+    1,
+    json_object(
+        'id', id,
+        'account', account,
+        'version', strftime('%Y-%m-%dT%H:%M:%S', version),
+        'created_at', strftime('%Y-%m-%dT%H:%M:%S', created_at),
+        'type', type_entry,
+        'data', json(COALESCE(item_data, item_variation_data, item_modification_data, item_delivery_data, item_control_data))
+    ),
+    version
+FROM catalogs;
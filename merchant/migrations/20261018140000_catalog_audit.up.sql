@@ -0,0 +1,19 @@
+CREATE TABLE IF NOT EXISTS catalog_audit
+(
+    id INTEGER PRIMARY KEY,
+    account VARCHAR(30) NOT NULL,
+    object_id INT NOT NULL,
+    actor VARCHAR(100) NOT NULL,
+    -- who the caller says it is, nothing checks it
+    claimed_actor VARCHAR(100) DEFAULT NULL,
+    address VARCHAR(100) DEFAULT NULL,
+    user_agent TEXT DEFAULT NULL,
+    operation VARCHAR(20) NOT NULL,
+    before JSONB DEFAULT NULL,
+    after JSONB DEFAULT NULL,
+    cmd JSONB DEFAULT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS catalog_audit_account ON catalog_audit (account, created_at);
+CREATE INDEX IF NOT EXISTS catalog_audit_actor ON catalog_audit (account, actor);
@@ -0,0 +1,126 @@
+-- Add down migration script here
+ALTER TABLE catalogs ADD COLUMN item_data JSONB DEFAULT NULL;
+ALTER TABLE catalogs ADD COLUMN item_variation_data JSONB DEFAULT NULL;
+ALTER TABLE catalogs ADD COLUMN item_modification_data JSONB DEFAULT NULL;
+ALTER TABLE catalogs ADD COLUMN item_delivery_data JSONB DEFAULT NULL;
+ALTER TABLE catalogs ADD COLUMN item_control_data JSONB DEFAULT NULL;
+
+UPDATE catalogs SET item_data = (
+    SELECT json_patch(
+        json_object(
+            'category', category,
+            'tags', json(tags),
+            'name', name,
+            'images', json(images),
+            'description', description,
+            'enabled', json(CASE WHEN enabled THEN 'true' ELSE 'false' END)
+        ),
+        CASE WHEN warranty_time IS NULL THEN '{}' ELSE json_object(
+            'warranty_time_type', json_extract(warranty_time, '$.type'),
+            'warranty_time_seconds', json_extract(warranty_time, '$.seconds')
+        ) END
+    )
+    FROM catalog_items WHERE catalog_items.id = catalogs.id
+) WHERE type_entry = 'Item';
+
+UPDATE catalogs SET item_variation_data = (
+    SELECT json_patch(
+        json_object(
+            'item_id', item_id,
+            'name', name,
+            'sku', sku,
+            'images', json(images),
+            'upc', upc,
+            'enabled', json(CASE WHEN enabled THEN 'true' ELSE 'false' END),
+            'measurement_units', measurement_units,
+            'available_units', available_units,
+            'reorder_threshold', reorder_threshold,
+            'price_type', price_type,
+            'price_amount', price_amount,
+            'price_asset_name', price_asset_name,
+            'price_asset_scale', price_asset_scale,
+            'extra_attributes', json(extra_attributes)
+        ),
+        CASE WHEN processing_time IS NULL THEN '{}' ELSE json_object(
+            'processing_time_type', json_extract(processing_time, '$.type'),
+            'processing_time_seconds', json_extract(processing_time, '$.seconds')
+        ) END
+    )
+    FROM catalog_variations WHERE catalog_variations.id = catalogs.id
+) WHERE type_entry = 'Variation';
+
+UPDATE catalogs SET item_modification_data = (
+    SELECT json_patch(
+        json_patch(
+            json_object(
+                'item_id', item_id,
+                'name', name,
+                'images', json(images),
+                'enabled', json(CASE WHEN enabled THEN 'true' ELSE 'false' END),
+                'price_type', price_type,
+                'price_amount', price_amount,
+                'price_asset_name', price_asset_name,
+                'price_asset_scale', price_asset_scale
+            ),
+            CASE WHEN processing_time IS NULL THEN '{}' ELSE json_object(
+                'processing_time_type', json_extract(processing_time, '$.type'),
+                'processing_time_seconds', json_extract(processing_time, '$.seconds')
+            ) END
+        ),
+        CASE WHEN warranty_time IS NULL THEN '{}' ELSE json_object(
+            'warranty_time_type', json_extract(warranty_time, '$.type'),
+            'warranty_time_seconds', json_extract(warranty_time, '$.seconds')
+        ) END
+    )
+    FROM catalog_modifications WHERE catalog_modifications.id = catalogs.id
+) WHERE type_entry = 'Modification';
+
+UPDATE catalogs SET item_delivery_data = (
+    SELECT json_object(
+        'item_id', item_id,
+        'delivery_type', delivery_type,
+        'delivery_width_mm', width_mm,
+        'delivery_length_mm', length_mm,
+        'delivery_height_mm', height_mm,
+        'delivery_weight_grams', weight_grams
+    )
+    FROM catalog_deliveries WHERE catalog_deliveries.id = catalogs.id
+) WHERE type_entry = 'Delivery';
+
+UPDATE catalogs SET item_control_data = (
+    SELECT json_object(
+        'item_id', item_id,
+        'control_type', control_type,
+        'control_data', json(control_data)
+    )
+    FROM catalog_controls WHERE catalog_controls.id = catalogs.id
+) WHERE type_entry = 'Control';
+
+INSERT INTO catalogs (
+    id, account, type_entry, version, created_at, item_variation_data,
+    item_modification_data, item_delivery_data, item_control_data
+)
+SELECT
+    id,
+    account,
+    type_entry,
+    version,
+    created_at,
+    CASE WHEN type_entry = 'Variation' THEN data END,
+    CASE WHEN type_entry = 'Modification' THEN data END,
+    CASE WHEN type_entry = 'Delivery' THEN data END,
+    CASE WHEN type_entry = 'Control' THEN data END
+FROM catalog_orphans;
+
+DROP TABLE IF EXISTS catalog_orphans;
+DROP TABLE IF EXISTS catalog_controls;
+DROP TABLE IF EXISTS catalog_deliveries;
+DROP TABLE IF EXISTS catalog_modifications;
+DROP TABLE IF EXISTS catalog_variations;
+DROP TABLE IF EXISTS catalog_items;
+
+CREATE INDEX IF NOT EXISTS item_index ON catalogs (item_data);
+CREATE INDEX IF NOT EXISTS item_variation_index ON catalogs (item_variation_data);
+CREATE INDEX IF NOT EXISTS item_modification_index ON catalogs (item_modification_data);
+CREATE INDEX IF NOT EXISTS item_delivery_index ON catalogs (item_delivery_data);
+CREATE INDEX IF NOT EXISTS item_control_index ON catalogs (item_control_data);
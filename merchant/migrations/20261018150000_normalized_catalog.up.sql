@@ -0,0 +1,207 @@
+-- every object keeps its row in catalogs, the fields of its type live in their own table
+CREATE TABLE IF NOT EXISTS catalog_items
+(
+    id INT PRIMARY KEY NOT NULL REFERENCES catalogs (id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    description TEXT NOT NULL,
+    category VARCHAR(30) NOT NULL,
+    tags JSONB NOT NULL,
+    images JSONB NOT NULL,
+    enabled BOOLEAN NOT NULL,
+    warranty_time JSONB DEFAULT NULL
+);
+
+CREATE TABLE IF NOT EXISTS catalog_variations
+(
+    id INT PRIMARY KEY NOT NULL REFERENCES catalogs (id) ON DELETE CASCADE,
+    item_id INT NOT NULL REFERENCES catalog_items (id),
+    name TEXT NOT NULL,
+    sku TEXT NOT NULL,
+    upc TEXT DEFAULT NULL,
+    images JSONB NOT NULL,
+    enabled BOOLEAN NOT NULL,
+    measurement_units VARCHAR(20) NOT NULL,
+    available_units INT NOT NULL,
+    reorder_threshold INT DEFAULT NULL,
+    processing_time JSONB DEFAULT NULL,
+    price_type VARCHAR(20) NOT NULL,
+    price_amount REAL NOT NULL,
+    price_asset_name TEXT NOT NULL,
+    price_asset_scale INT NOT NULL,
+    extra_attributes JSONB DEFAULT NULL
+);
+
+CREATE TABLE IF NOT EXISTS catalog_modifications
+(
+    id INT PRIMARY KEY NOT NULL REFERENCES catalogs (id) ON DELETE CASCADE,
+    item_id INT NOT NULL REFERENCES catalog_items (id),
+    name TEXT NOT NULL,
+    images JSONB NOT NULL,
+    enabled BOOLEAN NOT NULL,
+    processing_time JSONB DEFAULT NULL,
+    warranty_time JSONB DEFAULT NULL,
+    price_type VARCHAR(20) NOT NULL,
+    price_amount REAL NOT NULL,
+    price_asset_name TEXT NOT NULL,
+    price_asset_scale INT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS catalog_deliveries
+(
+    id INT PRIMARY KEY NOT NULL REFERENCES catalogs (id) ON DELETE CASCADE,
+    item_id INT NOT NULL REFERENCES catalog_items (id),
+    delivery_type VARCHAR(20) NOT NULL,
+    width_mm INT NOT NULL,
+    length_mm INT NOT NULL,
+    height_mm INT NOT NULL,
+    weight_grams INT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS catalog_controls
+(
+    id INT PRIMARY KEY NOT NULL REFERENCES catalogs (id) ON DELETE CASCADE,
+    item_id INT NOT NULL REFERENCES catalog_items (id),
+    control_type VARCHAR(20) NOT NULL,
+    control_data JSONB NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS catalog_items_name ON catalog_items (name);
+CREATE INDEX IF NOT EXISTS catalog_variations_item ON catalog_variations (item_id);
+CREATE INDEX IF NOT EXISTS catalog_variations_name ON catalog_variations (name);
+CREATE INDEX IF NOT EXISTS catalog_variations_sku ON catalog_variations (sku);
+CREATE INDEX IF NOT EXISTS catalog_variations_price ON catalog_variations (price_amount);
+CREATE INDEX IF NOT EXISTS catalog_modifications_item ON catalog_modifications (item_id);
+CREATE INDEX IF NOT EXISTS catalog_deliveries_item ON catalog_deliveries (item_id);
+CREATE INDEX IF NOT EXISTS catalog_controls_item ON catalog_controls (item_id);
+
+-- objects of items deleted before references were checked can't point to them anymore,
+-- they are moved aside as they were so they can be restored or dropped by hand
+CREATE TABLE IF NOT EXISTS catalog_orphans
+(
+    id INT PRIMARY KEY NOT NULL,
+    account VARCHAR(30) NOT NULL,
+    type_entry VARCHAR(20) NOT NULL,
+    version TIMESTAMP NOT NULL,
+    created_at TIMESTAMP DEFAULT NULL,
+    data JSONB NOT NULL,
+    quarantined_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+INSERT INTO catalog_orphans (id, account, type_entry, version, created_at, data)
+SELECT
+    id,
+    account,
+    type_entry,
+    version,
+    created_at,
+    COALESCE(item_variation_data, item_modification_data, item_delivery_data, item_control_data)
+FROM catalogs AS child WHERE type_entry <> 'Item' AND NOT EXISTS (
+    SELECT 1 FROM catalogs AS item WHERE item.type_entry = 'Item' AND item.id = json_extract(
+        COALESCE(
+            child.item_variation_data,
+            child.item_modification_data,
+            child.item_delivery_data,
+            child.item_control_data
+        ),
+        '$.item_id'
+    )
+);
+
+DELETE FROM catalogs WHERE id IN (SELECT id FROM catalog_orphans);
+
+INSERT INTO catalog_items (id, name, description, category, tags, images, enabled, warranty_time)
+SELECT
+    id,
+    json_extract(item_data, '$.name'),
+    json_extract(item_data, '$.description'),
+    json_extract(item_data, '$.category'),
+    json_extract(item_data, '$.tags'),
+    json_extract(item_data, '$.images'),
+    json_extract(item_data, '$.enabled'),
+    CASE WHEN json_extract(item_data, '$.warranty_time_type') IS NULL THEN NULL ELSE json_object(
+        'type', json_extract(item_data, '$.warranty_time_type'),
+        'seconds', json_extract(item_data, '$.warranty_time_seconds')
+    ) END
+FROM catalogs WHERE type_entry = 'Item';
+
+INSERT INTO catalog_variations (
+    id, item_id, name, sku, upc, images, enabled, measurement_units, available_units,
+    reorder_threshold, processing_time, price_type, price_amount, price_asset_name,
+    price_asset_scale, extra_attributes
+)
+SELECT
+    id,
+    json_extract(item_variation_data, '$.item_id'),
+    json_extract(item_variation_data, '$.name'),
+    json_extract(item_variation_data, '$.sku'),
+    json_extract(item_variation_data, '$.upc'),
+    json_extract(item_variation_data, '$.images'),
+    json_extract(item_variation_data, '$.enabled'),
+    json_extract(item_variation_data, '$.measurement_units'),
+    json_extract(item_variation_data, '$.available_units'),
+    json_extract(item_variation_data, '$.reorder_threshold'),
+    CASE WHEN json_extract(item_variation_data, '$.processing_time_type') IS NULL THEN NULL ELSE json_object(
+        'type', json_extract(item_variation_data, '$.processing_time_type'),
+        'seconds', json_extract(item_variation_data, '$.processing_time_seconds')
+    ) END,
+    json_extract(item_variation_data, '$.price_type'),
+    json_extract(item_variation_data, '$.price_amount'),
+    json_extract(item_variation_data, '$.price_asset_name'),
+    json_extract(item_variation_data, '$.price_asset_scale'),
+    json_extract(item_variation_data, '$.extra_attributes')
+FROM catalogs WHERE type_entry = 'Variation';
+
+INSERT INTO catalog_modifications (
+    id, item_id, name, images, enabled, processing_time, warranty_time, price_type,
+    price_amount, price_asset_name, price_asset_scale
+)
+SELECT
+    id,
+    json_extract(item_modification_data, '$.item_id'),
+    json_extract(item_modification_data, '$.name'),
+    json_extract(item_modification_data, '$.images'),
+    json_extract(item_modification_data, '$.enabled'),
+    CASE WHEN json_extract(item_modification_data, '$.processing_time_type') IS NULL THEN NULL ELSE json_object(
+        'type', json_extract(item_modification_data, '$.processing_time_type'),
+        'seconds', json_extract(item_modification_data, '$.processing_time_seconds')
+    ) END,
+    CASE WHEN json_extract(item_modification_data, '$.warranty_time_type') IS NULL THEN NULL ELSE json_object(
+        'type', json_extract(item_modification_data, '$.warranty_time_type'),
+        'seconds', json_extract(item_modification_data, '$.warranty_time_seconds')
+    ) END,
+    json_extract(item_modification_data, '$.price_type'),
+    json_extract(item_modification_data, '$.price_amount'),
+    json_extract(item_modification_data, '$.price_asset_name'),
+    json_extract(item_modification_data, '$.price_asset_scale')
+FROM catalogs WHERE type_entry = 'Modification';
+
+INSERT INTO catalog_deliveries (id, item_id, delivery_type, width_mm, length_mm, height_mm, weight_grams)
+SELECT
+    id,
+    json_extract(item_delivery_data, '$.item_id'),
+    json_extract(item_delivery_data, '$.delivery_type'),
+    json_extract(item_delivery_data, '$.delivery_width_mm'),
+    json_extract(item_delivery_data, '$.delivery_length_mm'),
+    json_extract(item_delivery_data, '$.delivery_height_mm'),
+    json_extract(item_delivery_data, '$.delivery_weight_grams')
+FROM catalogs WHERE type_entry = 'Delivery';
+
+INSERT INTO catalog_controls (id, item_id, control_type, control_data)
+SELECT
+    id,
+    json_extract(item_control_data, '$.item_id'),
+    json_extract(item_control_data, '$.control_type'),
+    json_extract(item_control_data, '$.control_data')
+FROM catalogs WHERE type_entry = 'Control';
+
+DROP INDEX IF EXISTS item_index;
+DROP INDEX IF EXISTS item_variation_index;
+DROP INDEX IF EXISTS item_modification_index;
+DROP INDEX IF EXISTS item_delivery_index;
+DROP INDEX IF EXISTS item_control_index;
+
+ALTER TABLE catalogs DROP COLUMN item_data;
+ALTER TABLE catalogs DROP COLUMN item_variation_data;
+ALTER TABLE catalogs DROP COLUMN item_modification_data;
+ALTER TABLE catalogs DROP COLUMN item_delivery_data;
+ALTER TABLE catalogs DROP COLUMN item_control_data;
@@ -0,0 +1,11 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS catalogs_account_created_at;
+DROP INDEX IF EXISTS catalogs_account_type;
+
+DROP TRIGGER IF EXISTS catalog_items_name_insert;
+DROP TRIGGER IF EXISTS catalog_items_name_update;
+DROP TRIGGER IF EXISTS catalog_items_name_delete;
+DROP TRIGGER IF EXISTS catalog_variations_name_insert;
+DROP TRIGGER IF EXISTS catalog_variations_name_update;
+DROP TRIGGER IF EXISTS catalog_variations_name_delete;
+DROP TABLE IF EXISTS catalog_names;
@@ -0,0 +1,30 @@
+-- every list is scoped to an account, usually of a single type or sorted by creation
+CREATE INDEX IF NOT EXISTS catalogs_account_type ON catalogs (account, type_entry);
+CREATE INDEX IF NOT EXISTS catalogs_account_created_at ON catalogs (account, created_at);
+
+-- names are searched by substring, the trigrams of the item and variation
+-- names find them without reading every name, the rowid is the object id
+CREATE VIRTUAL TABLE IF NOT EXISTS catalog_names USING fts5(name, tokenize = 'trigram');
+
+INSERT INTO catalog_names (rowid, name) SELECT id, name FROM catalog_items;
+INSERT INTO catalog_names (rowid, name) SELECT id, name FROM catalog_variations;
+
+CREATE TRIGGER IF NOT EXISTS catalog_items_name_insert AFTER INSERT ON catalog_items BEGIN
+    INSERT INTO catalog_names (rowid, name) VALUES (new.id, new.name);
+END;
+CREATE TRIGGER IF NOT EXISTS catalog_items_name_update AFTER UPDATE OF name ON catalog_items BEGIN
+    UPDATE catalog_names SET name = new.name WHERE rowid = old.id;
+END;
+CREATE TRIGGER IF NOT EXISTS catalog_items_name_delete AFTER DELETE ON catalog_items BEGIN
+    DELETE FROM catalog_names WHERE rowid = old.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS catalog_variations_name_insert AFTER INSERT ON catalog_variations BEGIN
+    INSERT INTO catalog_names (rowid, name) VALUES (new.id, new.name);
+END;
+CREATE TRIGGER IF NOT EXISTS catalog_variations_name_update AFTER UPDATE OF name ON catalog_variations BEGIN
+    UPDATE catalog_names SET name = new.name WHERE rowid = old.id;
+END;
+CREATE TRIGGER IF NOT EXISTS catalog_variations_name_delete AFTER DELETE ON catalog_variations BEGIN
+    DELETE FROM catalog_names WHERE rowid = old.id;
+END;
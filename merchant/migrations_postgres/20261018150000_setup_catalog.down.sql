@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS stock_levels;
+DROP TABLE IF EXISTS stock_locations;
+DROP TABLE IF EXISTS catalogs;
@@ -0,0 +1,44 @@
+CREATE TABLE IF NOT EXISTS catalogs
+(
+    id BIGINT PRIMARY KEY NOT NULL,
+    account VARCHAR(30) NOT NULL,
+    type_entry VARCHAR(20) NOT NULL,
+    version TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    item_data JSONB DEFAULT NULL,
+    item_variation_data JSONB DEFAULT NULL,
+    item_modification_data JSONB DEFAULT NULL,
+    item_delivery_data JSONB DEFAULT NULL,
+    item_control_data JSONB DEFAULT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS catalogs_account ON catalogs (account, created_at);
+-- containment queries (`@>`) on the documents use these
+CREATE INDEX IF NOT EXISTS item_index ON catalogs USING GIN (item_data jsonb_path_ops);
+CREATE INDEX IF NOT EXISTS item_variation_index ON catalogs USING GIN (item_variation_data jsonb_path_ops);
+CREATE INDEX IF NOT EXISTS item_modification_index ON catalogs USING GIN (item_modification_data jsonb_path_ops);
+CREATE INDEX IF NOT EXISTS item_delivery_index ON catalogs USING GIN (item_delivery_data jsonb_path_ops);
+CREATE INDEX IF NOT EXISTS item_control_index ON catalogs USING GIN (item_control_data jsonb_path_ops);
+
+CREATE TABLE IF NOT EXISTS stock_locations
+(
+    id BIGINT PRIMARY KEY NOT NULL,
+    account VARCHAR(30) NOT NULL,
+    version TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    location_data JSONB NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS stock_location_account_index ON stock_locations (account);
+
+CREATE TABLE IF NOT EXISTS stock_levels
+(
+    account VARCHAR(30) NOT NULL,
+    location_id BIGINT NOT NULL,
+    variation_id BIGINT NOT NULL,
+    units INTEGER NOT NULL DEFAULT 0,
+    version TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    PRIMARY KEY (account, location_id, variation_id)
+);
+
+CREATE INDEX IF NOT EXISTS stock_level_variation_index ON stock_levels (account, variation_id);
@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS item_variation_name_index;
+DROP INDEX IF EXISTS item_name_index;
@@ -0,0 +1,4 @@
+-- names are searched by substring ignoring the case, trigram indexes serve ILIKE
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+CREATE INDEX IF NOT EXISTS item_name_index ON catalogs USING GIN ((item_data->>'name') gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS item_variation_name_index ON catalogs USING GIN ((item_variation_data->>'name') gin_trgm_ops);
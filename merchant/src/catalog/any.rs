@@ -0,0 +1,339 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use serde_json::Value;
+
+use super::backend::{
+    Account, CatalogSQLService, Id, SqlAuditEntry, SqlCatalogArchive, SqlCatalogChange,
+    SqlCatalogObjectDocument, SqlCatalogQueryOptions, SqlCatalogRevision, SqlStockAlert,
+    SqlStockLocationDocument,
+};
+use super::matrix::CatalogMatrixService;
+use super::memory::CatalogMemoryService;
+use super::models::{
+    Actor, CatalogImport, CatalogObject, CatalogObjectBulkDocument, CatalogRevisionDiff,
+    StockLevel, StockLocation,
+};
+use super::postgres::CatalogPgService;
+use super::projection::Fields;
+use super::service::{
+    BulkDocumentReferencesResolver, CatalogArchiveService, CatalogAuditLog, CatalogChangeFeed,
+    CatalogCmd, CatalogError, CatalogProjection, CatalogRevisionService, CatalogService, Commander,
+    ListAuditOptions, ListCatalogChangesOptions, StockAlertHook, StockAlertService,
+    StockLocationService,
+};
+use crate::utils::broadcast::Receiver;
+
+// The backend picked at startup from `DATABASE_URL`. The change feed, stock
+// alerts, revisions, audit log and archives are only kept by the SQLite
+// backend, the others answer `Unsupported` for them. `limits` names what's
+// missing so the server can warn at startup.
+#[derive(Clone)]
+pub enum AnyCatalogService {
+    Sqlite(CatalogSQLService),
+    Postgres(CatalogPgService),
+    Memory(CatalogMemoryService),
+    Matrix(Box<CatalogMatrixService>),
+}
+
+macro_rules! dispatch {
+    ($self:ident, $service:ident => $call:expr) => {
+        match $self {
+            AnyCatalogService::Sqlite($service) => $call,
+            AnyCatalogService::Postgres($service) => $call,
+            AnyCatalogService::Memory($service) => $call,
+            AnyCatalogService::Matrix($service) => $call,
+        }
+    };
+}
+
+impl AnyCatalogService {
+    // what the backend doesn't keep
+    pub fn limits(&self) -> &'static [&'static str] {
+        match self {
+            Self::Sqlite(_) => &[],
+            _ => &[
+                "the change feed",
+                "stock alerts",
+                "revisions",
+                "the audit log",
+                "archives",
+                "webhooks",
+            ],
+        }
+    }
+
+    // the actor is only recorded in the audit log, which `limits` tells the
+    // other backends don't keep
+    pub fn with_actor(self, actor: Actor) -> Self {
+        match self {
+            Self::Sqlite(service) => Self::Sqlite(service.with_actor(actor)),
+            other => other,
+        }
+    }
+
+    pub fn with_alert_hook(self, hook: Arc<dyn StockAlertHook<Id, Account>>) -> Self {
+        match self {
+            Self::Sqlite(service) => Self::Sqlite(service.with_alert_hook(hook)),
+            other => {
+                eprintln!(
+                    "warning: stock alerts aren't kept with this database, the hook is never called"
+                );
+                other
+            }
+        }
+    }
+
+    fn sqlite(&self) -> Result<&CatalogSQLService, CatalogError> {
+        match self {
+            Self::Sqlite(service) => Ok(service),
+            _ => Err(CatalogError::Unsupported),
+        }
+    }
+}
+
+impl BulkDocumentReferencesResolver for AnyCatalogService {
+    type Id = Id;
+    fn resolve(
+        id_map: &HashMap<&str, Self::Id>,
+        catalog: &CatalogObject<String>,
+    ) -> Result<CatalogObject<Self::Id>, CatalogError> {
+        <CatalogSQLService as BulkDocumentReferencesResolver>::resolve(id_map, catalog)
+    }
+}
+
+#[async_trait]
+impl CatalogService for AnyCatalogService {
+    type Id = Id;
+    type Query = SqlCatalogQueryOptions;
+
+    async fn create(
+        &self,
+        account: &Account,
+        catalog: &CatalogObject<Id>,
+    ) -> Result<SqlCatalogObjectDocument, CatalogError> {
+        dispatch!(self, service => service.create(account, catalog).await)
+    }
+
+    async fn bulk_create(
+        &self,
+        account: &Account,
+        catalog: &[CatalogObjectBulkDocument<String>],
+    ) -> Result<Vec<SqlCatalogObjectDocument>, CatalogError> {
+        dispatch!(self, service => service.bulk_create(account, catalog).await)
+    }
+
+    async fn exists(&self, account: &Account, id: &Id) -> Result<bool, CatalogError> {
+        dispatch!(self, service => service.exists(account, id).await)
+    }
+
+    async fn read(
+        &self,
+        account: &Account,
+        id: &Id,
+    ) -> Result<SqlCatalogObjectDocument, CatalogError> {
+        dispatch!(self, service => service.read(account, id).await)
+    }
+
+    async fn update(
+        &self,
+        account: &Account,
+        id: &Id,
+        catalog: &CatalogObject<Id>,
+    ) -> Result<SqlCatalogObjectDocument, CatalogError> {
+        dispatch!(self, service => service.update(account, id, catalog).await)
+    }
+
+    async fn update_at_version(
+        &self,
+        account: &Account,
+        id: &Id,
+        catalog: &CatalogObject<Id>,
+        version: &NaiveDateTime,
+    ) -> Result<SqlCatalogObjectDocument, CatalogError> {
+        dispatch!(self, service => service.update_at_version(account, id, catalog, version).await)
+    }
+
+    async fn list(
+        &self,
+        account: &Account,
+        query: &Self::Query,
+    ) -> Result<Vec<SqlCatalogObjectDocument>, CatalogError> {
+        dispatch!(self, service => service.list(account, query).await)
+    }
+
+    async fn delete(
+        &self,
+        account: &Account,
+        id: &Id,
+    ) -> Result<SqlCatalogObjectDocument, CatalogError> {
+        dispatch!(self, service => service.delete(account, id).await)
+    }
+}
+
+#[async_trait]
+impl Commander for AnyCatalogService {
+    type Cmd = CatalogCmd<Id>;
+    type Account = Account;
+
+    async fn cmd(&self, account: &Account, cmd: Self::Cmd) -> Result<(), CatalogError> {
+        dispatch!(self, service => service.cmd(account, cmd).await)
+    }
+}
+
+#[async_trait]
+impl StockLocationService for AnyCatalogService {
+    async fn create_location(
+        &self,
+        account: &Account,
+        location: &StockLocation,
+    ) -> Result<SqlStockLocationDocument, CatalogError> {
+        dispatch!(self, service => service.create_location(account, location).await)
+    }
+
+    async fn read_location(
+        &self,
+        account: &Account,
+        id: &Id,
+    ) -> Result<SqlStockLocationDocument, CatalogError> {
+        dispatch!(self, service => service.read_location(account, id).await)
+    }
+
+    async fn update_location(
+        &self,
+        account: &Account,
+        id: &Id,
+        location: &StockLocation,
+    ) -> Result<SqlStockLocationDocument, CatalogError> {
+        dispatch!(self, service => service.update_location(account, id, location).await)
+    }
+
+    async fn list_locations(
+        &self,
+        account: &Account,
+    ) -> Result<Vec<SqlStockLocationDocument>, CatalogError> {
+        dispatch!(self, service => service.list_locations(account).await)
+    }
+
+    async fn stock_levels(
+        &self,
+        account: &Account,
+        variation_id: &Id,
+    ) -> Result<Vec<StockLevel<Id>>, CatalogError> {
+        dispatch!(self, service => service.stock_levels(account, variation_id).await)
+    }
+}
+
+#[async_trait]
+impl StockAlertService for AnyCatalogService {
+    async fn list_alerts(&self, account: &Account) -> Result<Vec<SqlStockAlert>, CatalogError> {
+        self.sqlite()?.list_alerts(account).await
+    }
+}
+
+#[async_trait]
+impl CatalogProjection for AnyCatalogService {
+    async fn list_fields(
+        &self,
+        account: &Account,
+        query: &SqlCatalogQueryOptions,
+        fields: &Fields,
+    ) -> Result<Vec<Value>, CatalogError> {
+        dispatch!(self, service => service.list_fields(account, query, fields).await)
+    }
+}
+
+#[async_trait]
+impl CatalogChangeFeed for AnyCatalogService {
+    async fn changes(
+        &self,
+        account: &Account,
+        options: &ListCatalogChangesOptions,
+    ) -> Result<Vec<SqlCatalogChange>, CatalogError> {
+        self.sqlite()?.changes(account, options).await
+    }
+
+    fn subscribe(&self) -> Result<Receiver<SqlCatalogChange>, CatalogError> {
+        self.sqlite()?.subscribe()
+    }
+}
+
+#[async_trait]
+impl CatalogRevisionService for AnyCatalogService {
+    async fn read_revision(
+        &self,
+        account: &Account,
+        id: &Id,
+        revision: i64,
+    ) -> Result<SqlCatalogRevision, CatalogError> {
+        self.sqlite()?.read_revision(account, id, revision).await
+    }
+
+    async fn read_revision_at(
+        &self,
+        account: &Account,
+        id: &Id,
+        at: &NaiveDateTime,
+    ) -> Result<SqlCatalogRevision, CatalogError> {
+        self.sqlite()?.read_revision_at(account, id, at).await
+    }
+
+    async fn list_revisions(
+        &self,
+        account: &Account,
+        id: &Id,
+    ) -> Result<Vec<SqlCatalogRevision>, CatalogError> {
+        self.sqlite()?.list_revisions(account, id).await
+    }
+
+    async fn diff_revisions(
+        &self,
+        account: &Account,
+        id: &Id,
+        from: i64,
+        to: i64,
+    ) -> Result<CatalogRevisionDiff, CatalogError> {
+        self.sqlite()?.diff_revisions(account, id, from, to).await
+    }
+
+    async fn restore_revision(
+        &self,
+        account: &Account,
+        id: &Id,
+        revision: i64,
+    ) -> Result<SqlCatalogObjectDocument, CatalogError> {
+        self.sqlite()?.restore_revision(account, id, revision).await
+    }
+}
+
+#[async_trait]
+impl CatalogAuditLog for AnyCatalogService {
+    async fn audit(
+        &self,
+        account: &Account,
+        options: &ListAuditOptions,
+    ) -> Result<Vec<SqlAuditEntry>, CatalogError> {
+        self.sqlite()?.audit(account, options).await
+    }
+}
+
+#[async_trait]
+impl CatalogArchiveService for AnyCatalogService {
+    async fn export(&self, account: &Account) -> Result<SqlCatalogArchive, CatalogError> {
+        self.sqlite()?.export(account).await
+    }
+
+    async fn import(
+        &self,
+        account: &Account,
+        archive: &SqlCatalogArchive,
+    ) -> Result<CatalogImport<Id>, CatalogError> {
+        self.sqlite()?.import(account, archive).await
+    }
+
+    async fn backup(&self, path: &str) -> Result<(), CatalogError> {
+        self.sqlite()?.backup(path).await
+    }
+}
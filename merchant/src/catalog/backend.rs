@@ -1,38 +1,50 @@
-use std::borrow::Borrow;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::sync::Arc;
 
 use async_trait::async_trait;
+use rand::Rng;
 
-use sea_query::{Cond, Expr, Iden, Query as Qsql, SqliteQueryBuilder as QueryBuilder};
-use serde::{Deserialize, Serialize};
-use sqlx::types::chrono::NaiveDateTime;
-use sqlx::{types::Json, FromRow, SqlitePool as Pool};
+use chrono::{NaiveDateTime, Utc};
+use sea_query::{
+    Expr, Iden, Query as Qsql, SelectStatement, SqliteQueryBuilder as QueryBuilder, Values,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sqlx::{types::Json, FromRow, Sqlite, SqlitePool as Pool, Transaction};
 
-use super::super::utils::query::{Order, Query};
+use super::super::utils::broadcast::{Broadcast, Receiver};
+use super::super::utils::diff::diff;
+use super::super::utils::query::{contains_pattern, Order};
+use super::super::utils::sqlite::{is_busy, BusyRetryPolicy};
 use super::models::{
-    CatalogObject, CatalogObjectBulkDocument, CatalogObjectDocument, Control, Item, ItemControl,
-    ItemDelivery, ItemModification, ItemVariation, MatrixControl,
+    Actor, CatalogChangeOperation, CatalogImport, CatalogObject, CatalogObjectBulkDocument,
+    CatalogRevisionDiff, Delivery, Image, Item, ItemControl, ItemDelivery, ItemModification,
+    ItemVariation, Price, StockLevel, StockLocation, Time, CATALOG_ARCHIVE_VERSION,
 };
+use super::projection::Fields;
 use super::service::{
-    BulkDocumentReferencesResolver, CatalogCmd, CatalogError, CatalogId, CatalogService, Commander,
-    ListCatalogQueryOptions,
+    bulk_create, BulkDocumentReferencesResolver, CatalogArchiveService, CatalogAuditLog,
+    CatalogChangeFeed, CatalogCmd, CatalogError, CatalogProjection, CatalogRevisionService,
+    CatalogService, Commander, ListCatalogChangesOptions, StockAlertHook, StockAlertService,
+    StockLocationService,
+};
+use super::types::resolve_references;
+use super::validation::validate;
+use crate::catalog::service::{
+    CatalogColumnOrder, IncreaseItemVariationUnitsAtPayload, ListAuditOptions,
+    TransferItemVariationUnitsPayload,
 };
-use crate::catalog::service::{CatalogColumnOrder, IncreaseItemVariationUnitsPayload};
 use sea_query::Order as OrderSql;
 
 sea_query::sea_query_driver_sqlite!();
 use sea_query_driver_sqlite::{bind_query, bind_query_as};
 
-pub type Id = u32;
-pub type Account = String;
-pub type SQlCatalogCmd = CatalogCmd<Id>;
-pub type SqlCatalogObject = CatalogObject<Id>;
-pub type SqlCatalogObjectDocument = CatalogObjectDocument<Id, Account>;
-#[allow(dead_code)]
-pub type SqlCatalogItemVariation = ItemVariation<Id>;
-#[allow(dead_code)]
-pub type SqlCatalogObjectBulkDocument = CatalogObjectBulkDocument<Id>;
-pub type SqlCatalogQueryOptions = Query<ListCatalogQueryOptions, CatalogColumnOrder>;
+pub use super::types::{
+    Account, Id, SQlCatalogCmd, SqlAuditEntry, SqlCatalogArchive, SqlCatalogChange,
+    SqlCatalogItemVariation, SqlCatalogObject, SqlCatalogObjectBulkDocument,
+    SqlCatalogObjectDocument, SqlCatalogQueryOptions, SqlCatalogRevision, SqlStockAlert,
+    SqlStockLocationDocument,
+};
 
 impl From<Order> for OrderSql {
     fn from(order_service: Order) -> Self {
@@ -49,512 +61,643 @@ impl BulkDocumentReferencesResolver for CatalogSQLService {
         id_map: &HashMap<&str, Self::Id>,
         catalog: &CatalogObject<String>,
     ) -> Result<CatalogObject<Self::Id>, CatalogError> {
-        let item = match &catalog {
-            CatalogObject::Item(item) => CatalogObject::Item(item.clone()),
-            CatalogObject::Modification(ItemModification {
-                processing_time,
-                warranty_time,
-                enabled,
-                images,
-                name,
-                price,
-                item_id,
-            }) => CatalogObject::Modification(ItemModification {
-                processing_time: processing_time.to_owned(),
-                warranty_time: warranty_time.to_owned(),
-                enabled: enabled.to_owned(),
-                images: images.to_owned(),
-                item_id: id_map
-                    .get(item_id.as_str())
-                    .ok_or(CatalogError::BulkReferenceNotExist(item_id.to_string()))?
-                    .clone(),
-                name: name.to_owned(),
-                price: price.to_owned(),
-            }),
-            CatalogObject::Variation(ItemVariation {
-                available_units,
-                enabled,
-                processing_time,
-                extra_attributes,
-                images,
-                measurement_units,
-                name,
-                price,
-                sku,
-                upc,
-                item_id,
-            }) => CatalogObject::Variation(ItemVariation {
-                available_units: available_units.to_owned(),
-                enabled: enabled.to_owned(),
-                images: images.to_owned(),
-                item_id: id_map
-                    .get(item_id.as_str())
-                    .ok_or(CatalogError::BulkReferenceNotExist(item_id.to_string()))?
-                    .clone(),
-                processing_time: processing_time.to_owned(),
-                extra_attributes: extra_attributes.to_owned(),
-                measurement_units: measurement_units.to_owned(),
-                name: name.to_owned(),
-                price: price.to_owned(),
-                sku: sku.to_owned(),
-                upc: upc.to_owned(),
-            }),
-            CatalogObject::Control(ItemControl { control, item_id }) => {
-                let control = match control {
-                    Control::Matrix(item) => {
-                        let mut combinations: HashMap<String, Self::Id> = HashMap::new();
-
-                        for (template_id, id_ref) in item.combinations.iter() {
-                            let id = id_map
-                                .get(id_ref.as_str())
-                                .clone()
-                                .ok_or(CatalogError::BulkReferenceNotExist(id_ref.to_string()))?;
-
-                            combinations
-                                .entry(template_id.to_string())
-                                .or_insert(id.to_owned());
-                        }
-
-                        Control::Matrix(MatrixControl {
-                            combinations,
-                            props: item.props.to_owned(),
-                            key_template: item.key_template.to_owned(),
-                        })
-                    }
-                    Control::Form(item) => Control::Form(item.to_vec()),
-                };
-                CatalogObject::Control(ItemControl {
-                    control,
-                    item_id: id_map
-                        .get(item_id.as_str())
-                        .ok_or(CatalogError::BulkReferenceNotExist(item_id.to_string()))?
-                        .clone(),
-                })
-            }
-            CatalogObject::Delivery(ItemDelivery { delivery, item_id }) => {
-                CatalogObject::Delivery(ItemDelivery {
-                    delivery: delivery.to_owned(),
-                    item_id: id_map
-                        .get(item_id.as_str())
-                        .ok_or(CatalogError::BulkReferenceNotExist(item_id.to_string()))?
-                        .clone(),
-                })
-            }
-        };
-        Ok(item)
+        resolve_references(id_map, catalog)
     }
 }
+
 #[derive(Clone)]
 pub struct CatalogSQLService {
     pool: Pool,
+    alert_hooks: Vec<Arc<dyn StockAlertHook<Id, Account>>>,
+    changes: Broadcast<SqlCatalogChange>,
+    actor: Actor,
+    busy_retry: BusyRetryPolicy,
+}
+
+// a row of a projection made in sql
+#[derive(Debug, FromRow)]
+pub struct ProjectionRow {
+    pub projection: Json<serde_json::Value>,
+}
+
+// a write with what it recorded in its transaction
+struct Committed {
+    document: SqlCatalogObjectDocument,
+    change: SqlCatalogChange,
+    alert: Option<SqlStockAlert>,
 }
 
 impl CatalogSQLService {
     pub fn new(pool: Pool) -> Self {
-        Self { pool }
+        Self {
+            pool,
+            alert_hooks: vec![],
+            changes: Broadcast::new(),
+            actor: Actor::default(),
+            busy_retry: BusyRetryPolicy::default(),
+        }
+    }
+
+    // the writes done through the returned service are audited as the actor's
+    pub fn with_actor(mut self, actor: Actor) -> Self {
+        self.actor = actor;
+        self
+    }
+
+    pub fn with_alert_hook(mut self, hook: Arc<dyn StockAlertHook<Id, Account>>) -> Self {
+        self.alert_hooks.push(hook);
+        self
+    }
+
+    pub fn with_busy_retry(mut self, policy: BusyRetryPolicy) -> Self {
+        self.busy_retry = policy;
+        self
+    }
+
+    // runs a write transaction again while the database is locked by another
+    // writer, with some jitter so the writers that collided don't meet again
+    async fn retry_busy<T, F, Fut>(&self, operation: F) -> Result<T, CatalogError>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T, CatalogError>>,
+    {
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            match operation().await {
+                Err(CatalogError::DatabaseBusy) if attempts < self.busy_retry.max_attempts => {
+                    let jitter = rand::thread_rng().gen_range(0.5..1.0);
+                    async_std::task::sleep(self.busy_retry.delay(attempts).mul_f64(jitter)).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    // A transaction holding the write lock from its start. A deferred one that
+    // reads first can't wait for a newer snapshot once it writes and fails as
+    // busy, this one waits on the busy timeout like any writer.
+    async fn begin_write(&self) -> Result<Transaction<'static, Sqlite>, CatalogError> {
+        let mut tx = self.pool.begin().await.map_err(database_error)?;
+        sqlx::query("UPDATE catalogs SET id = id WHERE 0")
+            .execute(&mut tx)
+            .await
+            .map_err(database_error)?;
+        Ok(tx)
+    }
+
+    async fn record_audit(
+        &self,
+        tx: &mut Transaction<'_, Sqlite>,
+        operation: CatalogChangeOperation,
+        before: Option<&SqlCatalogObjectDocument>,
+        after: Option<&SqlCatalogObjectDocument>,
+        cmd: Option<&SQlCatalogCmd>,
+    ) -> Result<(), CatalogError> {
+        let document = after
+            .or(before)
+            .ok_or_else(|| CatalogError::mapping("the change has no document"))?;
+        self.insert_audit(
+            tx,
+            &document.account,
+            &document.id,
+            operation,
+            to_json(before)?,
+            to_json(after)?,
+            cmd,
+        )
+        .await
+    }
+
+    async fn record_location_audit(
+        &self,
+        tx: &mut Transaction<'_, Sqlite>,
+        operation: CatalogChangeOperation,
+        before: Option<&SqlStockLocationDocument>,
+        after: &SqlStockLocationDocument,
+    ) -> Result<(), CatalogError> {
+        self.insert_audit(
+            tx,
+            &after.account,
+            &after.id,
+            operation,
+            to_json(before)?,
+            to_json(Some(after))?,
+            None,
+        )
+        .await
     }
 
-    fn get_sql_to_create(
+    #[allow(clippy::too_many_arguments)]
+    async fn insert_audit(
         &self,
-        field_data_name: CatalogSchema,
-        object_type: &CatalogObject<Id>,
-    ) -> String {
+        tx: &mut Transaction<'_, Sqlite>,
+        account: &Account,
+        id: &Id,
+        operation: CatalogChangeOperation,
+        before: Option<serde_json::Value>,
+        after: Option<serde_json::Value>,
+        cmd: Option<&SQlCatalogCmd>,
+    ) -> Result<(), CatalogError> {
         let (sql, _) = Qsql::insert()
-            .into_table(CatalogSchema::Table)
+            .into_table(AuditSchema::Table)
             .columns(vec![
-                CatalogSchema::Id,
-                CatalogSchema::TypeEntry,
-                CatalogSchema::Account,
-                field_data_name,
+                AuditSchema::Account,
+                AuditSchema::ObjectId,
+                AuditSchema::Actor,
+                AuditSchema::ClaimedActor,
+                AuditSchema::Address,
+                AuditSchema::UserAgent,
+                AuditSchema::Operation,
+                AuditSchema::Before,
+                AuditSchema::After,
+                AuditSchema::Cmd,
             ])
             .exprs_panic(vec![
                 Expr::value("$1"),
-                Expr::cust(format!("'{}'", object_type).as_ref()),
+                Expr::value("$2"),
                 Expr::value("$3"),
                 Expr::value("$4"),
+                Expr::value("$5"),
+                Expr::value("$6"),
+                Expr::value("$7"),
+                Expr::value("$8"),
+                Expr::value("$9"),
+                Expr::value("$10"),
             ])
-            .returning(Qsql::select().expr(Expr::asterisk()).take())
-            .build(QueryBuilder);
-        sql
-    }
-
-    fn get_sql_to_read(&self) -> String {
-        let (sql, _) = Qsql::select()
-            .expr(Expr::asterisk())
-            .from(CatalogSchema::Table)
-            .and_where(Expr::col(CatalogSchema::Id).eq("-1"))
-            .build(QueryBuilder);
-
-        sql
-    }
-
-    fn get_sql_to_exists(&self) -> String {
-        let (sql, _) = Qsql::select()
-            .expr(Expr::cust("COUNT(1) as count"))
-            .from(CatalogSchema::Table)
-            .and_where(Expr::col(CatalogSchema::Id).eq("-1"))
-            .and_where(Expr::col(CatalogSchema::Account).eq("-1"))
             .build(QueryBuilder);
 
-        println!("sql {:?}", sql);
-        sql
-    }
-
-    fn get_sql_to_update(&self, field: CatalogSchema, type_entry: &str) -> String {
-        let (sql, _) = Qsql::update()
-            .table(CatalogSchema::Table)
-            //.value_expr(CatalogSchema::Version, Expr::cust("now()"))
-            .value(field, "-1".into())
-            .and_where(Expr::col(CatalogSchema::Account).eq("1"))
-            .and_where(Expr::cust(
-                format!(
-                    "{} = '{}'",
-                    CatalogSchema::TypeEntry.to_string(),
-                    type_entry
-                )
-                .as_ref(),
-            ))
-            .and_where(Expr::cust_with_values("id = ?", vec!["-1"]))
-            .returning(Qsql::select().expr(Expr::asterisk()).take())
-            .build(QueryBuilder);
+        sqlx::query(sql.as_str())
+            .bind(account)
+            .bind(id)
+            .bind(&self.actor.id)
+            .bind(&self.actor.claimed_id)
+            .bind(&self.actor.address)
+            .bind(&self.actor.user_agent)
+            .bind(
+                serde_json::to_value(operation)
+                    .map_err(CatalogError::mapping)?
+                    .as_str(),
+            )
+            .bind(before.map(Json))
+            .bind(after.map(Json))
+            .bind(cmd.map(Json))
+            .execute(&mut *tx)
+            .await
+            .map_err(database_error)?;
 
-        sql
+        Ok(())
     }
 
-    fn to_catalog_schema(item: &CatalogObject<Id>) -> CatalogSchema {
-        match item {
-            CatalogObject::Control(_) => CatalogSchema::ItemControlData,
-            CatalogObject::Variation(_) => CatalogSchema::ItemVariationData,
-            CatalogObject::Item(_) => CatalogSchema::ItemData,
-            CatalogObject::Delivery(_) => CatalogSchema::ItemDeliveryData,
-            CatalogObject::Modification(_) => CatalogSchema::ItemModificationData,
+    // tells the subscribers and the alert hooks about a committed write
+    async fn publish(&self, committed: Committed) -> SqlCatalogObjectDocument {
+        self.changes.publish(&committed.change);
+        if let Some(alert) = committed.alert {
+            for hook in self.alert_hooks.iter() {
+                hook.notify(&alert).await;
+            }
         }
+        committed.document
     }
-}
-
-#[async_trait]
-impl CatalogService for CatalogSQLService {
-    type Id = Id;
-    type Query = SqlCatalogQueryOptions;
 
-    async fn create(
+    pub(crate) async fn insert(
         &self,
         account: &Account,
+        id: &Id,
         catalog_entry: &CatalogObject<Id>,
     ) -> Result<SqlCatalogObjectDocument, CatalogError> {
-        let (data, sql) = match catalog_entry {
-            item @ CatalogObject::Item(entry) => {
-                let data = serde_json::to_value(entry).map_err(|_| CatalogError::MappingError)?;
-                let sql = self.get_sql_to_create(CatalogSchema::ItemData, item);
-
-                (data, sql)
+        if let Some(item_id) = catalog_entry.item_id() {
+            if !self.exists(account, item_id).await? {
+                return Err(CatalogError::unknown_item(item_id));
             }
-            variation @ CatalogObject::Variation(ItemVariation { item_id, .. })
-            | variation @ CatalogObject::Modification(ItemModification { item_id, .. })
-            | variation @ CatalogObject::Control(ItemControl { item_id, .. })
-            | variation @ CatalogObject::Delivery(ItemDelivery { item_id, .. }) => {
-                // Here we get {type, data} json value
-                let data =
-                    serde_json::to_value(variation).map_err(|_| CatalogError::MappingError)?;
-                // we extract the data
-                let data = data
-                    .get("data")
-                    .ok_or(CatalogError::MappingError)?
-                    .to_owned();
-
-                let sql = self
-                    .get_sql_to_create(CatalogSQLService::to_catalog_schema(variation), variation);
-
-                if !self.exists(account, item_id).await? {
-                    println!(
-                        "the catalog id doesnt not exist, {:?}, A:{}, Id:{}",
-                        variation, account, item_id
-                    );
-
-                    return Err(CatalogError::CatalogBadRequest);
-                }
+        }
 
-                (data, sql)
-            }
-        };
+        let committed = self
+            .retry_busy(|| self.insert_row(account, id, catalog_entry))
+            .await?;
+        Ok(self.publish(committed).await)
+    }
 
-        println!("SQL_TO_CREATE{:?}", sql);
+    async fn insert_row(
+        &self,
+        account: &Account,
+        id: &Id,
+        catalog_entry: &CatalogObject<Id>,
+    ) -> Result<Committed, CatalogError> {
+        let (sql, values) = Qsql::insert()
+            .into_table(CatalogSchema::Table)
+            .columns(vec![
+                CatalogSchema::Id,
+                CatalogSchema::TypeEntry,
+                CatalogSchema::Account,
+            ])
+            .values_panic(vec![
+                (*id).into(),
+                catalog_entry.to_string().into(),
+                account.to_string().into(),
+            ])
+            .build(QueryBuilder);
 
-        let mut pool = self
-            .pool
-            .acquire()
-            .await
-            .map_err(|_| CatalogError::DatabaseError)?;
+        let mut tx = self.begin_write().await?;
 
-        let result: CatalogObjectRow = sqlx::query_as(sql.as_str())
-            .bind(rand::random::<Id>())
-            .bind(account)
-            .bind(Json(data))
-            .fetch_one(&mut pool)
+        bind_query(sqlx::query(&sql), &values)
+            .execute(&mut tx)
             .await
-            .map_err(|_| CatalogError::MappingError)?;
+            .map_err(|err| busy_or(err, CatalogError::mapping))?;
+        write_catalog_data(&mut tx, id, catalog_entry).await?;
+        let document = read_document(&mut tx, id).await?;
+        let change =
+            record_change(&mut tx, CatalogChangeOperation::Created, &document, None).await?;
+        self.record_audit(
+            &mut tx,
+            CatalogChangeOperation::Created,
+            None,
+            Some(&document),
+            None,
+        )
+        .await?;
 
-        Ok(result.to_catalog_entry_document()?)
+        tx.commit().await.map_err(database_error)?;
+        Ok(Committed {
+            document,
+            change,
+            alert: None,
+        })
     }
 
-    async fn bulk_create(
+    // Replaces every catalog object, location and stock level with the given
+    // ones, as they were stored. Nothing is logged, revised nor audited.
+    pub(crate) async fn restore(
         &self,
-        account: &Account,
-        catalog: &[CatalogObjectBulkDocument<String>],
-    ) -> Result<Vec<SqlCatalogObjectDocument>, CatalogError> {
-        let mut objects_dependency_count: HashMap<String, u32> = HashMap::new();
-        let mut objects_created_document: HashMap<String, SqlCatalogObjectDocument> =
-            HashMap::new();
-        let mut objects_created_document_id: HashMap<&str, CatalogId<Self>> = HashMap::new();
-        let mut objects_dependency_map: HashMap<String, &CatalogObject<String>> = HashMap::new();
-
-        for (index, item) in catalog.iter().enumerate() {
-            let key_id = match &item.id {
-                Some(id) => id.clone(),
-                None => make_id_by_index(index.try_into().unwrap()),
-            };
-
-            objects_dependency_map
-                .entry(key_id.clone())
-                .or_insert(&item.catalog_object);
-
-            objects_dependency_count.entry(key_id).or_insert(0);
-
-            match &item.catalog_object {
-                CatalogObject::Variation(ItemVariation { item_id, .. })
-                | CatalogObject::Modification(ItemModification { item_id, .. }) => {
-                    let key = item_id.clone();
-                    objects_dependency_count.entry(key).and_modify(|e| *e += 1);
-                }
-                CatalogObject::Control(ItemControl { item_id, control }) => {
-                    objects_dependency_count
-                        .entry(item_id.clone())
-                        .and_modify(|e| *e += 1);
-                    match &control {
-                        Control::Matrix(MatrixControl { combinations, .. }) => {
-                            for (key, id_ref) in combinations.iter() {
-                                println!("combination {:?} {:?}", key, id_ref);
-                                objects_dependency_count
-                                    .entry(id_ref.clone())
-                                    .and_modify(|e| *e += 1);
-                            }
-                        }
-                        _ => {}
-                    }
-                }
-                _ => {}
-            }
+        documents: &[SqlCatalogObjectDocument],
+        locations: &[SqlStockLocationDocument],
+        levels: &[(Account, StockLevel<Id>)],
+    ) -> Result<(), CatalogError> {
+        let mut tx = self.begin_write().await?;
+        for table in [
+            CatalogSchema::Table.to_string(),
+            StockLocationSchema::Table.to_string(),
+            StockLevelSchema::Table.to_string(),
+        ] {
+            sqlx::query(format!("DELETE FROM {}", table).as_str())
+                .execute(&mut tx)
+                .await
+                .map_err(database_error)?;
         }
+        for document in documents {
+            write_document(&mut tx, &document.account, document).await?;
+        }
+        for location in locations {
+            write_location(&mut tx, &location.account, &location.id, location).await?;
+        }
+        for (account, level) in levels {
+            write_stock_level(&mut tx, account, level).await?;
+        }
+        tx.commit().await.map_err(database_error)?;
+        Ok(())
+    }
 
-        let mut items_sorted_to_insert: Vec<(&String, &u32)> =
-            objects_dependency_count.iter().collect();
-
-        items_sorted_to_insert.sort_by(|a, b| b.1.cmp(a.1));
-
-        println!("THE SORTED ITEMS {:?}", items_sorted_to_insert);
-
-        // we start creating the dependencies from the less dependant
-        for (alias_id, _) in &items_sorted_to_insert {
-            let item = objects_dependency_map.get(*alias_id).unwrap();
-            println!("item iter {:?}", item);
-            println!(
-                "objects_created_document_id {:?}",
-                objects_created_document_id
-            );
-            match item {
-                item @ CatalogObject::Item(_) => {
-                    let catalog_object = <Self as BulkDocumentReferencesResolver>::resolve(
-                        &objects_created_document_id,
-                        item,
-                    )?;
-                    let document = self.create(account, &catalog_object).await?;
-                    println!("creating element Item {}, {:?}", item, document);
-
-                    objects_created_document_id
-                        .entry(alias_id)
-                        .or_insert(document.id.clone());
-
-                    objects_created_document
-                        .entry(alias_id.to_string())
-                        .or_insert(document);
-                }
-                v @ CatalogObject::Variation(ItemVariation { item_id, .. })
-                | v @ CatalogObject::Modification(ItemModification { item_id, .. })
-                | v @ CatalogObject::Control(ItemControl { item_id, .. })
-                | v @ CatalogObject::Delivery(ItemDelivery { item_id, .. }) => {
-                    println!(" item_id {:?}, variation {:?} ", item_id, v);
-                    println!("Objects documents {:?}", objects_created_document);
-
-                    if objects_created_document_id.get(item_id.as_str()).is_some() {
-                        println!("!ID FOUND {:?}", item_id);
-
-                        let catalog_object = <Self as BulkDocumentReferencesResolver>::resolve(
-                            &objects_created_document_id,
-                            v,
-                        )?;
-
-                        let document = self.create(account, &catalog_object).await?;
-
-                        objects_created_document_id
-                            .entry(alias_id)
-                            .or_insert(document.id.clone());
-
-                        objects_created_document
-                            .entry(alias_id.to_string())
-                            .or_insert(document);
-                    } else {
-                        return Err(CatalogError::BulkReferenceNotExist(item_id.to_string()));
-                    }
-                }
+    async fn update_object(
+        &self,
+        account: &Account,
+        id: &Id,
+        catalog_entry: &CatalogObject<Id>,
+        version: Option<&NaiveDateTime>,
+    ) -> Result<SqlCatalogObjectDocument, CatalogError> {
+        validate(catalog_entry)?;
+        if let Some(item_id) = catalog_entry.item_id() {
+            if !self.exists(account, item_id).await? {
+                return Err(CatalogError::unknown_item(item_id));
             }
         }
 
-        Ok(items_sorted_to_insert
-            .iter()
-            .map(|(id, _)| objects_created_document.remove(id.as_str()).unwrap())
-            .collect())
+        let committed = self
+            .retry_busy(|| self.update_row(account, id, catalog_entry, version))
+            .await?;
+        Ok(self.publish(committed).await)
     }
 
-    async fn exists(&self, _account: &Account, id: &Id) -> Result<bool, CatalogError> {
-        let mut pool = self
-            .pool
-            .acquire()
-            .await
-            .map_err(|_| CatalogError::DatabaseError)?;
+    async fn update_row(
+        &self,
+        account: &Account,
+        id: &Id,
+        catalog_entry: &CatalogObject<Id>,
+        version: Option<&NaiveDateTime>,
+    ) -> Result<Committed, CatalogError> {
+        let mut tx = self.begin_write().await?;
+
+        // the object has to exist with the same type
+        let before = read_owned(&mut tx, account, id)
+            .await?
+            .filter(|document| document.catalog_object.to_string() == catalog_entry.to_string())
+            .ok_or_else(|| CatalogError::CatalogEntryNotFound(id.to_string()))?;
+        if !bump_version(&mut tx, account, id, version).await? {
+            return Err(CatalogError::VersionMismatch(id.to_string()));
+        }
+        write_catalog_data(&mut tx, id, catalog_entry).await?;
+        let document = read_document(&mut tx, id).await?;
+        let change =
+            record_change(&mut tx, CatalogChangeOperation::Updated, &document, None).await?;
+        self.record_audit(
+            &mut tx,
+            CatalogChangeOperation::Updated,
+            Some(&before),
+            Some(&document),
+            None,
+        )
+        .await?;
+        let alert = record_reorder_alert(&mut tx, Some(&before), &change).await?;
+
+        tx.commit().await.map_err(database_error)?;
+        Ok(Committed {
+            document,
+            change,
+            alert,
+        })
+    }
 
-        println!("Check if exists {:?} {:?}", _account, id);
+    async fn delete_rows(&self, account: &Account, id: &Id) -> Result<Committed, CatalogError> {
+        let mut tx = self.begin_write().await?;
 
-        let catalog_row: Count = sqlx::query_as(self.get_sql_to_exists().as_str())
+        let document = read_owned(&mut tx, account, id)
+            .await?
+            .ok_or_else(|| CatalogError::CatalogEntryNotFound(id.to_string()))?;
+        if let CatalogObject::Item(_) = document.catalog_object {
+            // items can't be removed while variations, modifications, etc. point to them
+            let references: Count = sqlx::query_as(
+                format!(
+                    "SELECT COUNT(1) as count FROM (
+                        SELECT item_id FROM {variations}
+                        UNION ALL SELECT item_id FROM {modifications}
+                        UNION ALL SELECT item_id FROM {deliveries}
+                        UNION ALL SELECT item_id FROM {controls}
+                    ) WHERE item_id = ?",
+                    variations = CatalogDataSchema::Variations.to_string(),
+                    modifications = CatalogDataSchema::Modifications.to_string(),
+                    deliveries = CatalogDataSchema::Deliveries.to_string(),
+                    controls = CatalogDataSchema::Controls.to_string(),
+                )
+                .as_str(),
+            )
             .bind(id)
-            .bind(_account)
-            .fetch_one(&mut pool)
+            .fetch_one(&mut tx)
             .await
-            .map_err(|err| match err {
-                sqlx::Error::RowNotFound => CatalogError::CatalogEntryNotFound(id.to_string()),
-                _ => CatalogError::DatabaseError,
-            })?;
-
-        println!("row count {:?}", catalog_row);
+            .map_err(database_error)?;
 
-        Ok(catalog_row.count != 0)
-    }
+            if references.count != 0 {
+                return Err(CatalogError::item_in_use(id));
+            }
+        }
 
-    async fn read(
-        &self,
-        _account: &Account,
-        id: &Id,
-    ) -> Result<SqlCatalogObjectDocument, CatalogError> {
-        let mut pool = self
-            .pool
-            .acquire()
+        let (sql, values) = Qsql::delete()
+            .from_table(CatalogSchema::Table)
+            .and_where(Expr::col(CatalogSchema::Id).eq(*id))
+            .and_where(Expr::col(CatalogSchema::Account).eq(account.to_string()))
+            .build(QueryBuilder);
+        bind_query(sqlx::query(&sql), &values)
+            .execute(&mut tx)
             .await
-            .map_err(|_| CatalogError::DatabaseError)?;
-        let catalog_row: CatalogObjectRow = sqlx::query_as(self.get_sql_to_read().as_str())
-            .bind(id)
-            .fetch_one(&mut pool)
+            .map_err(database_error)?;
+
+        let (sql, values) = Qsql::delete()
+            .from_table(StockLevelSchema::Table)
+            .and_where(Expr::col(StockLevelSchema::VariationId).eq(*id))
+            .and_where(Expr::col(StockLevelSchema::Account).eq(account.to_string()))
+            .build(QueryBuilder);
+        bind_query(sqlx::query(&sql), &values)
+            .execute(&mut tx)
             .await
-            .map_err(|err| match err {
-                sqlx::Error::RowNotFound => CatalogError::CatalogEntryNotFound(id.to_string()),
-                _ => CatalogError::DatabaseError,
-            })?;
+            .map_err(database_error)?;
+        let change =
+            record_change(&mut tx, CatalogChangeOperation::Deleted, &document, None).await?;
+        self.record_audit(
+            &mut tx,
+            CatalogChangeOperation::Deleted,
+            Some(&document),
+            None,
+            None,
+        )
+        .await?;
 
-        Ok(catalog_row.to_catalog_entry_document()?)
+        tx.commit().await.map_err(database_error)?;
+        Ok(Committed {
+            document,
+            change,
+            alert: None,
+        })
     }
 
-    async fn update(
+    async fn cmd_rows(
         &self,
         account: &Account,
-        id: &Id,
-        catalog_entry: &CatalogObject<Id>,
-    ) -> Result<SqlCatalogObjectDocument, CatalogError> {
-        let (data, sql) = match catalog_entry {
-            CatalogObject::Item(entry) => {
-                let data = serde_json::to_value(entry).map_err(|_| CatalogError::MappingError)?;
-                let sql = self.get_sql_to_update(CatalogSchema::ItemData, "Item");
-                (data, sql)
+        cmd: &SQlCatalogCmd,
+    ) -> Result<Option<Committed>, CatalogError> {
+        let mut tx = self.begin_write().await?;
+
+        let before = read_owned(&mut tx, account, cmd.id()).await?;
+        match cmd {
+            CatalogCmd::IncreaseItemVariationUnits(options) => {
+                add_item_variation_units(&mut tx, account, &options.id, options.units).await?
             }
-            variation @ CatalogObject::Variation(ItemVariation { item_id, .. })
-            | variation @ CatalogObject::Modification(ItemModification { item_id, .. })
-            | variation @ CatalogObject::Control(ItemControl { item_id, .. })
-            | variation @ CatalogObject::Delivery(ItemDelivery { item_id, .. }) => {
-                // Here we get {type, data} json value
-                let data =
-                    serde_json::to_value(variation).map_err(|_| CatalogError::MappingError)?;
-                // we extract the data
-                let data = data
-                    .get("data")
-                    .ok_or(CatalogError::MappingError)?
-                    .to_owned();
-
-                println!("{:?}", data);
-                let sql = self.get_sql_to_update(
-                    CatalogSQLService::to_catalog_schema(variation),
-                    variation.to_string().as_str(),
-                );
-
-                if !self.exists(account, item_id).await? {
-                    println!("the catalog id doesnt not exist");
-                    return Err(CatalogError::CatalogBadRequest);
-                }
-                (data, sql)
+            CatalogCmd::IncreaseItemVariationUnitsAt(options) => {
+                increase_item_variation_units_at(&mut tx, account, options).await?
+            }
+            CatalogCmd::TransferItemVariationUnits(options) => {
+                transfer_item_variation_units(&mut tx, account, options).await?
             }
         };
 
-        let mut pool = self
-            .pool
-            .acquire()
-            .await
-            .map_err(|_| CatalogError::DatabaseError)?;
+        let committed = match read_owned(&mut tx, account, cmd.id()).await? {
+            Some(document) => {
+                let change = record_change(
+                    &mut tx,
+                    CatalogChangeOperation::Command,
+                    &document,
+                    Some(cmd),
+                )
+                .await?;
+                self.record_audit(
+                    &mut tx,
+                    CatalogChangeOperation::Command,
+                    before.as_ref(),
+                    Some(&document),
+                    Some(cmd),
+                )
+                .await?;
+                let alert = record_reorder_alert(&mut tx, before.as_ref(), &change).await?;
+                Some(Committed {
+                    document,
+                    change,
+                    alert,
+                })
+            }
+            None => None,
+        };
 
-        println!("SQL: {}", sql);
-        let result: CatalogObjectRow = sqlx::query_as(sql.as_str())
-            .bind(Json(data))
-            .bind(account.as_str())
-            .bind(id)
-            .fetch_one(&mut pool)
+        tx.commit().await.map_err(database_error)?;
+        Ok(committed)
+    }
+
+    // The documents, revisions and levels already carry the ids of the import,
+    // every object is logged and audited as created.
+    async fn write_archive(
+        &self,
+        account: &Account,
+        documents: &[SqlCatalogObjectDocument],
+        revisions: &[SqlCatalogRevision],
+        locations: &[SqlStockLocationDocument],
+        location_ids: &BTreeMap<Id, Id>,
+        stock_levels: &[StockLevel<Id>],
+    ) -> Result<Vec<SqlCatalogChange>, CatalogError> {
+        let mut tx = self.pool.begin().await.map_err(database_error)?;
+
+        for document in documents {
+            write_document(&mut tx, account, document).await?;
+        }
+
+        for revision in revisions {
+            sqlx::query(
+                "INSERT INTO catalog_revisions (account, object_id, revision, document, created_at)
+                VALUES ($1, $2, $3, $4, $5)",
+            )
+            .bind(account)
+            .bind(revision.document.id)
+            .bind(revision.revision)
+            .bind(Json(&revision.document))
+            .bind(revision.created_at)
+            .execute(&mut tx)
             .await
-            .map_err(|err| match err {
-                sqlx::Error::RowNotFound => CatalogError::CatalogEntryNotFound(id.to_string()),
-                _ => CatalogError::DatabaseError,
-            })?;
+            .map_err(database_error)?;
+        }
+
+        for location in locations {
+            write_location(&mut tx, account, &location_ids[&location.id], location).await?;
+        }
+
+        for level in stock_levels {
+            write_stock_level(&mut tx, account, level).await?;
+        }
+
+        // logged as they're read back
+        let mut changes = vec![];
+        for document in documents {
+            let document = read_document(&mut tx, &document.id).await?;
+            changes.push(
+                append_change(&mut tx, CatalogChangeOperation::Created, &document, None).await?,
+            );
+            self.record_audit(
+                &mut tx,
+                CatalogChangeOperation::Created,
+                None,
+                Some(&document),
+                None,
+            )
+            .await?;
+        }
 
-        Ok(result.to_catalog_entry_document()?)
+        tx.commit().await.map_err(database_error)?;
+        Ok(changes)
     }
 
-    async fn list(
-        &self,
+    // every object with the fields of its type, the columns of other types are NULL
+    fn select_catalog_objects() -> SelectStatement {
+        Self::select_catalog(CATALOG_OBJECT_COLUMNS)
+    }
+
+    // the objects joined with the tables of every type
+    fn select_catalog(columns: &str) -> SelectStatement {
+        let mut select = Qsql::select();
+        select.expr(Expr::cust(columns)).from(CatalogSchema::Table);
+        for table in CatalogDataSchema::TABLES {
+            select.left_join(
+                table,
+                Expr::tbl(table, CatalogSchema::Id).equals(CatalogSchema::Table, CatalogSchema::Id),
+            );
+        }
+        select
+    }
+
+    fn list_statement(account: &Account, query: &SqlCatalogQueryOptions) -> (String, Values) {
+        Self::list_columns_statement(CATALOG_OBJECT_COLUMNS, account, query)
+    }
+
+    fn list_columns_statement(
+        columns: &str,
         account: &Account,
-        query: &Self::Query,
-    ) -> Result<Vec<SqlCatalogObjectDocument>, CatalogError> {
-        let name_is_like_expr = |name: &str| {
-            Cond::any()
-                .add(Expr::cust_with_values(
-                    format!(
-                        "json_extract({}, '$.name') LIKE ?",
-                        CatalogSchema::ItemData.to_string().as_str()
-                    )
-                    .as_str(),
-                    vec![format!("%{}%", name)],
-                ))
-                .add(Expr::cust_with_values(
-                    format!(
-                        "json_extract({}, '$.name') LIKE ?",
-                        CatalogSchema::ItemVariationData.to_string().as_str()
-                    )
-                    .as_str(),
-                    vec![format!("%{}%", name)],
-                ))
+        query: &SqlCatalogQueryOptions,
+    ) -> (String, Values) {
+        // the filters on the fields of a type pick the ids from its table, so
+        // the index of the field is used instead of scanning the account
+        let id_in = |tables: &[CatalogDataSchema], condition: &str| {
+            let selects: Vec<String> = tables
+                .iter()
+                .map(|table| format!("SELECT id FROM {} WHERE {}", table.to_string(), condition))
+                .collect();
+            format!(
+                "{}.{} IN ({})",
+                CatalogSchema::Table.to_string(),
+                CatalogSchema::Id.to_string(),
+                selects.join(" UNION ALL ")
+            )
         };
+        let mut price_conditions: Vec<&str> = vec![];
+        let mut prices: Vec<f32> = vec![];
+        if let Some(min_price) = query.options.min_price {
+            price_conditions.push("price_amount >= ?");
+            prices.push(min_price);
+        }
+        if let Some(max_price) = query.options.max_price {
+            price_conditions.push("price_amount <= ?");
+            prices.push(max_price);
+        }
 
-        let (sql, values) = Qsql::select()
-            .expr(Expr::asterisk())
-            .from(CatalogSchema::Table)
-            .and_where(Expr::col(CatalogSchema::Account).eq(account.to_string()))
+        Self::select_catalog(columns)
+            .and_where(Expr::tbl(CatalogSchema::Table, CatalogSchema::Account).eq(account.to_string()))
             .conditions(
                 query.options.name.is_some(),
                 |q| {
                     let name = query.options.name.as_ref().unwrap();
-                    q.cond_where(name_is_like_expr(name.as_str()));
+                    // the trigrams need three characters, shorter names are
+                    // looked for in every name, both ignore the case
+                    let condition = if name.chars().count() >= 3 {
+                        Expr::cust_with_values(
+                            format!(
+                                "{}.{} IN (SELECT rowid FROM catalog_names WHERE catalog_names MATCH ?)",
+                                CatalogSchema::Table.to_string(),
+                                CatalogSchema::Id.to_string(),
+                            )
+                            .as_str(),
+                            vec![format!("\"{}\"", name.replace('"', "\"\""))],
+                        )
+                    } else {
+                        let pattern = contains_pattern(name);
+                        Expr::cust_with_values(
+                            id_in(
+                                &[CatalogDataSchema::Items, CatalogDataSchema::Variations],
+                                "name LIKE ? ESCAPE '\\'",
+                            )
+                            .as_str(),
+                            vec![pattern.clone(), pattern],
+                        )
+                    };
+                    q.and_where(condition);
+                },
+                |_| {},
+            )
+            .conditions(
+                query.options.item_id.is_some(),
+                |q| {
+                    let item_id = query.options.item_id.unwrap();
+                    let children = [
+                        CatalogDataSchema::Variations,
+                        CatalogDataSchema::Modifications,
+                        CatalogDataSchema::Deliveries,
+                        CatalogDataSchema::Controls,
+                    ];
+                    q.and_where(Expr::cust_with_values(
+                        id_in(&children, "item_id = ?").as_str(),
+                        vec![item_id; children.len()],
+                    ));
                 },
                 |_| {},
             )
@@ -562,16 +705,15 @@ impl CatalogService for CatalogSQLService {
                 query.options.tags.is_some(),
                 |q| {
                     let tags = query.options.tags.as_ref().unwrap();
-                    let value_array = serde_json::to_value(tags)
-                        .map_err(|_| CatalogError::MappingError)
-                        .unwrap();
-                    let str_json = serde_json::to_string(&value_array)
-                        .map_err(|_| CatalogError::MappingError)
-                        .unwrap();
+                    let str_json = serde_json::json!(tags).to_string();
+                    // the item must have every tag asked for, in any order
                     q.cond_where(Expr::cust_with_values(
                         format!(
-                            "json_extract({}, '$.tags') LIKE ?",
-                            CatalogSchema::ItemData.to_string()
+                            "{items}.tags IS NOT NULL AND NOT EXISTS (
+                                SELECT 1 FROM json_each(?) AS wanted
+                                WHERE wanted.value NOT IN (SELECT value FROM json_each({items}.tags))
+                            )",
+                            items = CatalogDataSchema::Items.to_string()
                         )
                         .as_str(),
                         vec![str_json],
@@ -580,31 +722,51 @@ impl CatalogService for CatalogSQLService {
                 |_| {},
             )
             .conditions(
-                query.options.max_price.is_some(),
+                !prices.is_empty(),
                 |q| {
-                    let max_price = query.options.max_price.unwrap();
-                    q.cond_where(Expr::cust_with_values(
-                        format!(
-                            "json_extract({}, '$.price_amount') <= ?",
-                            CatalogSchema::ItemVariationData.to_string()
-                        )
-                        .as_str(),
-                        vec![max_price],
+                    q.and_where(Expr::cust_with_values(
+                        id_in(&[CatalogDataSchema::Variations], &price_conditions.join(" AND "))
+                            .as_str(),
+                        prices.clone(),
                     ));
                 },
                 |_| {},
             )
             .conditions(
-                query.options.min_price.is_some(),
+                query.options.in_stock.is_some(),
                 |q| {
-                    let min_price = query.options.min_price.unwrap();
+                    let operator = match query.options.in_stock.unwrap() {
+                        true => ">",
+                        false => "<=",
+                    };
+                    q.cond_where(Expr::cust(
+                        format!(
+                            "{}.available_units {} 0",
+                            CatalogDataSchema::Variations.to_string(),
+                            operator
+                        )
+                        .as_str(),
+                    ));
+                },
+                |_| {},
+            )
+            .conditions(
+                query.options.available_at.is_some(),
+                |q| {
+                    let location_id = query.options.available_at.unwrap();
                     q.cond_where(Expr::cust_with_values(
                         format!(
-                            "json_extract({}, '$.price_amount') >= ?",
-                            CatalogSchema::ItemVariationData.to_string()
+                            "EXISTS (SELECT 1 FROM {levels} WHERE {levels}.{account} = {table}.{account} AND {levels}.{variation_id} = {table}.{id} AND {levels}.{location_id} = ? AND {levels}.{units} > 0)",
+                            levels = StockLevelSchema::Table.to_string(),
+                            table = CatalogSchema::Table.to_string(),
+                            account = CatalogSchema::Account.to_string(),
+                            id = CatalogSchema::Id.to_string(),
+                            variation_id = StockLevelSchema::VariationId.to_string(),
+                            location_id = StockLevelSchema::LocationId.to_string(),
+                            units = StockLevelSchema::Units.to_string(),
                         )
                         .as_str(),
-                        vec![min_price],
+                        vec![location_id],
                     ));
                 },
                 |_| {},
@@ -618,8 +780,8 @@ impl CatalogService for CatalogSQLService {
                             q.order_by_expr(
                                 Expr::cust(
                                     format!(
-                                        "json_extract({}, '$.price_amount')",
-                                        CatalogSchema::ItemVariationData.to_string()
+                                        "{}.price_amount",
+                                        CatalogDataSchema::Variations.to_string()
                                     )
                                     .as_str(),
                                 ),
@@ -627,8 +789,8 @@ impl CatalogService for CatalogSQLService {
                             );
                         }
                         CatalogColumnOrder::CreatedAt => {
-                            q.order_by_expr(
-                                Expr::cust(CatalogSchema::CreatedAt.to_string().as_str()),
+                            q.order_by(
+                                (CatalogSchema::Table, CatalogSchema::CreatedAt),
                                 OrderSql::from(order_by.direction),
                             );
                         }
@@ -636,173 +798,1967 @@ impl CatalogService for CatalogSQLService {
                 },
                 |_| {},
             )
+            .build(QueryBuilder)
+    }
+
+    // how SQLite runs the `list` query, one line per step of the plan
+    pub async fn explain_list(
+        &self,
+        account: &Account,
+        query: &SqlCatalogQueryOptions,
+    ) -> Result<Vec<String>, CatalogError> {
+        let (sql, values) = Self::list_statement(account, query);
+
+        let mut pool = self.pool.acquire().await.map_err(database_error)?;
+
+        let plan: Vec<QueryPlanRow> = bind_query_as(
+            sqlx::query_as(&format!("EXPLAIN QUERY PLAN {}", sql)),
+            &values,
+        )
+        .fetch_all(&mut pool)
+        .await
+        .map_err(database_error)?;
+
+        Ok(plan.into_iter().map(|step| step.detail).collect())
+    }
+
+    fn get_sql_to_exists(&self) -> String {
+        let (sql, _) = Qsql::select()
+            .expr(Expr::cust("COUNT(1) as count"))
+            .from(CatalogSchema::Table)
+            .and_where(Expr::col(CatalogSchema::Id).eq("-1"))
+            .and_where(Expr::col(CatalogSchema::Account).eq("-1"))
+            .build(QueryBuilder);
+
+        println!("sql {:?}", sql);
+        sql
+    }
+}
+
+#[async_trait]
+impl CatalogService for CatalogSQLService {
+    type Id = Id;
+    type Query = SqlCatalogQueryOptions;
+
+    async fn create(
+        &self,
+        account: &Account,
+        catalog_entry: &CatalogObject<Id>,
+    ) -> Result<SqlCatalogObjectDocument, CatalogError> {
+        validate(catalog_entry)?;
+        self.insert(account, &rand::random::<Id>(), catalog_entry)
+            .await
+    }
+
+    async fn bulk_create(
+        &self,
+        account: &Account,
+        catalog: &[CatalogObjectBulkDocument<String>],
+    ) -> Result<Vec<SqlCatalogObjectDocument>, CatalogError> {
+        bulk_create(self, account, catalog).await
+    }
+
+    async fn exists(&self, _account: &Account, id: &Id) -> Result<bool, CatalogError> {
+        let mut pool = self.pool.acquire().await.map_err(database_error)?;
+
+        println!("Check if exists {:?} {:?}", _account, id);
+
+        let catalog_row: Count = sqlx::query_as(self.get_sql_to_exists().as_str())
+            .bind(id)
+            .bind(_account)
+            .fetch_one(&mut pool)
+            .await
+            .map_err(|err| match err {
+                sqlx::Error::RowNotFound => CatalogError::CatalogEntryNotFound(id.to_string()),
+                err => database_error(err),
+            })?;
+
+        println!("row count {:?}", catalog_row);
+
+        Ok(catalog_row.count != 0)
+    }
+
+    async fn read(
+        &self,
+        _account: &Account,
+        id: &Id,
+    ) -> Result<SqlCatalogObjectDocument, CatalogError> {
+        let (sql, values) = Self::select_catalog_objects()
+            .and_where(Expr::tbl(CatalogSchema::Table, CatalogSchema::Id).eq(*id))
             .build(QueryBuilder);
 
-        let mut pool = self
-            .pool
-            .acquire()
+        let mut pool = self.pool.acquire().await.map_err(database_error)?;
+        let catalog_row: CatalogObjectRow = bind_query_as(sqlx::query_as(&sql), &values)
+            .fetch_one(&mut pool)
+            .await
+            .map_err(|err| match err {
+                sqlx::Error::RowNotFound => CatalogError::CatalogEntryNotFound(id.to_string()),
+                err => database_error(err),
+            })?;
+
+        catalog_row.to_catalog_entry_document()
+    }
+
+    async fn update(
+        &self,
+        account: &Account,
+        id: &Id,
+        catalog_entry: &CatalogObject<Id>,
+    ) -> Result<SqlCatalogObjectDocument, CatalogError> {
+        self.update_object(account, id, catalog_entry, None).await
+    }
+
+    async fn update_at_version(
+        &self,
+        account: &Account,
+        id: &Id,
+        catalog_entry: &CatalogObject<Id>,
+        version: &NaiveDateTime,
+    ) -> Result<SqlCatalogObjectDocument, CatalogError> {
+        self.update_object(account, id, catalog_entry, Some(version))
             .await
-            .map_err(|_| CatalogError::DatabaseError)?;
+    }
+
+    async fn delete(
+        &self,
+        account: &Account,
+        id: &Id,
+    ) -> Result<SqlCatalogObjectDocument, CatalogError> {
+        let committed = self.retry_busy(|| self.delete_rows(account, id)).await?;
+        Ok(self.publish(committed).await)
+    }
+
+    async fn list(
+        &self,
+        account: &Account,
+        query: &Self::Query,
+    ) -> Result<Vec<SqlCatalogObjectDocument>, CatalogError> {
+        let (sql, values) = Self::list_statement(account, query);
+
+        let mut pool = self.pool.acquire().await.map_err(database_error)?;
 
         let result: Vec<CatalogObjectRow> = bind_query_as(sqlx::query_as(&sql), &values)
             .fetch_all(&mut pool)
             .await
-            .map_err(|_| CatalogError::DatabaseError)?;
+            .map_err(database_error)?;
 
-        Ok(result
+        // a row that can't be read fails the list instead of the server
+        result
             .into_iter()
-            .map(|x| x.to_catalog_entry_document().unwrap())
-            .collect())
+            .map(|x| x.to_catalog_entry_document())
+            .collect()
     }
 }
 
-async fn increase_item_variation_units(
-    pool: &Pool,
-    account: &Account,
-    options: &IncreaseItemVariationUnitsPayload<Id>,
-) -> Result<(), CatalogError> {
-    let mut tx = pool
-        .begin()
+fn database_error(err: sqlx::Error) -> CatalogError {
+    busy_or(err, CatalogError::database)
+}
+
+// a locked database is told apart so the write can be retried
+fn busy_or(err: sqlx::Error, other: fn(sqlx::Error) -> CatalogError) -> CatalogError {
+    if is_busy(&err) {
+        CatalogError::DatabaseBusy
+    } else {
+        other(err)
+    }
+}
+
+// the object as the transaction sees it
+async fn read_document(
+    tx: &mut Transaction<'_, Sqlite>,
+    id: &Id,
+) -> Result<SqlCatalogObjectDocument, CatalogError> {
+    let (sql, values) = CatalogSQLService::select_catalog_objects()
+        .and_where(Expr::tbl(CatalogSchema::Table, CatalogSchema::Id).eq(*id))
+        .build(QueryBuilder);
+
+    let catalog_row: CatalogObjectRow = bind_query_as(sqlx::query_as(&sql), &values)
+        .fetch_one(&mut *tx)
         .await
-        .map_err(|_| CatalogError::DatabaseError)?;
+        .map_err(|err| match err {
+            sqlx::Error::RowNotFound => CatalogError::CatalogEntryNotFound(id.to_string()),
+            err => database_error(err),
+        })?;
 
-    let sql_increase_expr = format!(
-        "json_set({col_name}, '$.available_units', (
-          json_extract({col_name}, '$.available_units') + ?
-        ))",
-        col_name = CatalogSchema::ItemVariationData.to_string()
-    );
+    catalog_row.to_catalog_entry_document()
+}
 
+// the next version of an object, ahead of the last one even when the clock isn't
+const NEXT_VERSION: &str = "max(
+    strftime('%Y-%m-%d %H:%M:%f', 'now'),
+    strftime('%Y-%m-%d %H:%M:%f', version, '+0.001 seconds')
+)";
+
+// Moves the version of the object forward. With `expected` only an object
+// still at that version moves, false is returned for any other.
+async fn bump_version(
+    tx: &mut Transaction<'_, Sqlite>,
+    account: &Account,
+    id: &Id,
+    expected: Option<&NaiveDateTime>,
+) -> Result<bool, CatalogError> {
     let (sql, values) = Qsql::update()
         .table(CatalogSchema::Table)
-        .value_expr(
-            CatalogSchema::ItemVariationData,
-            Expr::cust_with_values(sql_increase_expr.as_str(), vec![options.units]),
-        )
-        .and_where(Expr::cust_with_values(
-            "id = ?",
-            vec![options.id.to_string()],
-        ))
+        .value_expr(CatalogSchema::Version, Expr::cust(NEXT_VERSION))
+        .and_where(Expr::col(CatalogSchema::Id).eq(*id))
         .and_where(Expr::col(CatalogSchema::Account).eq(account.to_string()))
+        // the stored text may have fewer decimals than the version it was read as
+        .and_where_option(expected.map(|version| {
+            Expr::cust_with_values(
+                "julianday(version) = julianday(?)",
+                vec![version.format("%Y-%m-%d %H:%M:%S%.f").to_string()],
+            )
+        }))
+        .build(QueryBuilder);
+
+    let result = bind_query(sqlx::query(&sql), &values)
+        .execute(&mut *tx)
+        .await
+        .map_err(database_error)?;
+    Ok(result.rows_affected() != 0)
+}
+
+// the object as the transaction sees it, none when the account doesn't own it
+async fn read_owned(
+    tx: &mut Transaction<'_, Sqlite>,
+    account: &Account,
+    id: &Id,
+) -> Result<Option<SqlCatalogObjectDocument>, CatalogError> {
+    match read_document(tx, id).await {
+        Ok(document) if &document.account == account => Ok(Some(document)),
+        Ok(_) | Err(CatalogError::CatalogEntryNotFound(_)) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+// records an alert when the units of the variation crossed its reorder threshold
+// the alert is kept along the change that raised it, so it's delivered from the log too
+async fn record_reorder_alert(
+    tx: &mut Transaction<'_, Sqlite>,
+    before: Option<&SqlCatalogObjectDocument>,
+    change: &SqlCatalogChange,
+) -> Result<Option<SqlStockAlert>, CatalogError> {
+    let after = &change.document;
+    let previous_units = match before.map(|document| &document.catalog_object) {
+        Some(CatalogObject::Variation(variation)) => variation.available_units,
+        _ => return Ok(None),
+    };
+    let variation = match &after.catalog_object {
+        CatalogObject::Variation(variation) => variation,
+        _ => return Ok(None),
+    };
+    let threshold = match variation.reorder_threshold {
+        Some(threshold) => threshold,
+        None => return Ok(None),
+    };
+    if variation.available_units > threshold || previous_units <= threshold {
+        return Ok(None);
+    }
+
+    let (sql, _) = Qsql::insert()
+        .into_table(StockAlertSchema::Table)
+        .columns(vec![
+            StockAlertSchema::Id,
+            StockAlertSchema::Account,
+            StockAlertSchema::VariationId,
+            StockAlertSchema::AvailableUnits,
+            StockAlertSchema::ReorderThreshold,
+            StockAlertSchema::Sequence,
+        ])
+        .exprs_panic(vec![
+            Expr::value("$1"),
+            Expr::value("$2"),
+            Expr::value("$3"),
+            Expr::value("$4"),
+            Expr::value("$5"),
+            Expr::value("$6"),
+        ])
+        .returning(Qsql::select().expr(Expr::asterisk()).take())
+        .build(QueryBuilder);
+
+    let alert: StockAlertRow = sqlx::query_as(sql.as_str())
+        .bind(rand::random::<Id>())
+        .bind(&after.account)
+        .bind(after.id)
+        .bind(variation.available_units)
+        .bind(threshold)
+        .bind(change.sequence)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(database_error)?;
+
+    Ok(Some(alert.into()))
+}
+
+// the alerts raised by a change of the log
+pub(crate) async fn change_alerts(
+    tx: &mut Transaction<'_, Sqlite>,
+    account: &Account,
+    sequence: i64,
+) -> Result<Vec<SqlStockAlert>, CatalogError> {
+    let (sql, values) = Qsql::select()
+        .expr(Expr::asterisk())
+        .from(StockAlertSchema::Table)
+        .and_where(Expr::col(StockAlertSchema::Account).eq(account.to_string()))
+        .and_where(Expr::col(StockAlertSchema::Sequence).eq(sequence))
         .build(QueryBuilder);
 
-    bind_query(sqlx::query(&sql), &values)
-        .execute(&mut tx)
+    let result: Vec<StockAlertRow> = bind_query_as(sqlx::query_as(&sql), &values)
+        .fetch_all(&mut *tx)
         .await
-        .map_err(|_| CatalogError::DatabaseError)?;
+        .map_err(database_error)?;
+
+    Ok(result.into_iter().map(|row| row.into()).collect())
+}
+
+// appends the change to the log of the account in the transaction of the write
+async fn record_change(
+    tx: &mut Transaction<'_, Sqlite>,
+    operation: CatalogChangeOperation,
+    document: &SqlCatalogObjectDocument,
+    cmd: Option<&SQlCatalogCmd>,
+) -> Result<SqlCatalogChange, CatalogError> {
+    // every written state is kept as a revision, deletes only end the history
+    if operation != CatalogChangeOperation::Deleted {
+        record_revision(tx, document).await?;
+    }
+    append_change(tx, operation, document, cmd).await
+}
+
+// the change alone, the imports bring the revisions with them
+async fn append_change(
+    tx: &mut Transaction<'_, Sqlite>,
+    operation: CatalogChangeOperation,
+    document: &SqlCatalogObjectDocument,
+    cmd: Option<&SQlCatalogCmd>,
+) -> Result<SqlCatalogChange, CatalogError> {
+    // the sequence is computed in the same statement so it stays monotonic per account
+    let change: CatalogChangeRow = sqlx::query_as(
+        "INSERT INTO catalog_changes (account, sequence, object_id, operation, document, cmd)
+        SELECT ?, COALESCE(MAX(sequence), 0) + 1, ?, ?, ?, ? FROM catalog_changes WHERE account = ?
+        RETURNING *",
+    )
+    .bind(&document.account)
+    .bind(document.id)
+    .bind(
+        serde_json::to_value(operation)
+            .map_err(CatalogError::mapping)?
+            .as_str(),
+    )
+    .bind(Json(document))
+    .bind(cmd.map(Json))
+    .bind(&document.account)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(database_error)?;
+
+    change.try_into()
+}
+
+async fn record_revision(
+    tx: &mut Transaction<'_, Sqlite>,
+    document: &SqlCatalogObjectDocument,
+) -> Result<(), CatalogError> {
+    sqlx::query(
+        "INSERT INTO catalog_revisions (account, object_id, revision, document)
+        SELECT ?, ?, COALESCE(MAX(revision), 0) + 1, ? FROM catalog_revisions WHERE object_id = ?",
+    )
+    .bind(&document.account)
+    .bind(document.id)
+    .bind(Json(document))
+    .bind(document.id)
+    .execute(&mut *tx)
+    .await
+    .map_err(database_error)?;
 
-    tx.commit().await.map_err(|_| CatalogError::DatabaseError)?;
     Ok(())
 }
 
-fn make_id_by_index(id: Id) -> String {
-    return format!("#{}-index", id);
+// writes a stored document keeping its version and creation time
+async fn write_document(
+    tx: &mut Transaction<'_, Sqlite>,
+    account: &Account,
+    document: &SqlCatalogObjectDocument,
+) -> Result<(), CatalogError> {
+    sqlx::query(
+        "INSERT INTO catalogs (id, account, type_entry, version, created_at)
+        VALUES ($1, $2, $3, $4, $5)",
+    )
+    .bind(document.id)
+    .bind(account)
+    .bind(document.catalog_object.to_string())
+    .bind(document.version)
+    .bind(document.created_at)
+    .execute(&mut *tx)
+    .await
+    .map_err(database_error)?;
+    write_catalog_data(tx, &document.id, &document.catalog_object).await
 }
 
-#[async_trait]
-impl Commander for CatalogSQLService {
-    type Cmd = CatalogCmd<Id>;
-    type Account = Account;
+async fn write_location(
+    tx: &mut Transaction<'_, Sqlite>,
+    account: &Account,
+    id: &Id,
+    document: &SqlStockLocationDocument,
+) -> Result<(), CatalogError> {
+    sqlx::query(
+        "INSERT INTO stock_locations (id, account, location_data, version, created_at)
+        VALUES ($1, $2, $3, $4, $5)",
+    )
+    .bind(id)
+    .bind(account)
+    .bind(Json(&document.location))
+    .bind(document.version)
+    .bind(document.created_at)
+    .execute(&mut *tx)
+    .await
+    .map_err(database_error)?;
+    Ok(())
+}
 
-    async fn cmd(&self, account: &Self::Account, cmd: Self::Cmd) -> Result<(), CatalogError> {
-        match cmd {
-            Self::Cmd::IncreaseItemVariationUnits(options) => {
-                increase_item_variation_units(&self.pool, account, &options).await?;
-                Ok(())
-            }
+async fn write_stock_level(
+    tx: &mut Transaction<'_, Sqlite>,
+    account: &Account,
+    level: &StockLevel<Id>,
+) -> Result<(), CatalogError> {
+    sqlx::query(
+        "INSERT INTO stock_levels (account, location_id, variation_id, units)
+        VALUES ($1, $2, $3, $4)",
+    )
+    .bind(account)
+    .bind(level.location_id)
+    .bind(level.variation_id)
+    .bind(level.units)
+    .execute(&mut *tx)
+    .await
+    .map_err(database_error)?;
+    Ok(())
+}
+
+// writes the fields of the object in the table of its type
+async fn write_catalog_data(
+    tx: &mut Transaction<'_, Sqlite>,
+    id: &Id,
+    catalog_entry: &CatalogObject<Id>,
+) -> Result<(), CatalogError> {
+    let table = CatalogDataSchema::of(catalog_entry);
+    let columns = table.columns();
+    let sql = format!(
+        "INSERT INTO {table} (id, {columns}) VALUES (?{params})
+        ON CONFLICT (id) DO UPDATE SET {updates}",
+        table = table.to_string(),
+        columns = columns.join(", "),
+        params = ", ?".repeat(columns.len()),
+        updates = columns
+            .iter()
+            .map(|column| format!("{column} = excluded.{column}"))
+            .collect::<Vec<_>>()
+            .join(", "),
+    );
+
+    let query = sqlx::query(sql.as_str()).bind(id);
+    let query = match catalog_entry {
+        CatalogObject::Item(item) => query
+            .bind(&item.name)
+            .bind(&item.description)
+            .bind(to_text(&item.category)?)
+            .bind(Json(&item.tags))
+            .bind(Json(&item.images))
+            .bind(item.enabled)
+            .bind(item.warranty_time.as_ref().map(Json)),
+        CatalogObject::Variation(variation) => {
+            let Price::Fixed {
+                amount,
+                asset_name,
+                asset_scale,
+            } = &variation.price;
+            query
+                .bind(variation.item_id)
+                .bind(&variation.name)
+                .bind(&variation.sku)
+                .bind(variation.upc.as_deref())
+                .bind(Json(&variation.images))
+                .bind(variation.enabled)
+                .bind(to_text(&variation.measurement_units)?)
+                .bind(variation.available_units)
+                .bind(variation.reorder_threshold)
+                .bind(variation.processing_time.as_ref().map(Json))
+                .bind("Fixed")
+                .bind(amount)
+                .bind(asset_name)
+                .bind(asset_scale)
+                .bind(variation.extra_attributes.as_ref().map(Json))
+        }
+        CatalogObject::Modification(modification) => {
+            let Price::Fixed {
+                amount,
+                asset_name,
+                asset_scale,
+            } = &modification.price;
+            query
+                .bind(modification.item_id)
+                .bind(&modification.name)
+                .bind(Json(&modification.images))
+                .bind(modification.enabled)
+                .bind(modification.processing_time.as_ref().map(Json))
+                .bind(modification.warranty_time.as_ref().map(Json))
+                .bind("Fixed")
+                .bind(amount)
+                .bind(asset_name)
+                .bind(asset_scale)
         }
+        CatalogObject::Delivery(delivery) => {
+            let Delivery::Shipping {
+                width_mm,
+                length_mm,
+                height_mm,
+                weight_grams,
+            } = &delivery.delivery;
+            query
+                .bind(delivery.item_id)
+                .bind("Shipping")
+                .bind(width_mm)
+                .bind(length_mm)
+                .bind(height_mm)
+                .bind(weight_grams)
+        }
+        CatalogObject::Control(control) => {
+            // {type, data} json value
+            let mut value =
+                serde_json::to_value(&control.control).map_err(CatalogError::mapping)?;
+            query
+                .bind(control.item_id)
+                .bind(to_text(&value["type"])?)
+                .bind(Json(value["data"].take()))
+        }
+    };
+
+    query.execute(&mut *tx).await.map_err(database_error)?;
+    Ok(())
+}
+
+fn to_json<T: Serialize>(value: Option<&T>) -> Result<Option<serde_json::Value>, CatalogError> {
+    value
+        .map(|value| serde_json::to_value(value).map_err(CatalogError::mapping))
+        .transpose()
+}
+
+// enums are stored by the name of their variant
+fn to_text<T: Serialize>(value: &T) -> Result<String, CatalogError> {
+    match serde_json::to_value(value).map_err(CatalogError::mapping)? {
+        serde_json::Value::String(text) => Ok(text),
+        other => Err(CatalogError::mapping(format!(
+            "{} isn't a variant name",
+            other
+        ))),
     }
 }
-#[derive(Serialize, Deserialize, Debug, FromRow)]
-struct Count {
-    count: i64,
+
+fn from_text<T: DeserializeOwned>(text: Option<String>) -> Result<T, CatalogError> {
+    serde_json::from_value(required(text)?.into()).map_err(CatalogError::mapping)
 }
-#[derive(Serialize, Deserialize, Debug, FromRow)]
-pub struct CatalogObjectRow {
-    pub id: Id,
-    pub account: String,
-    pub version: NaiveDateTime,
-    pub type_entry: String,
-    pub item_data: Option<Json<Item>>,
-    pub item_variation_data: Option<Json<ItemVariation<Id>>>,
-    pub item_modification_data: Option<Json<ItemModification<Id>>>,
-    pub item_control_data: Option<Json<ItemControl<Id>>>,
-    pub item_delivery_data: Option<Json<ItemDelivery<Id>>>,
-    pub created_at: NaiveDateTime,
+
+fn required<T>(value: Option<T>) -> Result<T, CatalogError> {
+    value.ok_or_else(|| CatalogError::mapping("a required column is null"))
 }
 
-impl CatalogObjectRow {
-    pub fn to_catalog_entry(&self) -> Result<CatalogObject<Id>, CatalogError> {
-        let mut value = serde_json::Map::new();
-        let type_entry_str: &str = self.type_entry.as_str();
+async fn add_item_variation_units(
+    tx: &mut Transaction<'_, Sqlite>,
+    account: &Account,
+    id: &Id,
+    units: i32,
+) -> Result<(), CatalogError> {
+    let available_units: Option<i32> = sqlx::query_scalar(
+        format!(
+            "UPDATE {variations} SET available_units = available_units + $1
+            WHERE id = $2 AND id IN (SELECT id FROM {table} WHERE account = $3)
+            RETURNING available_units",
+            variations = CatalogDataSchema::Variations.to_string(),
+            table = CatalogSchema::Table.to_string(),
+        )
+        .as_str(),
+    )
+    .bind(units)
+    .bind(id)
+    .bind(account)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(database_error)?;
+
+    match available_units {
+        // the units of a variation never go below zero
+        Some(units) if units < 0 => Err(CatalogError::InsufficientUnits(id.to_string())),
+        Some(_) => bump_version(tx, account, id, None).await.map(|_| ()),
+        None => Err(CatalogError::CatalogEntryNotFound(id.to_string())),
+    }
+}
+
+async fn insert_location(
+    tx: &mut Transaction<'_, Sqlite>,
+    account: &Account,
+    id: &Id,
+    location: &StockLocation,
+) -> Result<SqlStockLocationDocument, CatalogError> {
+    let (sql, _) = Qsql::insert()
+        .into_table(StockLocationSchema::Table)
+        .columns(vec![
+            StockLocationSchema::Id,
+            StockLocationSchema::Account,
+            StockLocationSchema::LocationData,
+        ])
+        .exprs_panic(vec![
+            Expr::value("$1"),
+            Expr::value("$2"),
+            Expr::value("$3"),
+        ])
+        .returning(Qsql::select().expr(Expr::asterisk()).take())
+        .build(QueryBuilder);
 
-        value.insert("type".to_string(), type_entry_str.into());
+    let result: StockLocationRow = sqlx::query_as(sql.as_str())
+        .bind(id)
+        .bind(account)
+        .bind(Json(location))
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(database_error)?;
 
-        let data = match Some(type_entry_str) {
-            Some("Item") => serde_json::to_value(self.item_data.as_ref().unwrap()),
-            Some("Variation") => serde_json::to_value(self.item_variation_data.as_ref().unwrap()),
-            Some("Modification") => {
-                serde_json::to_value(self.item_modification_data.as_ref().unwrap())
-            }
-            Some("Control") => serde_json::to_value(self.item_control_data.as_ref().unwrap()),
-            Some("Delivery") => serde_json::to_value(self.item_delivery_data.as_ref().unwrap()),
-            _ => {
-                println!("mapping not found");
-                return Err(CatalogError::CatalogBadRequest);
-            }
-        }
-        .map_err(|_| CatalogError::MappingError);
+    Ok(result.into())
+}
+
+async fn read_owned_location(
+    tx: &mut Transaction<'_, Sqlite>,
+    account: &Account,
+    id: &Id,
+) -> Result<SqlStockLocationDocument, CatalogError> {
+    let (sql, values) = Qsql::select()
+        .expr(Expr::asterisk())
+        .from(StockLocationSchema::Table)
+        .and_where(Expr::col(StockLocationSchema::Id).eq(*id))
+        .and_where(Expr::col(StockLocationSchema::Account).eq(account.to_string()))
+        .build(QueryBuilder);
 
-        value.insert("data".to_string(), data?);
+    let result: StockLocationRow = bind_query_as(sqlx::query_as(&sql), &values)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|err| match err {
+            sqlx::Error::RowNotFound => CatalogError::CatalogEntryNotFound(id.to_string()),
+            err => database_error(err),
+        })?;
 
-        let value: CatalogObject<Id> = serde_json::from_value(serde_json::Value::Object(value))
-            .map_err(|_| CatalogError::MappingError)?;
+    Ok(result.into())
+}
 
-        Ok(value)
+async fn add_stock_level_units(
+    tx: &mut Transaction<'_, Sqlite>,
+    account: &Account,
+    location_id: &Id,
+    variation_id: &Id,
+    units: i32,
+) -> Result<(), CatalogError> {
+    let level: StockLevelRow = sqlx::query_as(
+        "INSERT INTO stock_levels (account, location_id, variation_id, units) VALUES ($1, $2, $3, $4)
+        ON CONFLICT (account, location_id, variation_id)
+        DO UPDATE SET units = units + excluded.units, version = CURRENT_TIMESTAMP
+        RETURNING location_id, variation_id, units",
+    )
+    .bind(account)
+    .bind(location_id)
+    .bind(variation_id)
+    .bind(units)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(database_error)?;
+
+    if level.units < 0 {
+        return Err(CatalogError::InsufficientUnits(variation_id.to_string()));
     }
+    Ok(())
+}
 
-    pub fn to_catalog_entry_document(self) -> Result<SqlCatalogObjectDocument, CatalogError> {
-        let entry = self.to_catalog_entry()?;
-        Ok(SqlCatalogObjectDocument {
-            account: self.account,
-            created_at: self.created_at,
-            catalog_object: entry,
-            id: self.id,
-            version: self.version,
-        })
+async fn check_stock_references(
+    tx: &mut Transaction<'_, Sqlite>,
+    account: &Account,
+    variation_id: &Id,
+    location_ids: &[&Id],
+) -> Result<(), CatalogError> {
+    let variation: Count = sqlx::query_as(
+        "SELECT COUNT(1) as count FROM catalogs WHERE id = $1 AND account = $2 AND type_entry = 'Variation'",
+    )
+    .bind(variation_id)
+    .bind(account)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(database_error)?;
+
+    if variation.count == 0 {
+        return Err(CatalogError::CatalogEntryNotFound(variation_id.to_string()));
+    }
+
+    for location_id in location_ids {
+        let location: Count = sqlx::query_as(
+            "SELECT COUNT(1) as count FROM stock_locations WHERE id = $1 AND account = $2",
+        )
+        .bind(location_id)
+        .bind(account)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(database_error)?;
+
+        if location.count == 0 {
+            return Err(CatalogError::CatalogEntryNotFound(location_id.to_string()));
+        }
     }
+    Ok(())
 }
 
-pub enum CatalogSchema {
-    Table,
-    Id,
-    Account,
-    TypeEntry,
-    _Version,
-    ItemData,
-    ItemVariationData,
-    ItemModificationData,
-    ItemDeliveryData,
-    ItemControlData,
-    CreatedAt,
+async fn increase_item_variation_units_at(
+    tx: &mut Transaction<'_, Sqlite>,
+    account: &Account,
+    options: &IncreaseItemVariationUnitsAtPayload<Id>,
+) -> Result<(), CatalogError> {
+    check_stock_references(tx, account, &options.id, &[&options.location_id]).await?;
+    add_stock_level_units(
+        tx,
+        account,
+        &options.location_id,
+        &options.id,
+        options.units,
+    )
+    .await?;
+    add_item_variation_units(tx, account, &options.id, options.units).await
 }
 
-impl Iden for CatalogSchema {
-    fn unquoted(&self, s: &mut dyn std::fmt::Write) {
-        write!(
-            s,
-            "{}",
-            match self {
-                Self::Table => "catalogs",
-                Self::Id => "id",
-                Self::Account => "account",
-                Self::ItemData => "item_data",
-                Self::ItemVariationData => "item_variation_data",
-                Self::ItemModificationData => "item_modification_data",
-                Self::ItemDeliveryData => "item_delivery_data",
-                Self::ItemControlData => "item_control_data",
-                Self::TypeEntry => "type_entry",
-                Self::_Version => "version",
+async fn transfer_item_variation_units(
+    tx: &mut Transaction<'_, Sqlite>,
+    account: &Account,
+    options: &TransferItemVariationUnitsPayload<Id>,
+) -> Result<(), CatalogError> {
+    if options.units <= 0 || options.from_location_id == options.to_location_id {
+        return Err(CatalogError::invalid_transfer());
+    }
+
+    check_stock_references(
+        tx,
+        account,
+        &options.id,
+        &[&options.from_location_id, &options.to_location_id],
+    )
+    .await?;
+    add_stock_level_units(
+        tx,
+        account,
+        &options.from_location_id,
+        &options.id,
+        -options.units,
+    )
+    .await?;
+    add_stock_level_units(
+        tx,
+        account,
+        &options.to_location_id,
+        &options.id,
+        options.units,
+    )
+    .await
+}
+
+#[async_trait]
+impl Commander for CatalogSQLService {
+    type Cmd = CatalogCmd<Id>;
+    type Account = Account;
+
+    async fn cmd(&self, account: &Self::Account, cmd: Self::Cmd) -> Result<(), CatalogError> {
+        // commands on unknown variations don't change anything
+        if let Some(committed) = self.retry_busy(|| self.cmd_rows(account, &cmd)).await? {
+            self.publish(committed).await;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl CatalogAuditLog for CatalogSQLService {
+    async fn audit(
+        &self,
+        account: &Account,
+        options: &ListAuditOptions,
+    ) -> Result<Vec<SqlAuditEntry>, CatalogError> {
+        let (sql, values) = Qsql::select()
+            .expr(Expr::asterisk())
+            .from(AuditSchema::Table)
+            .and_where(Expr::col(AuditSchema::Account).eq(account.to_string()))
+            .and_where_option(
+                options
+                    .actor
+                    .as_ref()
+                    .map(|actor| Expr::col(AuditSchema::Actor).eq(actor.to_string())),
+            )
+            .and_where_option(
+                options
+                    .from
+                    .map(|from| Expr::col(AuditSchema::CreatedAt).gte(from.to_string())),
+            )
+            .and_where_option(
+                options
+                    .to
+                    .map(|to| Expr::col(AuditSchema::CreatedAt).lte(to.to_string())),
+            )
+            .order_by(AuditSchema::Id, OrderSql::Asc)
+            .conditions(
+                options.limit.is_some(),
+                |q| {
+                    q.limit(options.limit.unwrap().into());
+                },
+                |_| {},
+            )
+            .build(QueryBuilder);
+
+        let mut pool = self.pool.acquire().await.map_err(database_error)?;
+
+        let result: Vec<AuditRow> = bind_query_as(sqlx::query_as(&sql), &values)
+            .fetch_all(&mut pool)
+            .await
+            .map_err(database_error)?;
+
+        result.into_iter().map(|row| row.try_into()).collect()
+    }
+}
+
+#[async_trait]
+impl CatalogRevisionService for CatalogSQLService {
+    async fn read_revision(
+        &self,
+        account: &Account,
+        id: &Id,
+        revision: i64,
+    ) -> Result<SqlCatalogRevision, CatalogError> {
+        let (sql, values) = Qsql::select()
+            .expr(Expr::asterisk())
+            .from(CatalogRevisionSchema::Table)
+            .and_where(Expr::col(CatalogRevisionSchema::Account).eq(account.to_string()))
+            .and_where(Expr::col(CatalogRevisionSchema::ObjectId).eq(*id))
+            .and_where(Expr::col(CatalogRevisionSchema::Revision).eq(revision))
+            .build(QueryBuilder);
+
+        let mut pool = self.pool.acquire().await.map_err(database_error)?;
+
+        let result: CatalogRevisionRow = bind_query_as(sqlx::query_as(&sql), &values)
+            .fetch_one(&mut pool)
+            .await
+            .map_err(|err| match err {
+                sqlx::Error::RowNotFound => CatalogError::CatalogEntryNotFound(id.to_string()),
+                err => database_error(err),
+            })?;
+
+        Ok(result.into())
+    }
+
+    async fn read_revision_at(
+        &self,
+        account: &Account,
+        id: &Id,
+        at: &NaiveDateTime,
+    ) -> Result<SqlCatalogRevision, CatalogError> {
+        let (sql, values) = Qsql::select()
+            .expr(Expr::asterisk())
+            .from(CatalogRevisionSchema::Table)
+            .and_where(Expr::col(CatalogRevisionSchema::Account).eq(account.to_string()))
+            .and_where(Expr::col(CatalogRevisionSchema::ObjectId).eq(*id))
+            .and_where(Expr::col(CatalogRevisionSchema::CreatedAt).lte(at.to_string()))
+            .order_by(CatalogRevisionSchema::Revision, OrderSql::Desc)
+            .limit(1)
+            .build(QueryBuilder);
+
+        let mut pool = self.pool.acquire().await.map_err(database_error)?;
+
+        let result: CatalogRevisionRow = bind_query_as(sqlx::query_as(&sql), &values)
+            .fetch_one(&mut pool)
+            .await
+            .map_err(|err| match err {
+                sqlx::Error::RowNotFound => CatalogError::CatalogEntryNotFound(id.to_string()),
+                err => database_error(err),
+            })?;
+
+        Ok(result.into())
+    }
+
+    async fn list_revisions(
+        &self,
+        account: &Account,
+        id: &Id,
+    ) -> Result<Vec<SqlCatalogRevision>, CatalogError> {
+        let (sql, values) = Qsql::select()
+            .expr(Expr::asterisk())
+            .from(CatalogRevisionSchema::Table)
+            .and_where(Expr::col(CatalogRevisionSchema::Account).eq(account.to_string()))
+            .and_where(Expr::col(CatalogRevisionSchema::ObjectId).eq(*id))
+            .order_by(CatalogRevisionSchema::Revision, OrderSql::Asc)
+            .build(QueryBuilder);
+
+        let mut pool = self.pool.acquire().await.map_err(database_error)?;
+
+        let result: Vec<CatalogRevisionRow> = bind_query_as(sqlx::query_as(&sql), &values)
+            .fetch_all(&mut pool)
+            .await
+            .map_err(database_error)?;
+
+        Ok(result.into_iter().map(|row| row.into()).collect())
+    }
+
+    async fn diff_revisions(
+        &self,
+        account: &Account,
+        id: &Id,
+        from: i64,
+        to: i64,
+    ) -> Result<CatalogRevisionDiff, CatalogError> {
+        let before = self.read_revision(account, id, from).await?;
+        let after = self.read_revision(account, id, to).await?;
+
+        let before =
+            serde_json::to_value(&before.document.catalog_object).map_err(CatalogError::mapping)?;
+        let after =
+            serde_json::to_value(&after.document.catalog_object).map_err(CatalogError::mapping)?;
+
+        Ok(CatalogRevisionDiff {
+            from,
+            to,
+            changes: diff(&before, &after),
+        })
+    }
+
+    async fn restore_revision(
+        &self,
+        account: &Account,
+        id: &Id,
+        revision: i64,
+    ) -> Result<SqlCatalogObjectDocument, CatalogError> {
+        let revision = self.read_revision(account, id, revision).await?;
+        let catalog_object = &revision.document.catalog_object;
+
+        // deleted objects come back with the same id
+        if self.exists(account, id).await? {
+            self.update(account, id, catalog_object).await
+        } else {
+            self.insert(account, id, catalog_object).await
+        }
+    }
+}
+
+#[async_trait]
+impl StockLocationService for CatalogSQLService {
+    async fn create_location(
+        &self,
+        account: &Account,
+        location: &StockLocation,
+    ) -> Result<SqlStockLocationDocument, CatalogError> {
+        let mut tx = self.begin_write().await?;
+        let document = insert_location(&mut tx, account, &rand::random::<Id>(), location).await?;
+        self.record_location_audit(&mut tx, CatalogChangeOperation::Created, None, &document)
+            .await?;
+        tx.commit().await.map_err(database_error)?;
+        Ok(document)
+    }
+
+    async fn read_location(
+        &self,
+        account: &Account,
+        id: &Id,
+    ) -> Result<SqlStockLocationDocument, CatalogError> {
+        let mut tx = self.pool.begin().await.map_err(database_error)?;
+        read_owned_location(&mut tx, account, id).await
+    }
+
+    async fn update_location(
+        &self,
+        account: &Account,
+        id: &Id,
+        location: &StockLocation,
+    ) -> Result<SqlStockLocationDocument, CatalogError> {
+        let (sql, _) = Qsql::update()
+            .table(StockLocationSchema::Table)
+            .value(StockLocationSchema::LocationData, "-1".into())
+            .value_expr(
+                StockLocationSchema::Version,
+                Expr::cust("CURRENT_TIMESTAMP"),
+            )
+            .and_where(Expr::col(StockLocationSchema::Account).eq("-1"))
+            .and_where(Expr::col(StockLocationSchema::Id).eq("-1"))
+            .returning(Qsql::select().expr(Expr::asterisk()).take())
+            .build(QueryBuilder);
+
+        let mut tx = self.begin_write().await?;
+
+        let before = read_owned_location(&mut tx, account, id).await?;
+        let result: StockLocationRow = sqlx::query_as(sql.as_str())
+            .bind(Json(location))
+            .bind(account)
+            .bind(id)
+            .fetch_one(&mut tx)
+            .await
+            .map_err(database_error)?;
+        let document = result.into();
+        self.record_location_audit(
+            &mut tx,
+            CatalogChangeOperation::Updated,
+            Some(&before),
+            &document,
+        )
+        .await?;
+
+        tx.commit().await.map_err(database_error)?;
+        Ok(document)
+    }
+
+    async fn list_locations(
+        &self,
+        account: &Account,
+    ) -> Result<Vec<SqlStockLocationDocument>, CatalogError> {
+        let (sql, values) = Qsql::select()
+            .expr(Expr::asterisk())
+            .from(StockLocationSchema::Table)
+            .and_where(Expr::col(StockLocationSchema::Account).eq(account.to_string()))
+            .order_by(StockLocationSchema::CreatedAt, OrderSql::Asc)
+            .build(QueryBuilder);
+
+        let mut pool = self.pool.acquire().await.map_err(database_error)?;
+
+        let result: Vec<StockLocationRow> = bind_query_as(sqlx::query_as(&sql), &values)
+            .fetch_all(&mut pool)
+            .await
+            .map_err(database_error)?;
+
+        Ok(result.into_iter().map(|row| row.into()).collect())
+    }
+
+    async fn stock_levels(
+        &self,
+        account: &Account,
+        variation_id: &Id,
+    ) -> Result<Vec<StockLevel<Id>>, CatalogError> {
+        let (sql, values) = Qsql::select()
+            .columns(vec![
+                StockLevelSchema::LocationId,
+                StockLevelSchema::VariationId,
+                StockLevelSchema::Units,
+            ])
+            .from(StockLevelSchema::Table)
+            .and_where(Expr::col(StockLevelSchema::Account).eq(account.to_string()))
+            .and_where(Expr::col(StockLevelSchema::VariationId).eq(*variation_id))
+            .order_by(StockLevelSchema::LocationId, OrderSql::Asc)
+            .build(QueryBuilder);
+
+        let mut pool = self.pool.acquire().await.map_err(database_error)?;
+
+        let result: Vec<StockLevelRow> = bind_query_as(sqlx::query_as(&sql), &values)
+            .fetch_all(&mut pool)
+            .await
+            .map_err(database_error)?;
+
+        Ok(result
+            .into_iter()
+            .map(|row| StockLevel {
+                location_id: row.location_id,
+                variation_id: row.variation_id,
+                units: row.units,
+            })
+            .collect())
+    }
+}
+
+#[async_trait]
+impl StockAlertService for CatalogSQLService {
+    async fn list_alerts(&self, account: &Account) -> Result<Vec<SqlStockAlert>, CatalogError> {
+        let (sql, values) = Qsql::select()
+            .expr(Expr::asterisk())
+            .from(StockAlertSchema::Table)
+            .and_where(Expr::col(StockAlertSchema::Account).eq(account.to_string()))
+            .order_by(StockAlertSchema::CreatedAt, OrderSql::Desc)
+            .build(QueryBuilder);
+
+        let mut pool = self.pool.acquire().await.map_err(database_error)?;
+
+        let result: Vec<StockAlertRow> = bind_query_as(sqlx::query_as(&sql), &values)
+            .fetch_all(&mut pool)
+            .await
+            .map_err(database_error)?;
+
+        Ok(result.into_iter().map(|row| row.into()).collect())
+    }
+}
+
+#[async_trait]
+impl CatalogArchiveService for CatalogSQLService {
+    async fn export(&self, account: &Account) -> Result<SqlCatalogArchive, CatalogError> {
+        // a single read transaction so every part is taken from the same snapshot
+        let mut tx = self.pool.begin().await.map_err(database_error)?;
+
+        let (sql, values) = Self::select_catalog_objects()
+            .and_where(
+                Expr::tbl(CatalogSchema::Table, CatalogSchema::Account).eq(account.to_string()),
+            )
+            .order_by(
+                (CatalogSchema::Table, CatalogSchema::CreatedAt),
+                OrderSql::Asc,
+            )
+            .build(QueryBuilder);
+        let objects: Vec<CatalogObjectRow> = bind_query_as(sqlx::query_as(&sql), &values)
+            .fetch_all(&mut tx)
+            .await
+            .map_err(database_error)?;
+
+        let revisions: Vec<CatalogRevisionRow> = sqlx::query_as(
+            "SELECT * FROM catalog_revisions WHERE account = $1 ORDER BY object_id, revision",
+        )
+        .bind(account)
+        .fetch_all(&mut tx)
+        .await
+        .map_err(database_error)?;
+
+        let locations: Vec<StockLocationRow> =
+            sqlx::query_as("SELECT * FROM stock_locations WHERE account = $1 ORDER BY created_at")
+                .bind(account)
+                .fetch_all(&mut tx)
+                .await
+                .map_err(database_error)?;
+
+        let stock_levels: Vec<StockLevelRow> = sqlx::query_as(
+            "SELECT location_id, variation_id, units FROM stock_levels WHERE account = $1
+            ORDER BY variation_id, location_id",
+        )
+        .bind(account)
+        .fetch_all(&mut tx)
+        .await
+        .map_err(database_error)?;
+
+        tx.commit().await.map_err(database_error)?;
+
+        Ok(SqlCatalogArchive {
+            format_version: CATALOG_ARCHIVE_VERSION,
+            account: account.to_string(),
+            exported_at: Utc::now().naive_utc(),
+            objects: objects
+                .into_iter()
+                .map(|row| row.to_catalog_entry_document())
+                .collect::<Result<_, _>>()?,
+            revisions: revisions.into_iter().map(|row| row.into()).collect(),
+            locations: locations.into_iter().map(|row| row.into()).collect(),
+            stock_levels: stock_levels
+                .into_iter()
+                .map(|row| StockLevel {
+                    location_id: row.location_id,
+                    variation_id: row.variation_id,
+                    units: row.units,
+                })
+                .collect(),
+        })
+    }
+
+    async fn import(
+        &self,
+        account: &Account,
+        archive: &SqlCatalogArchive,
+    ) -> Result<CatalogImport<Id>, CatalogError> {
+        if archive.format_version != CATALOG_ARCHIVE_VERSION {
+            return Err(CatalogError::bad_request(format!(
+                "the archive format {} isn't supported",
+                archive.format_version
+            )));
+        }
+
+        // the objects can only point to items that come with them
+        let items: Vec<&Id> = archive
+            .objects
+            .iter()
+            .filter(|document| matches!(document.catalog_object, CatalogObject::Item(_)))
+            .map(|document| &document.id)
+            .collect();
+        for document in archive.objects.iter() {
+            if let Some(item_id) = document.catalog_object.item_id() {
+                if !items.contains(&item_id) {
+                    return Err(CatalogError::BulkReferenceNotExist(item_id.to_string()));
+                }
+            }
+        }
+
+        // the history can mention objects that are gone, they get new ids as well
+        let mut objects: BTreeMap<Id, Id> = BTreeMap::new();
+        let mut remap = |id: &mut Id| *id = *objects.entry(*id).or_insert_with(rand::random);
+        let mut documents: Vec<SqlCatalogObjectDocument> = archive.objects.clone();
+        // items go first, the rest reference them
+        documents
+            .sort_by_key(|document| !matches!(document.catalog_object, CatalogObject::Item(_)));
+        let mut revisions: Vec<SqlCatalogRevision> = archive.revisions.clone();
+        for document in documents
+            .iter_mut()
+            .chain(revisions.iter_mut().map(|revision| &mut revision.document))
+        {
+            remap(&mut document.id);
+            document
+                .catalog_object
+                .references_mut()
+                .into_iter()
+                .for_each(&mut remap);
+            document.account = account.to_string();
+        }
+
+        let locations: BTreeMap<Id, Id> = archive
+            .locations
+            .iter()
+            .map(|location| (location.id, rand::random()))
+            .collect();
+        let mut stock_levels = vec![];
+        for level in archive.stock_levels.iter() {
+            let location_id = locations.get(&level.location_id).ok_or_else(|| {
+                CatalogError::BulkReferenceNotExist(level.location_id.to_string())
+            })?;
+            let variation_id = objects.get(&level.variation_id).ok_or_else(|| {
+                CatalogError::BulkReferenceNotExist(level.variation_id.to_string())
+            })?;
+            stock_levels.push(StockLevel {
+                location_id: *location_id,
+                variation_id: *variation_id,
+                units: level.units,
+            });
+        }
+
+        let changes = self
+            .retry_busy(|| {
+                self.write_archive(
+                    account,
+                    &documents,
+                    &revisions,
+                    &archive.locations,
+                    &locations,
+                    &stock_levels,
+                )
+            })
+            .await?;
+        for change in changes.iter() {
+            self.changes.publish(change);
+        }
+
+        Ok(CatalogImport { objects, locations })
+    }
+
+    async fn backup(&self, path: &str) -> Result<(), CatalogError> {
+        let mut pool = self.pool.acquire().await.map_err(database_error)?;
+
+        sqlx::query("VACUUM INTO $1")
+            .bind(path)
+            .execute(&mut pool)
+            .await
+            .map_err(database_error)?;
+        // in-memory databases are vacuumed into memory as well
+        if !std::path::Path::new(path).exists() {
+            return Err(CatalogError::Unsupported);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl CatalogProjection for CatalogSQLService {
+    async fn list_fields(
+        &self,
+        account: &Account,
+        query: &Self::Query,
+        fields: &Fields,
+    ) -> Result<Vec<serde_json::Value>, CatalogError> {
+        let projection = fields.to_sql("json_object", sqlite_document_field, sqlite_data_field);
+        let (sql, values) =
+            Self::list_columns_statement(&format!("{} AS projection", projection), account, query);
+
+        let mut pool = self.pool.acquire().await.map_err(database_error)?;
+
+        let rows: Vec<ProjectionRow> = bind_query_as(sqlx::query_as(&sql), &values)
+            .fetch_all(&mut pool)
+            .await
+            .map_err(database_error)?;
+
+        Ok(rows.into_iter().map(|row| row.projection.0).collect())
+    }
+}
+
+// the fields of the document as serde writes them, timestamps with a `T`
+fn sqlite_document_field(field: &str) -> String {
+    match field {
+        "type" => "catalogs.type_entry".to_string(),
+        // without the milliseconds serde leaves out when there are none
+        "version" | "created_at" => {
+            format!("replace(replace(catalogs.{}, ' ', 'T'), '.000', '')", field)
+        }
+        _ => format!("catalogs.{}", field),
+    }
+}
+
+// the fields of `data` from the table of the type, the json columns and the
+// booleans are turned back into json
+fn sqlite_data_field(type_entry: &str, field: &str) -> String {
+    let table = match type_entry {
+        "Item" => CatalogDataSchema::Items,
+        "Variation" => CatalogDataSchema::Variations,
+        "Modification" => CatalogDataSchema::Modifications,
+        "Delivery" => CatalogDataSchema::Deliveries,
+        _ => CatalogDataSchema::Controls,
+    }
+    .to_string();
+    match field {
+        "tags" | "images" | "extra_attributes" | "control_data" => {
+            format!("json_extract({}.{}, '$')", table, field)
+        }
+        "enabled" => format!(
+            "json(CASE WHEN {}.enabled THEN 'true' ELSE 'false' END)",
+            table
+        ),
+        "warranty_time_type" => format!("json_extract({}.warranty_time, '$.type')", table),
+        "warranty_time_seconds" => format!("json_extract({}.warranty_time, '$.seconds')", table),
+        "processing_time_type" => format!("json_extract({}.processing_time, '$.type')", table),
+        "processing_time_seconds" => {
+            format!("json_extract({}.processing_time, '$.seconds')", table)
+        }
+        "delivery_width_mm"
+        | "delivery_length_mm"
+        | "delivery_height_mm"
+        | "delivery_weight_grams" => format!("{}.{}", table, &field["delivery_".len()..]),
+        _ => format!("{}.{}", table, field),
+    }
+}
+
+#[async_trait]
+impl CatalogChangeFeed for CatalogSQLService {
+    async fn changes(
+        &self,
+        account: &Account,
+        options: &ListCatalogChangesOptions,
+    ) -> Result<Vec<SqlCatalogChange>, CatalogError> {
+        let (sql, values) = Qsql::select()
+            .expr(Expr::asterisk())
+            .from(CatalogChangeSchema::Table)
+            .and_where(Expr::col(CatalogChangeSchema::Account).eq(account.to_string()))
+            .and_where(Expr::col(CatalogChangeSchema::Sequence).gt(options.since.unwrap_or(0)))
+            .order_by(CatalogChangeSchema::Sequence, OrderSql::Asc)
+            .conditions(
+                options.limit.is_some(),
+                |q| {
+                    q.limit(options.limit.unwrap().into());
+                },
+                |_| {},
+            )
+            .build(QueryBuilder);
+
+        let mut pool = self.pool.acquire().await.map_err(database_error)?;
+
+        let result: Vec<CatalogChangeRow> = bind_query_as(sqlx::query_as(&sql), &values)
+            .fetch_all(&mut pool)
+            .await
+            .map_err(database_error)?;
+
+        result.into_iter().map(|row| row.try_into()).collect()
+    }
+
+    fn subscribe(&self) -> Result<Receiver<SqlCatalogChange>, CatalogError> {
+        Ok(self.changes.subscribe())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, FromRow)]
+struct Count {
+    count: i64,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, FromRow)]
+struct QueryPlanRow {
+    detail: String,
+}
+// one column per field of any type, `COALESCE`d where types share a name
+const CATALOG_OBJECT_COLUMNS: &str = "catalogs.id AS id,
+    catalogs.account AS account,
+    catalogs.version AS version,
+    catalogs.type_entry AS type_entry,
+    catalogs.created_at AS created_at,
+    COALESCE(catalog_variations.item_id, catalog_modifications.item_id, catalog_deliveries.item_id, catalog_controls.item_id) AS item_id,
+    COALESCE(catalog_items.name, catalog_variations.name, catalog_modifications.name) AS name,
+    catalog_items.description AS description,
+    catalog_items.category AS category,
+    catalog_items.tags AS tags,
+    COALESCE(catalog_items.images, catalog_variations.images, catalog_modifications.images) AS images,
+    COALESCE(catalog_items.enabled, catalog_variations.enabled, catalog_modifications.enabled) AS enabled,
+    COALESCE(catalog_items.warranty_time, catalog_modifications.warranty_time) AS warranty_time,
+    COALESCE(catalog_variations.processing_time, catalog_modifications.processing_time) AS processing_time,
+    catalog_variations.sku AS sku,
+    catalog_variations.upc AS upc,
+    catalog_variations.measurement_units AS measurement_units,
+    catalog_variations.available_units AS available_units,
+    catalog_variations.reorder_threshold AS reorder_threshold,
+    catalog_variations.extra_attributes AS extra_attributes,
+    COALESCE(catalog_variations.price_type, catalog_modifications.price_type) AS price_type,
+    COALESCE(catalog_variations.price_amount, catalog_modifications.price_amount) AS price_amount,
+    COALESCE(catalog_variations.price_asset_name, catalog_modifications.price_asset_name) AS price_asset_name,
+    COALESCE(catalog_variations.price_asset_scale, catalog_modifications.price_asset_scale) AS price_asset_scale,
+    catalog_deliveries.delivery_type AS delivery_type,
+    catalog_deliveries.width_mm AS width_mm,
+    catalog_deliveries.length_mm AS length_mm,
+    catalog_deliveries.height_mm AS height_mm,
+    catalog_deliveries.weight_grams AS weight_grams,
+    catalog_controls.control_type AS control_type,
+    catalog_controls.control_data AS control_data";
+
+#[derive(Debug, FromRow)]
+pub struct CatalogObjectRow {
+    pub id: Id,
+    pub account: String,
+    pub version: NaiveDateTime,
+    pub type_entry: String,
+    pub created_at: NaiveDateTime,
+    pub item_id: Option<Id>,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub category: Option<String>,
+    pub tags: Option<Json<Vec<String>>>,
+    pub images: Option<Json<Vec<Image>>>,
+    pub enabled: Option<bool>,
+    pub warranty_time: Option<Json<Time>>,
+    pub processing_time: Option<Json<Time>>,
+    pub sku: Option<String>,
+    pub upc: Option<String>,
+    pub measurement_units: Option<String>,
+    pub available_units: Option<i32>,
+    pub reorder_threshold: Option<i32>,
+    pub extra_attributes: Option<Json<HashMap<String, String>>>,
+    pub price_type: Option<String>,
+    pub price_amount: Option<f32>,
+    pub price_asset_name: Option<String>,
+    pub price_asset_scale: Option<i8>,
+    pub delivery_type: Option<String>,
+    pub width_mm: Option<i32>,
+    pub length_mm: Option<i32>,
+    pub height_mm: Option<i32>,
+    pub weight_grams: Option<i32>,
+    pub control_type: Option<String>,
+    pub control_data: Option<Json<serde_json::Value>>,
+}
+
+impl CatalogObjectRow {
+    fn price(&mut self) -> Result<Price, CatalogError> {
+        match required(self.price_type.take())?.as_str() {
+            "Fixed" => Ok(Price::Fixed {
+                amount: required(self.price_amount)?,
+                asset_name: required(self.price_asset_name.take())?,
+                asset_scale: required(self.price_asset_scale)?,
+            }),
+            other => Err(CatalogError::mapping(format!("unknown price {}", other))),
+        }
+    }
+
+    fn delivery(&mut self) -> Result<Delivery, CatalogError> {
+        match required(self.delivery_type.take())?.as_str() {
+            "Shipping" => Ok(Delivery::Shipping {
+                width_mm: required(self.width_mm)?,
+                length_mm: required(self.length_mm)?,
+                height_mm: required(self.height_mm)?,
+                weight_grams: required(self.weight_grams)?,
+            }),
+            other => Err(CatalogError::mapping(format!("unknown delivery {}", other))),
+        }
+    }
+
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_catalog_entry(mut self) -> Result<CatalogObject<Id>, CatalogError> {
+        let entry = match self.type_entry.as_str() {
+            "Item" => CatalogObject::Item(Item {
+                category: from_text(self.category)?,
+                tags: required(self.tags)?.0,
+                name: required(self.name)?,
+                images: required(self.images)?.0,
+                description: required(self.description)?,
+                enabled: required(self.enabled)?,
+                warranty_time: self.warranty_time.map(|time| time.0),
+            }),
+            "Variation" => CatalogObject::Variation(ItemVariation {
+                price: self.price()?,
+                item_id: required(self.item_id)?,
+                name: required(self.name)?,
+                processing_time: self.processing_time.map(|time| time.0),
+                sku: required(self.sku)?,
+                images: required(self.images)?.0,
+                upc: self.upc,
+                enabled: required(self.enabled)?,
+                measurement_units: from_text(self.measurement_units)?,
+                available_units: required(self.available_units)?,
+                reorder_threshold: self.reorder_threshold,
+                extra_attributes: self.extra_attributes.map(|attributes| attributes.0),
+            }),
+            "Modification" => CatalogObject::Modification(ItemModification {
+                price: self.price()?,
+                item_id: required(self.item_id)?,
+                name: required(self.name)?,
+                processing_time: self.processing_time.map(|time| time.0),
+                warranty_time: self.warranty_time.map(|time| time.0),
+                images: required(self.images)?.0,
+                enabled: required(self.enabled)?,
+            }),
+            "Delivery" => CatalogObject::Delivery(ItemDelivery {
+                delivery: self.delivery()?,
+                item_id: required(self.item_id)?,
+            }),
+            "Control" => CatalogObject::Control(ItemControl {
+                control: serde_json::from_value(serde_json::json!({
+                    "type": required(self.control_type)?,
+                    "data": required(self.control_data)?.0,
+                }))
+                .map_err(CatalogError::mapping)?,
+                item_id: required(self.item_id)?,
+            }),
+            other => {
+                return Err(CatalogError::mapping(format!(
+                    "unknown object type {}",
+                    other
+                )))
+            }
+        };
+        Ok(entry)
+    }
+
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_catalog_entry_document(self) -> Result<SqlCatalogObjectDocument, CatalogError> {
+        Ok(SqlCatalogObjectDocument {
+            id: self.id,
+            account: self.account.clone(),
+            version: self.version,
+            created_at: self.created_at,
+            catalog_object: self.to_catalog_entry()?,
+        })
+    }
+}
+
+pub enum CatalogSchema {
+    Table,
+    Id,
+    Account,
+    TypeEntry,
+    Version,
+    CreatedAt,
+}
+
+impl Iden for CatalogSchema {
+    fn unquoted(&self, s: &mut dyn std::fmt::Write) {
+        write!(
+            s,
+            "{}",
+            match self {
+                Self::Table => "catalogs",
+                Self::Id => "id",
+                Self::Account => "account",
+                Self::TypeEntry => "type_entry",
+                Self::Version => "version",
+                Self::CreatedAt => "created_at",
+            }
+        )
+        .unwrap();
+    }
+}
+
+// the tables with the fields of each type, their `id` is the one in `catalogs`
+#[derive(Clone, Copy)]
+pub enum CatalogDataSchema {
+    Items,
+    Variations,
+    Modifications,
+    Deliveries,
+    Controls,
+}
+
+impl CatalogDataSchema {
+    const TABLES: [Self; 5] = [
+        Self::Items,
+        Self::Variations,
+        Self::Modifications,
+        Self::Deliveries,
+        Self::Controls,
+    ];
+
+    fn of(catalog_entry: &CatalogObject<Id>) -> Self {
+        match catalog_entry {
+            CatalogObject::Item(_) => Self::Items,
+            CatalogObject::Variation(_) => Self::Variations,
+            CatalogObject::Modification(_) => Self::Modifications,
+            CatalogObject::Delivery(_) => Self::Deliveries,
+            CatalogObject::Control(_) => Self::Controls,
+        }
+    }
+
+    // in the order `write_catalog_data` binds them
+    fn columns(&self) -> &'static [&'static str] {
+        match self {
+            Self::Items => &[
+                "name",
+                "description",
+                "category",
+                "tags",
+                "images",
+                "enabled",
+                "warranty_time",
+            ],
+            Self::Variations => &[
+                "item_id",
+                "name",
+                "sku",
+                "upc",
+                "images",
+                "enabled",
+                "measurement_units",
+                "available_units",
+                "reorder_threshold",
+                "processing_time",
+                "price_type",
+                "price_amount",
+                "price_asset_name",
+                "price_asset_scale",
+                "extra_attributes",
+            ],
+            Self::Modifications => &[
+                "item_id",
+                "name",
+                "images",
+                "enabled",
+                "processing_time",
+                "warranty_time",
+                "price_type",
+                "price_amount",
+                "price_asset_name",
+                "price_asset_scale",
+            ],
+            Self::Deliveries => &[
+                "item_id",
+                "delivery_type",
+                "width_mm",
+                "length_mm",
+                "height_mm",
+                "weight_grams",
+            ],
+            Self::Controls => &["item_id", "control_type", "control_data"],
+        }
+    }
+}
+
+impl Iden for CatalogDataSchema {
+    fn unquoted(&self, s: &mut dyn std::fmt::Write) {
+        write!(
+            s,
+            "{}",
+            match self {
+                Self::Items => "catalog_items",
+                Self::Variations => "catalog_variations",
+                Self::Modifications => "catalog_modifications",
+                Self::Deliveries => "catalog_deliveries",
+                Self::Controls => "catalog_controls",
+            }
+        )
+        .unwrap();
+    }
+}
+
+#[derive(Debug, FromRow)]
+pub struct StockLocationRow {
+    pub id: Id,
+    pub account: String,
+    pub version: NaiveDateTime,
+    pub location_data: Json<StockLocation>,
+    pub created_at: NaiveDateTime,
+}
+
+impl From<StockLocationRow> for SqlStockLocationDocument {
+    fn from(row: StockLocationRow) -> Self {
+        SqlStockLocationDocument {
+            id: row.id,
+            account: row.account,
+            version: row.version,
+            created_at: row.created_at,
+            location: row.location_data.0,
+        }
+    }
+}
+
+#[derive(Debug, FromRow)]
+struct StockLevelRow {
+    location_id: Id,
+    variation_id: Id,
+    units: i32,
+}
+
+pub enum StockLocationSchema {
+    Table,
+    Id,
+    Account,
+    Version,
+    LocationData,
+    CreatedAt,
+}
+
+impl Iden for StockLocationSchema {
+    fn unquoted(&self, s: &mut dyn std::fmt::Write) {
+        write!(
+            s,
+            "{}",
+            match self {
+                Self::Table => "stock_locations",
+                Self::Id => "id",
+                Self::Account => "account",
+                Self::Version => "version",
+                Self::LocationData => "location_data",
+                Self::CreatedAt => "created_at",
+            }
+        )
+        .unwrap();
+    }
+}
+
+pub enum StockLevelSchema {
+    Table,
+    Account,
+    LocationId,
+    VariationId,
+    Units,
+}
+
+impl Iden for StockLevelSchema {
+    fn unquoted(&self, s: &mut dyn std::fmt::Write) {
+        write!(
+            s,
+            "{}",
+            match self {
+                Self::Table => "stock_levels",
+                Self::Account => "account",
+                Self::LocationId => "location_id",
+                Self::VariationId => "variation_id",
+                Self::Units => "units",
+            }
+        )
+        .unwrap();
+    }
+}
+
+#[derive(Debug, FromRow)]
+struct StockAlertRow {
+    id: Id,
+    account: String,
+    variation_id: Id,
+    available_units: i32,
+    reorder_threshold: i32,
+    created_at: NaiveDateTime,
+}
+
+impl From<StockAlertRow> for SqlStockAlert {
+    fn from(row: StockAlertRow) -> Self {
+        SqlStockAlert {
+            id: row.id,
+            account: row.account,
+            variation_id: row.variation_id,
+            available_units: row.available_units,
+            reorder_threshold: row.reorder_threshold,
+            created_at: row.created_at,
+        }
+    }
+}
+
+pub enum StockAlertSchema {
+    Table,
+    Id,
+    Account,
+    VariationId,
+    AvailableUnits,
+    ReorderThreshold,
+    Sequence,
+    CreatedAt,
+}
+
+impl Iden for StockAlertSchema {
+    fn unquoted(&self, s: &mut dyn std::fmt::Write) {
+        write!(
+            s,
+            "{}",
+            match self {
+                Self::Table => "stock_alerts",
+                Self::Id => "id",
+                Self::Account => "account",
+                Self::VariationId => "variation_id",
+                Self::AvailableUnits => "available_units",
+                Self::ReorderThreshold => "reorder_threshold",
+                Self::Sequence => "sequence",
+                Self::CreatedAt => "created_at",
+            }
+        )
+        .unwrap();
+    }
+}
+
+#[derive(Debug, FromRow)]
+struct CatalogChangeRow {
+    account: String,
+    sequence: i64,
+    object_id: Id,
+    operation: String,
+    document: Json<SqlCatalogObjectDocument>,
+    cmd: Option<Json<SQlCatalogCmd>>,
+    created_at: NaiveDateTime,
+}
+
+impl TryFrom<CatalogChangeRow> for SqlCatalogChange {
+    type Error = CatalogError;
+
+    fn try_from(row: CatalogChangeRow) -> Result<Self, Self::Error> {
+        Ok(SqlCatalogChange {
+            sequence: row.sequence,
+            account: row.account,
+            id: row.object_id,
+            operation: serde_json::from_value(row.operation.into())
+                .map_err(CatalogError::mapping)?,
+            document: row.document.0,
+            cmd: row.cmd.map(|cmd| cmd.0),
+            created_at: row.created_at,
+        })
+    }
+}
+
+pub enum CatalogChangeSchema {
+    Table,
+    Account,
+    Sequence,
+}
+
+impl Iden for CatalogChangeSchema {
+    fn unquoted(&self, s: &mut dyn std::fmt::Write) {
+        write!(
+            s,
+            "{}",
+            match self {
+                Self::Table => "catalog_changes",
+                Self::Account => "account",
+                Self::Sequence => "sequence",
+            }
+        )
+        .unwrap();
+    }
+}
+
+#[derive(Debug, FromRow)]
+struct CatalogRevisionRow {
+    revision: i64,
+    document: Json<SqlCatalogObjectDocument>,
+    created_at: NaiveDateTime,
+}
+
+impl From<CatalogRevisionRow> for SqlCatalogRevision {
+    fn from(row: CatalogRevisionRow) -> Self {
+        SqlCatalogRevision {
+            revision: row.revision,
+            document: row.document.0,
+            created_at: row.created_at,
+        }
+    }
+}
+
+pub enum CatalogRevisionSchema {
+    Table,
+    Account,
+    ObjectId,
+    Revision,
+    CreatedAt,
+}
+
+impl Iden for CatalogRevisionSchema {
+    fn unquoted(&self, s: &mut dyn std::fmt::Write) {
+        write!(
+            s,
+            "{}",
+            match self {
+                Self::Table => "catalog_revisions",
+                Self::Account => "account",
+                Self::ObjectId => "object_id",
+                Self::Revision => "revision",
+                Self::CreatedAt => "created_at",
+            }
+        )
+        .unwrap();
+    }
+}
+
+#[derive(Debug, FromRow)]
+struct AuditRow {
+    id: i64,
+    account: String,
+    object_id: Id,
+    actor: String,
+    claimed_actor: Option<String>,
+    address: Option<String>,
+    user_agent: Option<String>,
+    operation: String,
+    before: Option<Json<serde_json::Value>>,
+    after: Option<Json<serde_json::Value>>,
+    cmd: Option<Json<SQlCatalogCmd>>,
+    created_at: NaiveDateTime,
+}
+
+impl TryFrom<AuditRow> for SqlAuditEntry {
+    type Error = CatalogError;
+
+    fn try_from(row: AuditRow) -> Result<Self, Self::Error> {
+        Ok(SqlAuditEntry {
+            id: row.id,
+            account: row.account,
+            object_id: row.object_id,
+            actor: Actor {
+                id: row.actor,
+                claimed_id: row.claimed_actor,
+                address: row.address,
+                user_agent: row.user_agent,
+            },
+            operation: serde_json::from_value(row.operation.into())
+                .map_err(CatalogError::mapping)?,
+            before: row.before.map(|before| before.0),
+            after: row.after.map(|after| after.0),
+            cmd: row.cmd.map(|cmd| cmd.0),
+            created_at: row.created_at,
+        })
+    }
+}
+
+pub enum AuditSchema {
+    Table,
+    Id,
+    Account,
+    ObjectId,
+    Actor,
+    ClaimedActor,
+    Address,
+    UserAgent,
+    Operation,
+    Before,
+    After,
+    Cmd,
+    CreatedAt,
+}
+
+impl Iden for AuditSchema {
+    fn unquoted(&self, s: &mut dyn std::fmt::Write) {
+        write!(
+            s,
+            "{}",
+            match self {
+                Self::Table => "catalog_audit",
+                Self::Id => "id",
+                Self::Account => "account",
+                Self::ObjectId => "object_id",
+                Self::Actor => "actor",
+                Self::ClaimedActor => "claimed_actor",
+                Self::Address => "address",
+                Self::UserAgent => "user_agent",
+                Self::Operation => "operation",
+                Self::Before => "before",
+                Self::After => "after",
+                Self::Cmd => "cmd",
                 Self::CreatedAt => "created_at",
             }
         )
@@ -0,0 +1,260 @@
+use std::error::Error;
+use std::fmt::{self, Debug, Display};
+use std::sync::Arc;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::validation::Violation;
+
+// The body of the error responses, RFC 7807 with the code of the error and
+// the object or the fields it is about.
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+// The error a database or a mapping error comes from. It's kept for
+// `source()` and the logs only, it isn't told to the clients and two errors
+// with different sources are still equal.
+#[derive(Clone)]
+pub struct ErrorSource(Arc<dyn Error + Send + Sync>);
+
+impl ErrorSource {
+    pub fn new(error: impl Into<Box<dyn Error + Send + Sync>>) -> Self {
+        Self(Arc::from(error.into()))
+    }
+}
+
+impl PartialEq for ErrorSource {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for ErrorSource {}
+
+impl Debug for ErrorSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Debug::fmt(&self.0, f)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CatalogError {
+    DatabaseError(ErrorSource),
+    // the database stayed locked by other writers after every retry
+    DatabaseBusy,
+    CatalogEntryNotFound(String),
+    // the request can't be applied, with the reason
+    CatalogBadRequest(String),
+    // the stored data doesn't fit the models
+    MappingError(ErrorSource),
+    BulkReferenceNotExist(String),
+    InsufficientUnits(String),
+    // the object changed since the version the write was based on
+    VersionMismatch(String),
+    // the backend in use doesn't provide the operation
+    Unsupported,
+    // the body isn't sent as the media type of the operation
+    UnsupportedMediaType(String),
+    // the fields of the object that break a rule
+    ValidationFailed(Vec<Violation>),
+}
+
+impl CatalogError {
+    pub fn database(error: impl Into<Box<dyn Error + Send + Sync>>) -> Self {
+        Self::DatabaseError(ErrorSource::new(error))
+    }
+
+    pub fn mapping(error: impl Into<Box<dyn Error + Send + Sync>>) -> Self {
+        Self::MappingError(ErrorSource::new(error))
+    }
+
+    pub fn bad_request(reason: impl Into<String>) -> Self {
+        Self::CatalogBadRequest(reason.into())
+    }
+
+    // the reasons every backend shares
+    pub fn unknown_item(id: impl Display) -> Self {
+        Self::bad_request(format!("the item {} doesn't exist", id))
+    }
+
+    pub fn item_in_use(id: impl Display) -> Self {
+        Self::bad_request(format!(
+            "the item {} still has objects that refer to it",
+            id
+        ))
+    }
+
+    pub fn invalid_transfer() -> Self {
+        Self::bad_request("the units must be more than zero and the locations different")
+    }
+
+    // stable, the clients tell the errors apart by them
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::DatabaseError(_) => "E_DATABASE",
+            Self::DatabaseBusy => "E_DATABASE_BUSY",
+            Self::CatalogEntryNotFound(_) => "E_NOT_FOUND",
+            Self::CatalogBadRequest(_) => "E_BAD_REQUEST",
+            Self::MappingError(_) => "E_MAPPING",
+            Self::BulkReferenceNotExist(_) => "E_BULK_ACTION",
+            Self::InsufficientUnits(_) => "E_INSUFFICIENT_UNITS",
+            Self::VersionMismatch(_) => "E_VERSION_MISMATCH",
+            Self::Unsupported => "E_UNSUPPORTED",
+            Self::UnsupportedMediaType(_) => "E_UNSUPPORTED_MEDIA_TYPE",
+            Self::ValidationFailed(_) => "E_VALIDATION",
+        }
+    }
+
+    pub fn status(&self) -> u16 {
+        match self {
+            Self::DatabaseError(_) | Self::MappingError(_) => 500,
+            Self::DatabaseBusy => 503,
+            Self::CatalogEntryNotFound(_) => 404,
+            Self::CatalogBadRequest(_) | Self::BulkReferenceNotExist(_) => 400,
+            Self::InsufficientUnits(_) => 409,
+            Self::VersionMismatch(_) => 412,
+            Self::Unsupported => 501,
+            Self::UnsupportedMediaType(_) => 415,
+            Self::ValidationFailed(_) => 422,
+        }
+    }
+
+    // the same for every error with the code
+    pub fn title(&self) -> &'static str {
+        match self {
+            Self::DatabaseError(_) => "Database error",
+            Self::DatabaseBusy => "Database busy",
+            Self::CatalogEntryNotFound(_) => "Not found",
+            Self::CatalogBadRequest(_) => "Bad request",
+            Self::MappingError(_) => "Corrupted data",
+            Self::BulkReferenceNotExist(_) => "Unknown reference",
+            Self::InsufficientUnits(_) => "Insufficient units",
+            Self::VersionMismatch(_) => "Version mismatch",
+            Self::Unsupported => "Unsupported",
+            Self::UnsupportedMediaType(_) => "Unsupported media type",
+            Self::ValidationFailed(_) => "Invalid object",
+        }
+    }
+
+    // the object the error is about
+    pub fn id(&self) -> Option<&str> {
+        match self {
+            Self::CatalogEntryNotFound(id)
+            | Self::BulkReferenceNotExist(id)
+            | Self::InsufficientUnits(id)
+            | Self::VersionMismatch(id) => Some(id),
+            _ => None,
+        }
+    }
+}
+
+impl Display for CatalogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DatabaseError(_) => write!(f, "the database failed"),
+            Self::DatabaseBusy => write!(f, "the database is busy, try again"),
+            Self::CatalogEntryNotFound(id) => write!(f, "the object {} doesn't exist", id),
+            Self::CatalogBadRequest(reason) => write!(f, "{}", reason),
+            Self::MappingError(_) => write!(f, "the stored data can't be read"),
+            Self::BulkReferenceNotExist(id) => {
+                write!(f, "the reference {} isn't an object of the request", id)
+            }
+            Self::InsufficientUnits(id) => write!(f, "not enough units of the variation {}", id),
+            Self::VersionMismatch(id) => {
+                write!(f, "the object {} was changed since the given version", id)
+            }
+            Self::Unsupported => write!(f, "not available with this database"),
+            Self::UnsupportedMediaType(media_type) => {
+                write!(f, "the body must be sent as {}", media_type)
+            }
+            Self::ValidationFailed(violations) => {
+                let plural = if violations.len() == 1 { "" } else { "s" };
+                write!(
+                    f,
+                    "the object has {} invalid field{}",
+                    violations.len(),
+                    plural
+                )
+            }
+        }
+    }
+}
+
+impl Error for CatalogError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::DatabaseError(source) | Self::MappingError(source) => Some(source.0.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, JsonSchema)]
+pub struct Problem {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub title: String,
+    pub status: u16,
+    pub detail: String,
+    // the path of the request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+    pub code: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub violations: Vec<Violation>,
+    // the id of a failure in the logs of the server
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+}
+
+impl Problem {
+    pub fn new(status: u16, code: &str, title: &str, detail: impl Into<String>) -> Self {
+        Self {
+            kind: problem_type(code),
+            title: title.to_string(),
+            status,
+            detail: detail.into(),
+            instance: None,
+            code: code.to_string(),
+            id: None,
+            violations: vec![],
+            correlation_id: None,
+        }
+    }
+
+    // the errors raised by the http layer, by their status
+    pub fn for_status(status: u16, title: &str, detail: impl Into<String>) -> Self {
+        let code = match status {
+            401 => "E_UNAUTHORIZED",
+            403 => "E_FORBIDDEN",
+            404 => "E_NOT_FOUND",
+            422 => "E_UNPROCESSABLE",
+            501 => "E_UNSUPPORTED",
+            status if status >= 500 => "E_INTERNAL",
+            _ => "E_BAD_REQUEST",
+        };
+        Self::new(status, code, title, detail)
+    }
+}
+
+impl From<&CatalogError> for Problem {
+    fn from(error: &CatalogError) -> Self {
+        let mut problem = Self::new(
+            error.status(),
+            error.code(),
+            error.title(),
+            error.to_string(),
+        );
+        problem.id = error.id().map(str::to_string);
+        if let CatalogError::ValidationFailed(violations) = error {
+            problem.violations = violations.clone();
+        }
+        problem
+    }
+}
+
+fn problem_type(code: &str) -> String {
+    format!("urn:merchant:error:{}", code)
+}
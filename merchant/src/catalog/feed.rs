@@ -0,0 +1,231 @@
+use std::fmt::Display;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::models::{CatalogObject, CatalogObjectDocument, Image, Item, ItemVariation, Price};
+
+// Product feeds in the format of Google Merchant Center, other marketplaces
+// read the same attributes. An entry is an enabled variation of an enabled
+// item, items without variations have no price and aren't listed.
+pub const TSV_COLUMNS: [&str; 11] = [
+    "id",
+    "title",
+    "description",
+    "link",
+    "image_link",
+    "additional_image_link",
+    "price",
+    "availability",
+    "gtin",
+    "mpn",
+    "item_group_id",
+];
+
+const GOOGLE_NAMESPACE: &str = "http://base.google.com/ns/1.0";
+// the most additional images a feed entry can have
+const MAX_ADDITIONAL_IMAGES: usize = 10;
+// replaced with the id of the variation in the link of the options
+const LINK_ID: &str = "{id}";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedFormat {
+    #[default]
+    Rss,
+    Tsv,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
+pub struct FeedOptions {
+    #[serde(default)]
+    pub format: FeedFormat,
+    // the page of a product, `{id}` is replaced with the id of the variation
+    pub link: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct FeedEntry {
+    pub id: String,
+    pub title: String,
+    pub description: String,
+    pub link: Option<String>,
+    pub image_link: Option<String>,
+    pub additional_image_links: Vec<String>,
+    pub price: String,
+    pub availability: &'static str,
+    pub gtin: Option<String>,
+    pub mpn: String,
+    pub item_group_id: String,
+}
+
+pub fn feed_entries<Id: Display + PartialEq, Account>(
+    documents: &[CatalogObjectDocument<Id, Account>],
+    options: &FeedOptions,
+) -> Vec<FeedEntry> {
+    let items: Vec<(&Id, &Item)> = documents
+        .iter()
+        .filter_map(|document| match &document.catalog_object {
+            CatalogObject::Item(item) if item.enabled => Some((&document.id, item)),
+            _ => None,
+        })
+        .collect();
+    documents
+        .iter()
+        .filter_map(|document| match &document.catalog_object {
+            CatalogObject::Variation(variation) if variation.enabled => {
+                let (item_id, item) = items.iter().find(|(id, _)| **id == variation.item_id)?;
+                Some(feed_entry(&document.id, variation, item_id, item, options))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+fn feed_entry<Id: Display>(
+    id: &Id,
+    variation: &ItemVariation<Id>,
+    item_id: &Id,
+    item: &Item,
+    options: &FeedOptions,
+) -> FeedEntry {
+    let id = id.to_string();
+    let title = if variation.name.is_empty() || variation.name == item.name {
+        item.name.clone()
+    } else {
+        format!("{} - {}", item.name, variation.name)
+    };
+    // the images of the variation come before the ones of its item
+    let mut images = variation
+        .images
+        .iter()
+        .chain(item.images.iter())
+        .map(|Image { url }| url.clone());
+    let image_link = images.next();
+    let additional_image_links = images.take(MAX_ADDITIONAL_IMAGES).collect();
+    let availability = if variation.available_units > 0 {
+        "in_stock"
+    } else {
+        "out_of_stock"
+    };
+    FeedEntry {
+        link: options.link.as_ref().map(|link| link.replace(LINK_ID, &id)),
+        id,
+        title,
+        description: item.description.clone(),
+        image_link,
+        additional_image_links,
+        price: feed_price(&variation.price),
+        availability,
+        gtin: variation.upc.clone().filter(|upc| !upc.is_empty()),
+        mpn: variation.sku.clone(),
+        item_group_id: item_id.to_string(),
+    }
+}
+
+// the amount with as many decimals as the scale of the asset, "12.50 USD"
+fn feed_price(price: &Price) -> String {
+    let Price::Fixed {
+        amount,
+        asset_name,
+        asset_scale,
+    } = price;
+    format!(
+        "{:.*} {}",
+        (*asset_scale).max(0) as usize,
+        amount,
+        asset_name
+    )
+}
+
+pub fn write_rss(title: &str, link: &str, entries: &[FeedEntry]) -> String {
+    let mut rss = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    rss.push_str(&format!(
+        "<rss version=\"2.0\" xmlns:g=\"{}\">\n<channel>\n",
+        GOOGLE_NAMESPACE
+    ));
+    rss.push_str(&element("title", title, 1));
+    rss.push_str(&element("link", link, 1));
+    rss.push_str(&element("description", title, 1));
+    for entry in entries {
+        rss.push_str("  <item>\n");
+        rss.push_str(&element("g:id", &entry.id, 2));
+        rss.push_str(&element("title", &entry.title, 2));
+        rss.push_str(&element("description", &entry.description, 2));
+        if let Some(link) = &entry.link {
+            rss.push_str(&element("link", link, 2));
+        }
+        if let Some(image_link) = &entry.image_link {
+            rss.push_str(&element("g:image_link", image_link, 2));
+        }
+        for image_link in entry.additional_image_links.iter() {
+            rss.push_str(&element("g:additional_image_link", image_link, 2));
+        }
+        rss.push_str(&element("g:price", &entry.price, 2));
+        rss.push_str(&element("g:availability", entry.availability, 2));
+        match &entry.gtin {
+            Some(gtin) => rss.push_str(&element("g:gtin", gtin, 2)),
+            None => rss.push_str(&element("g:identifier_exists", "no", 2)),
+        }
+        rss.push_str(&element("g:mpn", &entry.mpn, 2));
+        rss.push_str(&element("g:item_group_id", &entry.item_group_id, 2));
+        rss.push_str("  </item>\n");
+    }
+    rss.push_str("</channel>\n</rss>\n");
+    rss
+}
+
+fn element(name: &str, text: &str, depth: usize) -> String {
+    format!(
+        "{}<{}>{}</{}>\n",
+        "  ".repeat(depth),
+        name,
+        escape_xml(text),
+        name
+    )
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// a line per entry, multiple additional images are separated by commas
+pub fn write_tsv(entries: &[FeedEntry]) -> String {
+    let mut tsv = TSV_COLUMNS.join("\t");
+    tsv.push('\n');
+    for entry in entries {
+        let cells = [
+            entry.id.as_str(),
+            &entry.title,
+            &entry.description,
+            entry.link.as_deref().unwrap_or_default(),
+            entry.image_link.as_deref().unwrap_or_default(),
+            &entry.additional_image_links.join(","),
+            &entry.price,
+            entry.availability,
+            entry.gtin.as_deref().unwrap_or_default(),
+            &entry.mpn,
+            &entry.item_group_id,
+        ];
+        let cells: Vec<String> = cells.iter().map(|cell| tsv_cell(cell)).collect();
+        tsv.push_str(&cells.join("\t"));
+        tsv.push('\n');
+    }
+    tsv
+}
+
+// the format has no quoting, tabs and line breaks become spaces
+fn tsv_cell(text: &str) -> String {
+    text.replace(['\t', '\r', '\n'], " ")
+}
@@ -0,0 +1,402 @@
+use std::collections::HashSet;
+use std::fmt::Display;
+
+use serde_json::{json, Map, Value};
+
+use super::models::{
+    CatalogObject, CatalogObjectDocument, Control, Delivery, Item, ItemVariation, MatrixControl,
+    MatrixProp, Price, Time,
+};
+
+// schema.org markup of the items of a storefront. An item with variations is
+// a `ProductGroup` with a `Product` and an `Offer` per variation, one without
+// variations is a `Product` that can't be bought yet.
+pub const JSON_LD: &str = "application/ld+json";
+
+const SCHEMA_CONTEXT: &str = "https://schema.org";
+// properties schema.org has for the props of a matrix, the rest are
+// `additionalProperty`s
+const SCHEMA_PROPERTIES: [&str; 6] = [
+    "color",
+    "size",
+    "material",
+    "pattern",
+    "suggestedAge",
+    "suggestedGender",
+];
+
+// UN/CEFACT codes of the units of the model
+const GRAMS: &str = "GRM";
+const MILLIMETRES: &str = "MMT";
+const SECONDS: &str = "SEC";
+
+// the markup of an item or a variation, `documents` have the item and the
+// objects that belong to it. Other objects have none.
+pub fn json_ld<Id: Display + PartialEq, Account>(
+    document: &CatalogObjectDocument<Id, Account>,
+    documents: &[CatalogObjectDocument<Id, Account>],
+) -> Option<Value> {
+    match &document.catalog_object {
+        CatalogObject::Item(item) => {
+            let children = ItemChildren::new(&document.id, documents);
+            let mut markup = item_json_ld(&document.id, item, &children);
+            markup.insert("@context".to_string(), json!(SCHEMA_CONTEXT));
+            Some(Value::Object(markup))
+        }
+        CatalogObject::Variation(variation) => {
+            let item = documents
+                .iter()
+                .find(|item| item.id == variation.item_id)
+                .and_then(|item| item.catalog_object.item())?;
+            let children = ItemChildren::new(&variation.item_id, documents);
+            let mut markup = variant_json_ld(&document.id, variation, item, &children);
+            markup.insert(
+                "isVariantOf".to_string(),
+                json!({
+                    "@type": "ProductGroup",
+                    "productGroupID": variation.item_id.to_string(),
+                    "name": item.name,
+                }),
+            );
+            markup.insert("@context".to_string(), json!(SCHEMA_CONTEXT));
+            Some(Value::Object(markup))
+        }
+        _ => None,
+    }
+}
+
+// the objects that refine an item
+struct ItemChildren<'a, Id> {
+    variations: Vec<(&'a Id, &'a ItemVariation<Id>)>,
+    matrix: Option<&'a MatrixControl<Id>>,
+    delivery: Option<&'a Delivery>,
+}
+
+impl<'a, Id: PartialEq> ItemChildren<'a, Id> {
+    fn new<Account>(item_id: &Id, documents: &'a [CatalogObjectDocument<Id, Account>]) -> Self {
+        let mut children = Self {
+            variations: vec![],
+            matrix: None,
+            delivery: None,
+        };
+        for document in documents {
+            match &document.catalog_object {
+                CatalogObject::Variation(variation)
+                    if variation.item_id == *item_id && variation.enabled =>
+                {
+                    children.variations.push((&document.id, variation));
+                }
+                CatalogObject::Control(control) if control.item_id == *item_id => {
+                    if let Control::Matrix(matrix) = &control.control {
+                        children.matrix = Some(matrix);
+                    }
+                }
+                CatalogObject::Delivery(delivery) if delivery.item_id == *item_id => {
+                    children.delivery = Some(&delivery.delivery);
+                }
+                _ => {}
+            }
+        }
+        children
+    }
+
+    // the option of every prop that leads to the variation
+    fn options(&self, id: &Id) -> Vec<(&'a str, String)> {
+        let matrix = match self.matrix {
+            Some(matrix) => matrix,
+            None => return vec![],
+        };
+        let key = match matrix
+            .combinations
+            .iter()
+            .find(|(_, variation_id)| *variation_id == id)
+        {
+            Some((key, _)) => key,
+            None => return vec![],
+        };
+        match KeyParser::new(&matrix.key_template, &matrix.props).parse(key) {
+            Some(options) => matrix
+                .props
+                .iter()
+                .map(|prop| prop.name.as_str())
+                .zip(options.into_iter().map(str::to_string))
+                .collect(),
+            None => vec![],
+        }
+    }
+}
+
+// a piece of a key template
+enum Segment<'a> {
+    Text(&'a str),
+    // the index of the prop whose option goes there
+    Prop(usize),
+}
+
+// Reads the options back out of a key made with the template. Each prop only
+// tries the options the key goes on with and a position that failed isn't
+// tried again, so a key costs at most its length times the options instead
+// of every combination of the props.
+struct KeyParser<'a> {
+    segments: Vec<Segment<'a>>,
+    props: &'a [MatrixProp],
+    // the props the template has more than once, every time with one option
+    repeated: Vec<usize>,
+}
+
+type Failure<'a> = (usize, usize, Vec<Option<&'a str>>);
+
+impl<'a> KeyParser<'a> {
+    fn new(template: &'a str, props: &'a [MatrixProp]) -> Self {
+        let mut segments = vec![];
+        let mut text_start = 0;
+        let mut position = 0;
+        while position < template.len() {
+            let rest = &template[position..];
+            // the longest name, `:size` isn't the start of `:sizes`
+            let prop = rest.strip_prefix(':').and_then(|rest| {
+                props
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, prop)| !prop.name.is_empty() && rest.starts_with(&prop.name))
+                    .max_by_key(|(_, prop)| prop.name.len())
+            });
+            match prop {
+                Some((index, prop)) => {
+                    if text_start < position {
+                        segments.push(Segment::Text(&template[text_start..position]));
+                    }
+                    segments.push(Segment::Prop(index));
+                    position += 1 + prop.name.len();
+                    text_start = position;
+                }
+                None => position += rest.chars().next().map_or(1, char::len_utf8),
+            }
+        }
+        if text_start < template.len() {
+            segments.push(Segment::Text(&template[text_start..]));
+        }
+        let repeated = (0..props.len())
+            .filter(|index| {
+                segments
+                    .iter()
+                    .filter(|segment| matches!(segment, Segment::Prop(prop) if prop == index))
+                    .count()
+                    > 1
+            })
+            .collect();
+        Self {
+            segments,
+            props,
+            repeated,
+        }
+    }
+
+    // the option of every prop, none when the template didn't make the key
+    fn parse(&self, key: &str) -> Option<Vec<&'a str>> {
+        let mut options = vec![None; self.props.len()];
+        if !self.read(key, 0, 0, &mut options, &mut HashSet::new()) {
+            return None;
+        }
+        options.into_iter().collect()
+    }
+
+    fn read(
+        &self,
+        key: &str,
+        segment: usize,
+        position: usize,
+        options: &mut Vec<Option<&'a str>>,
+        failures: &mut HashSet<Failure<'a>>,
+    ) -> bool {
+        let rest = &key[position..];
+        let index = match self.segments.get(segment) {
+            None => return rest.is_empty(),
+            Some(Segment::Text(text)) => {
+                return rest.starts_with(text)
+                    && self.read(key, segment + 1, position + text.len(), options, failures)
+            }
+            Some(Segment::Prop(index)) => *index,
+        };
+        let failure = (
+            segment,
+            position,
+            self.repeated.iter().map(|prop| options[*prop]).collect(),
+        );
+        if failures.contains(&failure) {
+            return false;
+        }
+        let chosen = options[index];
+        let candidates: Vec<&'a str> = match chosen {
+            Some(option) => vec![option],
+            None => self.props[index]
+                .options
+                .iter()
+                .map(String::as_str)
+                .collect(),
+        };
+        for option in candidates {
+            if rest.starts_with(option) {
+                options[index] = Some(option);
+                if self.read(key, segment + 1, position + option.len(), options, failures) {
+                    return true;
+                }
+            }
+        }
+        options[index] = chosen;
+        failures.insert(failure);
+        false
+    }
+}
+
+fn item_json_ld<Id: Display + PartialEq>(
+    id: &Id,
+    item: &Item,
+    children: &ItemChildren<Id>,
+) -> Map<String, Value> {
+    let mut markup = Map::new();
+    let (kind, id_property) = if children.variations.is_empty() {
+        ("Product", "productID")
+    } else {
+        ("ProductGroup", "productGroupID")
+    };
+    markup.insert("@type".to_string(), json!(kind));
+    markup.insert(id_property.to_string(), json!(id.to_string()));
+    markup.insert("name".to_string(), json!(item.name));
+    markup.insert("description".to_string(), json!(item.description));
+    if !item.images.is_empty() {
+        let images: Vec<&String> = item.images.iter().map(|image| &image.url).collect();
+        markup.insert("image".to_string(), json!(images));
+    }
+    if !item.tags.is_empty() {
+        markup.insert("keywords".to_string(), json!(item.tags.join(",")));
+    }
+    insert_shipping(&mut markup, children.delivery);
+    if children.variations.is_empty() {
+        return markup;
+    }
+    if let Some(matrix) = children.matrix {
+        let varies_by: Vec<String> = matrix
+            .props
+            .iter()
+            .map(|prop| schema_property(&prop.name))
+            .collect();
+        markup.insert("variesBy".to_string(), json!(varies_by));
+    }
+    let variants: Vec<Value> = children
+        .variations
+        .iter()
+        .map(|(id, variation)| Value::Object(variant_json_ld(*id, variation, item, children)))
+        .collect();
+    markup.insert("hasVariant".to_string(), json!(variants));
+    markup
+}
+
+fn variant_json_ld<Id: Display + PartialEq>(
+    id: &Id,
+    variation: &ItemVariation<Id>,
+    item: &Item,
+    children: &ItemChildren<Id>,
+) -> Map<String, Value> {
+    let mut markup = Map::new();
+    markup.insert("@type".to_string(), json!("Product"));
+    markup.insert("productID".to_string(), json!(id.to_string()));
+    markup.insert("name".to_string(), json!(variation.name));
+    markup.insert("description".to_string(), json!(item.description));
+    markup.insert("sku".to_string(), json!(variation.sku));
+    if let Some(upc) = variation.upc.as_ref().filter(|upc| !upc.is_empty()) {
+        markup.insert("gtin".to_string(), json!(upc));
+    }
+    let images: Vec<&String> = variation
+        .images
+        .iter()
+        .chain(item.images.iter())
+        .map(|image| &image.url)
+        .collect();
+    if !images.is_empty() {
+        markup.insert("image".to_string(), json!(images));
+    }
+    let mut additional_properties = vec![];
+    for (prop, option) in children.options(id) {
+        if SCHEMA_PROPERTIES.contains(&prop) {
+            markup.insert(prop.to_string(), json!(option));
+        } else {
+            additional_properties.push(json!({
+                "@type": "PropertyValue",
+                "name": prop,
+                "value": option,
+            }));
+        }
+    }
+    if !additional_properties.is_empty() {
+        markup.insert(
+            "additionalProperty".to_string(),
+            json!(additional_properties),
+        );
+    }
+    insert_shipping(&mut markup, children.delivery);
+    markup.insert("offers".to_string(), offer(variation, item));
+    markup
+}
+
+fn offer<Id>(variation: &ItemVariation<Id>, item: &Item) -> Value {
+    let Price::Fixed {
+        amount,
+        asset_name,
+        asset_scale,
+    } = &variation.price;
+    let availability = if variation.available_units > 0 {
+        "https://schema.org/InStock"
+    } else {
+        "https://schema.org/OutOfStock"
+    };
+    let mut offer = json!({
+        "@type": "Offer",
+        "price": format!("{:.*}", (*asset_scale).max(0) as usize, amount),
+        "priceCurrency": asset_name,
+        "availability": availability,
+    });
+    if let Some(Time::Fixed { seconds }) = &item.warranty_time {
+        offer["warranty"] = json!({
+            "@type": "WarrantyPromise",
+            "durationOfWarranty": quantity(*seconds as i64, SECONDS),
+        });
+    }
+    offer
+}
+
+fn insert_shipping(markup: &mut Map<String, Value>, delivery: Option<&Delivery>) {
+    if let Some(Delivery::Shipping {
+        width_mm,
+        length_mm,
+        height_mm,
+        weight_grams,
+    }) = delivery
+    {
+        let dimensions = [
+            ("weight", quantity(*weight_grams as i64, GRAMS)),
+            ("width", quantity(*width_mm as i64, MILLIMETRES)),
+            ("depth", quantity(*length_mm as i64, MILLIMETRES)),
+            ("height", quantity(*height_mm as i64, MILLIMETRES)),
+        ];
+        for (name, value) in dimensions {
+            markup.insert(name.to_string(), value);
+        }
+    }
+}
+
+fn quantity(value: i64, unit_code: &str) -> Value {
+    json!({
+        "@type": "QuantitativeValue",
+        "value": value,
+        "unitCode": unit_code,
+    })
+}
+
+fn schema_property(name: &str) -> String {
+    if SCHEMA_PROPERTIES.contains(&name) {
+        format!("{}/{}", SCHEMA_CONTEXT, name)
+    } else {
+        name.to_string()
+    }
+}
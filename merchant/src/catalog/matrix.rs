@@ -0,0 +1,468 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_std::sync::Mutex;
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::{migrate::Migrator, sqlite::SqlitePoolOptions};
+use surf::Url;
+
+use super::backend::{
+    Account, CatalogSQLService, Id, SqlCatalogObjectDocument, SqlCatalogQueryOptions,
+    SqlStockLocationDocument,
+};
+use super::models::{CatalogObject, CatalogObjectBulkDocument, StockLevel, StockLocation};
+use super::projection::{project, Fields};
+use super::service::{
+    bulk_create, BulkDocumentReferencesResolver, CatalogCmd, CatalogError, CatalogProjection,
+    CatalogService, Commander, StockLocationService,
+};
+use super::types::resolve_references;
+
+static CACHE_MIGRATOR: Migrator = sqlx::migrate!();
+
+pub const ITEM_EVENT: &str = "network.virto.merchant.item";
+pub const VARIATION_EVENT: &str = "network.virto.merchant.variation";
+pub const MODIFICATION_EVENT: &str = "network.virto.merchant.modification";
+pub const DELIVERY_EVENT: &str = "network.virto.merchant.delivery";
+pub const CONTROL_EVENT: &str = "network.virto.merchant.control";
+pub const STOCK_LOCATION_EVENT: &str = "network.virto.merchant.stock_location";
+pub const STOCK_LEVEL_EVENT: &str = "network.virto.merchant.stock_level";
+
+fn event_type(catalog_object: &CatalogObject<Id>) -> &'static str {
+    match catalog_object {
+        CatalogObject::Item(_) => ITEM_EVENT,
+        CatalogObject::Variation(_) => VARIATION_EVENT,
+        CatalogObject::Modification(_) => MODIFICATION_EVENT,
+        CatalogObject::Delivery(_) => DELIVERY_EVENT,
+        CatalogObject::Control(_) => CONTROL_EVENT,
+    }
+}
+
+fn stock_level_key(level: &StockLevel<Id>) -> String {
+    format!("{}:{}", level.location_id, level.variation_id)
+}
+
+#[derive(Deserialize, Debug)]
+pub struct StateEvent {
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub state_key: String,
+    pub content: Value,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StockLevelContent {
+    account: Account,
+    #[serde(flatten)]
+    level: StockLevel<Id>,
+}
+
+// Talks to the client-server API of the homeserver for a single room
+#[derive(Clone)]
+pub struct MatrixClient {
+    client: surf::Client,
+    homeserver: Url,
+    access_token: String,
+    room_id: String,
+}
+
+impl MatrixClient {
+    pub fn new(homeserver: Url, access_token: &str, room_id: &str) -> Self {
+        let client: surf::Client = surf::Config::new()
+            .set_timeout(Some(Duration::from_secs(10)))
+            .try_into()
+            .expect("valid http client config");
+        Self {
+            client,
+            homeserver,
+            access_token: access_token.to_string(),
+            room_id: room_id.to_string(),
+        }
+    }
+
+    fn room_url(&self, path: &[&str]) -> Result<Url, CatalogError> {
+        let mut url = self.homeserver.clone();
+        url.path_segments_mut()
+            .map_err(|_| CatalogError::database("the homeserver url can't have a path"))?
+            .pop_if_empty()
+            .extend(["_matrix", "client", "v3", "rooms", self.room_id.as_str()])
+            .extend(path);
+        Ok(url)
+    }
+
+    pub async fn put_state(
+        &self,
+        event_type: &str,
+        state_key: &str,
+        content: &Value,
+    ) -> Result<(), CatalogError> {
+        let body = surf::Body::from_json(content).map_err(CatalogError::mapping)?;
+        let mut response = self
+            .client
+            .put(self.room_url(&["state", event_type, state_key])?)
+            .header("Authorization", format!("Bearer {}", self.access_token))
+            .body(body)
+            .await
+            .map_err(CatalogError::database)?;
+        // the body has to be read for the connection to be reused
+        let sent = response.status().is_success();
+        response
+            .body_bytes()
+            .await
+            .map_err(CatalogError::database)?;
+        if !sent {
+            return Err(CatalogError::database(format!(
+                "the homeserver answered {}",
+                response.status()
+            )));
+        }
+        Ok(())
+    }
+
+    pub async fn room_state(&self) -> Result<Vec<StateEvent>, CatalogError> {
+        let mut response = self
+            .client
+            .get(self.room_url(&["state"])?)
+            .header("Authorization", format!("Bearer {}", self.access_token))
+            .await
+            .map_err(CatalogError::database)?;
+        if !response.status().is_success() {
+            return Err(CatalogError::database(format!(
+                "the homeserver answered {}",
+                response.status()
+            )));
+        }
+        response.body_json().await.map_err(CatalogError::mapping)
+    }
+}
+
+// The room is the source of truth: every object is a state event with its id
+// as state key, removed ones have empty content. Queries are answered by a
+// SQLite cache built from the room state, which is rebuilt when a write
+// can't be stored in the room. Writes hold a lock until they're published so
+// a rebuild never drops a write that's still on its way to the room.
+#[derive(Clone)]
+pub struct CatalogMatrixService {
+    client: MatrixClient,
+    cache: CatalogSQLService,
+    writes: Arc<Mutex<()>>,
+}
+
+impl CatalogMatrixService {
+    pub async fn connect(client: MatrixClient) -> Result<Self, CatalogError> {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .map_err(CatalogError::database)?;
+        CACHE_MIGRATOR
+            .run(&pool)
+            .await
+            .map_err(CatalogError::database)?;
+        let service = Self {
+            client,
+            cache: CatalogSQLService::new(pool),
+            writes: Arc::new(Mutex::new(())),
+        };
+        service.rebuild().await?;
+        Ok(service)
+    }
+
+    pub async fn reload(&self) -> Result<(), CatalogError> {
+        let _writes = self.writes.lock().await;
+        self.rebuild().await
+    }
+
+    // the cache is replaced at once, the objects keep their versions
+    async fn rebuild(&self) -> Result<(), CatalogError> {
+        let events = self.client.room_state().await?;
+
+        let mut objects = vec![];
+        let mut locations = vec![];
+        let mut levels = vec![];
+        for event in events {
+            if event.content.as_object().is_none_or(|c| c.is_empty()) {
+                continue;
+            }
+            // the state key is what identifies the object in the room
+            match event.event_type.as_str() {
+                STOCK_LOCATION_EVENT => {
+                    let document: SqlStockLocationDocument =
+                        serde_json::from_value(event.content).map_err(CatalogError::mapping)?;
+                    if document.id.to_string() == event.state_key {
+                        locations.push(document);
+                    }
+                }
+                STOCK_LEVEL_EVENT => {
+                    let content: StockLevelContent =
+                        serde_json::from_value(event.content).map_err(CatalogError::mapping)?;
+                    if stock_level_key(&content.level) == event.state_key {
+                        levels.push((content.account, content.level));
+                    }
+                }
+                ITEM_EVENT | VARIATION_EVENT | MODIFICATION_EVENT | DELIVERY_EVENT
+                | CONTROL_EVENT => {
+                    let document: SqlCatalogObjectDocument =
+                        serde_json::from_value(event.content).map_err(CatalogError::mapping)?;
+                    if document.id.to_string() == event.state_key
+                        && event_type(&document.catalog_object) == event.event_type
+                    {
+                        objects.push(document);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // items go first for the references of the rest
+        objects.sort_by_key(|document| {
+            (
+                !matches!(document.catalog_object, CatalogObject::Item(_)),
+                document.created_at,
+            )
+        });
+        self.cache.restore(&objects, &locations, &levels).await
+    }
+
+    // The cache already has the change, it's undone if the room rejects it.
+    // The caller holds the writes lock.
+    async fn publish(&self, events: Vec<(&str, String, Value)>) -> Result<(), CatalogError> {
+        for (event_type, state_key, content) in events {
+            if let Err(error) = self
+                .client
+                .put_state(event_type, &state_key, &content)
+                .await
+            {
+                self.rebuild().await?;
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+
+    fn object_event(
+        document: &SqlCatalogObjectDocument,
+    ) -> Result<(&'static str, String, Value), CatalogError> {
+        Ok((
+            event_type(&document.catalog_object),
+            document.id.to_string(),
+            serde_json::to_value(document).map_err(CatalogError::mapping)?,
+        ))
+    }
+
+    fn location_event(
+        document: &SqlStockLocationDocument,
+    ) -> Result<(&'static str, String, Value), CatalogError> {
+        Ok((
+            STOCK_LOCATION_EVENT,
+            document.id.to_string(),
+            serde_json::to_value(document).map_err(CatalogError::mapping)?,
+        ))
+    }
+
+    fn level_event(
+        account: &Account,
+        level: StockLevel<Id>,
+    ) -> Result<(&'static str, String, Value), CatalogError> {
+        Ok((
+            STOCK_LEVEL_EVENT,
+            stock_level_key(&level),
+            serde_json::to_value(StockLevelContent {
+                account: account.to_owned(),
+                level,
+            })
+            .map_err(CatalogError::mapping)?,
+        ))
+    }
+}
+
+impl BulkDocumentReferencesResolver for CatalogMatrixService {
+    type Id = Id;
+    fn resolve(
+        id_map: &HashMap<&str, Self::Id>,
+        catalog: &CatalogObject<String>,
+    ) -> Result<CatalogObject<Self::Id>, CatalogError> {
+        resolve_references(id_map, catalog)
+    }
+}
+
+#[async_trait]
+impl CatalogService for CatalogMatrixService {
+    type Id = Id;
+    type Query = SqlCatalogQueryOptions;
+
+    async fn create(
+        &self,
+        account: &Account,
+        catalog_entry: &CatalogObject<Id>,
+    ) -> Result<SqlCatalogObjectDocument, CatalogError> {
+        let _writes = self.writes.lock().await;
+        let document = self.cache.create(account, catalog_entry).await?;
+        self.publish(vec![Self::object_event(&document)?]).await?;
+        Ok(document)
+    }
+
+    async fn bulk_create(
+        &self,
+        account: &Account,
+        catalog: &[CatalogObjectBulkDocument<String>],
+    ) -> Result<Vec<SqlCatalogObjectDocument>, CatalogError> {
+        bulk_create(self, account, catalog).await
+    }
+
+    async fn exists(&self, account: &Account, id: &Id) -> Result<bool, CatalogError> {
+        self.cache.exists(account, id).await
+    }
+
+    async fn read(
+        &self,
+        account: &Account,
+        id: &Id,
+    ) -> Result<SqlCatalogObjectDocument, CatalogError> {
+        self.cache.read(account, id).await
+    }
+
+    async fn update(
+        &self,
+        account: &Account,
+        id: &Id,
+        catalog_entry: &CatalogObject<Id>,
+    ) -> Result<SqlCatalogObjectDocument, CatalogError> {
+        let _writes = self.writes.lock().await;
+        let document = self.cache.update(account, id, catalog_entry).await?;
+        self.publish(vec![Self::object_event(&document)?]).await?;
+        Ok(document)
+    }
+
+    async fn update_at_version(
+        &self,
+        account: &Account,
+        id: &Id,
+        catalog_entry: &CatalogObject<Id>,
+        version: &NaiveDateTime,
+    ) -> Result<SqlCatalogObjectDocument, CatalogError> {
+        let _writes = self.writes.lock().await;
+        let document = self
+            .cache
+            .update_at_version(account, id, catalog_entry, version)
+            .await?;
+        self.publish(vec![Self::object_event(&document)?]).await?;
+        Ok(document)
+    }
+
+    async fn list(
+        &self,
+        account: &Account,
+        query: &Self::Query,
+    ) -> Result<Vec<SqlCatalogObjectDocument>, CatalogError> {
+        self.cache.list(account, query).await
+    }
+
+    async fn delete(
+        &self,
+        account: &Account,
+        id: &Id,
+    ) -> Result<SqlCatalogObjectDocument, CatalogError> {
+        let _writes = self.writes.lock().await;
+        let levels = self.cache.stock_levels(account, id).await?;
+        let document = self.cache.delete(account, id).await?;
+        // state events can't be removed, they are emptied instead
+        let mut events = vec![(
+            event_type(&document.catalog_object),
+            id.to_string(),
+            json!({}),
+        )];
+        for level in levels {
+            events.push((STOCK_LEVEL_EVENT, stock_level_key(&level), json!({})));
+        }
+        self.publish(events).await?;
+        Ok(document)
+    }
+}
+
+#[async_trait]
+impl Commander for CatalogMatrixService {
+    type Cmd = CatalogCmd<Id>;
+    type Account = Account;
+
+    async fn cmd(&self, account: &Self::Account, cmd: Self::Cmd) -> Result<(), CatalogError> {
+        let id = *cmd.id();
+        let _writes = self.writes.lock().await;
+        self.cache.cmd(account, cmd).await?;
+
+        let mut events = vec![Self::object_event(&self.cache.read(account, &id).await?)?];
+        for level in self.cache.stock_levels(account, &id).await? {
+            events.push(Self::level_event(account, level)?);
+        }
+        self.publish(events).await
+    }
+}
+
+#[async_trait]
+impl CatalogProjection for CatalogMatrixService {
+    async fn list_fields(
+        &self,
+        account: &Account,
+        query: &Self::Query,
+        fields: &Fields,
+    ) -> Result<Vec<Value>, CatalogError> {
+        self.list(account, query)
+            .await?
+            .iter()
+            .map(|document| project(document, fields))
+            .collect()
+    }
+}
+
+#[async_trait]
+impl StockLocationService for CatalogMatrixService {
+    async fn create_location(
+        &self,
+        account: &Account,
+        location: &StockLocation,
+    ) -> Result<SqlStockLocationDocument, CatalogError> {
+        let _writes = self.writes.lock().await;
+        let document = self.cache.create_location(account, location).await?;
+        self.publish(vec![Self::location_event(&document)?]).await?;
+        Ok(document)
+    }
+
+    async fn read_location(
+        &self,
+        account: &Account,
+        id: &Id,
+    ) -> Result<SqlStockLocationDocument, CatalogError> {
+        self.cache.read_location(account, id).await
+    }
+
+    async fn update_location(
+        &self,
+        account: &Account,
+        id: &Id,
+        location: &StockLocation,
+    ) -> Result<SqlStockLocationDocument, CatalogError> {
+        let _writes = self.writes.lock().await;
+        let document = self.cache.update_location(account, id, location).await?;
+        self.publish(vec![Self::location_event(&document)?]).await?;
+        Ok(document)
+    }
+
+    async fn list_locations(
+        &self,
+        account: &Account,
+    ) -> Result<Vec<SqlStockLocationDocument>, CatalogError> {
+        self.cache.list_locations(account).await
+    }
+
+    async fn stock_levels(
+        &self,
+        account: &Account,
+        variation_id: &Id,
+    ) -> Result<Vec<StockLevel<Id>>, CatalogError> {
+        self.cache.stock_levels(account, variation_id).await
+    }
+}
@@ -0,0 +1,559 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use async_trait::async_trait;
+use chrono::{NaiveDateTime, Utc};
+use serde_json::Value;
+
+use super::super::utils::query::Order;
+use super::models::{
+    CatalogObject, CatalogObjectBulkDocument, ItemControl, ItemDelivery, ItemModification,
+    ItemVariation, Price, StockLevel, StockLocation,
+};
+use super::projection::{project, Fields};
+use super::service::{
+    bulk_create, BulkDocumentReferencesResolver, CatalogCmd, CatalogColumnOrder, CatalogError,
+    CatalogProjection, CatalogService, Commander, IncreaseItemVariationUnitsAtPayload,
+    StockLocationService, TransferItemVariationUnitsPayload,
+};
+use super::types::{
+    resolve_references, Account, Id, SqlCatalogObjectDocument, SqlCatalogQueryOptions,
+    SqlStockLocationDocument,
+};
+use super::validation::validate;
+
+// Everything is kept in insertion order, which is the order SQLite returns
+// rows in when the query isn't sorted.
+#[derive(Default)]
+struct Store {
+    objects: Vec<SqlCatalogObjectDocument>,
+    locations: Vec<SqlStockLocationDocument>,
+    levels: Vec<(Account, StockLevel<Id>)>,
+}
+
+impl Store {
+    fn object(&self, id: &Id) -> Option<&SqlCatalogObjectDocument> {
+        self.objects.iter().find(|document| &document.id == id)
+    }
+
+    fn owned_object(&self, account: &Account, id: &Id) -> Option<&SqlCatalogObjectDocument> {
+        self.object(id)
+            .filter(|document| &document.account == account)
+    }
+
+    fn owned_location(&self, account: &Account, id: &Id) -> Option<&SqlStockLocationDocument> {
+        self.locations
+            .iter()
+            .find(|location| &location.id == id && &location.account == account)
+    }
+
+    fn level_units(&self, account: &Account, location_id: &Id, variation_id: &Id) -> i32 {
+        self.levels
+            .iter()
+            .find(|(owner, level)| {
+                owner == account
+                    && &level.location_id == location_id
+                    && &level.variation_id == variation_id
+            })
+            .map(|(_, level)| level.units)
+            .unwrap_or(0)
+    }
+
+    fn add_level_units(
+        &mut self,
+        account: &Account,
+        location_id: &Id,
+        variation_id: &Id,
+        units: i32,
+    ) {
+        let level = self.levels.iter_mut().find(|(owner, level)| {
+            owner == account
+                && &level.location_id == location_id
+                && &level.variation_id == variation_id
+        });
+        match level {
+            Some((_, level)) => level.units += units,
+            None => self.levels.push((
+                account.to_owned(),
+                StockLevel {
+                    location_id: *location_id,
+                    variation_id: *variation_id,
+                    units,
+                },
+            )),
+        }
+    }
+
+    // the units of a variation never go below zero
+    fn add_variation_units(
+        &mut self,
+        account: &Account,
+        id: &Id,
+        units: i32,
+    ) -> Result<(), CatalogError> {
+        let document = self
+            .objects
+            .iter_mut()
+            .find(|document| &document.id == id && &document.account == account);
+        if let Some(SqlCatalogObjectDocument {
+            catalog_object: CatalogObject::Variation(variation),
+            version,
+            ..
+        }) = document
+        {
+            if variation.available_units + units < 0 {
+                return Err(CatalogError::InsufficientUnits(id.to_string()));
+            }
+            variation.available_units += units;
+            *version = next_version(version);
+            return Ok(());
+        }
+        Err(CatalogError::CatalogEntryNotFound(id.to_string()))
+    }
+
+    fn check_stock_references(
+        &self,
+        account: &Account,
+        variation_id: &Id,
+        location_ids: &[&Id],
+    ) -> Result<(), CatalogError> {
+        match self.owned_object(account, variation_id) {
+            Some(SqlCatalogObjectDocument {
+                catalog_object: CatalogObject::Variation(_),
+                ..
+            }) => {}
+            _ => return Err(CatalogError::CatalogEntryNotFound(variation_id.to_string())),
+        }
+        for location_id in location_ids {
+            if self.owned_location(account, location_id).is_none() {
+                return Err(CatalogError::CatalogEntryNotFound(location_id.to_string()));
+            }
+        }
+        Ok(())
+    }
+}
+
+// A catalog without database, for tests and for running the frontend
+// against a throwaway store. It follows the behaviour of `CatalogSQLService`.
+#[derive(Clone, Default)]
+pub struct CatalogMemoryService {
+    store: Arc<Mutex<Store>>,
+}
+
+impl CatalogMemoryService {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn store(&self) -> Result<MutexGuard<'_, Store>, CatalogError> {
+        self.store
+            .lock()
+            .map_err(|err| CatalogError::database(err.to_string()))
+    }
+
+    fn item_id(catalog_object: &CatalogObject<Id>) -> Option<&Id> {
+        match catalog_object {
+            CatalogObject::Item(_) => None,
+            CatalogObject::Variation(ItemVariation { item_id, .. })
+            | CatalogObject::Modification(ItemModification { item_id, .. })
+            | CatalogObject::Control(ItemControl { item_id, .. })
+            | CatalogObject::Delivery(ItemDelivery { item_id, .. }) => Some(item_id),
+        }
+    }
+
+    fn update_object(
+        &self,
+        account: &Account,
+        id: &Id,
+        catalog_entry: &CatalogObject<Id>,
+        version: Option<&NaiveDateTime>,
+    ) -> Result<SqlCatalogObjectDocument, CatalogError> {
+        validate(catalog_entry)?;
+        let mut store = self.store()?;
+        if let Some(item_id) = Self::item_id(catalog_entry) {
+            if store.owned_object(account, item_id).is_none() {
+                return Err(CatalogError::unknown_item(item_id));
+            }
+        }
+        // the type of an object can't change
+        let document = store
+            .objects
+            .iter_mut()
+            .find(|document| {
+                &document.id == id
+                    && &document.account == account
+                    && document.catalog_object.to_string() == catalog_entry.to_string()
+            })
+            .ok_or_else(|| CatalogError::CatalogEntryNotFound(id.to_string()))?;
+        if version.is_some_and(|version| *version != document.version) {
+            return Err(CatalogError::VersionMismatch(id.to_string()));
+        }
+        document.catalog_object = catalog_entry.to_owned();
+        document.version = next_version(&document.version);
+        Ok(document.clone())
+    }
+}
+
+fn now() -> NaiveDateTime {
+    Utc::now().naive_utc()
+}
+
+// the next version of an object, ahead of the last one even when the clock isn't
+fn next_version(version: &NaiveDateTime) -> NaiveDateTime {
+    let nanos = version.timestamp_nanos() + 1_000;
+    let after = NaiveDateTime::from_timestamp(
+        nanos.div_euclid(1_000_000_000),
+        nanos.rem_euclid(1_000_000_000) as u32,
+    );
+    now().max(after)
+}
+
+fn price_of(document: &SqlCatalogObjectDocument) -> Option<f32> {
+    match &document.catalog_object {
+        CatalogObject::Variation(ItemVariation {
+            price: Price::Fixed { amount, .. },
+            ..
+        }) => Some(*amount),
+        _ => None,
+    }
+}
+
+fn matches(
+    store: &Store,
+    document: &SqlCatalogObjectDocument,
+    query: &SqlCatalogQueryOptions,
+) -> bool {
+    let options = &query.options;
+    if let Some(name) = &options.name {
+        // LIKE in SQLite ignores the case
+        let name = name.to_lowercase();
+        let found = match &document.catalog_object {
+            CatalogObject::Item(item) => item.name.to_lowercase().contains(&name),
+            CatalogObject::Variation(variation) => variation.name.to_lowercase().contains(&name),
+            _ => false,
+        };
+        if !found {
+            return false;
+        }
+    }
+    if let Some(item_id) = &options.item_id {
+        if document.catalog_object.item_id() != Some(item_id) {
+            return false;
+        }
+    }
+    if let Some(tags) = &options.tags {
+        match &document.catalog_object {
+            CatalogObject::Item(item) if tags.iter().all(|tag| item.tags.contains(tag)) => {}
+            _ => return false,
+        }
+    }
+    if let Some(max_price) = options.max_price {
+        if !matches!(price_of(document), Some(price) if price <= max_price) {
+            return false;
+        }
+    }
+    if let Some(min_price) = options.min_price {
+        if !matches!(price_of(document), Some(price) if price >= min_price) {
+            return false;
+        }
+    }
+    if let Some(in_stock) = options.in_stock {
+        match &document.catalog_object {
+            CatalogObject::Variation(variation) if (variation.available_units > 0) == in_stock => {}
+            _ => return false,
+        }
+    }
+    if let Some(location_id) = &options.available_at {
+        if store.level_units(&document.account, location_id, &document.id) <= 0 {
+            return false;
+        }
+    }
+    true
+}
+
+impl BulkDocumentReferencesResolver for CatalogMemoryService {
+    type Id = Id;
+    fn resolve(
+        id_map: &HashMap<&str, Self::Id>,
+        catalog: &CatalogObject<String>,
+    ) -> Result<CatalogObject<Self::Id>, CatalogError> {
+        resolve_references(id_map, catalog)
+    }
+}
+
+#[async_trait]
+impl CatalogService for CatalogMemoryService {
+    type Id = Id;
+    type Query = SqlCatalogQueryOptions;
+
+    async fn create(
+        &self,
+        account: &Account,
+        catalog_entry: &CatalogObject<Id>,
+    ) -> Result<SqlCatalogObjectDocument, CatalogError> {
+        validate(catalog_entry)?;
+        let mut store = self.store()?;
+        if let Some(item_id) = Self::item_id(catalog_entry) {
+            if store.owned_object(account, item_id).is_none() {
+                return Err(CatalogError::unknown_item(item_id));
+            }
+        }
+        let mut id = rand::random::<Id>();
+        while store.object(&id).is_some() {
+            id = rand::random::<Id>();
+        }
+        let created_at = now();
+        let document = SqlCatalogObjectDocument {
+            id,
+            account: account.to_owned(),
+            version: created_at,
+            created_at,
+            catalog_object: catalog_entry.to_owned(),
+        };
+        store.objects.push(document.clone());
+        Ok(document)
+    }
+
+    async fn bulk_create(
+        &self,
+        account: &Account,
+        catalog: &[CatalogObjectBulkDocument<String>],
+    ) -> Result<Vec<SqlCatalogObjectDocument>, CatalogError> {
+        bulk_create(self, account, catalog).await
+    }
+
+    async fn exists(&self, account: &Account, id: &Id) -> Result<bool, CatalogError> {
+        Ok(self.store()?.owned_object(account, id).is_some())
+    }
+
+    async fn read(
+        &self,
+        _account: &Account,
+        id: &Id,
+    ) -> Result<SqlCatalogObjectDocument, CatalogError> {
+        self.store()?
+            .object(id)
+            .cloned()
+            .ok_or_else(|| CatalogError::CatalogEntryNotFound(id.to_string()))
+    }
+
+    async fn update(
+        &self,
+        account: &Account,
+        id: &Id,
+        catalog_entry: &CatalogObject<Id>,
+    ) -> Result<SqlCatalogObjectDocument, CatalogError> {
+        self.update_object(account, id, catalog_entry, None)
+    }
+
+    async fn update_at_version(
+        &self,
+        account: &Account,
+        id: &Id,
+        catalog_entry: &CatalogObject<Id>,
+        version: &NaiveDateTime,
+    ) -> Result<SqlCatalogObjectDocument, CatalogError> {
+        self.update_object(account, id, catalog_entry, Some(version))
+    }
+
+    async fn delete(
+        &self,
+        account: &Account,
+        id: &Id,
+    ) -> Result<SqlCatalogObjectDocument, CatalogError> {
+        let mut store = self.store()?;
+        let document = store
+            .owned_object(account, id)
+            .cloned()
+            .ok_or_else(|| CatalogError::CatalogEntryNotFound(id.to_string()))?;
+
+        if let CatalogObject::Item(_) = document.catalog_object {
+            // items can't be removed while variations, modifications, etc. point to them
+            let referenced = store.objects.iter().any(|other| {
+                &other.account == account && Self::item_id(&other.catalog_object) == Some(id)
+            });
+            if referenced {
+                return Err(CatalogError::item_in_use(id));
+            }
+        }
+
+        store.objects.retain(|other| &other.id != id);
+        store
+            .levels
+            .retain(|(owner, level)| owner != account || &level.variation_id != id);
+        Ok(document)
+    }
+
+    async fn list(
+        &self,
+        account: &Account,
+        query: &Self::Query,
+    ) -> Result<Vec<SqlCatalogObjectDocument>, CatalogError> {
+        let store = self.store()?;
+        let mut documents: Vec<SqlCatalogObjectDocument> = store
+            .objects
+            .iter()
+            .filter(|document| &document.account == account && matches(&store, document, query))
+            .cloned()
+            .collect();
+
+        if let Some(order_by) = &query.order_by {
+            // objects without price go first like NULL in SQLite
+            documents.sort_by(|a, b| {
+                let ordering = match order_by.field {
+                    CatalogColumnOrder::Price => price_of(a)
+                        .partial_cmp(&price_of(b))
+                        .unwrap_or(std::cmp::Ordering::Equal),
+                    CatalogColumnOrder::CreatedAt => a.created_at.cmp(&b.created_at),
+                };
+                match order_by.direction {
+                    Order::Asc => ordering,
+                    Order::Desc => ordering.reverse(),
+                }
+            });
+        }
+        Ok(documents)
+    }
+}
+
+#[async_trait]
+impl Commander for CatalogMemoryService {
+    type Cmd = CatalogCmd<Id>;
+    type Account = Account;
+
+    async fn cmd(&self, account: &Self::Account, cmd: Self::Cmd) -> Result<(), CatalogError> {
+        let mut store = self.store()?;
+        match cmd {
+            Self::Cmd::IncreaseItemVariationUnits(options) => {
+                store.add_variation_units(account, &options.id, options.units)?;
+            }
+            Self::Cmd::IncreaseItemVariationUnitsAt(IncreaseItemVariationUnitsAtPayload {
+                id,
+                location_id,
+                units,
+            }) => {
+                store.check_stock_references(account, &id, &[&location_id])?;
+                if store.level_units(account, &location_id, &id) + units < 0 {
+                    return Err(CatalogError::InsufficientUnits(id.to_string()));
+                }
+                store.add_variation_units(account, &id, units)?;
+                store.add_level_units(account, &location_id, &id, units);
+            }
+            Self::Cmd::TransferItemVariationUnits(TransferItemVariationUnitsPayload {
+                id,
+                from_location_id,
+                to_location_id,
+                units,
+            }) => {
+                if units <= 0 || from_location_id == to_location_id {
+                    return Err(CatalogError::invalid_transfer());
+                }
+                store.check_stock_references(
+                    account,
+                    &id,
+                    &[&from_location_id, &to_location_id],
+                )?;
+                if store.level_units(account, &from_location_id, &id) < units {
+                    return Err(CatalogError::InsufficientUnits(id.to_string()));
+                }
+                store.add_level_units(account, &from_location_id, &id, -units);
+                store.add_level_units(account, &to_location_id, &id, units);
+            }
+        };
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl CatalogProjection for CatalogMemoryService {
+    async fn list_fields(
+        &self,
+        account: &Account,
+        query: &Self::Query,
+        fields: &Fields,
+    ) -> Result<Vec<Value>, CatalogError> {
+        self.list(account, query)
+            .await?
+            .iter()
+            .map(|document| project(document, fields))
+            .collect()
+    }
+}
+
+#[async_trait]
+impl StockLocationService for CatalogMemoryService {
+    async fn create_location(
+        &self,
+        account: &Account,
+        location: &StockLocation,
+    ) -> Result<SqlStockLocationDocument, CatalogError> {
+        let mut store = self.store()?;
+        let created_at = now();
+        let document = SqlStockLocationDocument {
+            id: rand::random::<Id>(),
+            account: account.to_owned(),
+            version: created_at,
+            created_at,
+            location: location.to_owned(),
+        };
+        store.locations.push(document.clone());
+        Ok(document)
+    }
+
+    async fn read_location(
+        &self,
+        account: &Account,
+        id: &Id,
+    ) -> Result<SqlStockLocationDocument, CatalogError> {
+        self.store()?
+            .owned_location(account, id)
+            .cloned()
+            .ok_or_else(|| CatalogError::CatalogEntryNotFound(id.to_string()))
+    }
+
+    async fn update_location(
+        &self,
+        account: &Account,
+        id: &Id,
+        location: &StockLocation,
+    ) -> Result<SqlStockLocationDocument, CatalogError> {
+        let mut store = self.store()?;
+        let document = store
+            .locations
+            .iter_mut()
+            .find(|document| &document.id == id && &document.account == account)
+            .ok_or_else(|| CatalogError::CatalogEntryNotFound(id.to_string()))?;
+        document.location = location.to_owned();
+        document.version = now();
+        Ok(document.clone())
+    }
+
+    async fn list_locations(
+        &self,
+        account: &Account,
+    ) -> Result<Vec<SqlStockLocationDocument>, CatalogError> {
+        Ok(self
+            .store()?
+            .locations
+            .iter()
+            .filter(|location| &location.account == account)
+            .cloned()
+            .collect())
+    }
+
+    async fn stock_levels(
+        &self,
+        account: &Account,
+        variation_id: &Id,
+    ) -> Result<Vec<StockLevel<Id>>, CatalogError> {
+        let mut levels: Vec<StockLevel<Id>> = self
+            .store()?
+            .levels
+            .iter()
+            .filter(|(owner, level)| owner == account && &level.variation_id == variation_id)
+            .map(|(_, level)| level.clone())
+            .collect();
+        levels.sort_by_key(|level| level.location_id);
+        Ok(levels)
+    }
+}
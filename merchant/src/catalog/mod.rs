@@ -1,3 +1,18 @@
+#[cfg(feature = "server")]
+pub mod any;
+#[cfg(feature = "server")]
 pub mod backend;
+pub mod error;
+pub mod feed;
+pub mod jsonld;
+#[cfg(feature = "server")]
+pub mod matrix;
+pub mod memory;
 pub mod models;
+#[cfg(feature = "server")]
+pub mod postgres;
+pub mod projection;
 pub mod service;
+pub mod spreadsheet;
+pub mod types;
+pub mod validation;
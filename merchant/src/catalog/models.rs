@@ -1,11 +1,17 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     fmt::{self, Display},
 };
 
+use async_graphql::{Enum, SimpleObject};
+use chrono::NaiveDateTime;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_with::with_prefix;
-use sqlx::types::chrono::NaiveDateTime;
+
+use super::service::CatalogCmd;
+use crate::utils::diff::JsonChange;
+use crate::utils::schema::{NotBlankKeys, Prefix, Prefixed};
 
 with_prefix!(price_prefix "price_");
 with_prefix!(warranty_prefix "warranty_time_");
@@ -13,7 +19,39 @@ with_prefix!(processing_prefix "processing_time_");
 with_prefix!(delivery_prefix "delivery_");
 with_prefix!(control_prefix "control_");
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+// the prefixes above for the schemas
+pub struct PricePrefix;
+pub struct WarrantyPrefix;
+pub struct ProcessingPrefix;
+pub struct DeliveryPrefix;
+pub struct ControlPrefix;
+
+impl Prefix for PricePrefix {
+    const PREFIX: &'static str = "price_";
+    const OPTIONAL: bool = false;
+}
+
+impl Prefix for WarrantyPrefix {
+    const PREFIX: &'static str = "warranty_time_";
+    const OPTIONAL: bool = true;
+}
+
+impl Prefix for ProcessingPrefix {
+    const PREFIX: &'static str = "processing_time_";
+    const OPTIONAL: bool = true;
+}
+
+impl Prefix for DeliveryPrefix {
+    const PREFIX: &'static str = "delivery_";
+    const OPTIONAL: bool = false;
+}
+
+impl Prefix for ControlPrefix {
+    const PREFIX: &'static str = "control_";
+    const OPTIONAL: bool = false;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, JsonSchema, Enum)]
 pub enum ItemMeasurmentUnits {
     Time,
     Area,
@@ -25,7 +63,7 @@ pub enum ItemMeasurmentUnits {
     Weight,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, JsonSchema, Enum)]
 pub enum ItemCategory {
     Shop,
     Restaurant,
@@ -48,102 +86,134 @@ pub enum ItemCategory {
     PaperWork,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, PartialOrd)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, PartialOrd, JsonSchema)]
 #[serde(tag = "type")]
 pub enum Price {
-    Fixed { 
+    Fixed {
+        #[schemars(range(min = 0))]
         amount: f32,
+        #[schemars(regex(pattern = r"\S"))]
         asset_name: String,
-        asset_scale: i8
+        #[schemars(range(min = 0))]
+        asset_scale: i8,
     },
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, PartialOrd, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, PartialOrd, Eq, JsonSchema)]
 #[serde(tag = "type")]
 pub enum Time {
     Fixed { seconds: u32 },
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, JsonSchema, SimpleObject)]
 pub struct Image {
+    #[schemars(regex(pattern = r"\S"))]
     pub url: String,
 }
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, JsonSchema)]
 pub struct Item {
     pub category: ItemCategory,
+    #[schemars(inner(regex(pattern = r"\S")))]
     pub tags: Vec<String>,
+    #[schemars(regex(pattern = r"\S"))]
     pub name: String,
     pub images: Vec<Image>,
     pub description: String,
     pub enabled: bool,
     #[serde(flatten, with = "warranty_prefix")]
+    #[schemars(with = "Prefixed<Time, WarrantyPrefix>")]
     pub warranty_time: Option<Time>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
 pub struct ItemVariation<Id> {
     pub item_id: Id,
+    #[schemars(regex(pattern = r"\S"))]
     pub name: String,
     #[serde(flatten, with = "processing_prefix")]
+    #[schemars(with = "Prefixed<Time, ProcessingPrefix>")]
     pub processing_time: Option<Time>,
+    #[schemars(regex(pattern = r"\S"))]
     pub sku: String,
     pub images: Vec<Image>,
+    #[schemars(regex(pattern = r"^[0-9]+$"))]
     pub upc: Option<String>,
     pub enabled: bool,
     pub measurement_units: ItemMeasurmentUnits,
+    #[schemars(range(min = 0))]
     pub available_units: i32,
+    // an alert is raised once `available_units` drops to this value
+    #[serde(default)]
+    #[schemars(range(min = 0))]
+    pub reorder_threshold: Option<i32>,
     #[serde(flatten, with = "price_prefix")]
+    #[schemars(with = "Prefixed<Price, PricePrefix>")]
     pub price: Price,
     // #[serde(flatten)]
+    #[schemars(with = "Option<NotBlankKeys<HashMap<String, String>>>")]
     pub extra_attributes: Option<HashMap<String, String>>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
 pub struct ItemModification<Id> {
     pub item_id: Id,
+    #[schemars(regex(pattern = r"\S"))]
     pub name: String,
     #[serde(flatten, with = "processing_prefix")]
+    #[schemars(with = "Prefixed<Time, ProcessingPrefix>")]
     pub processing_time: Option<Time>,
     #[serde(flatten, with = "warranty_prefix")]
+    #[schemars(with = "Prefixed<Time, WarrantyPrefix>")]
     pub warranty_time: Option<Time>,
     pub images: Vec<Image>,
     #[serde(flatten, with = "price_prefix")]
+    #[schemars(with = "Prefixed<Price, PricePrefix>")]
     pub price: Price,
     pub enabled: bool,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, PartialOrd)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, PartialOrd, JsonSchema)]
 #[serde(tag = "type")]
 pub enum Delivery {
     Shipping {
+        #[schemars(range(min = 1))]
         width_mm: i32,
+        #[schemars(range(min = 1))]
         length_mm: i32,
+        #[schemars(range(min = 1))]
         height_mm: i32,
+        #[schemars(range(min = 1))]
         weight_grams: i32,
     },
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
 pub struct ItemDelivery<Id> {
     pub item_id: Id,
     #[serde(flatten, with = "delivery_prefix")]
+    #[schemars(with = "Prefixed<Delivery, DeliveryPrefix>")]
     pub delivery: Delivery,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema, SimpleObject)]
 pub struct MatrixProp {
+    #[schemars(regex(pattern = r"\S"))]
     pub name: String,
+    #[schemars(length(min = 1))]
     pub options: Vec<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
 pub struct MatrixControl<Id> {
+    #[schemars(with = "NotBlankKeys<HashMap<String, Id>>")]
     pub combinations: HashMap<String, Id>,
+    #[schemars(regex(pattern = r"\S"))]
     pub key_template: String,
+    #[schemars(length(min = 1))]
     pub props: Vec<MatrixProp>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
 #[serde(tag = "type")]
 pub enum FormItem {
     Text(HashMap<String, String>),
@@ -151,21 +221,22 @@ pub enum FormItem {
     Password(HashMap<String, String>),
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
 #[serde(tag = "type", content = "data")]
 pub enum Control<Id> {
     Matrix(MatrixControl<Id>),
     Form(Vec<FormItem>),
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
 pub struct ItemControl<Id> {
     pub item_id: Id,
     #[serde(flatten, with = "control_prefix")]
+    #[schemars(with = "Prefixed<Control<Id>, ControlPrefix>")]
     pub control: Control<Id>,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
 #[serde(tag = "type", content = "data")]
 pub enum CatalogObject<Id> {
     Item(Item),
@@ -196,9 +267,36 @@ impl<Id> CatalogObject<Id> {
             _ => None,
         }
     }
+
+    // the item every other object belongs to
+    pub fn item_id(&self) -> Option<&Id> {
+        match self {
+            Self::Item(_) => None,
+            Self::Variation(ItemVariation { item_id, .. })
+            | Self::Modification(ItemModification { item_id, .. })
+            | Self::Delivery(ItemDelivery { item_id, .. })
+            | Self::Control(ItemControl { item_id, .. }) => Some(item_id),
+        }
+    }
+    // every id the object points to, to rewrite them when the objects get new ids
+    pub fn references_mut(&mut self) -> Vec<&mut Id> {
+        match self {
+            Self::Item(_) => vec![],
+            Self::Control(ItemControl {
+                item_id,
+                control: Control::Matrix(matrix),
+            }) => std::iter::once(item_id)
+                .chain(matrix.combinations.values_mut())
+                .collect(),
+            Self::Variation(ItemVariation { item_id, .. })
+            | Self::Modification(ItemModification { item_id, .. })
+            | Self::Delivery(ItemDelivery { item_id, .. })
+            | Self::Control(ItemControl { item_id, .. }) => vec![item_id],
+        }
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 pub struct CatalogObjectDocument<Id, Account> {
     pub id: Id,
     pub account: Account,
@@ -208,9 +306,145 @@ pub struct CatalogObjectDocument<Id, Account> {
     pub catalog_object: CatalogObject<Id>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 pub struct CatalogObjectBulkDocument<Id> {
     pub id: Option<Id>,
     #[serde(flatten)]
     pub catalog_object: CatalogObject<Id>,
 }
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, JsonSchema, Enum)]
+pub enum StockLocationKind {
+    Store,
+    Warehouse,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, JsonSchema)]
+pub struct StockLocation {
+    pub name: String,
+    pub kind: StockLocationKind,
+    pub address: Option<String>,
+    pub enabled: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct StockLocationDocument<Id, Account> {
+    pub id: Id,
+    pub account: Account,
+    pub version: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+    #[serde(flatten)]
+    pub location: StockLocation,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, JsonSchema)]
+pub struct StockLevel<Id> {
+    pub location_id: Id,
+    pub variation_id: Id,
+    pub units: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, JsonSchema)]
+pub struct StockAlert<Id, Account> {
+    pub id: Id,
+    pub account: Account,
+    pub variation_id: Id,
+    pub available_units: i32,
+    pub reorder_threshold: i32,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, JsonSchema, Enum)]
+pub enum CatalogChangeOperation {
+    Created,
+    Updated,
+    Deleted,
+    Command,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct CatalogChange<Id, Account> {
+    pub sequence: i64,
+    pub account: Account,
+    pub id: Id,
+    pub operation: CatalogChangeOperation,
+    // the object as it is after the change, the deleted one for `Deleted`
+    pub document: CatalogObjectDocument<Id, Account>,
+    pub cmd: Option<CatalogCmd<Id>>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct CatalogRevision<Id, Account> {
+    // starts at 1 and grows with every write of the object
+    pub revision: i64,
+    pub document: CatalogObjectDocument<Id, Account>,
+    pub created_at: NaiveDateTime,
+}
+
+pub const CATALOG_ARCHIVE_VERSION: u32 = 1;
+
+// The catalog of an account with its history and inventory, portable to
+// another instance. The ids only relate the parts of the archive, they are
+// replaced when it's imported.
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct CatalogArchive<Id, Account> {
+    pub format_version: u32,
+    pub account: Account,
+    pub exported_at: NaiveDateTime,
+    pub objects: Vec<CatalogObjectDocument<Id, Account>>,
+    pub revisions: Vec<CatalogRevision<Id, Account>>,
+    pub locations: Vec<StockLocationDocument<Id, Account>>,
+    pub stock_levels: Vec<StockLevel<Id>>,
+}
+
+// the ids the archived objects and locations got in the import
+#[derive(Serialize, Debug, Clone, JsonSchema)]
+#[serde(bound(serialize = "Id: Serialize + Ord"))]
+pub struct CatalogImport<Id> {
+    pub objects: BTreeMap<Id, Id>,
+    pub locations: BTreeMap<Id, Id>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+pub struct CatalogRevisionDiff {
+    pub from: i64,
+    pub to: i64,
+    pub changes: Vec<JsonChange>,
+}
+
+// Who is behind a write, the http layer fills it from the request. `id` is
+// who the server could authenticate, the name the caller gives itself is
+// only kept next to it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, JsonSchema)]
+pub struct Actor {
+    pub id: String,
+    pub claimed_id: Option<String>,
+    pub address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl Default for Actor {
+    fn default() -> Self {
+        Self {
+            id: "system".to_string(),
+            claimed_id: None,
+            address: None,
+            user_agent: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct AuditEntry<Id, Account> {
+    pub id: i64,
+    pub account: Account,
+    pub object_id: Id,
+    pub actor: Actor,
+    pub operation: CatalogChangeOperation,
+    // the object before and after the write, missing on creates and deletes
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+    pub cmd: Option<CatalogCmd<Id>>,
+    pub created_at: NaiveDateTime,
+}
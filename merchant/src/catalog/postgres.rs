@@ -0,0 +1,1084 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+
+use chrono::NaiveDateTime;
+use sea_query::{
+    Cond, Expr, Iden, Order as OrderSql, PostgresQueryBuilder as QueryBuilder, Query as Qsql,
+    Values,
+};
+use sqlx::{types::Json, FromRow, PgPool as Pool, Postgres, Transaction};
+
+use super::backend::{
+    Account, CatalogSQLService, Id, ProjectionRow, SqlCatalogObjectDocument,
+    SqlCatalogQueryOptions, SqlStockLocationDocument, StockLevelSchema, StockLocationSchema,
+};
+use super::models::{
+    CatalogObject, CatalogObjectBulkDocument, Item, ItemControl, ItemDelivery, ItemModification,
+    ItemVariation, StockLevel, StockLocation,
+};
+use super::projection::Fields;
+use super::service::{
+    bulk_create, BulkDocumentReferencesResolver, CatalogCmd, CatalogColumnOrder, CatalogError,
+    CatalogProjection, CatalogService, Commander, IncreaseItemVariationUnitsAtPayload,
+    IncreaseItemVariationUnitsPayload, StockLocationService, TransferItemVariationUnitsPayload,
+};
+use super::validation::validate;
+use crate::utils::query::contains_pattern;
+
+sea_query::sea_query_driver_postgres!();
+use sea_query_driver_postgres::{bind_query, bind_query_as};
+
+// Postgres has no unsigned integers, the ids are kept in BIGINT columns
+fn to_sql_id(id: &Id) -> i64 {
+    i64::from(*id)
+}
+
+fn from_sql_id(id: i64) -> Result<Id, CatalogError> {
+    id.try_into().map_err(CatalogError::mapping)
+}
+
+#[derive(Clone)]
+pub struct CatalogPgService {
+    pool: Pool,
+}
+
+impl CatalogPgService {
+    pub fn new(pool: Pool) -> Self {
+        Self { pool }
+    }
+
+    fn list_statement(
+        columns: &str,
+        account: &Account,
+        query: &SqlCatalogQueryOptions,
+    ) -> (String, Values) {
+        // anywhere in the name ignoring the case, the trigram indexes find them
+        let name_is_like_expr = |name: &str| {
+            Cond::any()
+                .add(Expr::cust_with_values(
+                    format!(
+                        "{}->>'name' ILIKE ? ESCAPE '\\'",
+                        PgCatalogSchema::ItemData.to_string().as_str()
+                    )
+                    .as_str(),
+                    vec![contains_pattern(name)],
+                ))
+                .add(Expr::cust_with_values(
+                    format!(
+                        "{}->>'name' ILIKE ? ESCAPE '\\'",
+                        PgCatalogSchema::ItemVariationData.to_string().as_str()
+                    )
+                    .as_str(),
+                    vec![contains_pattern(name)],
+                ))
+        };
+        let price_expr = format!(
+            "({}->>'price_amount')::real",
+            PgCatalogSchema::ItemVariationData.to_string()
+        );
+
+        Qsql::select()
+            .expr(Expr::cust(columns))
+            .from(PgCatalogSchema::Table)
+            .and_where(Expr::col(PgCatalogSchema::Account).eq(account.to_string()))
+            .conditions(
+                query.options.name.is_some(),
+                |q| {
+                    let name = query.options.name.as_ref().unwrap();
+                    q.cond_where(name_is_like_expr(name.as_str()));
+                },
+                |_| {},
+            )
+            .conditions(
+                query.options.item_id.is_some(),
+                |q| {
+                    let item_id = query.options.item_id.as_ref().unwrap();
+                    q.and_where(Expr::cust_with_values(
+                        format!(
+                            "(COALESCE({}, {}, {}, {})->>'item_id')::bigint = ?",
+                            PgCatalogSchema::ItemVariationData.to_string(),
+                            PgCatalogSchema::ItemModificationData.to_string(),
+                            PgCatalogSchema::ItemDeliveryData.to_string(),
+                            PgCatalogSchema::ItemControlData.to_string(),
+                        )
+                        .as_str(),
+                        vec![to_sql_id(item_id)],
+                    ));
+                },
+                |_| {},
+            )
+            .conditions(
+                query.options.tags.is_some(),
+                |q| {
+                    let tags = query.options.tags.as_ref().unwrap();
+                    let str_json = serde_json::json!({ "tags": tags }).to_string();
+                    // containment can be answered from the GIN index
+                    q.cond_where(Expr::cust_with_values(
+                        format!("{} @> ?::jsonb", PgCatalogSchema::ItemData.to_string()).as_str(),
+                        vec![str_json],
+                    ));
+                },
+                |_| {},
+            )
+            .conditions(
+                query.options.max_price.is_some(),
+                |q| {
+                    let max_price = query.options.max_price.unwrap();
+                    q.cond_where(Expr::cust_with_values(
+                        format!("{} <= ?", price_expr).as_str(),
+                        vec![max_price],
+                    ));
+                },
+                |_| {},
+            )
+            .conditions(
+                query.options.min_price.is_some(),
+                |q| {
+                    let min_price = query.options.min_price.unwrap();
+                    q.cond_where(Expr::cust_with_values(
+                        format!("{} >= ?", price_expr).as_str(),
+                        vec![min_price],
+                    ));
+                },
+                |_| {},
+            )
+            .conditions(
+                query.options.in_stock.is_some(),
+                |q| {
+                    let operator = match query.options.in_stock.unwrap() {
+                        true => ">",
+                        false => "<=",
+                    };
+                    q.cond_where(Expr::cust(
+                        format!(
+                            "({}->>'available_units')::integer {} 0",
+                            PgCatalogSchema::ItemVariationData.to_string(),
+                            operator
+                        )
+                        .as_str(),
+                    ));
+                },
+                |_| {},
+            )
+            .conditions(
+                query.options.available_at.is_some(),
+                |q| {
+                    let location_id = query.options.available_at.unwrap();
+                    q.cond_where(Expr::cust_with_values(
+                        format!(
+                            "EXISTS (SELECT 1 FROM {levels} WHERE {levels}.{account} = {table}.{account} AND {levels}.{variation_id} = {table}.{id} AND {levels}.{location_id} = ? AND {levels}.{units} > 0)",
+                            levels = StockLevelSchema::Table.to_string(),
+                            table = PgCatalogSchema::Table.to_string(),
+                            account = PgCatalogSchema::Account.to_string(),
+                            id = PgCatalogSchema::Id.to_string(),
+                            variation_id = StockLevelSchema::VariationId.to_string(),
+                            location_id = StockLevelSchema::LocationId.to_string(),
+                            units = StockLevelSchema::Units.to_string(),
+                        )
+                        .as_str(),
+                        vec![to_sql_id(&location_id)],
+                    ));
+                },
+                |_| {},
+            )
+            .conditions(
+                query.order_by.is_some(),
+                |q| {
+                    let order_by = query.order_by.as_ref().unwrap();
+                    match order_by.field {
+                        CatalogColumnOrder::Price => {
+                            // objects without price go first like in SQLite
+                            q.order_by_expr(
+                                Expr::cust(format!("{} IS NOT NULL", price_expr).as_str()),
+                                OrderSql::from(order_by.direction),
+                            );
+                            q.order_by_expr(
+                                Expr::cust(price_expr.as_str()),
+                                OrderSql::from(order_by.direction),
+                            );
+                        }
+                        CatalogColumnOrder::CreatedAt => {
+                            q.order_by_expr(
+                                Expr::cust(PgCatalogSchema::CreatedAt.to_string().as_str()),
+                                OrderSql::from(order_by.direction),
+                            );
+                        }
+                    };
+                },
+                |_| {},
+            )
+            .build(QueryBuilder)
+    }
+
+    fn get_sql_to_create(
+        &self,
+        field_data_name: PgCatalogSchema,
+        object_type: &CatalogObject<Id>,
+    ) -> String {
+        let (sql, _) = Qsql::insert()
+            .into_table(PgCatalogSchema::Table)
+            .columns(vec![
+                PgCatalogSchema::Id,
+                PgCatalogSchema::TypeEntry,
+                PgCatalogSchema::Account,
+                field_data_name,
+            ])
+            .exprs_panic(vec![
+                Expr::value("$1"),
+                Expr::cust(format!("'{}'", object_type).as_ref()),
+                Expr::value("$2"),
+                Expr::value("$3"),
+            ])
+            .returning(Qsql::select().expr(Expr::asterisk()).take())
+            .build(QueryBuilder);
+        sql
+    }
+
+    // with `versioned` the object must still be at the version bound last
+    fn get_sql_to_update(
+        &self,
+        field: PgCatalogSchema,
+        type_entry: &str,
+        versioned: bool,
+    ) -> String {
+        let (sql, _) = Qsql::update()
+            .table(PgCatalogSchema::Table)
+            .value(field, "-1".into())
+            .value_expr(PgCatalogSchema::Version, Expr::cust(NEXT_VERSION))
+            .and_where(Expr::col(PgCatalogSchema::Account).eq("1"))
+            .and_where(Expr::cust(
+                format!(
+                    "{} = '{}'",
+                    PgCatalogSchema::TypeEntry.to_string(),
+                    type_entry
+                )
+                .as_ref(),
+            ))
+            .and_where(Expr::col(PgCatalogSchema::Id).eq(-1i64))
+            .and_where_option(versioned.then(|| Expr::col(PgCatalogSchema::Version).eq("-1")))
+            .returning(Qsql::select().expr(Expr::asterisk()).take())
+            .build(QueryBuilder);
+
+        sql
+    }
+
+    async fn update_object(
+        &self,
+        account: &Account,
+        id: &Id,
+        catalog_entry: &CatalogObject<Id>,
+        version: Option<&NaiveDateTime>,
+    ) -> Result<SqlCatalogObjectDocument, CatalogError> {
+        validate(catalog_entry)?;
+        let (data, field, item_id) = Self::data_of(catalog_entry)?;
+        if let Some(item_id) = item_id {
+            if !self.exists(account, item_id).await? {
+                return Err(CatalogError::unknown_item(item_id));
+            }
+        }
+        let sql =
+            self.get_sql_to_update(field, catalog_entry.to_string().as_str(), version.is_some());
+
+        let mut pool = self.pool.acquire().await.map_err(CatalogError::database)?;
+
+        let result: Option<PgCatalogObjectRow> = sqlx::query_as(sql.as_str())
+            .bind(Json(data))
+            .bind(account.as_str())
+            .bind(to_sql_id(id))
+            .bind(version)
+            .fetch_optional(&mut pool)
+            .await
+            .map_err(CatalogError::database)?;
+
+        match result {
+            Some(result) => result.to_catalog_entry_document(),
+            // the version only decides between the objects that could be updated
+            None => match self.read(account, id).await {
+                Ok(document)
+                    if version.is_some()
+                        && &document.account == account
+                        && document.catalog_object.to_string() == catalog_entry.to_string() =>
+                {
+                    Err(CatalogError::VersionMismatch(id.to_string()))
+                }
+                _ => Err(CatalogError::CatalogEntryNotFound(id.to_string())),
+            },
+        }
+    }
+
+    // the json stored in the `*_data` column and the statement to write it
+    fn data_of(
+        catalog_entry: &CatalogObject<Id>,
+    ) -> Result<(serde_json::Value, PgCatalogSchema, Option<&Id>), CatalogError> {
+        match catalog_entry {
+            CatalogObject::Item(entry) => Ok((
+                serde_json::to_value(entry).map_err(CatalogError::mapping)?,
+                PgCatalogSchema::ItemData,
+                None,
+            )),
+            variation @ CatalogObject::Variation(ItemVariation { item_id, .. })
+            | variation @ CatalogObject::Modification(ItemModification { item_id, .. })
+            | variation @ CatalogObject::Control(ItemControl { item_id, .. })
+            | variation @ CatalogObject::Delivery(ItemDelivery { item_id, .. }) => {
+                // Here we get {type, data} json value
+                let data = serde_json::to_value(variation).map_err(CatalogError::mapping)?;
+                let data = data
+                    .get("data")
+                    .ok_or_else(|| CatalogError::mapping("the row has no data"))?
+                    .to_owned();
+                Ok((data, PgCatalogSchema::of(variation), Some(item_id)))
+            }
+        }
+    }
+}
+
+impl BulkDocumentReferencesResolver for CatalogPgService {
+    type Id = Id;
+    fn resolve(
+        id_map: &HashMap<&str, Self::Id>,
+        catalog: &CatalogObject<String>,
+    ) -> Result<CatalogObject<Self::Id>, CatalogError> {
+        <CatalogSQLService as BulkDocumentReferencesResolver>::resolve(id_map, catalog)
+    }
+}
+
+#[async_trait]
+impl CatalogService for CatalogPgService {
+    type Id = Id;
+    type Query = SqlCatalogQueryOptions;
+
+    async fn create(
+        &self,
+        account: &Account,
+        catalog_entry: &CatalogObject<Id>,
+    ) -> Result<SqlCatalogObjectDocument, CatalogError> {
+        validate(catalog_entry)?;
+        let (data, field, item_id) = Self::data_of(catalog_entry)?;
+        if let Some(item_id) = item_id {
+            if !self.exists(account, item_id).await? {
+                return Err(CatalogError::unknown_item(item_id));
+            }
+        }
+        let sql = self.get_sql_to_create(field, catalog_entry);
+
+        let mut pool = self.pool.acquire().await.map_err(CatalogError::database)?;
+
+        let result: PgCatalogObjectRow = sqlx::query_as(sql.as_str())
+            .bind(to_sql_id(&rand::random::<Id>()))
+            .bind(account)
+            .bind(Json(data))
+            .fetch_one(&mut pool)
+            .await
+            .map_err(CatalogError::mapping)?;
+
+        result.to_catalog_entry_document()
+    }
+
+    async fn bulk_create(
+        &self,
+        account: &Account,
+        catalog: &[CatalogObjectBulkDocument<String>],
+    ) -> Result<Vec<SqlCatalogObjectDocument>, CatalogError> {
+        bulk_create(self, account, catalog).await
+    }
+
+    async fn exists(&self, account: &Account, id: &Id) -> Result<bool, CatalogError> {
+        let (sql, values) = Qsql::select()
+            .expr(Expr::cust("COUNT(1) as count"))
+            .from(PgCatalogSchema::Table)
+            .and_where(Expr::col(PgCatalogSchema::Id).eq(to_sql_id(id)))
+            .and_where(Expr::col(PgCatalogSchema::Account).eq(account.to_string()))
+            .build(QueryBuilder);
+
+        let mut pool = self.pool.acquire().await.map_err(CatalogError::database)?;
+
+        let catalog_row: Count = bind_query_as(sqlx::query_as(&sql), &values)
+            .fetch_one(&mut pool)
+            .await
+            .map_err(CatalogError::database)?;
+
+        Ok(catalog_row.count != 0)
+    }
+
+    async fn read(
+        &self,
+        _account: &Account,
+        id: &Id,
+    ) -> Result<SqlCatalogObjectDocument, CatalogError> {
+        let (sql, values) = Qsql::select()
+            .expr(Expr::asterisk())
+            .from(PgCatalogSchema::Table)
+            .and_where(Expr::col(PgCatalogSchema::Id).eq(to_sql_id(id)))
+            .build(QueryBuilder);
+
+        let mut pool = self.pool.acquire().await.map_err(CatalogError::database)?;
+
+        let catalog_row: PgCatalogObjectRow = bind_query_as(sqlx::query_as(&sql), &values)
+            .fetch_one(&mut pool)
+            .await
+            .map_err(|err| match err {
+                sqlx::Error::RowNotFound => CatalogError::CatalogEntryNotFound(id.to_string()),
+                err => CatalogError::database(err),
+            })?;
+
+        catalog_row.to_catalog_entry_document()
+    }
+
+    async fn update(
+        &self,
+        account: &Account,
+        id: &Id,
+        catalog_entry: &CatalogObject<Id>,
+    ) -> Result<SqlCatalogObjectDocument, CatalogError> {
+        self.update_object(account, id, catalog_entry, None).await
+    }
+
+    async fn update_at_version(
+        &self,
+        account: &Account,
+        id: &Id,
+        catalog_entry: &CatalogObject<Id>,
+        version: &NaiveDateTime,
+    ) -> Result<SqlCatalogObjectDocument, CatalogError> {
+        self.update_object(account, id, catalog_entry, Some(version))
+            .await
+    }
+
+    async fn delete(
+        &self,
+        account: &Account,
+        id: &Id,
+    ) -> Result<SqlCatalogObjectDocument, CatalogError> {
+        let document = self.read(account, id).await?;
+        if &document.account != account {
+            return Err(CatalogError::CatalogEntryNotFound(id.to_string()));
+        }
+
+        let mut tx = self.pool.begin().await.map_err(CatalogError::database)?;
+
+        if let CatalogObject::Item(_) = document.catalog_object {
+            // items can't be removed while variations, modifications, etc. point to them
+            let references: Count = sqlx::query_as(
+                format!(
+                    "SELECT COUNT(1) as count FROM {table} WHERE {account} = $1 AND COALESCE(
+                        {variation}->>'item_id',
+                        {modification}->>'item_id',
+                        {delivery}->>'item_id',
+                        {control}->>'item_id'
+                    ) = $2",
+                    table = PgCatalogSchema::Table.to_string(),
+                    account = PgCatalogSchema::Account.to_string(),
+                    variation = PgCatalogSchema::ItemVariationData.to_string(),
+                    modification = PgCatalogSchema::ItemModificationData.to_string(),
+                    delivery = PgCatalogSchema::ItemDeliveryData.to_string(),
+                    control = PgCatalogSchema::ItemControlData.to_string(),
+                )
+                .as_str(),
+            )
+            .bind(account)
+            .bind(id.to_string())
+            .fetch_one(&mut tx)
+            .await
+            .map_err(CatalogError::database)?;
+
+            if references.count != 0 {
+                return Err(CatalogError::item_in_use(id));
+            }
+        }
+
+        let (sql, values) = Qsql::delete()
+            .from_table(PgCatalogSchema::Table)
+            .and_where(Expr::col(PgCatalogSchema::Id).eq(to_sql_id(id)))
+            .and_where(Expr::col(PgCatalogSchema::Account).eq(account.to_string()))
+            .build(QueryBuilder);
+        bind_query(sqlx::query(&sql), &values)
+            .execute(&mut tx)
+            .await
+            .map_err(CatalogError::database)?;
+
+        let (sql, values) = Qsql::delete()
+            .from_table(StockLevelSchema::Table)
+            .and_where(Expr::col(StockLevelSchema::VariationId).eq(to_sql_id(id)))
+            .and_where(Expr::col(StockLevelSchema::Account).eq(account.to_string()))
+            .build(QueryBuilder);
+        bind_query(sqlx::query(&sql), &values)
+            .execute(&mut tx)
+            .await
+            .map_err(CatalogError::database)?;
+
+        tx.commit().await.map_err(CatalogError::database)?;
+        Ok(document)
+    }
+
+    async fn list(
+        &self,
+        account: &Account,
+        query: &Self::Query,
+    ) -> Result<Vec<SqlCatalogObjectDocument>, CatalogError> {
+        let (sql, values) = Self::list_statement("*", account, query);
+
+        let mut pool = self.pool.acquire().await.map_err(CatalogError::database)?;
+
+        let result: Vec<PgCatalogObjectRow> = bind_query_as(sqlx::query_as(&sql), &values)
+            .fetch_all(&mut pool)
+            .await
+            .map_err(CatalogError::database)?;
+
+        result
+            .into_iter()
+            .map(|row| row.to_catalog_entry_document())
+            .collect()
+    }
+}
+
+#[async_trait]
+impl CatalogProjection for CatalogPgService {
+    async fn list_fields(
+        &self,
+        account: &Account,
+        query: &Self::Query,
+        fields: &Fields,
+    ) -> Result<Vec<serde_json::Value>, CatalogError> {
+        let projection = fields.to_sql("jsonb_build_object", pg_document_field, pg_data_field);
+        let (sql, values) =
+            Self::list_statement(&format!("{} AS projection", projection), account, query);
+
+        let mut pool = self.pool.acquire().await.map_err(CatalogError::database)?;
+
+        let rows: Vec<ProjectionRow> = bind_query_as(sqlx::query_as(&sql), &values)
+            .fetch_all(&mut pool)
+            .await
+            .map_err(CatalogError::database)?;
+
+        Ok(rows.into_iter().map(|row| row.projection.0).collect())
+    }
+}
+
+// the fields of the document as serde writes them, timestamps with 3 or 6
+// digits of fraction like chrono
+fn pg_document_field(field: &str) -> String {
+    match field {
+        "type" => PgCatalogSchema::TypeEntry.to_string(),
+        "version" | "created_at" => format!(
+            "to_char({field}, 'YYYY-MM-DD\"T\"HH24:MI:SS') || CASE \
+            WHEN date_part('microseconds', {field})::integer % 1000000 = 0 THEN '' \
+            WHEN date_part('microseconds', {field})::integer % 1000 = 0 THEN to_char({field}, '.MS') \
+            ELSE to_char({field}, '.US') END",
+            field = field
+        ),
+        _ => field.to_string(),
+    }
+}
+
+// the data of each type is kept as the models serialize it
+fn pg_data_field(type_entry: &str, field: &str) -> String {
+    let column = match type_entry {
+        "Item" => PgCatalogSchema::ItemData,
+        "Variation" => PgCatalogSchema::ItemVariationData,
+        "Modification" => PgCatalogSchema::ItemModificationData,
+        "Delivery" => PgCatalogSchema::ItemDeliveryData,
+        _ => PgCatalogSchema::ItemControlData,
+    };
+    format!("{} -> '{}'", column.to_string(), field)
+}
+
+async fn add_item_variation_units(
+    tx: &mut Transaction<'_, Postgres>,
+    account: &Account,
+    id: &Id,
+    units: i32,
+) -> Result<(), CatalogError> {
+    let sql_increase_expr = format!(
+        "jsonb_set({col_name}, '{{available_units}}', to_jsonb(({col_name}->>'available_units')::integer + ?))",
+        col_name = PgCatalogSchema::ItemVariationData.to_string()
+    );
+
+    let (sql, values) = Qsql::update()
+        .table(PgCatalogSchema::Table)
+        .value_expr(
+            PgCatalogSchema::ItemVariationData,
+            Expr::cust_with_values(sql_increase_expr.as_str(), vec![units]),
+        )
+        .value_expr(PgCatalogSchema::Version, Expr::cust(NEXT_VERSION))
+        .and_where(Expr::col(PgCatalogSchema::Id).eq(to_sql_id(id)))
+        .and_where(Expr::col(PgCatalogSchema::Account).eq(account.to_string()))
+        .returning(
+            Qsql::select()
+                .expr(Expr::cust(
+                    format!(
+                        "({}->>'available_units')::integer AS available_units",
+                        PgCatalogSchema::ItemVariationData.to_string()
+                    )
+                    .as_str(),
+                ))
+                .take(),
+        )
+        .build(QueryBuilder);
+
+    let variation: Option<VariationUnitsRow> = bind_query_as(sqlx::query_as(&sql), &values)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(CatalogError::database)?;
+
+    match variation {
+        // the units of a variation never go below zero
+        Some(variation) if variation.available_units < 0 => {
+            Err(CatalogError::InsufficientUnits(id.to_string()))
+        }
+        Some(_) => Ok(()),
+        None => Err(CatalogError::CatalogEntryNotFound(id.to_string())),
+    }
+}
+
+async fn add_stock_level_units(
+    tx: &mut Transaction<'_, Postgres>,
+    account: &Account,
+    location_id: &Id,
+    variation_id: &Id,
+    units: i32,
+) -> Result<(), CatalogError> {
+    let level: StockLevelRow = sqlx::query_as(
+        "INSERT INTO stock_levels (account, location_id, variation_id, units) VALUES ($1, $2, $3, $4)
+        ON CONFLICT (account, location_id, variation_id)
+        DO UPDATE SET units = stock_levels.units + excluded.units, version = CURRENT_TIMESTAMP
+        RETURNING location_id, variation_id, units",
+    )
+    .bind(account)
+    .bind(to_sql_id(location_id))
+    .bind(to_sql_id(variation_id))
+    .bind(units)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(CatalogError::database)?;
+
+    if level.units < 0 {
+        return Err(CatalogError::InsufficientUnits(variation_id.to_string()));
+    }
+    Ok(())
+}
+
+async fn check_stock_references(
+    tx: &mut Transaction<'_, Postgres>,
+    account: &Account,
+    variation_id: &Id,
+    location_ids: &[&Id],
+) -> Result<(), CatalogError> {
+    let variation: Count = sqlx::query_as(
+        "SELECT COUNT(1) as count FROM catalogs WHERE id = $1 AND account = $2 AND type_entry = 'Variation'",
+    )
+    .bind(to_sql_id(variation_id))
+    .bind(account)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(CatalogError::database)?;
+
+    if variation.count == 0 {
+        return Err(CatalogError::CatalogEntryNotFound(variation_id.to_string()));
+    }
+
+    for location_id in location_ids {
+        let location: Count = sqlx::query_as(
+            "SELECT COUNT(1) as count FROM stock_locations WHERE id = $1 AND account = $2",
+        )
+        .bind(to_sql_id(location_id))
+        .bind(account)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(CatalogError::database)?;
+
+        if location.count == 0 {
+            return Err(CatalogError::CatalogEntryNotFound(location_id.to_string()));
+        }
+    }
+    Ok(())
+}
+
+async fn increase_item_variation_units(
+    pool: &Pool,
+    account: &Account,
+    options: &IncreaseItemVariationUnitsPayload<Id>,
+) -> Result<(), CatalogError> {
+    let mut tx = pool.begin().await.map_err(CatalogError::database)?;
+
+    add_item_variation_units(&mut tx, account, &options.id, options.units).await?;
+
+    tx.commit().await.map_err(CatalogError::database)?;
+    Ok(())
+}
+
+async fn increase_item_variation_units_at(
+    pool: &Pool,
+    account: &Account,
+    options: &IncreaseItemVariationUnitsAtPayload<Id>,
+) -> Result<(), CatalogError> {
+    let mut tx = pool.begin().await.map_err(CatalogError::database)?;
+
+    check_stock_references(&mut tx, account, &options.id, &[&options.location_id]).await?;
+    add_stock_level_units(
+        &mut tx,
+        account,
+        &options.location_id,
+        &options.id,
+        options.units,
+    )
+    .await?;
+    add_item_variation_units(&mut tx, account, &options.id, options.units).await?;
+
+    tx.commit().await.map_err(CatalogError::database)?;
+    Ok(())
+}
+
+async fn transfer_item_variation_units(
+    pool: &Pool,
+    account: &Account,
+    options: &TransferItemVariationUnitsPayload<Id>,
+) -> Result<(), CatalogError> {
+    if options.units <= 0 || options.from_location_id == options.to_location_id {
+        return Err(CatalogError::invalid_transfer());
+    }
+
+    let mut tx = pool.begin().await.map_err(CatalogError::database)?;
+
+    check_stock_references(
+        &mut tx,
+        account,
+        &options.id,
+        &[&options.from_location_id, &options.to_location_id],
+    )
+    .await?;
+    add_stock_level_units(
+        &mut tx,
+        account,
+        &options.from_location_id,
+        &options.id,
+        -options.units,
+    )
+    .await?;
+    add_stock_level_units(
+        &mut tx,
+        account,
+        &options.to_location_id,
+        &options.id,
+        options.units,
+    )
+    .await?;
+
+    tx.commit().await.map_err(CatalogError::database)?;
+    Ok(())
+}
+
+#[async_trait]
+impl Commander for CatalogPgService {
+    type Cmd = CatalogCmd<Id>;
+    type Account = Account;
+
+    async fn cmd(&self, account: &Self::Account, cmd: Self::Cmd) -> Result<(), CatalogError> {
+        match &cmd {
+            Self::Cmd::IncreaseItemVariationUnits(options) => {
+                increase_item_variation_units(&self.pool, account, options).await
+            }
+            Self::Cmd::IncreaseItemVariationUnitsAt(options) => {
+                increase_item_variation_units_at(&self.pool, account, options).await
+            }
+            Self::Cmd::TransferItemVariationUnits(options) => {
+                transfer_item_variation_units(&self.pool, account, options).await
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl StockLocationService for CatalogPgService {
+    async fn create_location(
+        &self,
+        account: &Account,
+        location: &StockLocation,
+    ) -> Result<SqlStockLocationDocument, CatalogError> {
+        let (sql, _) = Qsql::insert()
+            .into_table(StockLocationSchema::Table)
+            .columns(vec![
+                StockLocationSchema::Id,
+                StockLocationSchema::Account,
+                StockLocationSchema::LocationData,
+            ])
+            .exprs_panic(vec![
+                Expr::value("$1"),
+                Expr::value("$2"),
+                Expr::value("$3"),
+            ])
+            .returning(Qsql::select().expr(Expr::asterisk()).take())
+            .build(QueryBuilder);
+
+        let mut pool = self.pool.acquire().await.map_err(CatalogError::database)?;
+
+        let result: PgStockLocationRow = sqlx::query_as(sql.as_str())
+            .bind(to_sql_id(&rand::random::<Id>()))
+            .bind(account)
+            .bind(Json(location))
+            .fetch_one(&mut pool)
+            .await
+            .map_err(CatalogError::database)?;
+
+        result.try_into()
+    }
+
+    async fn read_location(
+        &self,
+        account: &Account,
+        id: &Id,
+    ) -> Result<SqlStockLocationDocument, CatalogError> {
+        let (sql, values) = Qsql::select()
+            .expr(Expr::asterisk())
+            .from(StockLocationSchema::Table)
+            .and_where(Expr::col(StockLocationSchema::Id).eq(to_sql_id(id)))
+            .and_where(Expr::col(StockLocationSchema::Account).eq(account.to_string()))
+            .build(QueryBuilder);
+
+        let mut pool = self.pool.acquire().await.map_err(CatalogError::database)?;
+
+        let result: PgStockLocationRow = bind_query_as(sqlx::query_as(&sql), &values)
+            .fetch_one(&mut pool)
+            .await
+            .map_err(|err| match err {
+                sqlx::Error::RowNotFound => CatalogError::CatalogEntryNotFound(id.to_string()),
+                err => CatalogError::database(err),
+            })?;
+
+        result.try_into()
+    }
+
+    async fn update_location(
+        &self,
+        account: &Account,
+        id: &Id,
+        location: &StockLocation,
+    ) -> Result<SqlStockLocationDocument, CatalogError> {
+        let (sql, _) = Qsql::update()
+            .table(StockLocationSchema::Table)
+            .value(StockLocationSchema::LocationData, "-1".into())
+            .value_expr(
+                StockLocationSchema::Version,
+                Expr::cust("CURRENT_TIMESTAMP"),
+            )
+            .and_where(Expr::col(StockLocationSchema::Account).eq("-1"))
+            .and_where(Expr::col(StockLocationSchema::Id).eq(-1i64))
+            .returning(Qsql::select().expr(Expr::asterisk()).take())
+            .build(QueryBuilder);
+
+        let mut pool = self.pool.acquire().await.map_err(CatalogError::database)?;
+
+        let result: PgStockLocationRow = sqlx::query_as(sql.as_str())
+            .bind(Json(location))
+            .bind(account)
+            .bind(to_sql_id(id))
+            .fetch_one(&mut pool)
+            .await
+            .map_err(|err| match err {
+                sqlx::Error::RowNotFound => CatalogError::CatalogEntryNotFound(id.to_string()),
+                err => CatalogError::database(err),
+            })?;
+
+        result.try_into()
+    }
+
+    async fn list_locations(
+        &self,
+        account: &Account,
+    ) -> Result<Vec<SqlStockLocationDocument>, CatalogError> {
+        let (sql, values) = Qsql::select()
+            .expr(Expr::asterisk())
+            .from(StockLocationSchema::Table)
+            .and_where(Expr::col(StockLocationSchema::Account).eq(account.to_string()))
+            .order_by(StockLocationSchema::CreatedAt, OrderSql::Asc)
+            .build(QueryBuilder);
+
+        let mut pool = self.pool.acquire().await.map_err(CatalogError::database)?;
+
+        let result: Vec<PgStockLocationRow> = bind_query_as(sqlx::query_as(&sql), &values)
+            .fetch_all(&mut pool)
+            .await
+            .map_err(CatalogError::database)?;
+
+        result.into_iter().map(|row| row.try_into()).collect()
+    }
+
+    async fn stock_levels(
+        &self,
+        account: &Account,
+        variation_id: &Id,
+    ) -> Result<Vec<StockLevel<Id>>, CatalogError> {
+        let (sql, values) = Qsql::select()
+            .columns(vec![
+                StockLevelSchema::LocationId,
+                StockLevelSchema::VariationId,
+                StockLevelSchema::Units,
+            ])
+            .from(StockLevelSchema::Table)
+            .and_where(Expr::col(StockLevelSchema::Account).eq(account.to_string()))
+            .and_where(Expr::col(StockLevelSchema::VariationId).eq(to_sql_id(variation_id)))
+            .order_by(StockLevelSchema::LocationId, OrderSql::Asc)
+            .build(QueryBuilder);
+
+        let mut pool = self.pool.acquire().await.map_err(CatalogError::database)?;
+
+        let result: Vec<StockLevelRow> = bind_query_as(sqlx::query_as(&sql), &values)
+            .fetch_all(&mut pool)
+            .await
+            .map_err(CatalogError::database)?;
+
+        result.into_iter().map(|row| row.try_into()).collect()
+    }
+}
+
+// the next version of an object, ahead of the last one even when the clock isn't
+const NEXT_VERSION: &str = "GREATEST(LOCALTIMESTAMP, version + interval '1 microsecond')";
+
+#[derive(Debug, FromRow)]
+struct Count {
+    count: i64,
+}
+
+#[derive(Debug, FromRow)]
+struct VariationUnitsRow {
+    available_units: i32,
+}
+
+#[derive(Debug, FromRow)]
+struct PgCatalogObjectRow {
+    id: i64,
+    account: String,
+    version: NaiveDateTime,
+    type_entry: String,
+    item_data: Option<Json<Item>>,
+    item_variation_data: Option<Json<ItemVariation<Id>>>,
+    item_modification_data: Option<Json<ItemModification<Id>>>,
+    item_control_data: Option<Json<ItemControl<Id>>>,
+    item_delivery_data: Option<Json<ItemDelivery<Id>>>,
+    created_at: NaiveDateTime,
+}
+
+impl PgCatalogObjectRow {
+    // only the `*_data` column of the row's type is set
+    fn to_catalog_entry(&self) -> Result<CatalogObject<Id>, CatalogError> {
+        let data = match self.type_entry.as_str() {
+            "Item" => serde_json::to_value(&self.item_data),
+            "Variation" => serde_json::to_value(&self.item_variation_data),
+            "Modification" => serde_json::to_value(&self.item_modification_data),
+            "Control" => serde_json::to_value(&self.item_control_data),
+            "Delivery" => serde_json::to_value(&self.item_delivery_data),
+            other => {
+                return Err(CatalogError::mapping(format!(
+                    "unknown object type {}",
+                    other
+                )))
+            }
+        }
+        .map_err(CatalogError::mapping)?;
+
+        serde_json::from_value(serde_json::json!({ "type": self.type_entry, "data": data }))
+            .map_err(CatalogError::mapping)
+    }
+
+    #[allow(clippy::wrong_self_convention)]
+    fn to_catalog_entry_document(self) -> Result<SqlCatalogObjectDocument, CatalogError> {
+        Ok(SqlCatalogObjectDocument {
+            catalog_object: self.to_catalog_entry()?,
+            id: from_sql_id(self.id)?,
+            account: self.account,
+            version: self.version,
+            created_at: self.created_at,
+        })
+    }
+}
+
+pub enum PgCatalogSchema {
+    Table,
+    Id,
+    Account,
+    TypeEntry,
+    ItemData,
+    ItemVariationData,
+    ItemModificationData,
+    ItemDeliveryData,
+    ItemControlData,
+    Version,
+    CreatedAt,
+}
+
+impl PgCatalogSchema {
+    fn of(item: &CatalogObject<Id>) -> Self {
+        match item {
+            CatalogObject::Control(_) => Self::ItemControlData,
+            CatalogObject::Variation(_) => Self::ItemVariationData,
+            CatalogObject::Item(_) => Self::ItemData,
+            CatalogObject::Delivery(_) => Self::ItemDeliveryData,
+            CatalogObject::Modification(_) => Self::ItemModificationData,
+        }
+    }
+}
+
+impl Iden for PgCatalogSchema {
+    fn unquoted(&self, s: &mut dyn std::fmt::Write) {
+        write!(
+            s,
+            "{}",
+            match self {
+                Self::Table => "catalogs",
+                Self::Id => "id",
+                Self::Account => "account",
+                Self::ItemData => "item_data",
+                Self::ItemVariationData => "item_variation_data",
+                Self::ItemModificationData => "item_modification_data",
+                Self::ItemDeliveryData => "item_delivery_data",
+                Self::ItemControlData => "item_control_data",
+                Self::TypeEntry => "type_entry",
+                Self::Version => "version",
+                Self::CreatedAt => "created_at",
+            }
+        )
+        .unwrap();
+    }
+}
+
+#[derive(Debug, FromRow)]
+struct PgStockLocationRow {
+    id: i64,
+    account: String,
+    version: NaiveDateTime,
+    location_data: Json<StockLocation>,
+    created_at: NaiveDateTime,
+}
+
+impl TryFrom<PgStockLocationRow> for SqlStockLocationDocument {
+    type Error = CatalogError;
+
+    fn try_from(row: PgStockLocationRow) -> Result<Self, Self::Error> {
+        Ok(SqlStockLocationDocument {
+            id: from_sql_id(row.id)?,
+            account: row.account,
+            version: row.version,
+            created_at: row.created_at,
+            location: row.location_data.0,
+        })
+    }
+}
+
+#[derive(Debug, FromRow)]
+struct StockLevelRow {
+    location_id: i64,
+    variation_id: i64,
+    units: i32,
+}
+
+impl TryFrom<StockLevelRow> for StockLevel<Id> {
+    type Error = CatalogError;
+
+    fn try_from(row: StockLevelRow) -> Result<Self, Self::Error> {
+        Ok(StockLevel {
+            location_id: from_sql_id(row.location_id)?,
+            variation_id: from_sql_id(row.variation_id)?,
+            units: row.units,
+        })
+    }
+}
@@ -0,0 +1,214 @@
+use std::fmt::Display;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::models::CatalogObjectDocument;
+use super::service::CatalogError;
+
+// Sparse documents for `GET /catalog/:account?fields=id,name,price_amount`.
+// A projection keeps `id` and `type` so the objects can be told apart, the
+// fields of the document asked for, and the fields of `data` asked for that
+// the type of the object has, null when the object leaves them unset.
+
+// the fields around `data`
+pub const DOCUMENT_FIELDS: &[&str] = &["id", "account", "version", "created_at", "type"];
+
+// the fields of `data` by type, named as the models serialize them
+pub const DATA_FIELDS: &[(&str, &[&str])] = &[
+    (
+        "Item",
+        &[
+            "category",
+            "tags",
+            "name",
+            "images",
+            "description",
+            "enabled",
+            "warranty_time_type",
+            "warranty_time_seconds",
+        ],
+    ),
+    (
+        "Variation",
+        &[
+            "item_id",
+            "name",
+            "processing_time_type",
+            "processing_time_seconds",
+            "sku",
+            "images",
+            "upc",
+            "enabled",
+            "measurement_units",
+            "available_units",
+            "reorder_threshold",
+            "price_type",
+            "price_amount",
+            "price_asset_name",
+            "price_asset_scale",
+            "extra_attributes",
+        ],
+    ),
+    (
+        "Modification",
+        &[
+            "item_id",
+            "name",
+            "processing_time_type",
+            "processing_time_seconds",
+            "warranty_time_type",
+            "warranty_time_seconds",
+            "images",
+            "price_type",
+            "price_amount",
+            "price_asset_name",
+            "price_asset_scale",
+            "enabled",
+        ],
+    ),
+    (
+        "Delivery",
+        &[
+            "item_id",
+            "delivery_type",
+            "delivery_width_mm",
+            "delivery_length_mm",
+            "delivery_height_mm",
+            "delivery_weight_grams",
+        ],
+    ),
+    ("Control", &["item_id", "control_type", "control_data"]),
+];
+
+// the comma separated fields of a projection, every one exists in some type
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, JsonSchema)]
+#[serde(try_from = "String", into = "String")]
+#[schemars(with = "String")]
+pub struct Fields(Vec<String>);
+
+impl Fields {
+    pub fn new<S: Display>(fields: &[S]) -> Result<Self, CatalogError> {
+        let mut names: Vec<String> = vec![];
+        for field in fields {
+            let field = field.to_string();
+            let known = DOCUMENT_FIELDS.contains(&field.as_str())
+                || DATA_FIELDS
+                    .iter()
+                    .any(|(_, fields)| fields.contains(&field.as_str()));
+            if !known {
+                return Err(CatalogError::bad_request(format!(
+                    "the field {} doesn't exist",
+                    field
+                )));
+            }
+            if !names.contains(&field) {
+                names.push(field);
+            }
+        }
+        Ok(Self(names))
+    }
+
+    // the fields around `data`, `id` and `type` first
+    pub fn document(&self) -> Vec<&'static str> {
+        DOCUMENT_FIELDS
+            .iter()
+            .copied()
+            .filter(|field| {
+                matches!(*field, "id" | "type") || self.0.iter().any(|name| name == field)
+            })
+            .collect()
+    }
+
+    // the fields of `data` the type has
+    pub fn data(&self, type_entry: &str) -> Vec<&'static str> {
+        DATA_FIELDS
+            .iter()
+            .filter(|(name, _)| *name == type_entry)
+            .flat_map(|(_, fields)| fields.iter().copied())
+            .filter(|field| self.0.iter().any(|name| name == field))
+            .collect()
+    }
+
+    // The sql of the projection of a row, `object` builds a json object out
+    // of key value pairs, `document` and `data` read a field of the row.
+    pub fn to_sql(
+        &self,
+        object: &str,
+        document: impl Fn(&str) -> String,
+        data: impl Fn(&str, &str) -> String,
+    ) -> String {
+        let mut pairs: Vec<String> = self
+            .document()
+            .into_iter()
+            .map(|field| format!("'{}', {}", field, document(field)))
+            .collect();
+        let cases: Vec<String> = DATA_FIELDS
+            .iter()
+            .map(|(type_entry, _)| {
+                let fields: Vec<String> = self
+                    .data(type_entry)
+                    .into_iter()
+                    .map(|field| format!("'{}', {}", field, data(type_entry, field)))
+                    .collect();
+                format!(
+                    "WHEN '{}' THEN {}({})",
+                    type_entry,
+                    object,
+                    fields.join(", ")
+                )
+            })
+            .collect();
+        pairs.push(format!(
+            "'data', CASE {} {} END",
+            document("type"),
+            cases.join(" ")
+        ));
+        format!("{}({})", object, pairs.join(", "))
+    }
+}
+
+impl TryFrom<String> for Fields {
+    type Error = CatalogError;
+
+    fn try_from(fields: String) -> Result<Self, Self::Error> {
+        let fields: Vec<&str> = fields
+            .split(',')
+            .map(str::trim)
+            .filter(|field| !field.is_empty())
+            .collect();
+        Self::new(&fields)
+    }
+}
+
+impl From<Fields> for String {
+    fn from(fields: Fields) -> Self {
+        fields.0.join(",")
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
+pub struct ProjectionOptions {
+    pub fields: Option<Fields>,
+}
+
+// the projection of a whole document, for the services without sql
+pub fn project<Id: Serialize, Account: Serialize>(
+    document: &CatalogObjectDocument<Id, Account>,
+    fields: &Fields,
+) -> Result<Value, CatalogError> {
+    let mut value = serde_json::to_value(document).map_err(CatalogError::mapping)?;
+    let mut projection = Map::new();
+    for field in fields.document() {
+        projection.insert(field.to_string(), value[field].take());
+    }
+    let type_entry = projection["type"].as_str().unwrap_or_default().to_string();
+    let data: Map<String, Value> = fields
+        .data(&type_entry)
+        .into_iter()
+        .map(|field| (field.to_string(), value["data"][field].take()))
+        .collect();
+    projection.insert("data".to_string(), Value::Object(data));
+    Ok(Value::Object(projection))
+}
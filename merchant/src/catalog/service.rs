@@ -1,19 +1,70 @@
-use std::{collections::HashMap, fmt::Display};
+use std::{
+    collections::HashMap,
+    fmt::{Debug, Display},
+    str::FromStr,
+};
 
-use super::models::{CatalogObject, CatalogObjectBulkDocument, CatalogObjectDocument};
+pub use super::error::CatalogError;
+use super::models::{
+    AuditEntry, CatalogArchive, CatalogChange, CatalogImport, CatalogObject,
+    CatalogObjectBulkDocument, CatalogObjectDocument, CatalogRevision, CatalogRevisionDiff,
+    Control, ItemControl, ItemDelivery, ItemModification, ItemVariation, MatrixControl, StockAlert,
+    StockLevel, StockLocation, StockLocationDocument,
+};
+use super::projection::Fields;
+use super::validation::{validate_bulk, Violation};
+use crate::utils::broadcast::Receiver;
+use crate::utils::patch::merge_patch;
+use crate::utils::query::query_value;
+use async_graphql::Enum;
 use async_trait::async_trait;
-use serde::{Deserialize, Serialize};
+use chrono::NaiveDateTime;
+use schemars::JsonSchema;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 pub struct IncreaseItemVariationUnitsPayload<Id> {
     pub id: Id,
     pub units: i32,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct IncreaseItemVariationUnitsAtPayload<Id> {
+    pub id: Id,
+    pub location_id: Id,
+    pub units: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct TransferItemVariationUnitsPayload<Id> {
+    pub id: Id,
+    pub from_location_id: Id,
+    pub to_location_id: Id,
+    pub units: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "type", content = "data")]
 pub enum CatalogCmd<Id> {
     IncreaseItemVariationUnits(IncreaseItemVariationUnitsPayload<Id>),
+    // changes the stock of a single location, `available_units` follows the same delta
+    IncreaseItemVariationUnitsAt(IncreaseItemVariationUnitsAtPayload<Id>),
+    // moves units between two locations, `available_units` stays the same
+    TransferItemVariationUnits(TransferItemVariationUnitsPayload<Id>),
+}
+
+impl<Id> CatalogCmd<Id> {
+    // the variation the command is applied to
+    pub fn id(&self) -> &Id {
+        match self {
+            Self::IncreaseItemVariationUnits(IncreaseItemVariationUnitsPayload { id, .. })
+            | Self::IncreaseItemVariationUnitsAt(IncreaseItemVariationUnitsAtPayload {
+                id, ..
+            })
+            | Self::TransferItemVariationUnits(TransferItemVariationUnitsPayload { id, .. }) => id,
+        }
+    }
 }
 
 #[async_trait]
@@ -23,15 +74,30 @@ pub trait Commander {
     async fn cmd(&self, account: &Self::Account, cmd: Self::Cmd) -> Result<(), CatalogError>;
 }
 
-#[derive(Serialize, Deserialize, Debug, Default)]
-pub struct ListCatalogQueryOptions {
+#[derive(Serialize, Deserialize, Debug, Default, JsonSchema)]
+pub struct ListCatalogQueryOptions<Id> {
     pub name: Option<String>,
+    // the variations, modifications, deliveries and controls of the item, only
+    // used by the server, it isn't read from the requests
+    #[serde(skip)]
+    pub item_id: Option<Id>,
     pub tags: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "query_value")]
     pub max_price: Option<f32>,
+    #[serde(default, deserialize_with = "query_value")]
     pub min_price: Option<f32>,
+    // only variations with units left in the given location
+    #[serde(
+        default,
+        deserialize_with = "query_value",
+        bound(deserialize = "Id: Deserialize<'de> + FromStr, Id::Err: Display")
+    )]
+    pub available_at: Option<Id>,
+    #[serde(default, deserialize_with = "query_value")]
+    pub in_stock: Option<bool>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, JsonSchema, Enum)]
 pub enum CatalogColumnOrder {
     CreatedAt,
     Price,
@@ -40,12 +106,14 @@ pub enum CatalogColumnOrder {
 pub trait BulkDocumentReferencesResolver {
     type Id;
     fn resolve(
-        idMap: &HashMap<&str, Self::Id>,
+        id_map: &HashMap<&str, Self::Id>,
         catalog: &CatalogObject<String>,
     ) -> Result<CatalogObject<Self::Id>, CatalogError>;
 }
 
 pub type CatalogId<Trait> = <Trait as CatalogService>::Id;
+type ChangeReceiver<Trait> =
+    Receiver<CatalogChange<CatalogId<Trait>, <Trait as Commander>::Account>>;
 #[async_trait]
 pub trait CatalogService: BulkDocumentReferencesResolver<Id = CatalogId<Self>> + Commander {
     type Id;
@@ -82,26 +150,364 @@ pub trait CatalogService: BulkDocumentReferencesResolver<Id = CatalogId<Self>> +
         catalog_document: &CatalogObject<CatalogId<Self>>,
     ) -> Result<CatalogObjectDocument<CatalogId<Self>, Self::Account>, CatalogError>;
 
+    // an update that only applies while the object is still at `version`
+    async fn update_at_version(
+        &self,
+        account: &Self::Account,
+        id: &CatalogId<Self>,
+        catalog_document: &CatalogObject<CatalogId<Self>>,
+        version: &NaiveDateTime,
+    ) -> Result<CatalogObjectDocument<CatalogId<Self>, Self::Account>, CatalogError>;
+
     async fn list(
         &self,
         account: &Self::Account,
         query: &Self::Query,
     ) -> Result<Vec<CatalogObjectDocument<CatalogId<Self>, Self::Account>>, CatalogError>;
+
+    async fn delete(
+        &self,
+        account: &Self::Account,
+        id: &CatalogId<Self>,
+    ) -> Result<CatalogObjectDocument<CatalogId<Self>, Self::Account>, CatalogError>;
+}
+
+#[async_trait]
+pub trait CatalogProjection: CatalogService {
+    // the objects `list` returns with only the fields asked for, see `projection`
+    async fn list_fields(
+        &self,
+        account: &Self::Account,
+        query: &Self::Query,
+        fields: &Fields,
+    ) -> Result<Vec<Value>, CatalogError>;
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, JsonSchema)]
+pub struct ListCatalogChangesOptions {
+    // only the changes with a greater sequence number are returned
+    pub since: Option<i64>,
+    pub limit: Option<u16>,
+}
+
+#[async_trait]
+pub trait CatalogChangeFeed: CatalogService {
+    async fn changes(
+        &self,
+        account: &Self::Account,
+        options: &ListCatalogChangesOptions,
+    ) -> Result<Vec<CatalogChange<CatalogId<Self>, Self::Account>>, CatalogError>;
+
+    // changes of every account as they are recorded
+    fn subscribe(&self) -> Result<ChangeReceiver<Self>, CatalogError>;
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, JsonSchema)]
+pub struct ListRevisionsOptions {
+    // returns only the revision that was current at that time
+    pub at: Option<NaiveDateTime>,
+}
+
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+pub struct DiffRevisionsOptions {
+    pub from: i64,
+    pub to: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, JsonSchema)]
+pub struct ListAuditOptions {
+    pub actor: Option<String>,
+    pub from: Option<NaiveDateTime>,
+    pub to: Option<NaiveDateTime>,
+    pub limit: Option<u16>,
+}
+
+#[async_trait]
+pub trait CatalogAuditLog: CatalogService {
+    async fn audit(
+        &self,
+        account: &Self::Account,
+        options: &ListAuditOptions,
+    ) -> Result<Vec<AuditEntry<CatalogId<Self>, Self::Account>>, CatalogError>;
+}
+
+#[async_trait]
+pub trait CatalogRevisionService: CatalogService {
+    async fn read_revision(
+        &self,
+        account: &Self::Account,
+        id: &CatalogId<Self>,
+        revision: i64,
+    ) -> Result<CatalogRevision<CatalogId<Self>, Self::Account>, CatalogError>;
+
+    // the revision that was current at the given time
+    async fn read_revision_at(
+        &self,
+        account: &Self::Account,
+        id: &CatalogId<Self>,
+        at: &NaiveDateTime,
+    ) -> Result<CatalogRevision<CatalogId<Self>, Self::Account>, CatalogError>;
+
+    async fn list_revisions(
+        &self,
+        account: &Self::Account,
+        id: &CatalogId<Self>,
+    ) -> Result<Vec<CatalogRevision<CatalogId<Self>, Self::Account>>, CatalogError>;
+
+    async fn diff_revisions(
+        &self,
+        account: &Self::Account,
+        id: &CatalogId<Self>,
+        from: i64,
+        to: i64,
+    ) -> Result<CatalogRevisionDiff, CatalogError>;
+
+    // writes the object back as it was in the revision, it becomes a new revision
+    async fn restore_revision(
+        &self,
+        account: &Self::Account,
+        id: &CatalogId<Self>,
+        revision: i64,
+    ) -> Result<CatalogObjectDocument<CatalogId<Self>, Self::Account>, CatalogError>;
 }
 
-impl std::error::Error for CatalogError {}
+#[async_trait]
+pub trait StockLocationService: CatalogService {
+    async fn create_location(
+        &self,
+        account: &Self::Account,
+        location: &StockLocation,
+    ) -> Result<StockLocationDocument<CatalogId<Self>, Self::Account>, CatalogError>;
+
+    async fn read_location(
+        &self,
+        account: &Self::Account,
+        id: &CatalogId<Self>,
+    ) -> Result<StockLocationDocument<CatalogId<Self>, Self::Account>, CatalogError>;
+
+    async fn update_location(
+        &self,
+        account: &Self::Account,
+        id: &CatalogId<Self>,
+        location: &StockLocation,
+    ) -> Result<StockLocationDocument<CatalogId<Self>, Self::Account>, CatalogError>;
+
+    async fn list_locations(
+        &self,
+        account: &Self::Account,
+    ) -> Result<Vec<StockLocationDocument<CatalogId<Self>, Self::Account>>, CatalogError>;
+
+    async fn stock_levels(
+        &self,
+        account: &Self::Account,
+        variation_id: &CatalogId<Self>,
+    ) -> Result<Vec<StockLevel<CatalogId<Self>>>, CatalogError>;
+}
 
-#[derive(Debug, PartialEq, Eq)]
-pub enum CatalogError {
-    DatabaseError,
-    CatalogEntryNotFound(String),
-    CatalogBadRequest,
-    MappingError,
-    BulkReferenceNotExist(String),
+#[async_trait]
+pub trait StockAlertService: CatalogService {
+    async fn list_alerts(
+        &self,
+        account: &Self::Account,
+    ) -> Result<Vec<StockAlert<CatalogId<Self>, Self::Account>>, CatalogError>;
 }
 
-impl Display for CatalogError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "")
+#[async_trait]
+pub trait CatalogArchiveService: CatalogService {
+    // the objects, revisions, locations and stock levels of the account as they
+    // are at a single point in time
+    async fn export(
+        &self,
+        account: &Self::Account,
+    ) -> Result<CatalogArchive<CatalogId<Self>, Self::Account>, CatalogError>;
+
+    // adds the archive to the account with new ids, nothing is written if any part fails
+    async fn import(
+        &self,
+        account: &Self::Account,
+        archive: &CatalogArchive<CatalogId<Self>, Self::Account>,
+    ) -> Result<CatalogImport<CatalogId<Self>>, CatalogError>;
+
+    // a consistent copy of the whole database written to a new file while it's in use
+    async fn backup(&self, path: &str) -> Result<(), CatalogError>;
+}
+
+// Implemented by the modules that want to be told when a variation runs low,
+// they are called after the alert has been recorded.
+#[async_trait]
+pub trait StockAlertHook<Id, Account>: Send + Sync {
+    async fn notify(&self, alert: &StockAlert<Id, Account>);
+}
+
+// Creates the objects in dependency order, the references between them use the
+// aliases given in the documents and are resolved once the referenced object exists.
+pub async fn bulk_create<S>(
+    service: &S,
+    account: &S::Account,
+    catalog: &[CatalogObjectBulkDocument<String>],
+) -> Result<Vec<CatalogObjectDocument<CatalogId<S>, S::Account>>, CatalogError>
+where
+    S: CatalogService + Sync,
+    CatalogId<S>: Copy + Debug,
+    S::Account: Debug,
+{
+    validate_bulk(catalog)?;
+    let mut objects_dependency_count: HashMap<String, u32> = HashMap::new();
+    let mut objects_created_document: HashMap<
+        String,
+        CatalogObjectDocument<CatalogId<S>, S::Account>,
+    > = HashMap::new();
+    let mut objects_created_document_id: HashMap<&str, CatalogId<S>> = HashMap::new();
+    let mut objects_dependency_map: HashMap<String, &CatalogObject<String>> = HashMap::new();
+
+    for (index, item) in catalog.iter().enumerate() {
+        let key_id = match &item.id {
+            Some(id) => id.clone(),
+            None => make_id_by_index(index),
+        };
+
+        objects_dependency_map
+            .entry(key_id.clone())
+            .or_insert(&item.catalog_object);
+
+        objects_dependency_count.entry(key_id).or_insert(0);
+
+        match &item.catalog_object {
+            CatalogObject::Variation(ItemVariation { item_id, .. })
+            | CatalogObject::Modification(ItemModification { item_id, .. }) => {
+                let key = item_id.clone();
+                objects_dependency_count.entry(key).and_modify(|e| *e += 1);
+            }
+            CatalogObject::Control(ItemControl { item_id, control }) => {
+                objects_dependency_count
+                    .entry(item_id.clone())
+                    .and_modify(|e| *e += 1);
+                if let Control::Matrix(MatrixControl { combinations, .. }) = &control {
+                    for (key, id_ref) in combinations.iter() {
+                        println!("combination {:?} {:?}", key, id_ref);
+                        objects_dependency_count
+                            .entry(id_ref.clone())
+                            .and_modify(|e| *e += 1);
+                    }
+                }
+            }
+            _ => {}
+        }
     }
+
+    let mut items_sorted_to_insert: Vec<(&String, &u32)> =
+        objects_dependency_count.iter().collect();
+
+    items_sorted_to_insert.sort_by(|a, b| b.1.cmp(a.1));
+
+    println!("THE SORTED ITEMS {:?}", items_sorted_to_insert);
+
+    // we start creating the dependencies from the less dependant
+    for (alias_id, _) in &items_sorted_to_insert {
+        let item = objects_dependency_map
+            .get(*alias_id)
+            .ok_or_else(|| CatalogError::BulkReferenceNotExist(alias_id.to_string()))?;
+        println!("item iter {:?}", item);
+        println!(
+            "objects_created_document_id {:?}",
+            objects_created_document_id
+        );
+        match item {
+            item @ CatalogObject::Item(_) => {
+                let catalog_object = <S as BulkDocumentReferencesResolver>::resolve(
+                    &objects_created_document_id,
+                    item,
+                )?;
+                let document = service.create(account, &catalog_object).await?;
+                println!("creating element Item {}, {:?}", item, document);
+
+                objects_created_document_id
+                    .entry(alias_id)
+                    .or_insert(document.id);
+
+                objects_created_document
+                    .entry(alias_id.to_string())
+                    .or_insert(document);
+            }
+            v @ CatalogObject::Variation(ItemVariation { item_id, .. })
+            | v @ CatalogObject::Modification(ItemModification { item_id, .. })
+            | v @ CatalogObject::Control(ItemControl { item_id, .. })
+            | v @ CatalogObject::Delivery(ItemDelivery { item_id, .. }) => {
+                println!(" item_id {:?}, variation {:?} ", item_id, v);
+                println!("Objects documents {:?}", objects_created_document);
+
+                if objects_created_document_id.contains_key(item_id.as_str()) {
+                    println!("!ID FOUND {:?}", item_id);
+
+                    let catalog_object = <S as BulkDocumentReferencesResolver>::resolve(
+                        &objects_created_document_id,
+                        v,
+                    )?;
+
+                    let document = service.create(account, &catalog_object).await?;
+
+                    objects_created_document_id
+                        .entry(alias_id)
+                        .or_insert(document.id);
+
+                    objects_created_document
+                        .entry(alias_id.to_string())
+                        .or_insert(document);
+                } else {
+                    return Err(CatalogError::BulkReferenceNotExist(item_id.to_string()));
+                }
+            }
+        }
+    }
+
+    items_sorted_to_insert
+        .iter()
+        .map(|(id, _)| {
+            objects_created_document
+                .remove(id.as_str())
+                .ok_or_else(|| CatalogError::BulkReferenceNotExist(id.to_string()))
+        })
+        .collect()
+}
+
+// Merges an RFC 7396 patch into the stored object, the result is validated
+// and written like an update so it gets a revision too. The object must still
+// be at the version the patch was merged into, and at `version` when given.
+pub async fn patch<S>(
+    service: &S,
+    account: &S::Account,
+    id: &CatalogId<S>,
+    patch: &Value,
+    version: Option<&NaiveDateTime>,
+) -> Result<CatalogObjectDocument<CatalogId<S>, S::Account>, CatalogError>
+where
+    S: CatalogService + Sync,
+    CatalogId<S>: Serialize + DeserializeOwned + Display + Sync,
+    S::Account: Sync,
+{
+    let document = service.read(account, id).await?;
+    if version.is_some_and(|version| *version != document.version) {
+        return Err(CatalogError::VersionMismatch(id.to_string()));
+    }
+    let before = serde_json::to_value(&document.catalog_object).map_err(CatalogError::mapping)?;
+    let mut after = before.clone();
+    merge_patch(&mut after, patch);
+    if after.get("type") != before.get("type") {
+        return Err(CatalogError::ValidationFailed(vec![Violation::new(
+            "/type",
+            "immutable",
+            "the type of an object can't be changed",
+        )]));
+    }
+    let catalog_object: CatalogObject<CatalogId<S>> =
+        serde_json::from_value(after).map_err(|err| {
+            CatalogError::ValidationFailed(vec![Violation::new("", "format", err.to_string())])
+        })?;
+    service
+        .update_at_version(account, id, &catalog_object, &document.version)
+        .await
+}
+
+fn make_id_by_index(index: usize) -> String {
+    format!("#{}-index", index)
 }
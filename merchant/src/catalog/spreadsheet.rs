@@ -0,0 +1,468 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt::Display;
+use std::str::FromStr;
+
+use schemars::JsonSchema;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::models::{
+    CatalogObject, CatalogObjectBulkDocument, CatalogObjectDocument, Item, ItemVariation, Price,
+};
+use super::service::{CatalogError, CatalogId, CatalogService};
+
+// The sheet has a row per variation with the fields of its item repeated,
+// items without variations get a row of their own. Images and times aren't
+// part of it.
+pub const CSV_COLUMNS: [&str; 18] = [
+    "item_id",
+    "item_name",
+    "item_description",
+    "item_category",
+    "item_tags",
+    "item_enabled",
+    "variation_id",
+    "variation_name",
+    "sku",
+    "upc",
+    "variation_enabled",
+    "measurement_units",
+    "available_units",
+    "reorder_threshold",
+    "price_type",
+    "price_amount",
+    "price_asset_name",
+    "price_asset_scale",
+];
+
+// every key of `extra_attributes` is a column of its own
+const EXTRA_ATTRIBUTE_PREFIX: &str = "extra_attributes.";
+const TAG_SEPARATOR: &str = "|";
+
+const VARIATION_COLUMNS: [&str; 12] = [
+    "variation_name",
+    "sku",
+    "upc",
+    "variation_enabled",
+    "measurement_units",
+    "available_units",
+    "reorder_threshold",
+    "price_type",
+    "price_amount",
+    "price_asset_name",
+    "price_asset_scale",
+    "variation_id",
+];
+
+pub fn write_csv<Id: Display + PartialEq, Account>(
+    documents: &[CatalogObjectDocument<Id, Account>],
+) -> Result<String, CatalogError> {
+    let variations: Vec<(&Id, &ItemVariation<Id>)> = documents
+        .iter()
+        .filter_map(|document| match &document.catalog_object {
+            CatalogObject::Variation(variation) => Some((&document.id, variation)),
+            _ => None,
+        })
+        .collect();
+    let attributes: BTreeSet<&String> = variations
+        .iter()
+        .filter_map(|(_, variation)| variation.extra_attributes.as_ref())
+        .flat_map(|attributes| attributes.keys())
+        .collect();
+
+    let mut writer = csv::Writer::from_writer(vec![]);
+    let header = CSV_COLUMNS.iter().map(|column| column.to_string()).chain(
+        attributes
+            .iter()
+            .map(|key| format!("{}{}", EXTRA_ATTRIBUTE_PREFIX, key)),
+    );
+    writer.write_record(header).map_err(CatalogError::mapping)?;
+
+    for document in documents.iter() {
+        let item = match &document.catalog_object {
+            CatalogObject::Item(item) => item,
+            _ => continue,
+        };
+        let item_cells = vec![
+            document.id.to_string(),
+            item.name.clone(),
+            item.description.clone(),
+            variant_name(&item.category)?,
+            item.tags.join(TAG_SEPARATOR),
+            item.enabled.to_string(),
+        ];
+        let mut rows = 0;
+        for (id, variation) in variations
+            .iter()
+            .filter(|(_, variation)| variation.item_id == document.id)
+        {
+            let Price::Fixed {
+                amount,
+                asset_name,
+                asset_scale,
+            } = &variation.price;
+            let mut record = item_cells.clone();
+            record.extend([
+                id.to_string(),
+                variation.name.clone(),
+                variation.sku.clone(),
+                variation.upc.clone().unwrap_or_default(),
+                variation.enabled.to_string(),
+                variant_name(&variation.measurement_units)?,
+                variation.available_units.to_string(),
+                variation
+                    .reorder_threshold
+                    .map(|threshold| threshold.to_string())
+                    .unwrap_or_default(),
+                "Fixed".to_string(),
+                amount.to_string(),
+                asset_name.clone(),
+                asset_scale.to_string(),
+            ]);
+            record.extend(attributes.iter().map(|key| {
+                variation
+                    .extra_attributes
+                    .as_ref()
+                    .and_then(|attributes| attributes.get(*key))
+                    .cloned()
+                    .unwrap_or_default()
+            }));
+            writer.write_record(record).map_err(CatalogError::mapping)?;
+            rows += 1;
+        }
+        if rows == 0 {
+            let mut record = item_cells;
+            record.resize(CSV_COLUMNS.len() + attributes.len(), String::new());
+            writer.write_record(record).map_err(CatalogError::mapping)?;
+        }
+    }
+
+    let data = writer.into_inner().map_err(CatalogError::mapping)?;
+    String::from_utf8(data).map_err(CatalogError::mapping)
+}
+
+fn variant_name<T: Serialize>(value: &T) -> Result<String, CatalogError> {
+    match serde_json::to_value(value).map_err(CatalogError::mapping)? {
+        serde_json::Value::String(name) => Ok(name),
+        other => Err(CatalogError::mapping(format!(
+            "{} isn't a variant name",
+            other
+        ))),
+    }
+}
+
+// a cell that can't be imported, `line` is the line of the row in the file
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, JsonSchema)]
+pub struct CsvRowError {
+    pub line: u64,
+    pub column: Option<String>,
+    pub message: String,
+}
+
+#[derive(Serialize, Debug, JsonSchema)]
+pub struct CsvSheet {
+    pub documents: Vec<CatalogObjectBulkDocument<String>>,
+    pub errors: Vec<CsvRowError>,
+    // headers that don't match any column after the mapping
+    pub ignored_columns: Vec<String>,
+}
+
+// `columns` renames the headers of the file to the columns of the sheet
+pub fn read_csv(data: &str, columns: &HashMap<String, String>) -> CsvSheet {
+    let mut sheet = CsvSheet {
+        documents: vec![],
+        errors: vec![],
+        ignored_columns: vec![],
+    };
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_reader(data.as_bytes());
+
+    let header: Vec<String> = match reader.headers() {
+        Ok(header) => header
+            .iter()
+            .map(|name| {
+                columns
+                    .get(name)
+                    .cloned()
+                    .unwrap_or_else(|| name.to_string())
+            })
+            .collect(),
+        Err(err) => {
+            sheet.errors.push(CsvRowError {
+                line: 1,
+                column: None,
+                message: err.to_string(),
+            });
+            return sheet;
+        }
+    };
+    sheet.ignored_columns = header
+        .iter()
+        .filter(|column| {
+            !CSV_COLUMNS.contains(&column.as_str()) && !column.starts_with(EXTRA_ATTRIBUTE_PREFIX)
+        })
+        .cloned()
+        .collect();
+
+    let mut items: HashSet<String> = HashSet::new();
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(err) => {
+                sheet.errors.push(CsvRowError {
+                    line: err.position().map_or(0, |position| position.line()),
+                    column: None,
+                    message: err.to_string(),
+                });
+                continue;
+            }
+        };
+        let line = record.position().map_or(0, |position| position.line());
+        let mut row = Row {
+            line,
+            cells: header
+                .iter()
+                .zip(record.iter())
+                .filter(|(_, value)| !value.is_empty())
+                .map(|(column, value)| (column.as_str(), value))
+                .collect(),
+            errors: &mut sheet.errors,
+        };
+
+        // rows of the same item share its id, or its name when there are no ids
+        let key = match row.text("item_id").or_else(|| row.text("item_name")) {
+            Some(key) => key,
+            None => {
+                row.error(Some("item_id"), "the row needs an item_id or an item_name");
+                continue;
+            }
+        };
+        let item_alias = format!("item:{}", key);
+        if !items.contains(&key) {
+            if let Some(item) = row.item() {
+                sheet.documents.push(CatalogObjectBulkDocument {
+                    id: Some(item_alias.clone()),
+                    catalog_object: CatalogObject::Item(item),
+                });
+                items.insert(key);
+            }
+        }
+        if VARIATION_COLUMNS
+            .iter()
+            .any(|column| row.cells.contains_key(column))
+        {
+            if let Some(variation) = row.variation(item_alias) {
+                sheet.documents.push(CatalogObjectBulkDocument {
+                    id: Some(format!("row:{}", line)),
+                    catalog_object: CatalogObject::Variation(variation),
+                });
+            }
+        }
+    }
+    sheet
+}
+
+// the cells of a row by column, the problems found are added to `errors`
+struct Row<'a> {
+    line: u64,
+    cells: HashMap<&'a str, &'a str>,
+    errors: &'a mut Vec<CsvRowError>,
+}
+
+impl<'a> Row<'a> {
+    fn error(&mut self, column: Option<&str>, message: &str) {
+        self.errors.push(CsvRowError {
+            line: self.line,
+            column: column.map(|column| column.to_string()),
+            message: message.to_string(),
+        });
+    }
+
+    fn text(&self, column: &str) -> Option<String> {
+        self.cells.get(column).map(|value| value.to_string())
+    }
+
+    fn required(&mut self, column: &str) -> Option<String> {
+        let value = self.text(column);
+        if value.is_none() {
+            self.error(Some(column), "is required");
+        }
+        value
+    }
+
+    fn number<T: FromStr>(&mut self, column: &str) -> Option<Option<T>> {
+        match self.cells.get(column) {
+            None => Some(None),
+            Some(value) => match value.parse() {
+                Ok(number) => Some(Some(number)),
+                Err(_) => {
+                    self.error(Some(column), &format!("{:?} is not a valid number", value));
+                    None
+                }
+            },
+        }
+    }
+
+    fn flag(&mut self, column: &str) -> Option<bool> {
+        match self.cells.get(column).map(|value| value.to_lowercase()) {
+            None => Some(true),
+            Some(value) => match value.as_str() {
+                "true" | "yes" | "1" => Some(true),
+                "false" | "no" | "0" => Some(false),
+                _ => {
+                    self.error(Some(column), &format!("{:?} is not true or false", value));
+                    None
+                }
+            },
+        }
+    }
+
+    // the enums are written with the names of their variants
+    fn variant<T: DeserializeOwned>(&mut self, column: &str, default: &str) -> Option<T> {
+        let value = self.text(column).unwrap_or_else(|| default.to_string());
+        match serde_json::from_value(serde_json::Value::String(value.clone())) {
+            Ok(variant) => Some(variant),
+            Err(_) => {
+                self.error(Some(column), &format!("{:?} is not a known value", value));
+                None
+            }
+        }
+    }
+
+    fn item(&mut self) -> Option<Item> {
+        let name = self.required("item_name");
+        let category = self.variant("item_category", "Shop");
+        let enabled = self.flag("item_enabled");
+        let tags = self
+            .text("item_tags")
+            .map(|tags| {
+                tags.split(TAG_SEPARATOR)
+                    .map(|tag| tag.trim().to_string())
+                    .filter(|tag| !tag.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+        Some(Item {
+            name: name?,
+            description: self.text("item_description").unwrap_or_default(),
+            category: category?,
+            tags,
+            images: vec![],
+            enabled: enabled?,
+            warranty_time: None,
+        })
+    }
+
+    fn variation(&mut self, item_id: String) -> Option<ItemVariation<String>> {
+        let name = self.required("variation_name");
+        let sku = self.required("sku");
+        let enabled = self.flag("variation_enabled");
+        let measurement_units = self.variant("measurement_units", "Units");
+        let available_units = self.number("available_units");
+        let reorder_threshold = self.number("reorder_threshold");
+        let price = self.price();
+        let extra_attributes: HashMap<String, String> = self
+            .cells
+            .iter()
+            .filter_map(|(column, value)| {
+                column
+                    .strip_prefix(EXTRA_ATTRIBUTE_PREFIX)
+                    .map(|key| (key.to_string(), value.to_string()))
+            })
+            .collect();
+        Some(ItemVariation {
+            item_id,
+            name: name?,
+            processing_time: None,
+            sku: sku?,
+            images: vec![],
+            upc: self.text("upc"),
+            enabled: enabled?,
+            measurement_units: measurement_units?,
+            available_units: available_units?.unwrap_or_default(),
+            reorder_threshold: reorder_threshold?,
+            price: price?,
+            extra_attributes: (!extra_attributes.is_empty()).then_some(extra_attributes),
+        })
+    }
+
+    fn price(&mut self) -> Option<Price> {
+        let price_type = self
+            .text("price_type")
+            .unwrap_or_else(|| "Fixed".to_string());
+        if price_type != "Fixed" {
+            self.error(
+                Some("price_type"),
+                &format!("{:?} is not a known value", price_type),
+            );
+        }
+        let amount = self
+            .required("price_amount")
+            .and_then(|_| self.number("price_amount"));
+        let asset_name = self.required("price_asset_name");
+        let asset_scale = self
+            .required("price_asset_scale")
+            .and_then(|_| self.number("price_asset_scale"));
+        if price_type != "Fixed" {
+            return None;
+        }
+        Some(Price::Fixed {
+            amount: amount??,
+            asset_name: asset_name?,
+            asset_scale: asset_scale??,
+        })
+    }
+}
+
+#[derive(Deserialize, Debug, Default, JsonSchema)]
+pub struct CsvImportOptions {
+    // only reads the file, nothing is created
+    #[serde(default)]
+    pub preview: bool,
+    // `header:column` pairs separated by commas
+    pub columns: Option<String>,
+}
+
+impl CsvImportOptions {
+    pub fn column_mapping(&self) -> HashMap<String, String> {
+        self.columns
+            .iter()
+            .flat_map(|columns| columns.split(','))
+            .filter_map(|pair| pair.split_once(':'))
+            .map(|(header, column)| (header.trim().to_string(), column.trim().to_string()))
+            .collect()
+    }
+}
+
+#[derive(Serialize, Debug, JsonSchema)]
+pub struct CsvImportReport<Id, Account> {
+    pub preview: bool,
+    pub sheet: CsvSheet,
+    pub created: Vec<CatalogObjectDocument<Id, Account>>,
+}
+
+// Creates the objects of the sheet with `bulk_create`, a sheet with errors
+// isn't imported at all.
+pub async fn import_csv<S>(
+    service: &S,
+    account: &S::Account,
+    data: &str,
+    options: &CsvImportOptions,
+) -> Result<CsvImportReport<CatalogId<S>, S::Account>, CatalogError>
+where
+    S: CatalogService + Sync,
+    S::Account: Sync,
+{
+    let sheet = read_csv(data, &options.column_mapping());
+    let created = if options.preview || !sheet.errors.is_empty() {
+        vec![]
+    } else {
+        service.bulk_create(account, &sheet.documents).await?
+    };
+    Ok(CsvImportReport {
+        preview: options.preview,
+        sheet,
+        created,
+    })
+}
@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+
+use super::models::{
+    AuditEntry, CatalogArchive, CatalogChange, CatalogObject, CatalogObjectBulkDocument,
+    CatalogObjectDocument, CatalogRevision, Control, ItemControl, ItemDelivery, ItemModification,
+    ItemVariation, MatrixControl, StockAlert, StockLocationDocument,
+};
+use super::service::{CatalogCmd, CatalogColumnOrder, CatalogError, ListCatalogQueryOptions};
+use crate::utils::query::Query;
+
+// The ids and accounts every backend stores the catalog with, the memory one
+// included, so they don't need a database to be built.
+pub type Id = u32;
+pub type Account = String;
+pub type SQlCatalogCmd = CatalogCmd<Id>;
+pub type SqlCatalogObject = CatalogObject<Id>;
+pub type SqlCatalogObjectDocument = CatalogObjectDocument<Id, Account>;
+#[allow(dead_code)]
+pub type SqlCatalogItemVariation = ItemVariation<Id>;
+#[allow(dead_code)]
+pub type SqlCatalogObjectBulkDocument = CatalogObjectBulkDocument<Id>;
+pub type SqlCatalogQueryOptions = Query<ListCatalogQueryOptions<Id>, CatalogColumnOrder>;
+pub type SqlStockLocationDocument = StockLocationDocument<Id, Account>;
+pub type SqlStockAlert = StockAlert<Id, Account>;
+pub type SqlCatalogChange = CatalogChange<Id, Account>;
+pub type SqlCatalogRevision = CatalogRevision<Id, Account>;
+pub type SqlAuditEntry = AuditEntry<Id, Account>;
+pub type SqlCatalogArchive = CatalogArchive<Id, Account>;
+
+// the objects of a bulk request with their references to other objects of the
+// request swapped by the ids they were created with
+pub fn resolve_references(
+    id_map: &HashMap<&str, Id>,
+    catalog: &CatalogObject<String>,
+) -> Result<CatalogObject<Id>, CatalogError> {
+    let item = match &catalog {
+        CatalogObject::Item(item) => CatalogObject::Item(item.clone()),
+        CatalogObject::Modification(ItemModification {
+            processing_time,
+            warranty_time,
+            enabled,
+            images,
+            name,
+            price,
+            item_id,
+        }) => CatalogObject::Modification(ItemModification {
+            processing_time: processing_time.to_owned(),
+            warranty_time: warranty_time.to_owned(),
+            enabled: enabled.to_owned(),
+            images: images.to_owned(),
+            item_id: *id_map
+                .get(item_id.as_str())
+                .ok_or(CatalogError::BulkReferenceNotExist(item_id.to_string()))?,
+            name: name.to_owned(),
+            price: price.to_owned(),
+        }),
+        CatalogObject::Variation(ItemVariation {
+            available_units,
+            enabled,
+            processing_time,
+            extra_attributes,
+            images,
+            measurement_units,
+            name,
+            price,
+            sku,
+            upc,
+            item_id,
+            reorder_threshold,
+        }) => CatalogObject::Variation(ItemVariation {
+            available_units: available_units.to_owned(),
+            reorder_threshold: reorder_threshold.to_owned(),
+            enabled: enabled.to_owned(),
+            images: images.to_owned(),
+            item_id: *id_map
+                .get(item_id.as_str())
+                .ok_or(CatalogError::BulkReferenceNotExist(item_id.to_string()))?,
+            processing_time: processing_time.to_owned(),
+            extra_attributes: extra_attributes.to_owned(),
+            measurement_units: measurement_units.to_owned(),
+            name: name.to_owned(),
+            price: price.to_owned(),
+            sku: sku.to_owned(),
+            upc: upc.to_owned(),
+        }),
+        CatalogObject::Control(ItemControl { control, item_id }) => {
+            let control = match control {
+                Control::Matrix(item) => {
+                    let mut combinations: HashMap<String, Id> = HashMap::new();
+
+                    for (template_id, id_ref) in item.combinations.iter() {
+                        let id = id_map
+                            .get(id_ref.as_str())
+                            .ok_or(CatalogError::BulkReferenceNotExist(id_ref.to_string()))?;
+
+                        combinations
+                            .entry(template_id.to_string())
+                            .or_insert(id.to_owned());
+                    }
+
+                    Control::Matrix(MatrixControl {
+                        combinations,
+                        props: item.props.to_owned(),
+                        key_template: item.key_template.to_owned(),
+                    })
+                }
+                Control::Form(item) => Control::Form(item.to_vec()),
+            };
+            CatalogObject::Control(ItemControl {
+                control,
+                item_id: *id_map
+                    .get(item_id.as_str())
+                    .ok_or(CatalogError::BulkReferenceNotExist(item_id.to_string()))?,
+            })
+        }
+        CatalogObject::Delivery(ItemDelivery { delivery, item_id }) => {
+            CatalogObject::Delivery(ItemDelivery {
+                delivery: delivery.to_owned(),
+                item_id: *id_map
+                    .get(item_id.as_str())
+                    .ok_or(CatalogError::BulkReferenceNotExist(item_id.to_string()))?,
+            })
+        }
+    };
+    Ok(item)
+}
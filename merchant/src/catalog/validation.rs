@@ -0,0 +1,233 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::models::{
+    CatalogObject, CatalogObjectBulkDocument, Control, Delivery, Image, Item, ItemControl,
+    ItemDelivery, ItemModification, ItemVariation, MatrixControl, Price,
+};
+use super::service::CatalogError;
+use crate::utils::diff::escape_pointer_token;
+
+// A field of a request body that breaks a rule, `pointer` is the RFC 6901
+// JSON pointer to it in the body as it was sent and `constraint` the name of
+// the rule.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, JsonSchema)]
+pub struct Violation {
+    pub pointer: String,
+    pub constraint: String,
+    pub message: String,
+}
+
+impl Violation {
+    pub fn new(
+        pointer: impl Into<String>,
+        constraint: impl Into<String>,
+        message: impl Into<String>,
+    ) -> Self {
+        Self {
+            pointer: pointer.into(),
+            constraint: constraint.into(),
+            message: message.into(),
+        }
+    }
+}
+
+// every rule the object breaks, serde already checked the types
+pub fn validate<Id>(object: &CatalogObject<Id>) -> Result<(), CatalogError> {
+    into_result(violations(object, ""))
+}
+
+// the objects of a bulk request, the pointers start with their index
+pub fn validate_bulk<Id>(documents: &[CatalogObjectBulkDocument<Id>]) -> Result<(), CatalogError> {
+    into_result(
+        documents
+            .iter()
+            .enumerate()
+            .flat_map(|(index, document)| {
+                violations(&document.catalog_object, &format!("/{}", index))
+            })
+            .collect(),
+    )
+}
+
+fn into_result(violations: Vec<Violation>) -> Result<(), CatalogError> {
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(CatalogError::ValidationFailed(violations))
+    }
+}
+
+// the fields of the object are under `data`, with the nested models
+// flattened with their prefixes
+pub fn violations<Id>(object: &CatalogObject<Id>, pointer: &str) -> Vec<Violation> {
+    let mut rules = Rules {
+        pointer: format!("{}/data", pointer),
+        violations: vec![],
+    };
+    match object {
+        CatalogObject::Item(item) => rules.item(item),
+        CatalogObject::Variation(variation) => rules.variation(variation),
+        CatalogObject::Modification(modification) => rules.modification(modification),
+        CatalogObject::Delivery(delivery) => rules.delivery(delivery),
+        CatalogObject::Control(control) => rules.control(control),
+    }
+    rules.violations
+}
+
+struct Rules {
+    pointer: String,
+    violations: Vec<Violation>,
+}
+
+impl Rules {
+    fn fail(&mut self, field: &str, constraint: &str, message: &str) {
+        self.violations.push(Violation::new(
+            format!("{}/{}", self.pointer, field),
+            constraint,
+            message,
+        ));
+    }
+
+    fn not_blank(&mut self, field: &str, value: &str) {
+        if value.trim().is_empty() {
+            self.fail(field, "not_blank", "must not be blank");
+        }
+    }
+
+    fn not_negative(&mut self, field: &str, value: i32) {
+        if value < 0 {
+            self.fail(field, "not_negative", "must not be negative");
+        }
+    }
+
+    fn positive(&mut self, field: &str, value: i32) {
+        if value <= 0 {
+            self.fail(field, "positive", "must be greater than zero");
+        }
+    }
+
+    fn images(&mut self, images: &[Image]) {
+        for (index, image) in images.iter().enumerate() {
+            self.not_blank(&format!("images/{}/url", index), &image.url);
+        }
+    }
+
+    fn price(&mut self, price: &Price) {
+        let Price::Fixed {
+            amount,
+            asset_name,
+            asset_scale,
+        } = price;
+        if !amount.is_finite() || *amount < 0.0 {
+            self.fail(
+                "price_amount",
+                "not_negative",
+                "must be a positive number or zero",
+            );
+        }
+        self.not_blank("price_asset_name", asset_name);
+        self.not_negative("price_asset_scale", *asset_scale as i32);
+    }
+
+    fn item(&mut self, item: &Item) {
+        self.not_blank("name", &item.name);
+        for (index, tag) in item.tags.iter().enumerate() {
+            self.not_blank(&format!("tags/{}", index), tag);
+        }
+        self.images(&item.images);
+    }
+
+    fn variation<Id>(&mut self, variation: &ItemVariation<Id>) {
+        self.not_blank("name", &variation.name);
+        self.not_blank("sku", &variation.sku);
+        if let Some(upc) = &variation.upc {
+            if upc.is_empty() || !upc.chars().all(|c| c.is_ascii_digit()) {
+                self.fail("upc", "digits", "must be digits only");
+            }
+        }
+        self.images(&variation.images);
+        self.not_negative("available_units", variation.available_units);
+        if let Some(reorder_threshold) = variation.reorder_threshold {
+            self.not_negative("reorder_threshold", reorder_threshold);
+        }
+        self.price(&variation.price);
+        for name in variation
+            .extra_attributes
+            .iter()
+            .flat_map(|attributes| attributes.keys())
+        {
+            if name.trim().is_empty() {
+                self.fail(
+                    &format!("extra_attributes/{}", escape_pointer_token(name)),
+                    "not_blank",
+                    "the name must not be blank",
+                );
+            }
+        }
+    }
+
+    fn modification<Id>(&mut self, modification: &ItemModification<Id>) {
+        self.not_blank("name", &modification.name);
+        self.images(&modification.images);
+        self.price(&modification.price);
+    }
+
+    fn delivery<Id>(&mut self, delivery: &ItemDelivery<Id>) {
+        let Delivery::Shipping {
+            width_mm,
+            length_mm,
+            height_mm,
+            weight_grams,
+        } = &delivery.delivery;
+        self.positive("delivery_width_mm", *width_mm);
+        self.positive("delivery_length_mm", *length_mm);
+        self.positive("delivery_height_mm", *height_mm);
+        self.positive("delivery_weight_grams", *weight_grams);
+    }
+
+    fn control<Id>(&mut self, control: &ItemControl<Id>) {
+        match &control.control {
+            Control::Matrix(matrix) => self.matrix(matrix),
+            Control::Form(_) => {}
+        }
+    }
+
+    fn matrix<Id>(&mut self, matrix: &MatrixControl<Id>) {
+        self.not_blank("control_data/key_template", &matrix.key_template);
+        if matrix.props.is_empty() {
+            self.fail(
+                "control_data/props",
+                "not_empty",
+                "must have a prop at least",
+            );
+        }
+        for (index, prop) in matrix.props.iter().enumerate() {
+            let field = format!("control_data/props/{}", index);
+            self.not_blank(&format!("{}/name", field), &prop.name);
+            if prop.options.is_empty() {
+                self.fail(
+                    &format!("{}/options", field),
+                    "not_empty",
+                    "must have an option at least",
+                );
+            }
+            if !matrix.key_template.contains(&format!(":{}", prop.name)) {
+                self.fail(
+                    &format!("{}/name", field),
+                    "in_key_template",
+                    "isn't part of the key template",
+                );
+            }
+        }
+        for key in matrix.combinations.keys() {
+            if key.trim().is_empty() {
+                self.fail(
+                    &format!("control_data/combinations/{}", escape_pointer_token(key)),
+                    "not_blank",
+                    "the key must not be blank",
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,987 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_graphql::futures_util::Stream;
+use async_graphql::{
+    Context, ErrorExtensions, Json, Object, Result, Schema, SimpleObject, Subscription, Union,
+};
+use async_std::sync::Mutex;
+use chrono::NaiveDateTime;
+use futures_lite::{stream, StreamExt};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::catalog::any::AnyCatalogService;
+use crate::catalog::backend::{
+    Account, Id, SQlCatalogCmd, SqlCatalogChange, SqlCatalogObject, SqlCatalogObjectDocument,
+    SqlCatalogQueryOptions, SqlStockLocationDocument,
+};
+use crate::catalog::error::Problem;
+use crate::catalog::models::{
+    Actor, CatalogChangeOperation, CatalogObject, Control, Delivery, FormItem, Image, Item,
+    ItemCategory, ItemControl, ItemDelivery, ItemMeasurmentUnits, ItemModification, ItemVariation,
+    MatrixControl, MatrixProp, Price, StockLevel, StockLocationKind, Time,
+};
+use crate::catalog::service::{
+    CatalogChangeFeed, CatalogColumnOrder, CatalogError, CatalogService, Commander,
+    ListCatalogChangesOptions, ListCatalogQueryOptions, StockLocationService,
+};
+use crate::utils::query::{Order, OrderBy};
+
+// The catalog as a graph. The types mirror the models, an item resolves its
+// variations, modifications, deliveries and controls, and the writes take the
+// objects in the same json as the rest api.
+pub type CatalogSchema = Schema<QueryRoot, MutationRoot, SubscriptionRoot>;
+
+// enough for an item, its controls, the variations of their combinations
+// and back, a query past them is refused before it runs
+const MAX_DEPTH: usize = 10;
+const MAX_COMPLEXITY: usize = 1000;
+
+pub fn schema(service: AnyCatalogService) -> CatalogSchema {
+    Schema::build(QueryRoot, MutationRoot, SubscriptionRoot)
+        .data(service)
+        .limit_depth(MAX_DEPTH)
+        .limit_complexity(MAX_COMPLEXITY)
+        .finish()
+}
+
+// the body of `POST /graphql`
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphQLRequest {
+    pub query: String,
+    #[serde(default)]
+    pub operation_name: Option<String>,
+    #[serde(default)]
+    pub variables: Option<Value>,
+}
+
+// the query of `GET /graphql/_stream`, the variables are a json string
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphQLStreamOptions {
+    pub query: String,
+    #[serde(default)]
+    pub operation_name: Option<String>,
+    #[serde(default)]
+    pub variables: Option<String>,
+}
+
+impl GraphQLRequest {
+    // with the data every request needs, `actor` is who writes
+    pub fn into_request(self, actor: Actor) -> async_graphql::Request {
+        let mut request = async_graphql::Request::new(self.query)
+            .data(actor)
+            .data(Snapshot::default());
+        if let Some(operation_name) = self.operation_name {
+            request = request.operation_name(operation_name);
+        }
+        if let Some(variables) = self.variables {
+            request = request.variables(async_graphql::Variables::from_json(variables));
+        }
+        request
+    }
+}
+
+impl TryFrom<GraphQLStreamOptions> for GraphQLRequest {
+    type Error = serde_json::Error;
+
+    fn try_from(options: GraphQLStreamOptions) -> Result<Self, Self::Error> {
+        Ok(Self {
+            query: options.query,
+            operation_name: options.operation_name,
+            variables: options
+                .variables
+                .map(|variables| serde_json::from_str(&variables))
+                .transpose()?,
+        })
+    }
+}
+
+// the errors carry the fields of the problem the rest api would answer
+impl ErrorExtensions for CatalogError {
+    fn extend(&self) -> async_graphql::Error {
+        let problem = Problem::from(self);
+        async_graphql::Error::new(problem.detail).extend_with(|_, extensions| {
+            extensions.set("code", problem.code);
+            extensions.set("status", problem.status);
+            if let Some(id) = problem.id {
+                extensions.set("id", id);
+            }
+            if !problem.violations.is_empty() {
+                let violations = serde_json::to_value(&problem.violations).unwrap_or_default();
+                if let Ok(violations) = async_graphql::Value::from_json(violations) {
+                    extensions.set("violations", violations);
+                }
+            }
+        })
+    }
+}
+
+fn error(err: CatalogError) -> async_graphql::Error {
+    err.extend()
+}
+
+// as in the json of the rest api
+fn timestamp(time: &NaiveDateTime) -> String {
+    time.format("%Y-%m-%dT%H:%M:%S%.f").to_string()
+}
+
+fn service<'a>(ctx: &Context<'a>) -> Result<&'a AnyCatalogService> {
+    ctx.data::<AnyCatalogService>()
+}
+
+// the service that writes on behalf of the actor of the request
+fn writer(ctx: &Context<'_>) -> Result<AnyCatalogService> {
+    let service = service(ctx)?.clone();
+    Ok(match ctx.data_opt::<Actor>() {
+        Some(actor) => service.with_actor(actor.clone()),
+        None => service,
+    })
+}
+
+// The objects loaded while answering a request, by the item they belong to
+// and by id. Each parent loads only its own objects, once per request.
+#[derive(Default)]
+pub struct Snapshot(Mutex<Loaded>);
+
+#[derive(Default)]
+struct Loaded {
+    children: HashMap<(Account, Id), Arc<Vec<SqlCatalogObjectDocument>>>,
+    objects: HashMap<(Account, Id), Option<SqlCatalogObjectDocument>>,
+}
+
+impl Snapshot {
+    // a write makes the objects read before it outdated
+    async fn forget(ctx: &Context<'_>, account: &Account) {
+        if let Some(snapshot) = ctx.data_opt::<Snapshot>() {
+            let mut loaded = snapshot.0.lock().await;
+            loaded.children.retain(|(owner, _), _| owner != account);
+            loaded.objects.retain(|(owner, _), _| owner != account);
+        }
+    }
+
+    async fn children(
+        ctx: &Context<'_>,
+        account: &Account,
+        item_id: Id,
+    ) -> Result<Vec<CatalogNode>> {
+        let key = (account.clone(), item_id);
+        let cached = match ctx.data_opt::<Snapshot>() {
+            Some(snapshot) => snapshot.0.lock().await.children.get(&key).cloned(),
+            None => None,
+        };
+        let children = match cached {
+            Some(children) => children,
+            None => {
+                let query = SqlCatalogQueryOptions {
+                    limit: None,
+                    order_by: None,
+                    options: ListCatalogQueryOptions {
+                        item_id: Some(item_id),
+                        ..Default::default()
+                    },
+                };
+                let children = Arc::new(service(ctx)?.list(account, &query).await.map_err(error)?);
+                if let Some(snapshot) = ctx.data_opt::<Snapshot>() {
+                    snapshot
+                        .0
+                        .lock()
+                        .await
+                        .children
+                        .insert(key, children.clone());
+                }
+                children
+            }
+        };
+        Ok(children.iter().cloned().map(CatalogNode::from).collect())
+    }
+
+    async fn object(ctx: &Context<'_>, account: &Account, id: Id) -> Result<Option<CatalogNode>> {
+        let key = (account.clone(), id);
+        let cached = match ctx.data_opt::<Snapshot>() {
+            Some(snapshot) => snapshot.0.lock().await.objects.get(&key).cloned(),
+            None => None,
+        };
+        let object = match cached {
+            Some(object) => object,
+            None => {
+                let object = match service(ctx)?.read(account, &id).await {
+                    // only the objects of the account
+                    Ok(document) if &document.account == account => Some(document),
+                    Ok(_) | Err(CatalogError::CatalogEntryNotFound(_)) => None,
+                    Err(err) => return Err(error(err)),
+                };
+                if let Some(snapshot) = ctx.data_opt::<Snapshot>() {
+                    snapshot.0.lock().await.objects.insert(key, object.clone());
+                }
+                object
+            }
+        };
+        Ok(object.map(CatalogNode::from))
+    }
+}
+
+#[derive(Union)]
+pub enum CatalogNode {
+    Item(ItemNode),
+    Variation(VariationNode),
+    Modification(ModificationNode),
+    Delivery(DeliveryNode),
+    Control(ControlNode),
+}
+
+impl From<SqlCatalogObjectDocument> for CatalogNode {
+    fn from(document: SqlCatalogObjectDocument) -> Self {
+        let meta = Meta {
+            id: document.id,
+            account: document.account,
+            version: document.version,
+            created_at: document.created_at,
+        };
+        match document.catalog_object {
+            CatalogObject::Item(item) => Self::Item(ItemNode { meta, item }),
+            CatalogObject::Variation(variation) => {
+                Self::Variation(VariationNode { meta, variation })
+            }
+            CatalogObject::Modification(modification) => {
+                Self::Modification(ModificationNode { meta, modification })
+            }
+            CatalogObject::Delivery(delivery) => Self::Delivery(DeliveryNode { meta, delivery }),
+            CatalogObject::Control(control) => Self::Control(ControlNode { meta, control }),
+        }
+    }
+}
+
+// the fields of the document around the object
+struct Meta {
+    id: Id,
+    account: Account,
+    version: NaiveDateTime,
+    created_at: NaiveDateTime,
+}
+
+// the children of an item of a single kind
+macro_rules! children {
+    ($ctx:expr, $meta:expr, $node:ident) => {
+        Ok(Snapshot::children($ctx, &$meta.account, $meta.id)
+            .await?
+            .into_iter()
+            .filter_map(|node| match node {
+                CatalogNode::$node(node) => Some(node),
+                _ => None,
+            })
+            .collect())
+    };
+}
+
+async fn parent(ctx: &Context<'_>, meta: &Meta, item_id: Id) -> Result<Option<ItemNode>> {
+    Ok(match Snapshot::object(ctx, &meta.account, item_id).await? {
+        Some(CatalogNode::Item(item)) => Some(item),
+        _ => None,
+    })
+}
+
+pub struct ItemNode {
+    meta: Meta,
+    item: Item,
+}
+
+#[Object(name = "Item")]
+impl ItemNode {
+    async fn id(&self) -> Id {
+        self.meta.id
+    }
+
+    async fn account(&self) -> &str {
+        &self.meta.account
+    }
+
+    async fn version(&self) -> String {
+        timestamp(&self.meta.version)
+    }
+
+    async fn created_at(&self) -> String {
+        timestamp(&self.meta.created_at)
+    }
+
+    async fn category(&self) -> ItemCategory {
+        self.item.category
+    }
+
+    async fn tags(&self) -> &[String] {
+        &self.item.tags
+    }
+
+    async fn name(&self) -> &str {
+        &self.item.name
+    }
+
+    async fn images(&self) -> &[Image] {
+        &self.item.images
+    }
+
+    async fn description(&self) -> &str {
+        &self.item.description
+    }
+
+    async fn enabled(&self) -> bool {
+        self.item.enabled
+    }
+
+    async fn warranty_time(&self) -> Option<TimeNode> {
+        self.item.warranty_time.as_ref().map(TimeNode::from)
+    }
+
+    async fn variations(&self, ctx: &Context<'_>) -> Result<Vec<VariationNode>> {
+        children!(ctx, self.meta, Variation)
+    }
+
+    async fn modifications(&self, ctx: &Context<'_>) -> Result<Vec<ModificationNode>> {
+        children!(ctx, self.meta, Modification)
+    }
+
+    async fn deliveries(&self, ctx: &Context<'_>) -> Result<Vec<DeliveryNode>> {
+        children!(ctx, self.meta, Delivery)
+    }
+
+    async fn controls(&self, ctx: &Context<'_>) -> Result<Vec<ControlNode>> {
+        children!(ctx, self.meta, Control)
+    }
+}
+
+pub struct VariationNode {
+    meta: Meta,
+    variation: ItemVariation<Id>,
+}
+
+#[Object(name = "Variation")]
+impl VariationNode {
+    async fn id(&self) -> Id {
+        self.meta.id
+    }
+
+    async fn account(&self) -> &str {
+        &self.meta.account
+    }
+
+    async fn version(&self) -> String {
+        timestamp(&self.meta.version)
+    }
+
+    async fn created_at(&self) -> String {
+        timestamp(&self.meta.created_at)
+    }
+
+    async fn item_id(&self) -> Id {
+        self.variation.item_id
+    }
+
+    async fn item(&self, ctx: &Context<'_>) -> Result<Option<ItemNode>> {
+        parent(ctx, &self.meta, self.variation.item_id).await
+    }
+
+    async fn name(&self) -> &str {
+        &self.variation.name
+    }
+
+    async fn processing_time(&self) -> Option<TimeNode> {
+        self.variation.processing_time.as_ref().map(TimeNode::from)
+    }
+
+    async fn sku(&self) -> &str {
+        &self.variation.sku
+    }
+
+    async fn images(&self) -> &[Image] {
+        &self.variation.images
+    }
+
+    async fn upc(&self) -> Option<&str> {
+        self.variation.upc.as_deref()
+    }
+
+    async fn enabled(&self) -> bool {
+        self.variation.enabled
+    }
+
+    async fn measurement_units(&self) -> ItemMeasurmentUnits {
+        self.variation.measurement_units
+    }
+
+    async fn available_units(&self) -> i32 {
+        self.variation.available_units
+    }
+
+    async fn reorder_threshold(&self) -> Option<i32> {
+        self.variation.reorder_threshold
+    }
+
+    async fn price(&self) -> PriceNode {
+        PriceNode::from(&self.variation.price)
+    }
+
+    // sorted by name
+    async fn extra_attributes(&self) -> Vec<Attribute> {
+        let mut attributes: Vec<Attribute> = self
+            .variation
+            .extra_attributes
+            .iter()
+            .flatten()
+            .map(|(name, value)| Attribute {
+                name: name.clone(),
+                value: value.clone(),
+            })
+            .collect();
+        attributes.sort_by(|a, b| a.name.cmp(&b.name));
+        attributes
+    }
+
+    // the units of the variation by location
+    async fn stock(&self, ctx: &Context<'_>) -> Result<Vec<StockLevelNode>> {
+        let levels = service(ctx)?
+            .stock_levels(&self.meta.account, &self.meta.id)
+            .await
+            .map_err(error)?;
+        Ok(levels
+            .into_iter()
+            .map(|level| StockLevelNode {
+                account: self.meta.account.clone(),
+                level,
+            })
+            .collect())
+    }
+}
+
+pub struct ModificationNode {
+    meta: Meta,
+    modification: ItemModification<Id>,
+}
+
+#[Object(name = "Modification")]
+impl ModificationNode {
+    async fn id(&self) -> Id {
+        self.meta.id
+    }
+
+    async fn account(&self) -> &str {
+        &self.meta.account
+    }
+
+    async fn version(&self) -> String {
+        timestamp(&self.meta.version)
+    }
+
+    async fn created_at(&self) -> String {
+        timestamp(&self.meta.created_at)
+    }
+
+    async fn item_id(&self) -> Id {
+        self.modification.item_id
+    }
+
+    async fn item(&self, ctx: &Context<'_>) -> Result<Option<ItemNode>> {
+        parent(ctx, &self.meta, self.modification.item_id).await
+    }
+
+    async fn name(&self) -> &str {
+        &self.modification.name
+    }
+
+    async fn processing_time(&self) -> Option<TimeNode> {
+        self.modification
+            .processing_time
+            .as_ref()
+            .map(TimeNode::from)
+    }
+
+    async fn warranty_time(&self) -> Option<TimeNode> {
+        self.modification.warranty_time.as_ref().map(TimeNode::from)
+    }
+
+    async fn images(&self) -> &[Image] {
+        &self.modification.images
+    }
+
+    async fn price(&self) -> PriceNode {
+        PriceNode::from(&self.modification.price)
+    }
+
+    async fn enabled(&self) -> bool {
+        self.modification.enabled
+    }
+}
+
+pub struct DeliveryNode {
+    meta: Meta,
+    delivery: ItemDelivery<Id>,
+}
+
+#[Object(name = "Delivery")]
+impl DeliveryNode {
+    async fn id(&self) -> Id {
+        self.meta.id
+    }
+
+    async fn account(&self) -> &str {
+        &self.meta.account
+    }
+
+    async fn version(&self) -> String {
+        timestamp(&self.meta.version)
+    }
+
+    async fn created_at(&self) -> String {
+        timestamp(&self.meta.created_at)
+    }
+
+    async fn item_id(&self) -> Id {
+        self.delivery.item_id
+    }
+
+    async fn item(&self, ctx: &Context<'_>) -> Result<Option<ItemNode>> {
+        parent(ctx, &self.meta, self.delivery.item_id).await
+    }
+
+    // shipping is the only kind of delivery
+    async fn shipping(&self) -> ShippingNode {
+        let Delivery::Shipping {
+            width_mm,
+            length_mm,
+            height_mm,
+            weight_grams,
+        } = self.delivery.delivery;
+        ShippingNode {
+            width_mm,
+            length_mm,
+            height_mm,
+            weight_grams,
+        }
+    }
+}
+
+pub struct ControlNode {
+    meta: Meta,
+    control: ItemControl<Id>,
+}
+
+#[Object(name = "Control")]
+impl ControlNode {
+    async fn id(&self) -> Id {
+        self.meta.id
+    }
+
+    async fn account(&self) -> &str {
+        &self.meta.account
+    }
+
+    async fn version(&self) -> String {
+        timestamp(&self.meta.version)
+    }
+
+    async fn created_at(&self) -> String {
+        timestamp(&self.meta.created_at)
+    }
+
+    async fn item_id(&self) -> Id {
+        self.control.item_id
+    }
+
+    async fn item(&self, ctx: &Context<'_>) -> Result<Option<ItemNode>> {
+        parent(ctx, &self.meta, self.control.item_id).await
+    }
+
+    // one of `matrix` and `form` is set
+    async fn matrix(&self) -> Option<MatrixNode> {
+        match &self.control.control {
+            Control::Matrix(matrix) => Some(MatrixNode {
+                account: self.meta.account.clone(),
+                matrix: matrix.clone(),
+            }),
+            Control::Form(_) => None,
+        }
+    }
+
+    async fn form(&self) -> Option<Vec<FormField>> {
+        match &self.control.control {
+            Control::Form(items) => Some(items.iter().map(FormField::from).collect()),
+            Control::Matrix(_) => None,
+        }
+    }
+}
+
+pub struct MatrixNode {
+    account: Account,
+    matrix: MatrixControl<Id>,
+}
+
+#[Object(name = "Matrix")]
+impl MatrixNode {
+    async fn key_template(&self) -> &str {
+        &self.matrix.key_template
+    }
+
+    async fn props(&self) -> &[MatrixProp] {
+        &self.matrix.props
+    }
+
+    // sorted by key
+    async fn combinations(&self) -> Vec<CombinationNode> {
+        let mut combinations: Vec<CombinationNode> = self
+            .matrix
+            .combinations
+            .iter()
+            .map(|(key, variation_id)| CombinationNode {
+                account: self.account.clone(),
+                key: key.clone(),
+                variation_id: *variation_id,
+            })
+            .collect();
+        combinations.sort_by(|a, b| a.key.cmp(&b.key));
+        combinations
+    }
+}
+
+pub struct CombinationNode {
+    account: Account,
+    key: String,
+    variation_id: Id,
+}
+
+#[Object(name = "Combination")]
+impl CombinationNode {
+    async fn key(&self) -> &str {
+        &self.key
+    }
+
+    async fn variation_id(&self) -> Id {
+        self.variation_id
+    }
+
+    async fn variation(&self, ctx: &Context<'_>) -> Result<Option<VariationNode>> {
+        Ok(
+            match Snapshot::object(ctx, &self.account, self.variation_id).await? {
+                Some(CatalogNode::Variation(variation)) => Some(variation),
+                _ => None,
+            },
+        )
+    }
+}
+
+#[derive(SimpleObject)]
+#[graphql(name = "FormField")]
+pub struct FormField {
+    // `Text`, `Email` or `Password`
+    kind: String,
+    attributes: Vec<Attribute>,
+}
+
+impl From<&FormItem> for FormField {
+    fn from(item: &FormItem) -> Self {
+        let (kind, attributes) = match item {
+            FormItem::Text(attributes) => ("Text", attributes),
+            FormItem::Email(attributes) => ("Email", attributes),
+            FormItem::Password(attributes) => ("Password", attributes),
+        };
+        let mut attributes: Vec<Attribute> = attributes
+            .iter()
+            .map(|(name, value)| Attribute {
+                name: name.clone(),
+                value: value.clone(),
+            })
+            .collect();
+        attributes.sort_by(|a, b| a.name.cmp(&b.name));
+        Self {
+            kind: kind.to_string(),
+            attributes,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct Attribute {
+    name: String,
+    value: String,
+}
+
+#[derive(SimpleObject)]
+#[graphql(name = "Price")]
+pub struct PriceNode {
+    amount: f32,
+    asset_name: String,
+    asset_scale: i8,
+}
+
+impl From<&Price> for PriceNode {
+    fn from(price: &Price) -> Self {
+        let Price::Fixed {
+            amount,
+            asset_name,
+            asset_scale,
+        } = price;
+        Self {
+            amount: *amount,
+            asset_name: asset_name.clone(),
+            asset_scale: *asset_scale,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+#[graphql(name = "Time")]
+pub struct TimeNode {
+    seconds: u32,
+}
+
+impl From<&Time> for TimeNode {
+    fn from(time: &Time) -> Self {
+        let Time::Fixed { seconds } = time;
+        Self { seconds: *seconds }
+    }
+}
+
+#[derive(SimpleObject)]
+#[graphql(name = "Shipping")]
+pub struct ShippingNode {
+    width_mm: i32,
+    length_mm: i32,
+    height_mm: i32,
+    weight_grams: i32,
+}
+
+pub struct StockLevelNode {
+    account: Account,
+    level: StockLevel<Id>,
+}
+
+#[Object(name = "StockLevel")]
+impl StockLevelNode {
+    async fn location_id(&self) -> Id {
+        self.level.location_id
+    }
+
+    async fn units(&self) -> i32 {
+        self.level.units
+    }
+
+    async fn location(&self, ctx: &Context<'_>) -> Result<LocationNode> {
+        let location = service(ctx)?
+            .read_location(&self.account, &self.level.location_id)
+            .await
+            .map_err(error)?;
+        Ok(LocationNode(location))
+    }
+}
+
+pub struct LocationNode(SqlStockLocationDocument);
+
+#[Object(name = "StockLocation")]
+impl LocationNode {
+    async fn id(&self) -> Id {
+        self.0.id
+    }
+
+    async fn version(&self) -> String {
+        timestamp(&self.0.version)
+    }
+
+    async fn created_at(&self) -> String {
+        timestamp(&self.0.created_at)
+    }
+
+    async fn name(&self) -> &str {
+        &self.0.location.name
+    }
+
+    async fn kind(&self) -> StockLocationKind {
+        self.0.location.kind
+    }
+
+    async fn address(&self) -> Option<&str> {
+        self.0.location.address.as_deref()
+    }
+
+    async fn enabled(&self) -> bool {
+        self.0.location.enabled
+    }
+}
+
+pub struct ChangeNode(SqlCatalogChange);
+
+#[Object(name = "CatalogChange")]
+impl ChangeNode {
+    async fn sequence(&self) -> i64 {
+        self.0.sequence
+    }
+
+    async fn id(&self) -> Id {
+        self.0.id
+    }
+
+    async fn operation(&self) -> CatalogChangeOperation {
+        self.0.operation
+    }
+
+    // the object after the change, the deleted one for `DELETED`
+    async fn object(&self) -> CatalogNode {
+        CatalogNode::from(self.0.document.clone())
+    }
+
+    async fn cmd(&self) -> Option<Json<SQlCatalogCmd>> {
+        self.0.cmd.clone().map(Json)
+    }
+
+    async fn created_at(&self) -> String {
+        timestamp(&self.0.created_at)
+    }
+}
+
+#[derive(async_graphql::InputObject)]
+pub struct OrderByInput {
+    field: CatalogColumnOrder,
+    direction: Order,
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    async fn object(&self, ctx: &Context<'_>, account: Account, id: Id) -> Result<CatalogNode> {
+        let document = service(ctx)?.read(&account, &id).await.map_err(error)?;
+        Ok(CatalogNode::from(document))
+    }
+
+    // the arguments are the options of `GET /catalog/:account`
+    #[allow(clippy::too_many_arguments)]
+    async fn catalog(
+        &self,
+        ctx: &Context<'_>,
+        account: Account,
+        name: Option<String>,
+        tags: Option<Vec<String>>,
+        max_price: Option<f32>,
+        min_price: Option<f32>,
+        available_at: Option<Id>,
+        in_stock: Option<bool>,
+        limit: Option<u16>,
+        order_by: Option<OrderByInput>,
+    ) -> Result<Vec<CatalogNode>> {
+        let query = SqlCatalogQueryOptions {
+            limit,
+            order_by: order_by.map(|order_by| OrderBy {
+                field: order_by.field,
+                direction: order_by.direction,
+            }),
+            options: ListCatalogQueryOptions {
+                name,
+                item_id: None,
+                tags,
+                max_price,
+                min_price,
+                available_at,
+                in_stock,
+            },
+        };
+        let documents = service(ctx)?.list(&account, &query).await.map_err(error)?;
+        Ok(documents.into_iter().map(CatalogNode::from).collect())
+    }
+
+    async fn locations(&self, ctx: &Context<'_>, account: Account) -> Result<Vec<LocationNode>> {
+        let locations = service(ctx)?
+            .list_locations(&account)
+            .await
+            .map_err(error)?;
+        Ok(locations.into_iter().map(LocationNode).collect())
+    }
+}
+
+pub struct MutationRoot;
+
+#[Object]
+impl MutationRoot {
+    // `object` is the body of `POST /catalog/:account`
+    async fn create(
+        &self,
+        ctx: &Context<'_>,
+        account: Account,
+        object: Json<SqlCatalogObject>,
+    ) -> Result<CatalogNode> {
+        let document = writer(ctx)?
+            .create(&account, &object)
+            .await
+            .map_err(error)?;
+        Snapshot::forget(ctx, &account).await;
+        Ok(CatalogNode::from(document))
+    }
+
+    async fn update(
+        &self,
+        ctx: &Context<'_>,
+        account: Account,
+        id: Id,
+        object: Json<SqlCatalogObject>,
+    ) -> Result<CatalogNode> {
+        let document = writer(ctx)?
+            .update(&account, &id, &object)
+            .await
+            .map_err(error)?;
+        Snapshot::forget(ctx, &account).await;
+        Ok(CatalogNode::from(document))
+    }
+
+    async fn delete(&self, ctx: &Context<'_>, account: Account, id: Id) -> Result<CatalogNode> {
+        let document = writer(ctx)?.delete(&account, &id).await.map_err(error)?;
+        Snapshot::forget(ctx, &account).await;
+        Ok(CatalogNode::from(document))
+    }
+
+    // `cmd` is the body of `POST /catalog/:account/cmd`
+    async fn cmd(
+        &self,
+        ctx: &Context<'_>,
+        account: Account,
+        cmd: Json<SQlCatalogCmd>,
+    ) -> Result<bool> {
+        writer(ctx)?.cmd(&account, cmd.0).await.map_err(error)?;
+        Snapshot::forget(ctx, &account).await;
+        Ok(true)
+    }
+}
+
+pub struct SubscriptionRoot;
+
+#[Subscription]
+impl SubscriptionRoot {
+    // the changes of the account as they happen, after the ones with a
+    // greater sequence than `since` when given
+    async fn changes(
+        &self,
+        ctx: &Context<'_>,
+        account: Account,
+        since: Option<i64>,
+    ) -> Result<impl Stream<Item = ChangeNode>> {
+        let service = service(ctx)?;
+        // subscribe before reading the missed changes so nothing falls in between
+        let changes = service.subscribe().map_err(error)?;
+        let missed = match since {
+            Some(since) => {
+                let options = ListCatalogChangesOptions {
+                    since: Some(since),
+                    limit: None,
+                };
+                service.changes(&account, &options).await.map_err(error)?
+            }
+            None => vec![],
+        };
+        let last_sequence = missed
+            .last()
+            .map(|change| change.sequence)
+            .or(since)
+            .unwrap_or(0);
+        let live = changes
+            .filter(move |change| change.account == account && change.sequence > last_sequence);
+        Ok(stream::iter(missed).chain(live).map(ChangeNode))
+    }
+}
@@ -1,2 +1,8 @@
 pub mod catalog;
+#[cfg(feature = "server")]
+pub mod graphql;
+#[cfg(feature = "server")]
+pub mod openapi;
 pub mod utils;
+#[cfg(feature = "server")]
+pub mod webhooks;
@@ -1,92 +1,271 @@
-mod catalog;
-mod utils;
+use merchant::{catalog, graphql, openapi, utils, webhooks};
 
 use catalog::{
-    backend::{CatalogSQLService, SQlCatalogCmd, SqlCatalogObject, SqlCatalogQueryOptions},
-    models::CatalogObjectBulkDocument,
-    service::{CatalogError, CatalogService, Commander},
+    any::AnyCatalogService,
+    backend::{
+        Account, CatalogSQLService, Id, SQlCatalogCmd, SqlCatalogArchive, SqlCatalogChange,
+        SqlCatalogObject, SqlCatalogQueryOptions, SqlStockAlert,
+    },
+    error::{Problem, PROBLEM_JSON},
+    feed::{feed_entries, write_rss, write_tsv, FeedFormat, FeedOptions},
+    jsonld::{json_ld, JSON_LD},
+    matrix::{CatalogMatrixService, MatrixClient},
+    memory::CatalogMemoryService,
+    models::{
+        Actor, CatalogChangeOperation, CatalogObject, CatalogObjectBulkDocument, StockLocation,
+    },
+    postgres::CatalogPgService,
+    projection::ProjectionOptions,
+    service::{
+        CatalogArchiveService, CatalogAuditLog, CatalogChangeFeed, CatalogError, CatalogProjection,
+        CatalogRevisionService, CatalogService, Commander, DiffRevisionsOptions, ListAuditOptions,
+        ListCatalogChangesOptions, ListCatalogQueryOptions, ListRevisionsOptions, StockAlertHook,
+        StockAlertService, StockLocationService,
+    },
+    spreadsheet::{self, write_csv, CsvImportOptions},
+    validation::Violation,
 };
 
-use serde::Serialize;
+use graphql::{CatalogSchema, GraphQLRequest, GraphQLStreamOptions};
+use utils::patch::MERGE_PATCH_JSON;
+use utils::sqlite::SqliteConfig;
+
+use webhooks::{
+    backend::WebhookSQLService,
+    models::WebhookSubscription,
+    service::WebhookService,
+    worker::{WebhookWorker, DELIVERY_RETRY},
+};
+
+use async_trait::async_trait;
+use futures_lite::{FutureExt, StreamExt};
+use serde::{de::DeserializeOwned, Serialize};
 use serde_json::json;
-use sqlx::{migrate::Migrator, sqlite::SqlitePoolOptions as PoolOptions};
+use sqlx::{migrate::Migrator, postgres::PgPoolOptions, types::chrono::NaiveDateTime};
+use std::{error::Error, panic::AssertUnwindSafe, str::FromStr, sync::Arc, time::Duration};
 use tide::{
     http::headers::HeaderValue,
+    http::StatusCode,
     security::{CorsMiddleware, Origin},
-    Body, Request, Response,
+    sse, Body, Endpoint, Middleware, Next, Request, Response,
 };
 
 static MIGRATOR: Migrator = sqlx::migrate!();
+static PG_MIGRATOR: Migrator = sqlx::migrate!("./migrations_postgres");
 #[derive(Clone)]
 struct MyState {
-    catalog_service: CatalogSQLService,
+    catalog_service: AnyCatalogService,
+    webhook_service: Option<WebhookSQLService>,
+    admin_token: Option<String>,
+    graphql_schema: CatalogSchema,
 }
 
 impl MyState {
-    fn new(catalog_service: CatalogSQLService) -> Self {
-        Self { catalog_service }
+    fn new(
+        catalog_service: AnyCatalogService,
+        webhook_service: Option<WebhookSQLService>,
+        admin_token: Option<String>,
+    ) -> Self {
+        Self {
+            graphql_schema: graphql::schema(catalog_service.clone()),
+            catalog_service,
+            webhook_service,
+            admin_token,
+        }
+    }
+
+    // backups, exports and imports need `ADMIN_TOKEN` as bearer token, they
+    // are disabled when it isn't set
+    fn authorize_admin(&self, request: &Request<MyState>) -> tide::Result<()> {
+        let token = self.admin_token.as_ref().ok_or_else(|| {
+            tide::Error::from_str(StatusCode::Forbidden, "admin operations are disabled")
+        })?;
+        match request.header("Authorization") {
+            Some(value) if value.as_str() == format!("Bearer {}", token) => Ok(()),
+            _ => Err(tide::Error::from_str(
+                StatusCode::Unauthorized,
+                "invalid admin token",
+            )),
+        }
+    }
+
+    // the webhooks are stored with the SQLite backend only
+    fn webhook_service(&self) -> tide::Result<WebhookSQLService> {
+        self.webhook_service.clone().ok_or_else(|| {
+            tide::Error::from_str(
+                StatusCode::NotImplemented,
+                "webhooks are not available with this database",
+            )
+        })
     }
 }
 
-fn wrap_result<T: Serialize>(
-    result: &Result<T, CatalogError>,
-) -> Result<Response, Box<dyn std::error::Error>> {
+struct LogStockAlertHook;
+
+#[async_trait]
+impl StockAlertHook<Id, Account> for LogStockAlertHook {
+    async fn notify(&self, alert: &SqlStockAlert) {
+        println!("Low-Stock({}) - {:?}", alert.account, alert);
+    }
+}
+
+// Only the admin token authenticates, the other callers are anonymous. The
+// name they give in `X-Actor` is kept as claimed, next to where they came from.
+fn actor(request: &Request<MyState>) -> Actor {
+    let authenticated = request.state().authorize_admin(request).is_ok();
+    Actor {
+        id: if authenticated { "admin" } else { "anonymous" }.to_string(),
+        claimed_id: request
+            .header("X-Actor")
+            .map(|value| value.as_str().to_string()),
+        address: request.remote().map(|address| address.to_string()),
+        user_agent: request
+            .header("User-Agent")
+            .map(|value| value.as_str().to_string()),
+    }
+}
+
+fn wrap_result<T: Serialize>(result: &Result<T, CatalogError>) -> Response {
     match result {
-        Ok(result) => {
-            let mut res = Response::new(200);
-            res.set_body(Body::from_json(&result)?);
-            Ok(res)
-        }
-        Err(err) => match err {
-            CatalogError::BulkReferenceNotExist(id) => {
-                let mut res = Response::new(400);
-                res.set_body(json!({
-                  "success": false,
-                  "error": "E_BULK_ACTION",
-                  "error_message": format!("not found the item {}", id)
-                }));
-                Ok(res)
-            }
-            CatalogError::CatalogEntryNotFound(id) => {
-                let mut res = Response::new(400);
-                res.set_body(json!({
-                  "success": false,
-                  "error": "E_NOT_FOUND",
-                  "error_message": format!("not found the item {}", id)
-                }));
-                Ok(res)
+        Ok(result) => match Body::from_json(&result) {
+            Ok(body) => {
+                let mut res = Response::new(200);
+                res.set_body(body);
+                res
             }
-            CatalogError::CatalogBadRequest => {
-                let mut res = Response::new(400);
-                res.set_body(json!({
-                  "success": false,
-                  "error": "E_BAD_REQUEST",
-                  "error_message": ""
-                }));
-                Ok(res)
+            Err(err) => error_response(&CatalogError::mapping(err.to_string())),
+        },
+        Err(err) => error_response(err),
+    }
+}
+
+fn error_response(err: &CatalogError) -> Response {
+    if let Some(source) = err.source() {
+        println!("Error({}) - {} - {}", err.code(), err, source);
+    }
+    let mut res = problem_response(&Problem::from(err));
+    if let CatalogError::DatabaseBusy = err {
+        res.insert_header("Retry-After", "1");
+    }
+    res
+}
+
+fn problem_response(problem: &Problem) -> Response {
+    let mut res = Response::new(problem.status);
+    set_problem(&mut res, problem);
+    res
+}
+
+fn set_problem(res: &mut Response, problem: &Problem) {
+    res.set_body(serde_json::to_string(problem).unwrap_or_default());
+    res.set_content_type(PROBLEM_JSON);
+}
+
+// Every error is answered with a problem. The ones of the handlers get the
+// path of the request, the ones tide raises (a parameter or a body that
+// can't be read, a missing admin token) are turned into one.
+struct ProblemMiddleware;
+
+#[async_trait]
+impl Middleware<MyState> for ProblemMiddleware {
+    async fn handle(&self, request: Request<MyState>, next: Next<'_, MyState>) -> tide::Result {
+        let instance = request.url().path().to_string();
+        let mut res = next.run(request).await;
+        let is_problem = res
+            .content_type()
+            .is_some_and(|mime| mime.essence() == PROBLEM_JSON);
+        let mut problem = match res.error() {
+            Some(error) => {
+                let status = error.status();
+                // the errors of the server aren't told to the clients
+                let detail = if status.is_server_error() {
+                    status.canonical_reason().to_string()
+                } else {
+                    error.to_string()
+                };
+                Problem::for_status(status as u16, status.canonical_reason(), detail)
             }
-            CatalogError::DatabaseError => {
-                let mut res = Response::new(500);
-                res.set_body(json!({
-                  "success": false,
-                  "error": "E_DATABASE",
-                  "error_message": "Please contact with administrator database is down"
-                }));
-                Ok(res)
+            None if is_problem => match res.take_body().into_json::<Problem>().await {
+                Ok(problem) => problem,
+                Err(err) => return Err(err),
+            },
+            // the statuses set without a body, unknown routes too
+            None if res.status().is_client_error() || res.status().is_server_error() => {
+                match res.is_empty() {
+                    Some(true) => {
+                        let status = res.status();
+                        let reason = status.canonical_reason();
+                        Problem::for_status(status as u16, reason, reason)
+                    }
+                    _ => return Ok(res),
+                }
             }
-            CatalogError::MappingError => {
-                let mut res = Response::new(500);
-                res.set_body(json!({
-                  "success": false,
-                  "error": "E_MAPPING",
-                  "error_message": "Data corrupted please contact with adminstrator"
-                }));
+            None => return Ok(res),
+        };
+        problem.instance = Some(instance);
+        set_problem(&mut res, &problem);
+        Ok(res)
+    }
+}
+
+// a path parameter that isn't of its type is the client's mistake
+fn parse_param<T: FromStr>(name: &str, value: &str) -> tide::Result<T> {
+    value.parse().map_err(|_| {
+        tide::Error::from_str(
+            StatusCode::BadRequest,
+            format!("the {} {} isn't valid", name, value),
+        )
+    })
+}
+
+// A handler that panics is answered with a 500 instead of a dropped
+// connection. The panic is logged with a correlation id that the problem
+// carries too, so a report can be matched with the log.
+struct PanicMiddleware;
+
+#[async_trait]
+impl Middleware<MyState> for PanicMiddleware {
+    async fn handle(&self, request: Request<MyState>, next: Next<'_, MyState>) -> tide::Result {
+        let route = format!("{} {}", request.method(), request.url().path());
+        match AssertUnwindSafe(next.run(request)).catch_unwind().await {
+            Ok(res) => Ok(res),
+            Err(panic) => {
+                let correlation_id = format!("{:016x}", rand::random::<u64>());
+                let message = panic
+                    .downcast_ref::<&str>()
+                    .map(|message| message.to_string())
+                    .or_else(|| panic.downcast_ref::<String>().cloned())
+                    .unwrap_or_default();
+                println!("Panic({}) - {} - {}", correlation_id, route, message);
+                let status = StatusCode::InternalServerError;
+                let mut problem = Problem::for_status(
+                    status as u16,
+                    status.canonical_reason(),
+                    "the request failed unexpectedly, report it with the correlation id",
+                );
+                problem.correlation_id = Some(correlation_id.clone());
+                let mut res = problem_response(&problem);
+                res.insert_header("X-Correlation-Id", correlation_id);
                 Ok(res)
             }
-        },
+        }
     }
 }
 
+// the object of a create or an update, a body that can't be read is a
+// violation of the whole document
+async fn catalog_body<T: DeserializeOwned>(
+    request: &mut Request<MyState>,
+) -> Result<T, CatalogError> {
+    let body = request
+        .body_string()
+        .await
+        .map_err(|err| CatalogError::bad_request(err.to_string()))?;
+    serde_json::from_str(&body).map_err(|err| {
+        CatalogError::ValidationFailed(vec![Violation::new("", "format", err.to_string())])
+    })
+}
+
 async fn read(request: Request<MyState>) -> tide::Result {
     let account_id = request.param("account")?;
     let id = request.param("id")?;
@@ -94,59 +273,369 @@ async fn read(request: Request<MyState>) -> tide::Result {
     let state = request.state().clone();
     let service = state.catalog_service.clone();
     println!("retriving the service id");
-    let result = service.read(&account_id.to_string(), &id.parse()?).await;
-    Ok(wrap_result(&result).unwrap())
+    let result = service
+        .read(&account_id.to_string(), &parse_param("id", id)?)
+        .await;
+    match result {
+        Ok(document) if accepts(&request, JSON_LD) => {
+            // only items and variations are products
+            let item_id = match &document.catalog_object {
+                CatalogObject::Item(_) => document.id,
+                CatalogObject::Variation(variation) => variation.item_id,
+                _ => return Ok(Response::new(StatusCode::NotAcceptable)),
+            };
+            // the markup needs the item and its objects, not the whole account
+            let query = SqlCatalogQueryOptions {
+                limit: None,
+                order_by: None,
+                options: ListCatalogQueryOptions {
+                    item_id: Some(item_id),
+                    ..Default::default()
+                },
+            };
+            let mut documents = match service.list(&account_id.to_string(), &query).await {
+                Ok(documents) => documents,
+                Err(err) => return Ok(error_response(&err)),
+            };
+            if item_id == document.id {
+                documents.push(document.clone());
+            } else {
+                match service.read(&account_id.to_string(), &item_id).await {
+                    Ok(item) => documents.push(item),
+                    Err(err) => return Ok(error_response(&err)),
+                }
+            }
+            let markup = match json_ld(&document, &documents) {
+                Some(markup) => markup,
+                None => return Ok(Response::new(StatusCode::NotAcceptable)),
+            };
+            let mut res = Response::new(200);
+            res.set_body(markup);
+            res.set_content_type(JSON_LD);
+            Ok(res)
+        }
+        _ => Ok(wrap_result(&result)),
+    }
+}
+
+fn accepts(request: &Request<MyState>, mime: &str) -> bool {
+    request.header("Accept").is_some_and(|accept| {
+        accept
+            .as_str()
+            .split(',')
+            .any(|media_range| media_range.split(';').next().unwrap_or("").trim() == mime)
+    })
 }
 
 async fn list(request: Request<MyState>) -> tide::Result {
     let account_id = request.param("account")?;
-    let query: SqlCatalogQueryOptions = request.query().unwrap();
-    println!("List({}) - {:?}", account_id, query);
+    let query: SqlCatalogQueryOptions = request.query()?;
+    let projection: ProjectionOptions = request.query()?;
+    println!("List({}) - {:?} {:?}", account_id, query, projection);
     let state = request.state().clone();
     let service = state.catalog_service.clone();
+    if let Some(fields) = projection.fields {
+        let result = service
+            .list_fields(&account_id.to_string(), &query, &fields)
+            .await;
+        return Ok(wrap_result(&result));
+    }
     let result = service.list(&account_id.to_string(), &query).await;
-    Ok(wrap_result(&result).unwrap())
+    Ok(wrap_result(&result))
 }
 
 async fn create(mut request: Request<MyState>) -> tide::Result {
-    let catalog: SqlCatalogObject = request.body_json().await?;
+    let catalog: SqlCatalogObject = match catalog_body(&mut request).await {
+        Ok(catalog) => catalog,
+        Err(err) => return Ok(error_response(&err)),
+    };
     let account_id = request.param("account")?;
     println!("Create({}) - {:?}", account_id, catalog);
     let state = request.state().clone();
-    let service = state.catalog_service.clone();
+    let service = state.catalog_service.clone().with_actor(actor(&request));
     let result = service.create(&account_id.to_string(), &catalog).await;
-    Ok(wrap_result(&result).unwrap())
+    Ok(wrap_result(&result))
 }
 
 async fn update(mut request: Request<MyState>) -> tide::Result {
-    let catalog: SqlCatalogObject = request.body_json().await?;
+    let catalog: SqlCatalogObject = match catalog_body(&mut request).await {
+        Ok(catalog) => catalog,
+        Err(err) => return Ok(error_response(&err)),
+    };
     let account_id = request.param("account")?;
     println!("Create({}) - {:?}", account_id, catalog);
     let id = request.param("id")?;
     let state = request.state().clone();
+    let service = state.catalog_service.clone().with_actor(actor(&request));
+    let result = service
+        .update(&account_id.to_string(), &parse_param("id", id)?, &catalog)
+        .await;
+    Ok(wrap_result(&result))
+}
+
+// `If-Match` takes the version of the document the patch was made from
+async fn patch(mut request: Request<MyState>) -> tide::Result {
+    let is_merge_patch = request
+        .content_type()
+        .is_some_and(|mime| mime.essence() == MERGE_PATCH_JSON);
+    if !is_merge_patch {
+        let err = CatalogError::UnsupportedMediaType(MERGE_PATCH_JSON.to_string());
+        return Ok(error_response(&err));
+    }
+    let patch: serde_json::Value = match catalog_body(&mut request).await {
+        Ok(patch) => patch,
+        Err(err) => return Ok(error_response(&err)),
+    };
+    let account_id = request.param("account")?;
+    let id = parse_param("id", request.param("id")?)?;
+    println!("Patch({}, {}) - {}", account_id, id, patch);
+    let version: Option<NaiveDateTime> = match request.header("If-Match") {
+        Some(values) => Some(parse_param(
+            "version",
+            values.last().as_str().trim_matches('"'),
+        )?),
+        None => None,
+    };
+    let state = request.state().clone();
+    let service = state.catalog_service.clone().with_actor(actor(&request));
+    let result = catalog::service::patch(
+        &service,
+        &account_id.to_string(),
+        &id,
+        &patch,
+        version.as_ref(),
+    )
+    .await;
+    Ok(wrap_result(&result))
+}
+
+async fn delete(request: Request<MyState>) -> tide::Result {
+    let account_id = request.param("account")?;
+    let id = request.param("id")?;
+    println!("Delete({}, {})", account_id, id);
+    let state = request.state().clone();
+    let service = state.catalog_service.clone().with_actor(actor(&request));
+    let result = service
+        .delete(&account_id.to_string(), &parse_param("id", id)?)
+        .await;
+    Ok(wrap_result(&result))
+}
+
+async fn list_revisions(request: Request<MyState>) -> tide::Result {
+    let account_id = request.param("account")?;
+    let id = request.param("id")?;
+    let query: ListRevisionsOptions = request.query()?;
+    let state = request.state().clone();
+    let service = state.catalog_service.clone();
+    match query.at {
+        Some(at) => {
+            let result = service
+                .read_revision_at(&account_id.to_string(), &parse_param("id", id)?, &at)
+                .await;
+            Ok(wrap_result(&result))
+        }
+        None => {
+            let result = service
+                .list_revisions(&account_id.to_string(), &parse_param("id", id)?)
+                .await;
+            Ok(wrap_result(&result))
+        }
+    }
+}
+
+async fn read_revision(request: Request<MyState>) -> tide::Result {
+    let account_id = request.param("account")?;
+    let id = request.param("id")?;
+    let revision = request.param("revision")?;
+    let state = request.state().clone();
     let service = state.catalog_service.clone();
     let result = service
-        .update(&account_id.to_string(), &id.parse()?, &catalog)
+        .read_revision(
+            &account_id.to_string(),
+            &parse_param("id", id)?,
+            parse_param("revision", revision)?,
+        )
+        .await;
+    Ok(wrap_result(&result))
+}
+
+async fn diff_revisions(request: Request<MyState>) -> tide::Result {
+    let account_id = request.param("account")?;
+    let id = request.param("id")?;
+    let query: DiffRevisionsOptions = request.query()?;
+    let state = request.state().clone();
+    let service = state.catalog_service.clone();
+    let result = service
+        .diff_revisions(
+            &account_id.to_string(),
+            &parse_param("id", id)?,
+            query.from,
+            query.to,
+        )
+        .await;
+    Ok(wrap_result(&result))
+}
+
+async fn restore_revision(request: Request<MyState>) -> tide::Result {
+    let account_id = request.param("account")?;
+    let id = request.param("id")?;
+    let revision = request.param("revision")?;
+    println!("Restore({}, {}) - revision {}", account_id, id, revision);
+    let state = request.state().clone();
+    let service = state.catalog_service.clone().with_actor(actor(&request));
+    let result = service
+        .restore_revision(
+            &account_id.to_string(),
+            &parse_param("id", id)?,
+            parse_param("revision", revision)?,
+        )
+        .await;
+    Ok(wrap_result(&result))
+}
+
+async fn audit(request: Request<MyState>) -> tide::Result {
+    let account_id = request.param("account")?;
+    let options: ListAuditOptions = request.query()?;
+    let state = request.state().clone();
+    let service = state.catalog_service.clone();
+    let result = service.audit(&account_id.to_string(), &options).await;
+    Ok(wrap_result(&result))
+}
+
+async fn changes(request: Request<MyState>) -> tide::Result {
+    let account_id = request.param("account")?;
+    let options: ListCatalogChangesOptions = request.query()?;
+    let state = request.state().clone();
+    let service = state.catalog_service.clone();
+    let result = service.changes(&account_id.to_string(), &options).await;
+    Ok(wrap_result(&result))
+}
+
+async fn send_change(sender: &sse::Sender, change: &SqlCatalogChange) -> tide::Result<()> {
+    let event = match change.operation {
+        CatalogChangeOperation::Created => "created",
+        CatalogChangeOperation::Updated => "updated",
+        CatalogChangeOperation::Deleted => "deleted",
+        CatalogChangeOperation::Command => "inventory",
+    };
+    sender
+        .send(
+            event,
+            serde_json::to_string(change)?,
+            Some(change.sequence.to_string().as_str()),
+        )
+        .await?;
+    Ok(())
+}
+
+// without a change feed the stream would end right away, the client is told
+// why before it's opened
+async fn open_stream(request: Request<MyState>) -> tide::Result {
+    if let Err(err) = request.state().catalog_service.subscribe() {
+        return Ok(error_response(&err));
+    }
+    sse::endpoint(stream).call(request).await
+}
+
+async fn stream(request: Request<MyState>, sender: sse::Sender) -> tide::Result<()> {
+    let account_id = request.param("account")?.to_string();
+    let last_event_id = request
+        .header("Last-Event-ID")
+        .and_then(|value| value.as_str().parse::<i64>().ok());
+    println!("Stream({}) - from {:?}", account_id, last_event_id);
+    let state = request.state().clone();
+    let service = state.catalog_service.clone();
+    // subscribe before reading the missed changes so nothing falls in between
+    let changes = service.subscribe()?;
+
+    let mut last_sequence = 0;
+    if let Some(since) = last_event_id {
+        last_sequence = since;
+        let options = ListCatalogChangesOptions {
+            since: Some(since),
+            limit: None,
+        };
+        for change in service.changes(&account_id, &options).await? {
+            send_change(&sender, &change).await?;
+            last_sequence = change.sequence;
+        }
+    }
+
+    while let Ok(change) = changes.recv().await {
+        if change.account != account_id || change.sequence <= last_sequence {
+            continue;
+        }
+        send_change(&sender, &change).await?;
+        last_sequence = change.sequence;
+    }
+    Ok(())
+}
+
+async fn graphql(mut request: Request<MyState>) -> tide::Result {
+    let query: GraphQLRequest = match catalog_body(&mut request).await {
+        Ok(query) => query,
+        Err(err) => return Ok(error_response(&err)),
+    };
+    println!("GraphQL - {:?}", query.operation_name);
+    let state = request.state().clone();
+    let response = state
+        .graphql_schema
+        .execute(query.into_request(actor(&request)))
         .await;
-    Ok(wrap_result(&result).unwrap())
+    // the errors of a query are in the body, as graphql clients expect them
+    Ok(Response::builder(StatusCode::Ok)
+        .body(serde_json::to_string(&response)?)
+        .content_type(tide::http::mime::JSON)
+        .build())
+}
+
+async fn graphql_sdl(request: Request<MyState>) -> tide::Result {
+    Ok(Response::builder(StatusCode::Ok)
+        .body(request.state().graphql_schema.sdl())
+        .content_type(tide::http::mime::PLAIN)
+        .build())
+}
+
+// the results of a subscription as `next` events, then `complete`
+async fn graphql_stream(request: Request<MyState>, sender: sse::Sender) -> tide::Result<()> {
+    let options: GraphQLStreamOptions = request.query()?;
+    let query = GraphQLRequest::try_from(options)
+        .map_err(|err| tide::Error::from_str(StatusCode::BadRequest, err.to_string()))?;
+    println!("GraphQL-Stream - {:?}", query.operation_name);
+    let state = request.state().clone();
+    let mut responses = state
+        .graphql_schema
+        .execute_stream(query.into_request(actor(&request)));
+    while let Some(response) = responses.next().await {
+        sender
+            .send("next", serde_json::to_string(&response)?, None)
+            .await?;
+    }
+    sender.send("complete", "", None).await?;
+    Ok(())
 }
 
 async fn bulk_create(mut request: Request<MyState>) -> tide::Result {
-    let catalog: Vec<CatalogObjectBulkDocument<String>> = request.body_json().await?;
+    let catalog: Vec<CatalogObjectBulkDocument<String>> = match catalog_body(&mut request).await {
+        Ok(catalog) => catalog,
+        Err(err) => return Ok(error_response(&err)),
+    };
     let account_id = request.param("account")?;
     println!("Bulk-Create({}) - {:?}", account_id, catalog);
     let state = request.state().clone();
-    let service = state.catalog_service.clone();
+    let service = state.catalog_service.clone().with_actor(actor(&request));
     let result = service.bulk_create(&account_id.to_string(), &catalog).await;
-    Ok(wrap_result(&result).unwrap())
+    Ok(wrap_result(&result))
 }
 
 async fn cmd(mut request: Request<MyState>) -> tide::Result {
     let cmd: SQlCatalogCmd = request.body_json().await?;
     let account_id = request.param("account")?;
     let state = request.state().clone();
-    let service = state.catalog_service.clone();
-    service.cmd(&account_id.to_string(), cmd).await?;
+    let service = state.catalog_service.clone().with_actor(actor(&request));
+    if let Err(err) = service.cmd(&account_id.to_string(), cmd).await {
+        return Ok(error_response(&err));
+    }
     let mut res = Response::new(200);
     res.set_body(json!({
       "success": true
@@ -154,8 +643,259 @@ async fn cmd(mut request: Request<MyState>) -> tide::Result {
     Ok(res)
 }
 
+async fn list_locations(request: Request<MyState>) -> tide::Result {
+    let account_id = request.param("account")?;
+    let state = request.state().clone();
+    let service = state.catalog_service.clone();
+    let result = service.list_locations(&account_id.to_string()).await;
+    Ok(wrap_result(&result))
+}
+
+async fn create_location(mut request: Request<MyState>) -> tide::Result {
+    let location: StockLocation = request.body_json().await?;
+    let account_id = request.param("account")?;
+    println!("Create-Location({}) - {:?}", account_id, location);
+    let state = request.state().clone();
+    let service = state.catalog_service.clone().with_actor(actor(&request));
+    let result = service
+        .create_location(&account_id.to_string(), &location)
+        .await;
+    Ok(wrap_result(&result))
+}
+
+async fn read_location(request: Request<MyState>) -> tide::Result {
+    let account_id = request.param("account")?;
+    let id = request.param("id")?;
+    let state = request.state().clone();
+    let service = state.catalog_service.clone();
+    let result = service
+        .read_location(&account_id.to_string(), &parse_param("id", id)?)
+        .await;
+    Ok(wrap_result(&result))
+}
+
+async fn update_location(mut request: Request<MyState>) -> tide::Result {
+    let location: StockLocation = request.body_json().await?;
+    let account_id = request.param("account")?;
+    let id = request.param("id")?;
+    let state = request.state().clone();
+    let service = state.catalog_service.clone().with_actor(actor(&request));
+    let result = service
+        .update_location(&account_id.to_string(), &parse_param("id", id)?, &location)
+        .await;
+    Ok(wrap_result(&result))
+}
+
+async fn list_alerts(request: Request<MyState>) -> tide::Result {
+    let account_id = request.param("account")?;
+    let state = request.state().clone();
+    let service = state.catalog_service.clone();
+    let result = service.list_alerts(&account_id.to_string()).await;
+    Ok(wrap_result(&result))
+}
+
+async fn stock_levels(request: Request<MyState>) -> tide::Result {
+    let account_id = request.param("account")?;
+    let id = request.param("id")?;
+    let state = request.state().clone();
+    let service = state.catalog_service.clone();
+    let result = service
+        .stock_levels(&account_id.to_string(), &parse_param("id", id)?)
+        .await;
+    Ok(wrap_result(&result))
+}
+
+async fn list_webhooks(request: Request<MyState>) -> tide::Result {
+    let account_id = request.param("account")?;
+    let state = request.state().clone();
+    let service = state.webhook_service()?;
+    let result = service.list_subscriptions(&account_id.to_string()).await;
+    Ok(wrap_result(&result))
+}
+
+async fn create_webhook(mut request: Request<MyState>) -> tide::Result {
+    let subscription: WebhookSubscription = request.body_json().await?;
+    let account_id = request.param("account")?;
+    println!("Create-Webhook({}) - {}", account_id, subscription.url);
+    let state = request.state().clone();
+    let service = state.webhook_service()?;
+    let result = service
+        .create_subscription(&account_id.to_string(), &subscription)
+        .await;
+    Ok(wrap_result(&result))
+}
+
+async fn read_webhook(request: Request<MyState>) -> tide::Result {
+    let account_id = request.param("account")?;
+    let id = request.param("id")?;
+    let state = request.state().clone();
+    let service = state.webhook_service()?;
+    let result = service
+        .read_subscription(&account_id.to_string(), &parse_param("id", id)?)
+        .await;
+    Ok(wrap_result(&result))
+}
+
+async fn update_webhook(mut request: Request<MyState>) -> tide::Result {
+    let subscription: WebhookSubscription = request.body_json().await?;
+    let account_id = request.param("account")?;
+    let id = request.param("id")?;
+    let state = request.state().clone();
+    let service = state.webhook_service()?;
+    let result = service
+        .update_subscription(
+            &account_id.to_string(),
+            &parse_param("id", id)?,
+            &subscription,
+        )
+        .await;
+    Ok(wrap_result(&result))
+}
+
+async fn delete_webhook(request: Request<MyState>) -> tide::Result {
+    let account_id = request.param("account")?;
+    let id = request.param("id")?;
+    let state = request.state().clone();
+    let service = state.webhook_service()?;
+    let result = service
+        .delete_subscription(&account_id.to_string(), &parse_param("id", id)?)
+        .await;
+    Ok(wrap_result(&result))
+}
+
+async fn list_webhook_deliveries(request: Request<MyState>) -> tide::Result {
+    let account_id = request.param("account")?;
+    let id = request.param("id")?;
+    let state = request.state().clone();
+    let service = state.webhook_service()?;
+    let result = service
+        .list_deliveries(&account_id.to_string(), &parse_param("id", id)?)
+        .await;
+    Ok(wrap_result(&result))
+}
+
+async fn export_csv(request: Request<MyState>) -> tide::Result {
+    let account_id = request.param("account")?;
+    let state = request.state().clone();
+    let service = state.catalog_service.clone();
+    let query = SqlCatalogQueryOptions {
+        limit: None,
+        order_by: None,
+        options: Default::default(),
+    };
+    let result = service
+        .list(&account_id.to_string(), &query)
+        .await
+        .and_then(|documents| write_csv(&documents));
+    match result {
+        Ok(data) => {
+            let mut res = Response::new(200);
+            res.set_body(data);
+            res.set_content_type("text/csv");
+            Ok(res)
+        }
+        Err(err) => Ok(error_response(&err)),
+    }
+}
+
+async fn import_csv(mut request: Request<MyState>) -> tide::Result {
+    let data = request.body_string().await?;
+    let options: CsvImportOptions = request.query()?;
+    let account_id = request.param("account")?;
+    println!("Import-Csv({}) - {:?}", account_id, options);
+    let state = request.state().clone();
+    let service = state.catalog_service.clone().with_actor(actor(&request));
+    let result = spreadsheet::import_csv(&service, &account_id.to_string(), &data, &options).await;
+    let mut res = wrap_result(&result);
+    // nothing is created from a sheet with errors
+    if let Ok(report) = &result {
+        if !report.sheet.errors.is_empty() {
+            res.set_status(StatusCode::BadRequest);
+        }
+    }
+    Ok(res)
+}
+
+async fn feed(request: Request<MyState>) -> tide::Result {
+    let options: FeedOptions = request.query()?;
+    let account_id = request.param("account")?;
+    let state = request.state().clone();
+    let service = state.catalog_service.clone();
+    let query = SqlCatalogQueryOptions {
+        limit: None,
+        order_by: None,
+        options: Default::default(),
+    };
+    let documents = match service.list(&account_id.to_string(), &query).await {
+        Ok(documents) => documents,
+        Err(err) => return Ok(error_response(&err)),
+    };
+    let entries = feed_entries(&documents, &options);
+    let mut res = Response::new(200);
+    match options.format {
+        FeedFormat::Rss => {
+            res.set_body(write_rss(account_id, request.url().as_str(), &entries));
+            res.set_content_type("application/rss+xml");
+        }
+        FeedFormat::Tsv => {
+            res.set_body(write_tsv(&entries));
+            res.set_content_type("text/tab-separated-values");
+        }
+    }
+    Ok(res)
+}
+
+async fn backup(request: Request<MyState>) -> tide::Result {
+    let state = request.state().clone();
+    state.authorize_admin(&request)?;
+    let service = state.catalog_service.clone();
+    let path = std::env::temp_dir().join(format!("merchant-backup-{}.db", rand::random::<u32>()));
+    let path = path.to_string_lossy().to_string();
+    println!("Backup - {}", path);
+    if let Err(err) = service.backup(&path).await {
+        return Ok(error_response(&err));
+    }
+    let backup = async_std::fs::read(&path).await;
+    let _ = async_std::fs::remove_file(&path).await;
+    let mut res = Response::new(200);
+    res.set_body(backup?);
+    res.set_content_type("application/vnd.sqlite3");
+    res.insert_header(
+        "Content-Disposition",
+        "attachment; filename=\"merchant.db\"",
+    );
+    Ok(res)
+}
+
+async fn export(request: Request<MyState>) -> tide::Result {
+    let account_id = request.param("account")?;
+    let state = request.state().clone();
+    state.authorize_admin(&request)?;
+    let service = state.catalog_service.clone();
+    let result = service.export(&account_id.to_string()).await;
+    Ok(wrap_result(&result))
+}
+
+async fn import(mut request: Request<MyState>) -> tide::Result {
+    let state = request.state().clone();
+    state.authorize_admin(&request)?;
+    let archive: SqlCatalogArchive = request.body_json().await?;
+    let account_id = request.param("account")?;
+    println!(
+        "Import({}) - {} objects from {}",
+        account_id,
+        archive.objects.len(),
+        archive.account
+    );
+    let service = state.catalog_service.clone().with_actor(actor(&request));
+    let result = service.import(&account_id.to_string(), &archive).await;
+    Ok(wrap_result(&result))
+}
+
 const DEFAULT_DB_FILE: &str = "sqlite:merchant.db";
 const DEFAULT_PORT: &str = "5555";
+const DEFAULT_PG_CONNECTIONS: u32 = 10;
+const WEBHOOK_POLL_INTERVAL: Duration = Duration::from_secs(5);
 
 #[async_std::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -163,28 +903,155 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let port = std::env::var("PORT").unwrap_or(DEFAULT_PORT.into());
 
-    let conn = PoolOptions::new()
-        .max_connections(1)
-        .connect(&db_file)
-        .await?;
-    MIGRATOR.run(&conn).await?;
-    let mut app = tide::with_state(MyState::new(CatalogSQLService::new(conn)));
+    let (catalog_service, webhook_service) = if db_file == "memory:" {
+        // nothing is persisted, handy to run the frontend without a database
+        (AnyCatalogService::Memory(CatalogMemoryService::new()), None)
+    } else if db_file == "matrix:" {
+        let homeserver = std::env::var("MATRIX_HOMESERVER")?;
+        let access_token = std::env::var("MATRIX_ACCESS_TOKEN")?;
+        let room_id = std::env::var("MATRIX_ROOM_ID")?;
+        let client = MatrixClient::new(homeserver.parse()?, &access_token, &room_id);
+        (
+            AnyCatalogService::Matrix(Box::new(CatalogMatrixService::connect(client).await?)),
+            None,
+        )
+    } else if db_file.starts_with("postgres:") || db_file.starts_with("postgresql:") {
+        let conn = PgPoolOptions::new()
+            .max_connections(DEFAULT_PG_CONNECTIONS)
+            .connect(&db_file)
+            .await?;
+        PG_MIGRATOR.run(&conn).await?;
+        (
+            AnyCatalogService::Postgres(CatalogPgService::new(conn)),
+            None,
+        )
+    } else {
+        let config = SqliteConfig::from_env()?;
+        let conn = config.connect(&db_file).await?;
+        MIGRATOR.run(&conn).await?;
+        let mut webhook_service = WebhookSQLService::new(conn.clone());
+        if std::env::var("WEBHOOK_PRIVATE_TARGETS").is_ok_and(|value| value == "true") {
+            webhook_service = webhook_service.with_private_targets();
+        }
+        let catalog_service = CatalogSQLService::new(conn)
+            .with_busy_retry(config.busy_retry)
+            .with_alert_hook(Arc::new(LogStockAlertHook));
+        (
+            AnyCatalogService::Sqlite(catalog_service),
+            Some(webhook_service),
+        )
+    };
+    // a backend without them only starts when the operator asks for it
+    let limits = catalog_service.limits();
+    if !limits.is_empty() {
+        let missing = format!("{} aren't kept with this database", limits.join(", "));
+        if !std::env::var("ALLOW_MISSING_FEATURES").is_ok_and(|value| value == "true") {
+            return Err(format!(
+                "{}, set ALLOW_MISSING_FEATURES=true to start anyway",
+                missing
+            )
+            .into());
+        }
+        eprintln!("warning: {}, their endpoints answer 501", missing);
+    }
+
+    if let Some(webhook_service) = &webhook_service {
+        let worker = WebhookWorker::new(webhook_service.clone(), DELIVERY_RETRY);
+        async_std::task::spawn(worker.run(catalog_service.clone(), WEBHOOK_POLL_INTERVAL));
+    }
+
+    let admin_token = std::env::var("ADMIN_TOKEN")
+        .ok()
+        .filter(|token| !token.is_empty());
+    let mut app = tide::with_state(MyState::new(catalog_service, webhook_service, admin_token));
 
     app.with(
         CorsMiddleware::new()
-            .allow_methods("GET, POST, PUT, OPTIONS".parse::<HeaderValue>().unwrap())
+            .allow_methods(
+                "GET, POST, PUT, PATCH, DELETE, OPTIONS"
+                    .parse::<HeaderValue>()
+                    .unwrap(),
+            )
             .allow_origin(Origin::from("*"))
             .allow_credentials(false),
     );
+    app.with(ProblemMiddleware);
+    app.with(PanicMiddleware);
 
     app.at("/")
         .get(|_| async move { Ok(json!({ "version": "1" })) });
 
+    app.at("/openapi.json")
+        .get(|_| async move { Ok(openapi::openapi()) });
+
     app.at("/catalog/:account").get(list).post(create);
 
     app.at("/catalog/:account/_bulk").post(bulk_create);
 
-    app.at("/catalog/:account/:id").get(read).put(update);
+    app.at("/catalog/:account/_csv")
+        .get(export_csv)
+        .post(import_csv);
+
+    app.at("/catalog/:account/_feed.xml").get(feed);
+
+    app.at("/catalog/:account/_locations")
+        .get(list_locations)
+        .post(create_location);
+
+    app.at("/catalog/:account/_alerts").get(list_alerts);
+
+    app.at("/catalog/:account/_changes").get(changes);
+
+    app.at("/catalog/:account/_audit").get(audit);
+
+    app.at("/catalog/:account/_export").get(export);
+
+    app.at("/catalog/:account/_import").post(import);
+
+    app.at("/_admin/backup").get(backup);
+
+    app.at("/catalog/:account/_stream").get(open_stream);
+
+    app.at("/graphql").get(graphql_sdl).post(graphql);
+
+    app.at("/graphql/_stream")
+        .get(sse::endpoint(graphql_stream));
+
+    app.at("/catalog/:account/_webhooks")
+        .get(list_webhooks)
+        .post(create_webhook);
+
+    app.at("/catalog/:account/_webhooks/:id")
+        .get(read_webhook)
+        .put(update_webhook)
+        .delete(delete_webhook);
+
+    app.at("/catalog/:account/_webhooks/:id/deliveries")
+        .get(list_webhook_deliveries);
+
+    app.at("/catalog/:account/_locations/:id")
+        .get(read_location)
+        .put(update_location);
+
+    app.at("/catalog/:account/:id")
+        .get(read)
+        .put(update)
+        .patch(patch)
+        .delete(delete);
+
+    app.at("/catalog/:account/:id/_stock").get(stock_levels);
+
+    app.at("/catalog/:account/:id/revisions")
+        .get(list_revisions);
+
+    app.at("/catalog/:account/:id/revisions/_diff")
+        .get(diff_revisions);
+
+    app.at("/catalog/:account/:id/revisions/:revision")
+        .get(read_revision);
+
+    app.at("/catalog/:account/:id/revisions/:revision/_restore")
+        .post(restore_revision);
 
     app.at("/catalog/:account/cmd").post(cmd);
 
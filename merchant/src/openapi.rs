@@ -0,0 +1,539 @@
+use schemars::gen::{SchemaGenerator, SchemaSettings};
+use schemars::schema::{Schema, SchemaObject, SubschemaValidation};
+use schemars::JsonSchema;
+use serde_json::{json, Map, Value};
+
+use crate::catalog::backend::{
+    Account, Id, SQlCatalogCmd, SqlAuditEntry, SqlCatalogArchive, SqlCatalogChange,
+    SqlCatalogObject, SqlCatalogObjectDocument, SqlCatalogQueryOptions, SqlCatalogRevision,
+    SqlStockAlert, SqlStockLocationDocument,
+};
+use crate::catalog::error::{Problem, PROBLEM_JSON};
+use crate::catalog::feed::FeedOptions;
+use crate::catalog::models::{
+    CatalogImport, CatalogObjectBulkDocument, CatalogRevisionDiff, StockLevel, StockLocation,
+};
+use crate::catalog::projection::ProjectionOptions;
+use crate::catalog::service::{
+    DiffRevisionsOptions, ListAuditOptions, ListCatalogChangesOptions, ListRevisionsOptions,
+};
+use crate::catalog::spreadsheet::{CsvImportOptions, CsvImportReport};
+use crate::graphql::{GraphQLRequest, GraphQLStreamOptions};
+use crate::utils::patch::MERGE_PATCH_JSON;
+use crate::webhooks::backend::{SqlWebhookDelivery, SqlWebhookSubscriptionDocument};
+use crate::webhooks::models::WebhookSubscription;
+
+// The contract of the http api. The routes mirror the ones `main` serves,
+// the schemas come from the serde models so they can't drift apart.
+pub const OPENAPI_VERSION: &str = "3.0.3";
+
+type SchemaFn = fn(&mut SchemaGenerator) -> Schema;
+
+pub enum Content {
+    Empty,
+    Json(SchemaFn),
+    // a body that isn't json, by media type
+    Raw(&'static [&'static str]),
+}
+
+pub struct Route {
+    pub method: &'static str,
+    // in the syntax of the router, `:account`
+    pub path: &'static str,
+    pub summary: &'static str,
+    pub query: Option<SchemaFn>,
+    pub body: Content,
+    pub response: Content,
+    // needs the `ADMIN_TOKEN` as a bearer token
+    pub admin: bool,
+}
+
+const fn route(method: &'static str, path: &'static str, summary: &'static str) -> Route {
+    Route {
+        method,
+        path,
+        summary,
+        query: None,
+        body: Content::Empty,
+        response: Content::Empty,
+        admin: false,
+    }
+}
+
+fn schema<T: JsonSchema>(gen: &mut SchemaGenerator) -> Schema {
+    gen.subschema_for::<T>()
+}
+
+// a schema of the bodies `main` writes with `json!`
+fn inline_schema(schema: Value) -> Schema {
+    serde_json::from_value(schema).unwrap_or(Schema::Bool(true))
+}
+
+fn version_schema(_: &mut SchemaGenerator) -> Schema {
+    inline_schema(json!({
+        "type": "object",
+        "required": ["version"],
+        "properties": { "version": { "type": "string" } },
+    }))
+}
+
+fn success_schema(_: &mut SchemaGenerator) -> Schema {
+    inline_schema(json!({
+        "type": "object",
+        "required": ["success"],
+        "properties": { "success": { "type": "boolean" } },
+    }))
+}
+
+fn openapi_schema(_: &mut SchemaGenerator) -> Schema {
+    inline_schema(json!({ "type": "object" }))
+}
+
+// the graphql response, `data` and `errors` follow the sdl of `GET /graphql`
+fn graphql_schema(_: &mut SchemaGenerator) -> Schema {
+    inline_schema(json!({
+        "type": "object",
+        "properties": {
+            "data": { "type": "object", "nullable": true },
+            "errors": { "type": "array", "items": { "type": "object" } },
+        },
+    }))
+}
+
+// every revision, or the one current at `at`
+fn revisions_schema(gen: &mut SchemaGenerator) -> Schema {
+    Schema::Object(SchemaObject {
+        subschemas: Some(Box::new(SubschemaValidation {
+            one_of: Some(vec![
+                gen.subschema_for::<Vec<SqlCatalogRevision>>(),
+                gen.subschema_for::<SqlCatalogRevision>(),
+            ]),
+            ..Default::default()
+        })),
+        ..Default::default()
+    })
+}
+
+// the options of the listing and the `fields` of its projection
+fn list_query_schema(gen: &mut SchemaGenerator) -> Schema {
+    let mut query = object_schema::<SqlCatalogQueryOptions>(gen);
+    let projection = object_schema::<ProjectionOptions>(gen);
+    if let Some(projection) = projection.object {
+        query.object().properties.extend(projection.properties);
+    }
+    Schema::Object(query)
+}
+
+fn object_schema<T: JsonSchema>(gen: &mut SchemaGenerator) -> SchemaObject {
+    let schema = gen.subschema_for::<T>();
+    match gen.dereference(&schema) {
+        Some(schema) => schema.clone().into_object(),
+        None => schema.into_object(),
+    }
+}
+
+// the documents, or only their `fields`
+fn list_schema(gen: &mut SchemaGenerator) -> Schema {
+    Schema::Object(SchemaObject {
+        subschemas: Some(Box::new(SubschemaValidation {
+            one_of: Some(vec![
+                gen.subschema_for::<Vec<SqlCatalogObjectDocument>>(),
+                inline_schema(json!({
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "required": ["id", "type", "data"],
+                        "properties": {
+                            "id": { "type": "integer" },
+                            "type": { "type": "string" },
+                            "data": { "type": "object" },
+                        },
+                    },
+                })),
+            ]),
+            ..Default::default()
+        })),
+        ..Default::default()
+    })
+}
+
+pub fn routes() -> Vec<Route> {
+    vec![
+        Route {
+            response: Content::Json(version_schema),
+            ..route("get", "/", "Version of the api")
+        },
+        Route {
+            response: Content::Json(openapi_schema),
+            ..route("get", "/openapi.json", "This document")
+        },
+        Route {
+            query: Some(list_query_schema),
+            response: Content::Json(list_schema),
+            ..route("get", "/catalog/:account", "List the catalog")
+        },
+        Route {
+            body: Content::Json(schema::<SqlCatalogObject>),
+            response: Content::Json(schema::<SqlCatalogObjectDocument>),
+            ..route("post", "/catalog/:account", "Create a catalog object")
+        },
+        Route {
+            body: Content::Json(schema::<Vec<CatalogObjectBulkDocument<String>>>),
+            response: Content::Json(schema::<Vec<SqlCatalogObjectDocument>>),
+            ..route(
+                "post",
+                "/catalog/:account/_bulk",
+                "Create objects that reference each other by their ids in the request",
+            )
+        },
+        Route {
+            response: Content::Raw(&["text/csv"]),
+            ..route("get", "/catalog/:account/_csv", "Export the catalog as CSV")
+        },
+        Route {
+            query: Some(schema::<CsvImportOptions>),
+            body: Content::Raw(&["text/csv"]),
+            response: Content::Json(schema::<CsvImportReport<Id, Account>>),
+            ..route("post", "/catalog/:account/_csv", "Import a CSV sheet")
+        },
+        Route {
+            query: Some(schema::<FeedOptions>),
+            response: Content::Raw(&["application/rss+xml", "text/tab-separated-values"]),
+            ..route("get", "/catalog/:account/_feed.xml", "Product feed")
+        },
+        Route {
+            response: Content::Json(schema::<Vec<SqlStockLocationDocument>>),
+            ..route(
+                "get",
+                "/catalog/:account/_locations",
+                "List the stock locations",
+            )
+        },
+        Route {
+            body: Content::Json(schema::<StockLocation>),
+            response: Content::Json(schema::<SqlStockLocationDocument>),
+            ..route(
+                "post",
+                "/catalog/:account/_locations",
+                "Create a stock location",
+            )
+        },
+        Route {
+            response: Content::Json(schema::<Vec<SqlStockAlert>>),
+            ..route("get", "/catalog/:account/_alerts", "List the stock alerts")
+        },
+        Route {
+            query: Some(schema::<ListCatalogChangesOptions>),
+            response: Content::Json(schema::<Vec<SqlCatalogChange>>),
+            ..route("get", "/catalog/:account/_changes", "List the changes")
+        },
+        Route {
+            query: Some(schema::<ListAuditOptions>),
+            response: Content::Json(schema::<Vec<SqlAuditEntry>>),
+            ..route("get", "/catalog/:account/_audit", "List the audit log")
+        },
+        Route {
+            response: Content::Json(schema::<SqlCatalogArchive>),
+            admin: true,
+            ..route("get", "/catalog/:account/_export", "Export the account")
+        },
+        Route {
+            body: Content::Json(schema::<SqlCatalogArchive>),
+            response: Content::Json(schema::<CatalogImport<Id>>),
+            admin: true,
+            ..route(
+                "post",
+                "/catalog/:account/_import",
+                "Import an exported account",
+            )
+        },
+        Route {
+            response: Content::Raw(&["application/vnd.sqlite3"]),
+            admin: true,
+            ..route("get", "/_admin/backup", "Backup of the database")
+        },
+        Route {
+            response: Content::Raw(&["text/event-stream"]),
+            ..route(
+                "get",
+                "/catalog/:account/_stream",
+                "The changes as server-sent events",
+            )
+        },
+        Route {
+            response: Content::Raw(&["text/plain"]),
+            ..route("get", "/graphql", "The GraphQL schema")
+        },
+        Route {
+            body: Content::Json(schema::<GraphQLRequest>),
+            response: Content::Json(graphql_schema),
+            ..route("post", "/graphql", "Run a GraphQL query or mutation")
+        },
+        Route {
+            query: Some(schema::<GraphQLStreamOptions>),
+            response: Content::Raw(&["text/event-stream"]),
+            ..route(
+                "get",
+                "/graphql/_stream",
+                "A GraphQL subscription as server-sent events",
+            )
+        },
+        Route {
+            response: Content::Json(schema::<Vec<SqlWebhookSubscriptionDocument>>),
+            ..route("get", "/catalog/:account/_webhooks", "List the webhooks")
+        },
+        Route {
+            body: Content::Json(schema::<WebhookSubscription>),
+            response: Content::Json(schema::<SqlWebhookSubscriptionDocument>),
+            ..route("post", "/catalog/:account/_webhooks", "Subscribe a webhook")
+        },
+        Route {
+            response: Content::Json(schema::<SqlWebhookSubscriptionDocument>),
+            ..route("get", "/catalog/:account/_webhooks/:id", "Read a webhook")
+        },
+        Route {
+            body: Content::Json(schema::<WebhookSubscription>),
+            response: Content::Json(schema::<SqlWebhookSubscriptionDocument>),
+            ..route("put", "/catalog/:account/_webhooks/:id", "Update a webhook")
+        },
+        Route {
+            response: Content::Json(schema::<SqlWebhookSubscriptionDocument>),
+            ..route(
+                "delete",
+                "/catalog/:account/_webhooks/:id",
+                "Delete a webhook",
+            )
+        },
+        Route {
+            response: Content::Json(schema::<Vec<SqlWebhookDelivery>>),
+            ..route(
+                "get",
+                "/catalog/:account/_webhooks/:id/deliveries",
+                "List the deliveries of a webhook",
+            )
+        },
+        Route {
+            response: Content::Json(schema::<SqlStockLocationDocument>),
+            ..route(
+                "get",
+                "/catalog/:account/_locations/:id",
+                "Read a stock location",
+            )
+        },
+        Route {
+            body: Content::Json(schema::<StockLocation>),
+            response: Content::Json(schema::<SqlStockLocationDocument>),
+            ..route(
+                "put",
+                "/catalog/:account/_locations/:id",
+                "Update a stock location",
+            )
+        },
+        Route {
+            response: Content::Json(schema::<SqlCatalogObjectDocument>),
+            ..route(
+                "get",
+                "/catalog/:account/:id",
+                "Read a catalog object, as schema.org JSON-LD with `Accept: application/ld+json`",
+            )
+        },
+        Route {
+            body: Content::Json(schema::<SqlCatalogObject>),
+            response: Content::Json(schema::<SqlCatalogObjectDocument>),
+            ..route("put", "/catalog/:account/:id", "Update a catalog object")
+        },
+        Route {
+            body: Content::Raw(&[MERGE_PATCH_JSON]),
+            response: Content::Json(schema::<SqlCatalogObjectDocument>),
+            ..route(
+                "patch",
+                "/catalog/:account/:id",
+                "Merge a JSON merge patch into a catalog object, only while at the version of `If-Match` when given",
+            )
+        },
+        Route {
+            response: Content::Json(schema::<SqlCatalogObjectDocument>),
+            ..route("delete", "/catalog/:account/:id", "Delete a catalog object")
+        },
+        Route {
+            response: Content::Json(schema::<Vec<StockLevel<Id>>>),
+            ..route(
+                "get",
+                "/catalog/:account/:id/_stock",
+                "Stock of a variation by location",
+            )
+        },
+        Route {
+            query: Some(schema::<ListRevisionsOptions>),
+            response: Content::Json(revisions_schema),
+            ..route(
+                "get",
+                "/catalog/:account/:id/revisions",
+                "List the revisions of an object",
+            )
+        },
+        Route {
+            query: Some(schema::<DiffRevisionsOptions>),
+            response: Content::Json(schema::<CatalogRevisionDiff>),
+            ..route(
+                "get",
+                "/catalog/:account/:id/revisions/_diff",
+                "Changes between two revisions",
+            )
+        },
+        Route {
+            response: Content::Json(schema::<SqlCatalogRevision>),
+            ..route(
+                "get",
+                "/catalog/:account/:id/revisions/:revision",
+                "Read a revision",
+            )
+        },
+        Route {
+            response: Content::Json(schema::<SqlCatalogObjectDocument>),
+            ..route(
+                "post",
+                "/catalog/:account/:id/revisions/:revision/_restore",
+                "Write a revision back as the current object",
+            )
+        },
+        Route {
+            body: Content::Json(schema::<SQlCatalogCmd>),
+            response: Content::Json(success_schema),
+            ..route("post", "/catalog/:account/cmd", "Run an inventory command")
+        },
+    ]
+}
+
+pub fn schema_generator() -> SchemaGenerator {
+    SchemaSettings::openapi3().into_generator()
+}
+
+pub fn openapi() -> Value {
+    let mut gen = schema_generator();
+    let mut paths = Map::new();
+    for route in routes() {
+        let path = openapi_path(route.path);
+        let operation = operation(&route, &mut gen);
+        match paths.get_mut(&path) {
+            Some(Value::Object(item)) => {
+                item.insert(route.method.to_string(), operation);
+            }
+            _ => {
+                paths.insert(path, json!({ route.method: operation }));
+            }
+        }
+    }
+    json!({
+        "openapi": OPENAPI_VERSION,
+        "info": {
+            "title": "merchant",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": paths,
+        "components": {
+            "schemas": gen.definitions(),
+            "securitySchemes": {
+                "admin": { "type": "http", "scheme": "bearer" },
+            },
+        },
+    })
+}
+
+// `/catalog/:account` is `/catalog/{account}`
+pub fn openapi_path(path: &str) -> String {
+    path.split('/')
+        .map(|segment| match segment.strip_prefix(':') {
+            Some(name) => format!("{{{}}}", name),
+            None => segment.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn operation(route: &Route, gen: &mut SchemaGenerator) -> Value {
+    let mut parameters: Vec<Value> = route
+        .path
+        .split('/')
+        .filter_map(|segment| segment.strip_prefix(':'))
+        .map(|name| {
+            let schema = match name {
+                "account" => json!({ "type": "string" }),
+                _ => json!({ "type": "integer", "format": "uint32", "minimum": 0 }),
+            };
+            json!({ "name": name, "in": "path", "required": true, "schema": schema })
+        })
+        .collect();
+    if let Some(query) = route.query {
+        parameters.extend(query_parameters(query(gen), gen));
+    }
+
+    let mut operation = json!({
+        "summary": route.summary,
+        "parameters": parameters,
+        "responses": {
+            "200": response(&route.response, gen),
+            "default": {
+                "description": "the request failed",
+                "content": { PROBLEM_JSON: { "schema": error_schema(gen) } },
+            },
+        },
+    });
+    if let Some(content) = content(&route.body, gen) {
+        operation["requestBody"] = json!({ "required": true, "content": content });
+    }
+    if route.admin {
+        operation["security"] = json!([{ "admin": [] }]);
+    }
+    operation
+}
+
+fn response(body: &Content, gen: &mut SchemaGenerator) -> Value {
+    let mut response = json!({ "description": "ok" });
+    if let Some(content) = content(body, gen) {
+        response["content"] = content;
+    }
+    response
+}
+
+fn content(body: &Content, gen: &mut SchemaGenerator) -> Option<Value> {
+    match body {
+        Content::Empty => None,
+        Content::Json(schema) => Some(json!({ "application/json": { "schema": schema(gen) } })),
+        Content::Raw(media_types) => Some(Value::Object(
+            media_types
+                .iter()
+                .map(|media_type| (media_type.to_string(), json!({})))
+                .collect(),
+        )),
+    }
+}
+
+// a parameter per property of the query, with the flattened ones inline
+fn query_parameters(schema: Schema, gen: &SchemaGenerator) -> Vec<Value> {
+    let schema = match gen.dereference(&schema) {
+        Some(schema) => schema.clone().into_object(),
+        None => schema.into_object(),
+    };
+    let object = match schema.object {
+        Some(object) => object,
+        None => return vec![],
+    };
+    object
+        .properties
+        .into_iter()
+        .map(|(name, schema)| {
+            json!({
+                "name": name,
+                "in": "query",
+                "required": object.required.contains(&name),
+                "schema": schema,
+            })
+        })
+        .collect()
+}
+
+// the body of every error of the api
+fn error_schema(gen: &mut SchemaGenerator) -> Value {
+    json!(gen.subschema_for::<Problem>())
+}
@@ -0,0 +1,43 @@
+use std::sync::{Arc, Mutex};
+
+pub use async_channel::Receiver;
+use async_channel::{Sender, TrySendError};
+
+// events a subscriber can fall behind before it gets disconnected
+const SUBSCRIBER_CAPACITY: usize = 256;
+
+// In-process fan-out of events, every subscriber gets its own copy of each
+// published event. Slow subscribers are dropped instead of blocking publishers.
+#[derive(Clone)]
+pub struct Broadcast<T> {
+    subscribers: Arc<Mutex<Vec<Sender<T>>>>,
+}
+
+impl<T: Clone> Broadcast<T> {
+    pub fn new() -> Self {
+        Self {
+            subscribers: Arc::new(Mutex::new(vec![])),
+        }
+    }
+
+    pub fn subscribe(&self) -> Receiver<T> {
+        let (sender, receiver) = async_channel::bounded(SUBSCRIBER_CAPACITY);
+        self.subscribers.lock().unwrap().push(sender);
+        receiver
+    }
+
+    pub fn publish(&self, event: &T) {
+        self.subscribers.lock().unwrap().retain(|subscriber| {
+            match subscriber.try_send(event.clone()) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_)) | Err(TrySendError::Closed(_)) => false,
+            }
+        });
+    }
+}
+
+impl<T: Clone> Default for Broadcast<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
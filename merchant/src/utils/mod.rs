@@ -1 +1,7 @@
+pub mod broadcast;
+pub mod diff;
+pub mod patch;
 pub mod query;
+pub mod schema;
+#[cfg(feature = "server")]
+pub mod sqlite;
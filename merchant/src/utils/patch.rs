@@ -0,0 +1,27 @@
+use serde_json::Value;
+
+pub const MERGE_PATCH_JSON: &str = "application/merge-patch+json";
+
+// RFC 7396, the members of an object patch replace the ones of the target
+// and `null` removes them, any other patch replaces the target as a whole
+pub fn merge_patch(target: &mut Value, patch: &Value) {
+    let patch = match patch {
+        Value::Object(patch) => patch,
+        patch => {
+            *target = patch.clone();
+            return;
+        }
+    };
+    if !target.is_object() {
+        *target = Value::Object(Default::default());
+    }
+    if let Value::Object(target) = target {
+        for (key, value) in patch {
+            if value.is_null() {
+                target.remove(key);
+            } else {
+                merge_patch(target.entry(key.as_str()).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
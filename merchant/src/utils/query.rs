@@ -1,27 +1,42 @@
-use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::str::FromStr;
+
+use async_graphql::Enum;
+use schemars::JsonSchema;
+use serde::{de, Deserialize, Deserializer, Serialize};
 use serde_with::with_prefix;
 
+use super::schema::{Prefix, Prefixed};
+
 with_prefix!(order_by_prefix "order_by_");
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OrderByPrefix;
+
+impl Prefix for OrderByPrefix {
+    const PREFIX: &'static str = "order_by_";
+    const OPTIONAL: bool = true;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, JsonSchema, Enum)]
 pub enum Order {
     Asc,
     Desc,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 pub struct OrderBy<F> {
     pub field: F,
     pub direction: Order,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 pub struct Query<Opts, OrdF>
 where
     for<'a> OrdF: serde::Deserialize<'a> + Serialize,
 {
     pub limit: Option<u16>,
     #[serde(flatten, with = "order_by_prefix")]
+    #[schemars(with = "Prefixed<OrderBy<OrdF>, OrderByPrefix>")]
     pub order_by: Option<OrderBy<OrdF>>,
     #[serde(flatten)]
     pub options: Opts,
@@ -40,3 +55,38 @@ where
         }
     }
 }
+
+// An option flattened into the query. The query string gives it as a string
+// once flattened, json keeps its type, both are read.
+pub fn query_value<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + FromStr,
+    T::Err: Display,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Value<T> {
+        Typed(T),
+        Text(String),
+    }
+    match Option::<Value<T>>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(Value::Typed(value)) => Ok(Some(value)),
+        Some(Value::Text(text)) => text.parse().map(Some).map_err(de::Error::custom),
+    }
+}
+
+// a LIKE pattern matching the text anywhere in the value, its wildcards are
+// escaped with `\`
+pub fn contains_pattern(text: &str) -> String {
+    let mut pattern = String::from("%");
+    for c in text.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    pattern
+}
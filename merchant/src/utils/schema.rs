@@ -0,0 +1,154 @@
+use std::collections::BTreeSet;
+use std::marker::PhantomData;
+
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{InstanceType, Schema, SchemaObject, StringValidation, SubschemaValidation};
+use schemars::JsonSchema;
+use serde_json::Value;
+
+// the `serde_with::with_prefix!` module of a field, for its schema
+pub trait Prefix {
+    const PREFIX: &'static str;
+    // the field is an `Option`
+    const OPTIONAL: bool;
+}
+
+// The schema of a field flattened with a prefix, used as
+// `#[schemars(with = "Prefixed<T, P>")]` with `T` the type without `Option`.
+// Every variant of an enum ends up in the same object, a property the
+// variants type differently accepts any of them and only the properties of
+// every variant are required. The properties of an `Option` never are.
+pub struct Prefixed<T, P>(PhantomData<(T, P)>);
+
+impl<T: JsonSchema, P: Prefix> JsonSchema for Prefixed<T, P> {
+    fn is_referenceable() -> bool {
+        false
+    }
+
+    fn schema_name() -> String {
+        format!("{}{}", P::PREFIX, T::schema_name())
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        prefixed::<T>(gen, P::PREFIX, P::OPTIONAL)
+    }
+}
+
+// The schema of a map whose keys must not be blank, used as
+// `#[schemars(with = "NotBlankKeys<T>")]` with `T` the type of the field.
+pub struct NotBlankKeys<T>(PhantomData<T>);
+
+impl<T: JsonSchema> JsonSchema for NotBlankKeys<T> {
+    fn is_referenceable() -> bool {
+        false
+    }
+
+    fn schema_name() -> String {
+        T::schema_name()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        let mut schema = T::json_schema(gen).into_object();
+        schema.object().property_names = Some(Box::new(Schema::Object(SchemaObject {
+            instance_type: Some(InstanceType::String.into()),
+            string: Some(Box::new(StringValidation {
+                pattern: Some(r"\S".to_string()),
+                ..Default::default()
+            })),
+            ..Default::default()
+        })));
+        Schema::Object(schema)
+    }
+}
+
+fn prefixed<T: JsonSchema>(gen: &mut SchemaGenerator, prefix: &str, optional: bool) -> Schema {
+    let schema = T::json_schema(gen).into_object();
+    let variants: Vec<SchemaObject> = match schema.subschemas.as_ref() {
+        Some(subschemas) => match subschemas.one_of.as_ref().or(subschemas.any_of.as_ref()) {
+            Some(variants) => variants.iter().cloned().map(Schema::into_object).collect(),
+            None => vec![schema],
+        },
+        None => vec![schema],
+    };
+
+    let mut merged = SchemaObject {
+        instance_type: Some(InstanceType::Object.into()),
+        ..Default::default()
+    };
+    let mut required: Option<BTreeSet<String>> = None;
+    for mut variant in variants {
+        let object = variant.object();
+        for (name, property) in std::mem::take(&mut object.properties) {
+            let name = format!("{}{}", prefix, name);
+            let properties = &mut merged.object().properties;
+            let property = match properties.remove(&name) {
+                Some(other) => union(other, property),
+                None => property,
+            };
+            properties.insert(name, property);
+        }
+        let variant_required = object
+            .required
+            .iter()
+            .map(|name| format!("{}{}", prefix, name))
+            .collect();
+        required = Some(match required {
+            Some(required) => required.intersection(&variant_required).cloned().collect(),
+            None => variant_required,
+        });
+    }
+    if !optional {
+        merged.object().required = required.unwrap_or_default();
+    }
+    Schema::Object(merged)
+}
+
+// the tags of the variants are merged in a single enum
+fn union(a: Schema, b: Schema) -> Schema {
+    if a == b {
+        return a;
+    }
+    match (string_enum(&a), string_enum(&b)) {
+        (Some(mut values), Some(other)) => {
+            for value in other {
+                if !values.contains(&value) {
+                    values.push(value);
+                }
+            }
+            Schema::Object(SchemaObject {
+                instance_type: Some(InstanceType::String.into()),
+                enum_values: Some(values),
+                ..Default::default()
+            })
+        }
+        _ => {
+            let mut any_of = match a {
+                Schema::Object(SchemaObject {
+                    subschemas: Some(ref subschemas),
+                    ..
+                }) if subschemas.any_of.is_some() => subschemas.any_of.clone().unwrap_or_default(),
+                a => vec![a],
+            };
+            if !any_of.contains(&b) {
+                any_of.push(b);
+            }
+            Schema::Object(SchemaObject {
+                subschemas: Some(Box::new(SubschemaValidation {
+                    any_of: Some(any_of),
+                    ..Default::default()
+                })),
+                ..Default::default()
+            })
+        }
+    }
+}
+
+fn string_enum(schema: &Schema) -> Option<Vec<Value>> {
+    match schema {
+        Schema::Object(SchemaObject {
+            enum_values: Some(values),
+            ..
+        }) if values.iter().all(Value::is_string) => Some(values.clone()),
+        _ => None,
+    }
+}
@@ -0,0 +1,125 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteSynchronous};
+use sqlx::SqlitePool as Pool;
+
+type ConfigError = Box<dyn std::error::Error>;
+
+// How the SQLite database is opened. With WAL readers don't block the writer,
+// writers still go one at a time and wait up to `busy_timeout` for the lock.
+#[derive(Debug, Clone)]
+pub struct SqliteConfig {
+    pub max_connections: u32,
+    pub journal_mode: SqliteJournalMode,
+    pub busy_timeout: Duration,
+    pub synchronous: SqliteSynchronous,
+    pub statement_cache_capacity: usize,
+    pub busy_retry: BusyRetryPolicy,
+}
+
+impl Default for SqliteConfig {
+    fn default() -> Self {
+        Self {
+            max_connections: 4,
+            journal_mode: SqliteJournalMode::Wal,
+            busy_timeout: Duration::from_secs(5),
+            synchronous: SqliteSynchronous::Normal,
+            statement_cache_capacity: 100,
+            busy_retry: BusyRetryPolicy::default(),
+        }
+    }
+}
+
+impl SqliteConfig {
+    // the defaults overridden by the `SQLITE_*` variables that are set
+    pub fn from_env() -> Result<Self, ConfigError> {
+        let mut config = Self::default();
+        if let Some(value) = env_var("SQLITE_MAX_CONNECTIONS") {
+            config.max_connections = value.parse()?;
+        }
+        if let Some(value) = env_var("SQLITE_JOURNAL_MODE") {
+            config.journal_mode = value.parse()?;
+        }
+        if let Some(value) = env_var("SQLITE_BUSY_TIMEOUT_MS") {
+            config.busy_timeout = Duration::from_millis(value.parse()?);
+        }
+        if let Some(value) = env_var("SQLITE_SYNCHRONOUS") {
+            config.synchronous = value.parse()?;
+        }
+        if let Some(value) = env_var("SQLITE_STATEMENT_CACHE") {
+            config.statement_cache_capacity = value.parse()?;
+        }
+        if let Some(value) = env_var("SQLITE_BUSY_RETRIES") {
+            config.busy_retry.max_attempts = value.parse()?;
+        }
+        Ok(config)
+    }
+
+    pub async fn connect(&self, url: &str) -> Result<Pool, sqlx::Error> {
+        let options = SqliteConnectOptions::from_str(url)?
+            .journal_mode(self.journal_mode.clone())
+            .busy_timeout(self.busy_timeout)
+            .synchronous(self.synchronous.clone())
+            .statement_cache_capacity(self.statement_cache_capacity);
+        // every connection to an in-memory database opens a different one
+        let max_connections = if is_in_memory(url) {
+            1
+        } else {
+            self.max_connections.max(1)
+        };
+        SqlitePoolOptions::new()
+            .max_connections(max_connections)
+            .connect_with(options)
+            .await
+    }
+}
+
+fn env_var(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+fn is_in_memory(url: &str) -> bool {
+    url.contains(":memory:") || url.contains("mode=memory")
+}
+
+// Retries of a write that found the database locked. The busy timeout covers
+// most waits, but a transaction that read before writing can't wait for a
+// newer snapshot and has to start over.
+#[derive(Debug, Clone)]
+pub struct BusyRetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl BusyRetryPolicy {
+    // exponential backoff after the given number of failed attempts
+    pub fn delay(&self, attempts: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+        self.base_delay
+            .checked_mul(factor)
+            .map_or(self.max_delay, |delay| delay.min(self.max_delay))
+    }
+}
+
+impl Default for BusyRetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(500),
+        }
+    }
+}
+
+// SQLITE_BUSY and its extended codes (SQLITE_BUSY_SNAPSHOT, ...)
+pub fn is_busy(err: &sqlx::Error) -> bool {
+    match err {
+        sqlx::Error::Database(err) => err
+            .code()
+            .and_then(|code| code.parse::<i32>().ok())
+            .is_some_and(|code| code & 0xff == 5),
+        _ => false,
+    }
+}
@@ -0,0 +1,625 @@
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use sea_query::{Expr, Iden, Order as OrderSql, Query as Qsql, SqliteQueryBuilder as QueryBuilder};
+use sqlx::{types::Json, FromRow, Sqlite, SqlitePool as Pool, Transaction};
+
+use super::models::{
+    WebhookDelivery, WebhookDeliveryStatus, WebhookEvent, WebhookSubscription,
+    WebhookSubscriptionDocument,
+};
+use super::service::WebhookService;
+use crate::catalog::backend::{change_alerts, Account, Id};
+use crate::catalog::service::CatalogError;
+
+sea_query::sea_query_driver_sqlite!();
+use sea_query_driver_sqlite::bind_query_as;
+
+pub type SqlWebhookSubscriptionDocument = WebhookSubscriptionDocument<Id, Account>;
+pub type SqlWebhookDelivery = WebhookDelivery<Id, Account>;
+
+// how many due deliveries are attempted on every pass of the worker
+const DUE_DELIVERIES_BATCH: u64 = 50;
+
+// The first subscription of an account starts its cursor at the last change,
+// the older ones aren't sent. The cursor of an account that had webhooks
+// before is moved too, nothing queued it meanwhile.
+const START_CURSOR: &str = "INSERT INTO webhook_cursors (account, sequence)
+    SELECT $1, COALESCE(MAX(sequence), 0) FROM catalog_changes WHERE account = $1
+    ON CONFLICT (account) DO UPDATE SET sequence = excluded.sequence
+    WHERE NOT EXISTS (SELECT 1 FROM webhook_subscriptions WHERE account = excluded.account)";
+const MOVE_CURSOR: &str =
+    "UPDATE webhook_cursors SET sequence = MAX(sequence, $1) WHERE account = $2";
+// only the accounts with webhooks are followed
+const LIST_CURSORS: &str = "SELECT account, sequence FROM webhook_cursors
+    WHERE account IN (SELECT account FROM webhook_subscriptions) ORDER BY account";
+
+#[derive(Clone)]
+pub struct WebhookSQLService {
+    pool: Pool,
+    private_targets: bool,
+}
+
+impl WebhookSQLService {
+    pub fn new(pool: Pool) -> Self {
+        Self {
+            pool,
+            private_targets: false,
+        }
+    }
+
+    // lets the webhooks reach loopback and private addresses, for receivers
+    // running next to the server in development and the tests
+    pub fn with_private_targets(mut self) -> Self {
+        self.private_targets = true;
+        self
+    }
+
+    // checked on every registration and again before every delivery, the
+    // host may resolve elsewhere since
+    pub async fn check_target(
+        &self,
+        subscription: &WebhookSubscription,
+    ) -> Result<(), CatalogError> {
+        subscription.validate()?;
+        if self.private_targets {
+            return Ok(());
+        }
+        subscription.check_public().await
+    }
+
+    // pending deliveries whose next attempt is due, along with their subscription
+    pub async fn due_deliveries(
+        &self,
+    ) -> Result<Vec<(SqlWebhookDelivery, SqlWebhookSubscriptionDocument)>, CatalogError> {
+        let (sql, values) = Qsql::select()
+            .expr(Expr::asterisk())
+            .from(WebhookDeliverySchema::Table)
+            .and_where(
+                Expr::col(WebhookDeliverySchema::Status)
+                    .eq(to_text(WebhookDeliveryStatus::Pending)?),
+            )
+            .and_where(Expr::cust(
+                format!(
+                    "{} <= CURRENT_TIMESTAMP",
+                    WebhookDeliverySchema::NextAttemptAt.to_string()
+                )
+                .as_str(),
+            ))
+            .order_by(WebhookDeliverySchema::NextAttemptAt, OrderSql::Asc)
+            .limit(DUE_DELIVERIES_BATCH)
+            .build(QueryBuilder);
+
+        let mut pool = self.pool.acquire().await.map_err(CatalogError::database)?;
+
+        let deliveries: Vec<WebhookDeliveryRow> = bind_query_as(sqlx::query_as(&sql), &values)
+            .fetch_all(&mut pool)
+            .await
+            .map_err(CatalogError::database)?;
+        drop(pool);
+
+        let mut due = vec![];
+        for delivery in deliveries {
+            let delivery: SqlWebhookDelivery = delivery.try_into()?;
+            match self
+                .read_subscription(&delivery.account, &delivery.subscription_id)
+                .await
+            {
+                Ok(subscription) => due.push((delivery, subscription)),
+                // the subscription was removed after the event was queued
+                Err(CatalogError::CatalogEntryNotFound(_)) => {
+                    self.record_attempt(
+                        &delivery,
+                        WebhookDeliveryStatus::Failed,
+                        None,
+                        Some("subscription removed".to_string()),
+                        None,
+                    )
+                    .await?;
+                }
+                // the others are still delivered
+                Err(err) => println!(
+                    "the subscription of the delivery {} couldn't be read {:?}",
+                    delivery.id, err
+                ),
+            }
+        }
+        Ok(due)
+    }
+
+    // where the worker goes on in the change log of every account with webhooks
+    pub async fn cursors(&self) -> Result<Vec<(Account, i64)>, CatalogError> {
+        let mut pool = self.pool.acquire().await.map_err(CatalogError::database)?;
+
+        sqlx::query_as(LIST_CURSORS)
+            .fetch_all(&mut pool)
+            .await
+            .map_err(CatalogError::database)
+    }
+
+    // queues a change of the log, with the low stock alerts it raised, and
+    // moves the cursor of the account past it, a change is never queued
+    // twice nor skipped
+    pub async fn enqueue_change(
+        &self,
+        account: &Account,
+        sequence: i64,
+        event: WebhookEvent,
+        payload: &serde_json::Value,
+    ) -> Result<Vec<SqlWebhookDelivery>, CatalogError> {
+        let subscriptions = self.list_subscriptions(account).await?;
+
+        let mut tx = self.pool.begin().await.map_err(CatalogError::database)?;
+        let mut deliveries =
+            insert_deliveries(&mut tx, account, &subscriptions, event, payload).await?;
+        for alert in change_alerts(&mut tx, account, sequence).await? {
+            let payload = serde_json::to_value(alert).map_err(CatalogError::mapping)?;
+            deliveries.extend(
+                insert_deliveries(
+                    &mut tx,
+                    account,
+                    &subscriptions,
+                    WebhookEvent::LowStock,
+                    &payload,
+                )
+                .await?,
+            );
+        }
+        sqlx::query(MOVE_CURSOR)
+            .bind(sequence)
+            .bind(account)
+            .execute(&mut tx)
+            .await
+            .map_err(CatalogError::database)?;
+        tx.commit().await.map_err(CatalogError::database)?;
+
+        Ok(deliveries)
+    }
+
+    pub async fn record_attempt(
+        &self,
+        delivery: &SqlWebhookDelivery,
+        status: WebhookDeliveryStatus,
+        response_status: Option<u16>,
+        error: Option<String>,
+        next_attempt_at: Option<NaiveDateTime>,
+    ) -> Result<SqlWebhookDelivery, CatalogError> {
+        let (sql, _) = Qsql::update()
+            .table(WebhookDeliverySchema::Table)
+            .value(WebhookDeliverySchema::Status, "-1".into())
+            .value(WebhookDeliverySchema::ResponseStatus, "-1".into())
+            .value(WebhookDeliverySchema::Error, "-1".into())
+            .value(WebhookDeliverySchema::NextAttemptAt, "-1".into())
+            .value_expr(
+                WebhookDeliverySchema::Attempts,
+                Expr::cust(format!("{} + 1", WebhookDeliverySchema::Attempts.to_string()).as_str()),
+            )
+            .value_expr(
+                WebhookDeliverySchema::Version,
+                Expr::cust("CURRENT_TIMESTAMP"),
+            )
+            .and_where(Expr::col(WebhookDeliverySchema::Id).eq("-1"))
+            .returning(Qsql::select().expr(Expr::asterisk()).take())
+            .build(QueryBuilder);
+
+        let mut pool = self.pool.acquire().await.map_err(CatalogError::database)?;
+
+        let result: WebhookDeliveryRow = sqlx::query_as(sql.as_str())
+            .bind(to_text(status)?)
+            .bind(response_status)
+            .bind(error)
+            .bind(next_attempt_at)
+            .bind(delivery.id)
+            .fetch_one(&mut pool)
+            .await
+            .map_err(CatalogError::database)?;
+
+        result.try_into()
+    }
+}
+
+#[async_trait]
+impl WebhookService for WebhookSQLService {
+    type Id = Id;
+    type Account = Account;
+
+    async fn create_subscription(
+        &self,
+        account: &Account,
+        subscription: &WebhookSubscription,
+    ) -> Result<SqlWebhookSubscriptionDocument, CatalogError> {
+        self.check_target(subscription).await?;
+        let (sql, _) = Qsql::insert()
+            .into_table(WebhookSubscriptionSchema::Table)
+            .columns(vec![
+                WebhookSubscriptionSchema::Id,
+                WebhookSubscriptionSchema::Account,
+                WebhookSubscriptionSchema::Url,
+                WebhookSubscriptionSchema::Events,
+                WebhookSubscriptionSchema::Secret,
+                WebhookSubscriptionSchema::Enabled,
+            ])
+            .exprs_panic(vec![
+                Expr::value("$1"),
+                Expr::value("$2"),
+                Expr::value("$3"),
+                Expr::value("$4"),
+                Expr::value("$5"),
+                Expr::value("$6"),
+            ])
+            .returning(Qsql::select().expr(Expr::asterisk()).take())
+            .build(QueryBuilder);
+
+        let mut tx = self.pool.begin().await.map_err(CatalogError::database)?;
+
+        sqlx::query(START_CURSOR)
+            .bind(account)
+            .execute(&mut tx)
+            .await
+            .map_err(CatalogError::database)?;
+        let result: WebhookSubscriptionRow = sqlx::query_as(sql.as_str())
+            .bind(rand::random::<Id>())
+            .bind(account)
+            .bind(&subscription.url)
+            .bind(Json(&subscription.events))
+            .bind(&subscription.secret)
+            .bind(subscription.enabled)
+            .fetch_one(&mut tx)
+            .await
+            .map_err(CatalogError::database)?;
+        tx.commit().await.map_err(CatalogError::database)?;
+
+        Ok(result.into())
+    }
+
+    async fn read_subscription(
+        &self,
+        account: &Account,
+        id: &Id,
+    ) -> Result<SqlWebhookSubscriptionDocument, CatalogError> {
+        let (sql, values) = Qsql::select()
+            .expr(Expr::asterisk())
+            .from(WebhookSubscriptionSchema::Table)
+            .and_where(Expr::col(WebhookSubscriptionSchema::Id).eq(*id))
+            .and_where(Expr::col(WebhookSubscriptionSchema::Account).eq(account.to_string()))
+            .build(QueryBuilder);
+
+        let mut pool = self.pool.acquire().await.map_err(CatalogError::database)?;
+
+        let result: WebhookSubscriptionRow = bind_query_as(sqlx::query_as(&sql), &values)
+            .fetch_one(&mut pool)
+            .await
+            .map_err(|err| match err {
+                sqlx::Error::RowNotFound => CatalogError::CatalogEntryNotFound(id.to_string()),
+                err => CatalogError::database(err),
+            })?;
+
+        Ok(result.into())
+    }
+
+    async fn update_subscription(
+        &self,
+        account: &Account,
+        id: &Id,
+        subscription: &WebhookSubscription,
+    ) -> Result<SqlWebhookSubscriptionDocument, CatalogError> {
+        self.check_target(subscription).await?;
+        let (sql, _) = Qsql::update()
+            .table(WebhookSubscriptionSchema::Table)
+            .value(WebhookSubscriptionSchema::Url, "-1".into())
+            .value(WebhookSubscriptionSchema::Events, "-1".into())
+            .value(WebhookSubscriptionSchema::Secret, "-1".into())
+            .value(WebhookSubscriptionSchema::Enabled, "-1".into())
+            .value_expr(
+                WebhookSubscriptionSchema::Version,
+                Expr::cust("CURRENT_TIMESTAMP"),
+            )
+            .and_where(Expr::col(WebhookSubscriptionSchema::Account).eq("-1"))
+            .and_where(Expr::col(WebhookSubscriptionSchema::Id).eq("-1"))
+            .returning(Qsql::select().expr(Expr::asterisk()).take())
+            .build(QueryBuilder);
+
+        let mut pool = self.pool.acquire().await.map_err(CatalogError::database)?;
+
+        let result: WebhookSubscriptionRow = sqlx::query_as(sql.as_str())
+            .bind(&subscription.url)
+            .bind(Json(&subscription.events))
+            .bind(&subscription.secret)
+            .bind(subscription.enabled)
+            .bind(account)
+            .bind(id)
+            .fetch_one(&mut pool)
+            .await
+            .map_err(|err| match err {
+                sqlx::Error::RowNotFound => CatalogError::CatalogEntryNotFound(id.to_string()),
+                err => CatalogError::database(err),
+            })?;
+
+        Ok(result.into())
+    }
+
+    async fn delete_subscription(
+        &self,
+        account: &Account,
+        id: &Id,
+    ) -> Result<SqlWebhookSubscriptionDocument, CatalogError> {
+        let (sql, values) = Qsql::delete()
+            .from_table(WebhookSubscriptionSchema::Table)
+            .and_where(Expr::col(WebhookSubscriptionSchema::Id).eq(*id))
+            .and_where(Expr::col(WebhookSubscriptionSchema::Account).eq(account.to_string()))
+            .returning(Qsql::select().expr(Expr::asterisk()).take())
+            .build(QueryBuilder);
+
+        let mut pool = self.pool.acquire().await.map_err(CatalogError::database)?;
+
+        let result: WebhookSubscriptionRow = bind_query_as(sqlx::query_as(&sql), &values)
+            .fetch_one(&mut pool)
+            .await
+            .map_err(|err| match err {
+                sqlx::Error::RowNotFound => CatalogError::CatalogEntryNotFound(id.to_string()),
+                err => CatalogError::database(err),
+            })?;
+
+        Ok(result.into())
+    }
+
+    async fn list_subscriptions(
+        &self,
+        account: &Account,
+    ) -> Result<Vec<SqlWebhookSubscriptionDocument>, CatalogError> {
+        let (sql, values) = Qsql::select()
+            .expr(Expr::asterisk())
+            .from(WebhookSubscriptionSchema::Table)
+            .and_where(Expr::col(WebhookSubscriptionSchema::Account).eq(account.to_string()))
+            .order_by(WebhookSubscriptionSchema::CreatedAt, OrderSql::Asc)
+            .build(QueryBuilder);
+
+        let mut pool = self.pool.acquire().await.map_err(CatalogError::database)?;
+
+        let result: Vec<WebhookSubscriptionRow> = bind_query_as(sqlx::query_as(&sql), &values)
+            .fetch_all(&mut pool)
+            .await
+            .map_err(CatalogError::database)?;
+
+        Ok(result.into_iter().map(|row| row.into()).collect())
+    }
+
+    async fn enqueue(
+        &self,
+        account: &Account,
+        event: WebhookEvent,
+        payload: &serde_json::Value,
+    ) -> Result<Vec<SqlWebhookDelivery>, CatalogError> {
+        let subscriptions = self.list_subscriptions(account).await?;
+
+        let mut tx = self.pool.begin().await.map_err(CatalogError::database)?;
+        let deliveries =
+            insert_deliveries(&mut tx, account, &subscriptions, event, payload).await?;
+        tx.commit().await.map_err(CatalogError::database)?;
+
+        Ok(deliveries)
+    }
+
+    async fn list_deliveries(
+        &self,
+        account: &Account,
+        subscription_id: &Id,
+    ) -> Result<Vec<SqlWebhookDelivery>, CatalogError> {
+        let (sql, values) = Qsql::select()
+            .expr(Expr::asterisk())
+            .from(WebhookDeliverySchema::Table)
+            .and_where(Expr::col(WebhookDeliverySchema::Account).eq(account.to_string()))
+            .and_where(Expr::col(WebhookDeliverySchema::SubscriptionId).eq(*subscription_id))
+            .order_by(WebhookDeliverySchema::CreatedAt, OrderSql::Desc)
+            .build(QueryBuilder);
+
+        let mut pool = self.pool.acquire().await.map_err(CatalogError::database)?;
+
+        let result: Vec<WebhookDeliveryRow> = bind_query_as(sqlx::query_as(&sql), &values)
+            .fetch_all(&mut pool)
+            .await
+            .map_err(CatalogError::database)?;
+
+        result.into_iter().map(|row| row.try_into()).collect()
+    }
+}
+
+// a pending delivery for every subscription that accepts the event
+async fn insert_deliveries(
+    tx: &mut Transaction<'_, Sqlite>,
+    account: &Account,
+    subscriptions: &[SqlWebhookSubscriptionDocument],
+    event: WebhookEvent,
+    payload: &serde_json::Value,
+) -> Result<Vec<SqlWebhookDelivery>, CatalogError> {
+    let (sql, _) = Qsql::insert()
+        .into_table(WebhookDeliverySchema::Table)
+        .columns(vec![
+            WebhookDeliverySchema::Id,
+            WebhookDeliverySchema::Account,
+            WebhookDeliverySchema::SubscriptionId,
+            WebhookDeliverySchema::Event,
+            WebhookDeliverySchema::Payload,
+            WebhookDeliverySchema::Status,
+            WebhookDeliverySchema::NextAttemptAt,
+        ])
+        .exprs_panic(vec![
+            Expr::value("$1"),
+            Expr::value("$2"),
+            Expr::value("$3"),
+            Expr::value("$4"),
+            Expr::value("$5"),
+            Expr::value("$6"),
+            Expr::cust("CURRENT_TIMESTAMP"),
+        ])
+        .returning(Qsql::select().expr(Expr::asterisk()).take())
+        .build(QueryBuilder);
+
+    let mut deliveries = vec![];
+    for subscription in subscriptions
+        .iter()
+        .filter(|document| document.subscription.accepts(event))
+    {
+        let delivery: WebhookDeliveryRow = sqlx::query_as(sql.as_str())
+            .bind(rand::random::<Id>())
+            .bind(account)
+            .bind(subscription.id)
+            .bind(to_text(event)?)
+            .bind(Json(payload))
+            .bind(to_text(WebhookDeliveryStatus::Pending)?)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(CatalogError::database)?;
+        deliveries.push(delivery.try_into()?);
+    }
+    Ok(deliveries)
+}
+
+fn to_text<T: serde::Serialize>(value: T) -> Result<String, CatalogError> {
+    match serde_json::to_value(value).map_err(CatalogError::mapping)? {
+        serde_json::Value::String(text) => Ok(text),
+        other => Err(CatalogError::mapping(format!(
+            "{} isn't a variant name",
+            other
+        ))),
+    }
+}
+
+#[derive(Debug, FromRow)]
+struct WebhookSubscriptionRow {
+    id: Id,
+    account: String,
+    url: String,
+    events: Json<Vec<WebhookEvent>>,
+    secret: String,
+    enabled: bool,
+    version: NaiveDateTime,
+    created_at: NaiveDateTime,
+}
+
+impl From<WebhookSubscriptionRow> for SqlWebhookSubscriptionDocument {
+    fn from(row: WebhookSubscriptionRow) -> Self {
+        SqlWebhookSubscriptionDocument {
+            id: row.id,
+            account: row.account,
+            version: row.version,
+            created_at: row.created_at,
+            subscription: WebhookSubscription {
+                url: row.url,
+                events: row.events.0,
+                secret: row.secret,
+                enabled: row.enabled,
+            },
+        }
+    }
+}
+
+#[derive(Debug, FromRow)]
+struct WebhookDeliveryRow {
+    id: Id,
+    account: String,
+    subscription_id: Id,
+    event: String,
+    payload: Json<serde_json::Value>,
+    status: String,
+    attempts: u32,
+    response_status: Option<u16>,
+    error: Option<String>,
+    next_attempt_at: Option<NaiveDateTime>,
+    version: NaiveDateTime,
+    created_at: NaiveDateTime,
+}
+
+impl TryFrom<WebhookDeliveryRow> for SqlWebhookDelivery {
+    type Error = CatalogError;
+
+    fn try_from(row: WebhookDeliveryRow) -> Result<Self, Self::Error> {
+        Ok(SqlWebhookDelivery {
+            id: row.id,
+            account: row.account,
+            subscription_id: row.subscription_id,
+            event: serde_json::from_value(row.event.into()).map_err(CatalogError::mapping)?,
+            payload: row.payload.0,
+            status: serde_json::from_value(row.status.into()).map_err(CatalogError::mapping)?,
+            attempts: row.attempts,
+            response_status: row.response_status,
+            error: row.error,
+            next_attempt_at: row.next_attempt_at,
+            version: row.version,
+            created_at: row.created_at,
+        })
+    }
+}
+
+pub enum WebhookSubscriptionSchema {
+    Table,
+    Id,
+    Account,
+    Url,
+    Events,
+    Secret,
+    Enabled,
+    Version,
+    CreatedAt,
+}
+
+impl Iden for WebhookSubscriptionSchema {
+    fn unquoted(&self, s: &mut dyn std::fmt::Write) {
+        write!(
+            s,
+            "{}",
+            match self {
+                Self::Table => "webhook_subscriptions",
+                Self::Id => "id",
+                Self::Account => "account",
+                Self::Url => "url",
+                Self::Events => "events",
+                Self::Secret => "secret",
+                Self::Enabled => "enabled",
+                Self::Version => "version",
+                Self::CreatedAt => "created_at",
+            }
+        )
+        .unwrap();
+    }
+}
+
+pub enum WebhookDeliverySchema {
+    Table,
+    Id,
+    Account,
+    SubscriptionId,
+    Event,
+    Payload,
+    Status,
+    Attempts,
+    ResponseStatus,
+    Error,
+    NextAttemptAt,
+    Version,
+    CreatedAt,
+}
+
+impl Iden for WebhookDeliverySchema {
+    fn unquoted(&self, s: &mut dyn std::fmt::Write) {
+        write!(
+            s,
+            "{}",
+            match self {
+                Self::Table => "webhook_deliveries",
+                Self::Id => "id",
+                Self::Account => "account",
+                Self::SubscriptionId => "subscription_id",
+                Self::Event => "event",
+                Self::Payload => "payload",
+                Self::Status => "status",
+                Self::Attempts => "attempts",
+                Self::ResponseStatus => "response_status",
+                Self::Error => "error",
+                Self::NextAttemptAt => "next_attempt_at",
+                Self::Version => "version",
+                Self::CreatedAt => "created_at",
+            }
+        )
+        .unwrap();
+    }
+}
@@ -0,0 +1,4 @@
+pub mod backend;
+pub mod models;
+pub mod service;
+pub mod worker;
@@ -0,0 +1,150 @@
+use async_std::net::ToSocketAddrs;
+use chrono::NaiveDateTime;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use surf::http::url::Host;
+
+use crate::catalog::service::CatalogError;
+use crate::catalog::validation::Violation;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, JsonSchema)]
+pub enum WebhookEvent {
+    CatalogCreated,
+    CatalogUpdated,
+    CatalogDeleted,
+    InventoryChanged,
+    LowStock,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, JsonSchema)]
+pub struct WebhookSubscription {
+    pub url: String,
+    // an empty filter receives every event
+    #[serde(default)]
+    pub events: Vec<WebhookEvent>,
+    // used to sign the payloads, it's never sent back
+    #[serde(skip_serializing)]
+    pub secret: String,
+    pub enabled: bool,
+}
+
+impl WebhookSubscription {
+    pub fn accepts(&self, event: WebhookEvent) -> bool {
+        self.enabled && (self.events.is_empty() || self.events.contains(&event))
+    }
+
+    // the deliveries are posted to the url, it must be http or https with a host
+    pub fn validate(&self) -> Result<(), CatalogError> {
+        let valid = surf::Url::parse(&self.url).is_ok_and(|url| {
+            matches!(url.scheme(), "http" | "https")
+                && url.host_str().is_some_and(|host| !host.is_empty())
+        });
+        if valid {
+            return Ok(());
+        }
+        Err(CatalogError::ValidationFailed(vec![Violation::new(
+            "/url",
+            "http_url",
+            "must be an http or https url with a host",
+        )]))
+    }
+
+    // Resolves the host of the url, every address it resolves to must be
+    // public. Whoever registers a webhook can't have the server post to its
+    // own network.
+    pub async fn check_public(&self) -> Result<(), CatalogError> {
+        let not_public = || {
+            CatalogError::ValidationFailed(vec![Violation::new(
+                "/url",
+                "public_url",
+                "must only resolve to public addresses",
+            )])
+        };
+        let url = surf::Url::parse(&self.url).map_err(|_| not_public())?;
+        let port = url.port_or_known_default().unwrap_or_default();
+        let addresses: Vec<IpAddr> = match url.host() {
+            Some(Host::Ipv4(ip)) => vec![ip.into()],
+            Some(Host::Ipv6(ip)) => vec![ip.into()],
+            Some(Host::Domain(domain)) => (domain, port)
+                .to_socket_addrs()
+                .await
+                .map_err(|_| not_public())?
+                .map(|address| address.ip())
+                .collect(),
+            None => vec![],
+        };
+        if addresses.is_empty() || !addresses.into_iter().all(is_public) {
+            return Err(not_public());
+        }
+        Ok(())
+    }
+}
+
+// loopback, private, link-local (cloud metadata), shared and other special
+// ranges aren't public
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_v4(ip),
+            None => is_public_v6(ip),
+        },
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [first, second, ..] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        // 0.0.0.0/8, 100.64.0.0/10 and 240.0.0.0/4
+        || first == 0
+        || (first == 100 && (64..128).contains(&second))
+        || first >= 240)
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        || ip.is_unique_local()
+        || ip.is_unicast_link_local())
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct WebhookSubscriptionDocument<Id, Account> {
+    pub id: Id,
+    pub account: Account,
+    pub version: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+    #[serde(flatten)]
+    pub subscription: WebhookSubscription,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, JsonSchema)]
+pub enum WebhookDeliveryStatus {
+    Pending,
+    Delivered,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct WebhookDelivery<Id, Account> {
+    pub id: Id,
+    pub account: Account,
+    pub subscription_id: Id,
+    pub event: WebhookEvent,
+    pub payload: serde_json::Value,
+    pub status: WebhookDeliveryStatus,
+    pub attempts: u32,
+    pub response_status: Option<u16>,
+    pub error: Option<String>,
+    pub next_attempt_at: Option<NaiveDateTime>,
+    pub version: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+}
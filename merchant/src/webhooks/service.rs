@@ -0,0 +1,56 @@
+use async_trait::async_trait;
+
+use super::models::{
+    WebhookDelivery, WebhookEvent, WebhookSubscription, WebhookSubscriptionDocument,
+};
+use crate::catalog::service::CatalogError;
+
+#[async_trait]
+pub trait WebhookService {
+    type Id;
+    type Account;
+
+    async fn create_subscription(
+        &self,
+        account: &Self::Account,
+        subscription: &WebhookSubscription,
+    ) -> Result<WebhookSubscriptionDocument<Self::Id, Self::Account>, CatalogError>;
+
+    async fn read_subscription(
+        &self,
+        account: &Self::Account,
+        id: &Self::Id,
+    ) -> Result<WebhookSubscriptionDocument<Self::Id, Self::Account>, CatalogError>;
+
+    async fn update_subscription(
+        &self,
+        account: &Self::Account,
+        id: &Self::Id,
+        subscription: &WebhookSubscription,
+    ) -> Result<WebhookSubscriptionDocument<Self::Id, Self::Account>, CatalogError>;
+
+    async fn delete_subscription(
+        &self,
+        account: &Self::Account,
+        id: &Self::Id,
+    ) -> Result<WebhookSubscriptionDocument<Self::Id, Self::Account>, CatalogError>;
+
+    async fn list_subscriptions(
+        &self,
+        account: &Self::Account,
+    ) -> Result<Vec<WebhookSubscriptionDocument<Self::Id, Self::Account>>, CatalogError>;
+
+    // queues the event for every subscription of the account that accepts it
+    async fn enqueue(
+        &self,
+        account: &Self::Account,
+        event: WebhookEvent,
+        payload: &serde_json::Value,
+    ) -> Result<Vec<WebhookDelivery<Self::Id, Self::Account>>, CatalogError>;
+
+    async fn list_deliveries(
+        &self,
+        account: &Self::Account,
+        subscription_id: &Self::Id,
+    ) -> Result<Vec<WebhookDelivery<Self::Id, Self::Account>>, CatalogError>;
+}
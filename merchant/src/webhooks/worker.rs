@@ -0,0 +1,268 @@
+use async_std::future::timeout;
+use chrono::{NaiveDateTime, Utc};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::time::Duration;
+
+use super::backend::{SqlWebhookDelivery, SqlWebhookSubscriptionDocument, WebhookSQLService};
+use super::models::{WebhookDeliveryStatus, WebhookEvent};
+use crate::catalog::backend::{Account, Id, SqlCatalogChange};
+use crate::catalog::models::CatalogChangeOperation;
+use crate::catalog::service::{
+    CatalogChangeFeed, CatalogError, CatalogService, ListCatalogChangesOptions,
+};
+use crate::utils::sqlite::BusyRetryPolicy;
+
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+// unix time the delivery was signed at, it's part of the signature
+pub const TIMESTAMP_HEADER: &str = "X-Webhook-Timestamp";
+pub const EVENT_HEADER: &str = "X-Webhook-Event";
+pub const DELIVERY_HEADER: &str = "X-Webhook-Delivery";
+
+// the backoff of the deliveries that failed, an hour apart at most
+pub const DELIVERY_RETRY: BusyRetryPolicy = BusyRetryPolicy {
+    max_attempts: 8,
+    base_delay: Duration::from_secs(30),
+    max_delay: Duration::from_secs(60 * 60),
+};
+
+// how many changes of an account are read from the log at once
+const CHANGES_BATCH: u16 = 100;
+
+// Receivers refuse the deliveries whose timestamp is further than this from
+// their clock, a captured delivery can't be replayed once it's past.
+pub const SIGNATURE_TOLERANCE: Duration = Duration::from_secs(5 * 60);
+
+fn signature_mac(secret: &str, timestamp: i64, payload: &[u8]) -> Hmac<Sha256> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key size");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(payload);
+    mac
+}
+
+// hex encoded HMAC-SHA256 of `timestamp.payload`, sent in the signature header
+// as `sha256=<hex>` along the timestamp header
+pub fn sign(secret: &str, timestamp: i64, payload: &[u8]) -> String {
+    hex::encode(
+        signature_mac(secret, timestamp, payload)
+            .finalize()
+            .into_bytes(),
+    )
+}
+
+// what receivers check, the signature of the payload at the timestamp and the
+// timestamp within the tolerance of `now`
+pub fn verify(secret: &str, timestamp: i64, payload: &[u8], signature: &str, now: i64) -> bool {
+    let signature = match signature
+        .strip_prefix("sha256=")
+        .and_then(|signature| hex::decode(signature).ok())
+    {
+        Some(signature) => signature,
+        None => return false,
+    };
+    now.abs_diff(timestamp) <= SIGNATURE_TOLERANCE.as_secs()
+        && signature_mac(secret, timestamp, payload)
+            .verify_slice(&signature)
+            .is_ok()
+}
+
+pub fn event_for(change: &SqlCatalogChange) -> WebhookEvent {
+    match change.operation {
+        CatalogChangeOperation::Created => WebhookEvent::CatalogCreated,
+        CatalogChangeOperation::Updated => WebhookEvent::CatalogUpdated,
+        CatalogChangeOperation::Deleted => WebhookEvent::CatalogDeleted,
+        CatalogChangeOperation::Command => WebhookEvent::InventoryChanged,
+    }
+}
+
+#[derive(Clone)]
+pub struct WebhookWorker {
+    service: WebhookSQLService,
+    client: surf::Client,
+    policy: BusyRetryPolicy,
+}
+
+impl WebhookWorker {
+    pub fn new(service: WebhookSQLService, policy: BusyRetryPolicy) -> Self {
+        let client: surf::Client = surf::Config::new()
+            .set_timeout(Some(Duration::from_secs(10)))
+            .try_into()
+            .expect("valid http client config");
+        Self {
+            service,
+            client,
+            policy,
+        }
+    }
+
+    pub async fn enqueue_change(
+        &self,
+        change: &SqlCatalogChange,
+    ) -> Result<Vec<SqlWebhookDelivery>, CatalogError> {
+        let payload = serde_json::to_value(change).map_err(CatalogError::mapping)?;
+        self.service
+            .enqueue_change(
+                &change.account,
+                change.sequence,
+                event_for(change),
+                &payload,
+            )
+            .await
+    }
+
+    // queues the changes of the log after the cursor of every account with
+    // webhooks, returns how many were queued. An account that fails is left
+    // for the next pass, the others go on.
+    pub async fn catch_up<S>(&self, catalog: &S) -> Result<usize, CatalogError>
+    where
+        S: CatalogChangeFeed<Account = Account> + CatalogService<Id = Id>,
+    {
+        let mut queued = 0;
+        for (account, mut sequence) in self.service.cursors().await? {
+            loop {
+                let options = ListCatalogChangesOptions {
+                    since: Some(sequence),
+                    limit: Some(CHANGES_BATCH),
+                };
+                let changes = match catalog.changes(&account, &options).await {
+                    Ok(changes) => changes,
+                    Err(err) => {
+                        println!("the changes of {} couldn't be read {:?}", account, err);
+                        break;
+                    }
+                };
+                for change in &changes {
+                    if let Err(err) = self.enqueue_change(change).await {
+                        println!("the change couldn't be queued for webhooks {:?}", err);
+                        break;
+                    }
+                    sequence = change.sequence;
+                    queued += 1;
+                }
+                if changes.last().map(|change| change.sequence) != Some(sequence)
+                    || changes.len() < CHANGES_BATCH.into()
+                {
+                    break;
+                }
+            }
+        }
+        Ok(queued)
+    }
+
+    // attempts every due delivery once, returns how many were attempted, the
+    // ones whose attempt can't be recorded are due again on the next pass
+    pub async fn deliver_pending(&self) -> Result<usize, CatalogError> {
+        let due = self.service.due_deliveries().await?;
+        let attempted = due.len();
+        for (delivery, subscription) in due {
+            if let Err(err) = self.deliver(&delivery, &subscription).await {
+                println!(
+                    "the attempt of the delivery {} couldn't be recorded {:?}",
+                    delivery.id, err
+                );
+            }
+        }
+        Ok(attempted)
+    }
+
+    async fn deliver(
+        &self,
+        delivery: &SqlWebhookDelivery,
+        subscription: &SqlWebhookSubscriptionDocument,
+    ) -> Result<SqlWebhookDelivery, CatalogError> {
+        let body = serde_json::to_vec(&delivery.payload).map_err(CatalogError::mapping)?;
+        let timestamp = Utc::now().timestamp();
+        let signature = sign(&subscription.subscription.secret, timestamp, &body);
+        let event = serde_json::to_value(delivery.event).map_err(CatalogError::mapping)?;
+
+        let response = match self.service.check_target(&subscription.subscription).await {
+            Ok(()) => self
+                .client
+                .post(&subscription.subscription.url)
+                .header(SIGNATURE_HEADER, format!("sha256={}", signature))
+                .header(TIMESTAMP_HEADER, timestamp.to_string())
+                .header(EVENT_HEADER, event.as_str().unwrap_or_default())
+                .header(DELIVERY_HEADER, delivery.id.to_string())
+                .content_type(surf::http::mime::JSON)
+                .body(body)
+                .await
+                .map_err(|err| err.to_string()),
+            Err(err) => Err(err.to_string()),
+        };
+
+        let (response_status, error) = match response {
+            Ok(response) if response.status().is_success() => {
+                return self
+                    .service
+                    .record_attempt(
+                        delivery,
+                        WebhookDeliveryStatus::Delivered,
+                        Some(response.status().into()),
+                        None,
+                        None,
+                    )
+                    .await;
+            }
+            Ok(response) => (
+                Some(u16::from(response.status())),
+                format!("unexpected status {}", response.status()),
+            ),
+            Err(err) => (None, err),
+        };
+
+        let attempts = delivery.attempts + 1;
+        if attempts >= self.policy.max_attempts {
+            return self
+                .service
+                .record_attempt(
+                    delivery,
+                    WebhookDeliveryStatus::Failed,
+                    response_status,
+                    Some(error),
+                    None,
+                )
+                .await;
+        }
+        let delay = self.policy.delay(attempts).as_secs() as i64;
+        let next_attempt_at = NaiveDateTime::from_timestamp(Utc::now().timestamp() + delay, 0);
+        self.service
+            .record_attempt(
+                delivery,
+                WebhookDeliveryStatus::Pending,
+                response_status,
+                Some(error),
+                Some(next_attempt_at),
+            )
+            .await
+    }
+
+    // Queues the changes of the log and delivers what's due, then waits for
+    // the next change or the poll interval. The published changes only wake
+    // the worker up, the ones it missed falling behind or while the server
+    // was down are queued from the log all the same.
+    pub async fn run<S>(self, catalog: S, poll_interval: Duration)
+    where
+        S: CatalogChangeFeed<Account = Account> + CatalogService<Id = Id> + Send + Sync,
+    {
+        let mut changes = catalog.subscribe().ok();
+        loop {
+            if let Err(err) = self.catch_up(&catalog).await {
+                println!("the changes couldn't be queued for webhooks {:?}", err);
+            }
+            if let Err(err) = self.deliver_pending().await {
+                println!("the webhooks couldn't be delivered {:?}", err);
+            }
+            match &changes {
+                Some(receiver) => {
+                    // dropped for falling behind, subscribe again
+                    if let Ok(Err(_)) = timeout(poll_interval, receiver.recv()).await {
+                        changes = catalog.subscribe().ok();
+                    }
+                }
+                None => async_std::task::sleep(poll_interval).await,
+            }
+        }
+    }
+}
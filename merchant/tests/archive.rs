@@ -0,0 +1,281 @@
+mod fixtures;
+mod utils;
+
+use fixtures::catalog::{fake_item, fake_item_control, fake_item_variation, fake_stock_location};
+use merchant::catalog::backend::{CatalogSQLService, SqlCatalogObject, SqlCatalogQueryOptions};
+use merchant::catalog::models::{
+    CatalogChangeOperation, CatalogObject, Control, StockLevel, StockLocationKind,
+};
+use merchant::catalog::service::{
+    CatalogArchiveService, CatalogAuditLog, CatalogChangeFeed, CatalogCmd, CatalogError,
+    CatalogRevisionService, CatalogService, Commander, IncreaseItemVariationUnitsAtPayload,
+    ListAuditOptions, ListCatalogChangesOptions, StockLocationService,
+};
+use merchant::utils::sqlite::SqliteConfig;
+use utils::{check_if_error_is, migrate, restore_db, AnyHow};
+
+const CATALOG_ACCOUNT: &str = "account";
+const OTHER_ACCOUNT: &str = "other";
+
+fn everything() -> SqlCatalogQueryOptions {
+    SqlCatalogQueryOptions {
+        limit: None,
+        order_by: None,
+        options: Default::default(),
+    }
+}
+
+// an item with a variation in stock, a control pointing to the variation and
+// the history of an update and a removed variation
+async fn fill_catalog(catalog_service: &CatalogSQLService) -> Result<(), AnyHow> {
+    let account = CATALOG_ACCOUNT.to_string();
+    let item_doc = catalog_service
+        .create(&account, &SqlCatalogObject::Item(fake_item()))
+        .await?;
+    catalog_service
+        .update(&account, &item_doc.id, &SqlCatalogObject::Item(fake_item()))
+        .await?;
+    let variation_doc = catalog_service
+        .create(
+            &account,
+            &SqlCatalogObject::Variation(fake_item_variation(item_doc.id)),
+        )
+        .await?;
+    let removed_doc = catalog_service
+        .create(
+            &account,
+            &SqlCatalogObject::Variation(fake_item_variation(item_doc.id)),
+        )
+        .await?;
+    catalog_service.delete(&account, &removed_doc.id).await?;
+    let mut control = fake_item_control(item_doc.id);
+    if let Control::Matrix(matrix) = &mut control.control {
+        matrix
+            .combinations
+            .insert("Blue-S".to_string(), variation_doc.id);
+    }
+    catalog_service
+        .create(&account, &SqlCatalogObject::Control(control))
+        .await?;
+    let store = catalog_service
+        .create_location(&account, &fake_stock_location(StockLocationKind::Store))
+        .await?;
+    catalog_service
+        .cmd(
+            &account,
+            CatalogCmd::IncreaseItemVariationUnitsAt(IncreaseItemVariationUnitsAtPayload {
+                id: variation_doc.id,
+                location_id: store.id,
+                units: 7,
+            }),
+        )
+        .await?;
+    Ok(())
+}
+
+#[async_std::test]
+async fn export_and_import_into_another_instance() -> Result<(), AnyHow> {
+    let source = CatalogSQLService::new(restore_db().await?);
+    fill_catalog(&source).await?;
+    let archive = source.export(&CATALOG_ACCOUNT.to_string()).await?;
+    assert_eq!(archive.objects.len(), 3);
+    assert_eq!(archive.locations.len(), 1);
+    assert_eq!(archive.stock_levels.len(), 1);
+
+    // through json like the http api does
+    let archive = serde_json::from_str(&serde_json::to_string(&archive)?)?;
+    let target = CatalogSQLService::new(restore_db().await?);
+    let account = OTHER_ACCOUNT.to_string();
+    let import = target.import(&account, &archive).await?;
+
+    let objects = target.list(&account, &everything()).await?;
+    assert_eq!(objects.len(), 3);
+    for document in archive.objects.iter() {
+        let new_id = import.objects[&document.id];
+        let imported = target.read(&account, &new_id).await?;
+        assert_eq!(imported.account, account);
+        assert_eq!(imported.created_at, document.created_at);
+        let mut expected = document.catalog_object.clone();
+        for id in expected.references_mut() {
+            *id = import.objects[id];
+        }
+        assert_eq!(imported.catalog_object, expected);
+
+        let revisions = target.list_revisions(&account, &new_id).await?;
+        let archived_revisions = archive
+            .revisions
+            .iter()
+            .filter(|revision| revision.document.id == document.id)
+            .count();
+        assert_eq!(revisions.len(), archived_revisions);
+        assert!(
+            revisions
+                .iter()
+                .all(|revision| revision.document.id == new_id
+                    && revision.document.account == account)
+        );
+    }
+    // the removed variation keeps its history under a new id as well
+    assert_eq!(import.objects.len(), 4);
+
+    let location_id = import.locations[&archive.locations[0].id];
+    let variation_id = import.objects[&archive.stock_levels[0].variation_id];
+    assert_eq!(
+        target.stock_levels(&account, &variation_id).await?,
+        vec![StockLevel {
+            location_id,
+            variation_id,
+            units: 7,
+        }]
+    );
+    assert_eq!(
+        target.read_location(&account, &location_id).await?.location,
+        archive.locations[0].location
+    );
+    Ok(())
+}
+
+#[async_std::test]
+async fn imported_objects_are_logged_and_audited() -> Result<(), AnyHow> {
+    let source = CatalogSQLService::new(restore_db().await?);
+    fill_catalog(&source).await?;
+    let archive = source.export(&CATALOG_ACCOUNT.to_string()).await?;
+    let target = CatalogSQLService::new(restore_db().await?);
+    let account = OTHER_ACCOUNT.to_string();
+    let subscription = target.subscribe()?;
+    let import = target.import(&account, &archive).await?;
+
+    let changes = target
+        .changes(&account, &ListCatalogChangesOptions::default())
+        .await?;
+    assert_eq!(changes.len(), archive.objects.len());
+    for document in archive.objects.iter() {
+        let new_id = import.objects[&document.id];
+        let change = changes
+            .iter()
+            .find(|change| change.id == new_id)
+            .expect("a change for every object");
+        assert_eq!(change.operation, CatalogChangeOperation::Created);
+        let imported = target.read(&account, &new_id).await?;
+        assert_eq!(change.document.catalog_object, imported.catalog_object);
+        assert_eq!(change.document.version, imported.version);
+        assert_eq!(subscription.recv().await?.sequence, change.sequence);
+    }
+    assert!(subscription.is_empty());
+
+    let audit = target.audit(&account, &ListAuditOptions::default()).await?;
+    assert_eq!(audit.len(), archive.objects.len());
+    assert!(audit
+        .iter()
+        .all(|entry| entry.operation == CatalogChangeOperation::Created));
+    Ok(())
+}
+
+#[async_std::test]
+async fn imports_are_all_or_nothing() -> Result<(), AnyHow> {
+    let source = CatalogSQLService::new(restore_db().await?);
+    fill_catalog(&source).await?;
+    let archive = source.export(&CATALOG_ACCOUNT.to_string()).await?;
+    let target = CatalogSQLService::new(restore_db().await?);
+    let account = OTHER_ACCOUNT.to_string();
+
+    let mut unknown_location = archive.clone();
+    unknown_location.stock_levels[0].location_id += 1;
+    let result = target.import(&account, &unknown_location).await;
+    check_if_error_is(
+        result.unwrap_err(),
+        CatalogError::BulkReferenceNotExist(
+            unknown_location.stock_levels[0].location_id.to_string(),
+        ),
+    );
+
+    let mut without_items = archive.clone();
+    without_items
+        .objects
+        .retain(|document| !matches!(document.catalog_object, CatalogObject::Item(_)));
+    assert!(target.import(&account, &without_items).await.is_err());
+
+    let mut newer_format = archive.clone();
+    newer_format.format_version += 1;
+    let result = target.import(&account, &newer_format).await;
+    check_if_error_is(
+        result.unwrap_err(),
+        CatalogError::bad_request(format!(
+            "the archive format {} isn't supported",
+            newer_format.format_version
+        )),
+    );
+
+    assert!(target.list(&account, &everything()).await?.is_empty());
+    assert!(target.list_locations(&account).await?.is_empty());
+    Ok(())
+}
+
+#[async_std::test]
+async fn the_export_only_has_the_account() -> Result<(), AnyHow> {
+    let catalog_service = CatalogSQLService::new(restore_db().await?);
+    fill_catalog(&catalog_service).await?;
+    catalog_service
+        .create(
+            &OTHER_ACCOUNT.to_string(),
+            &SqlCatalogObject::Item(fake_item()),
+        )
+        .await?;
+
+    let archive = catalog_service.export(&OTHER_ACCOUNT.to_string()).await?;
+    assert_eq!(archive.account, OTHER_ACCOUNT);
+    assert_eq!(archive.objects.len(), 1);
+    assert_eq!(archive.revisions.len(), 1);
+    assert!(archive.locations.is_empty());
+    assert!(archive.stock_levels.is_empty());
+    Ok(())
+}
+
+#[async_std::test]
+async fn backups_open_as_a_database() -> Result<(), AnyHow> {
+    let dir = std::env::temp_dir();
+    let id = rand::random::<u32>();
+    let database = dir.join(format!("merchant-{}.db", id));
+    let path = dir.join(format!("merchant-{}-backup.db", id));
+    let path = path.to_string_lossy().to_string();
+    let pool = SqliteConfig::default()
+        .connect(&format!("sqlite://{}?mode=rwc", database.display()))
+        .await?;
+    migrate(&pool).await?;
+    let catalog_service = CatalogSQLService::new(pool);
+    fill_catalog(&catalog_service).await?;
+
+    catalog_service.backup(&path).await?;
+    let restored = CatalogSQLService::new(
+        SqliteConfig::default()
+            .connect(&format!("sqlite://{}", path))
+            .await?,
+    );
+    let account = CATALOG_ACCOUNT.to_string();
+    assert_eq!(
+        serde_json::to_value(restored.export(&account).await?.objects)?,
+        serde_json::to_value(catalog_service.export(&account).await?.objects)?
+    );
+
+    // the file isn't overwritten
+    let result = catalog_service.backup(&path).await;
+    assert!(matches!(
+        result.unwrap_err(),
+        CatalogError::DatabaseError(_)
+    ));
+    for file in [format!("{}", database.display()), path] {
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", file, suffix));
+        }
+    }
+    Ok(())
+}
+
+#[async_std::test]
+async fn in_memory_databases_cant_be_backed_up() -> Result<(), AnyHow> {
+    let catalog_service = CatalogSQLService::new(restore_db().await?);
+    let path = std::env::temp_dir().join(format!("merchant-{}.db", rand::random::<u32>()));
+    let result = catalog_service.backup(&path.to_string_lossy()).await;
+    check_if_error_is(result.unwrap_err(), CatalogError::Unsupported);
+    Ok(())
+}
@@ -0,0 +1,182 @@
+mod fixtures;
+mod utils;
+
+use fixtures::catalog::{fake_item, fake_item_variation, fake_stock_location};
+use merchant::catalog::backend::{CatalogSQLService, SqlCatalogObject};
+use merchant::catalog::models::{Actor, CatalogChangeOperation, StockLocationKind};
+use merchant::catalog::service::{
+    CatalogAuditLog, CatalogCmd, CatalogService, Commander, IncreaseItemVariationUnitsPayload,
+    ListAuditOptions, StockLocationService,
+};
+use sqlx::types::chrono::{NaiveDateTime, Utc};
+use utils::{restore_db, AnyHow};
+
+const CATALOG_ACCOUNT: &str = "account";
+
+fn fake_actor(id: &str) -> Actor {
+    Actor {
+        id: id.to_string(),
+        claimed_id: Some(format!("{} from the header", id)),
+        address: Some("127.0.0.1:4000".to_string()),
+        user_agent: Some("tests".to_string()),
+    }
+}
+
+#[async_std::test]
+async fn every_write_is_audited() -> Result<(), AnyHow> {
+    let pool = restore_db().await?;
+    let catalog_service = CatalogSQLService::new(pool);
+    let alice = catalog_service.clone().with_actor(fake_actor("alice"));
+    let bob = catalog_service.clone().with_actor(fake_actor("bob"));
+    let account = CATALOG_ACCOUNT.to_string();
+
+    let item_doc = alice
+        .create(&account, &SqlCatalogObject::Item(fake_item()))
+        .await?;
+    let variation_doc = alice
+        .create(
+            &account,
+            &SqlCatalogObject::Variation(fake_item_variation(item_doc.id)),
+        )
+        .await?;
+    bob.update(&account, &item_doc.id, &SqlCatalogObject::Item(fake_item()))
+        .await?;
+    bob.cmd(
+        &account,
+        CatalogCmd::IncreaseItemVariationUnits(IncreaseItemVariationUnitsPayload {
+            id: variation_doc.id,
+            units: 2,
+        }),
+    )
+    .await?;
+    bob.delete(&account, &variation_doc.id).await?;
+
+    let entries = catalog_service
+        .audit(&account, &ListAuditOptions::default())
+        .await?;
+    let operations: Vec<CatalogChangeOperation> =
+        entries.iter().map(|entry| entry.operation).collect();
+    assert_eq!(
+        operations,
+        vec![
+            CatalogChangeOperation::Created,
+            CatalogChangeOperation::Created,
+            CatalogChangeOperation::Updated,
+            CatalogChangeOperation::Command,
+            CatalogChangeOperation::Deleted,
+        ]
+    );
+    assert_eq!(entries[0].actor, fake_actor("alice"));
+    assert!(entries[0].before.is_none());
+    assert_eq!(entries[2].actor.id, "bob");
+    assert_eq!(
+        entries[2].before.as_ref().unwrap()["data"]["name"],
+        serde_json::to_value(&item_doc)?["data"]["name"]
+    );
+    assert_eq!(
+        entries[3].after.as_ref().unwrap()["data"]["available_units"],
+        entries[3].before.as_ref().unwrap()["data"]["available_units"]
+            .as_i64()
+            .unwrap()
+            + 2
+    );
+    assert!(entries[3].cmd.is_some());
+    assert!(entries[4].after.is_none());
+    assert_eq!(entries[4].object_id, variation_doc.id);
+    Ok(())
+}
+
+#[async_std::test]
+async fn filter_audit_by_actor_and_time() -> Result<(), AnyHow> {
+    let pool = restore_db().await?;
+    let catalog_service = CatalogSQLService::new(pool);
+    let account = CATALOG_ACCOUNT.to_string();
+    let started_at = NaiveDateTime::from_timestamp(Utc::now().timestamp() - 1, 0);
+
+    catalog_service
+        .clone()
+        .with_actor(fake_actor("alice"))
+        .create(&account, &SqlCatalogObject::Item(fake_item()))
+        .await?;
+    catalog_service
+        .clone()
+        .with_actor(fake_actor("bob"))
+        .create(&account, &SqlCatalogObject::Item(fake_item()))
+        .await?;
+    catalog_service
+        .create(&"other".to_string(), &SqlCatalogObject::Item(fake_item()))
+        .await?;
+
+    let by_bob = catalog_service
+        .audit(
+            &account,
+            &ListAuditOptions {
+                actor: Some("bob".to_string()),
+                ..Default::default()
+            },
+        )
+        .await?;
+    assert_eq!(by_bob.len(), 1);
+    assert_eq!(by_bob[0].actor.id, "bob");
+
+    let since_start = catalog_service
+        .audit(
+            &account,
+            &ListAuditOptions {
+                from: Some(started_at),
+                ..Default::default()
+            },
+        )
+        .await?;
+    assert_eq!(since_start.len(), 2);
+
+    let before_start = catalog_service
+        .audit(
+            &account,
+            &ListAuditOptions {
+                to: Some(NaiveDateTime::from_timestamp(0, 0)),
+                ..Default::default()
+            },
+        )
+        .await?;
+    assert!(before_start.is_empty());
+
+    let others = catalog_service
+        .audit(&"other".to_string(), &ListAuditOptions::default())
+        .await?;
+    assert_eq!(others.len(), 1);
+    assert_eq!(others[0].actor, Actor::default());
+    Ok(())
+}
+
+#[async_std::test]
+async fn location_writes_are_audited() -> Result<(), AnyHow> {
+    let pool = restore_db().await?;
+    let catalog_service = CatalogSQLService::new(pool).with_actor(fake_actor("alice"));
+    let account = CATALOG_ACCOUNT.to_string();
+
+    let location = catalog_service
+        .create_location(&account, &fake_stock_location(StockLocationKind::Store))
+        .await?;
+    let renamed = catalog_service
+        .update_location(
+            &account,
+            &location.id,
+            &fake_stock_location(StockLocationKind::Warehouse),
+        )
+        .await?;
+
+    let entries = catalog_service
+        .audit(&account, &ListAuditOptions::default())
+        .await?;
+    assert_eq!(entries.len(), 2);
+    assert!(entries
+        .iter()
+        .all(|entry| entry.object_id == location.id && entry.actor == fake_actor("alice")));
+    assert_eq!(entries[0].operation, CatalogChangeOperation::Created);
+    assert!(entries[0].before.is_none());
+    assert_eq!(entries[1].operation, CatalogChangeOperation::Updated);
+    assert_eq!(entries[1].before, Some(serde_json::to_value(&location)?));
+    assert_eq!(entries[1].after, Some(serde_json::to_value(&renamed)?));
+    Ok(())
+}
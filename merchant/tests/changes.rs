@@ -0,0 +1,121 @@
+mod fixtures;
+mod utils;
+
+use fixtures::catalog::{fake_item, fake_item_variation};
+use merchant::catalog::any::AnyCatalogService;
+use merchant::catalog::backend::{CatalogSQLService, SqlCatalogObject};
+use merchant::catalog::memory::CatalogMemoryService;
+use merchant::catalog::models::CatalogChangeOperation;
+use merchant::catalog::service::{
+    CatalogChangeFeed, CatalogCmd, CatalogError, CatalogService, Commander,
+    IncreaseItemVariationUnitsPayload, ListCatalogChangesOptions,
+};
+use utils::{check_if_error_is, restore_db, AnyHow};
+
+const CATALOG_ACCOUNT: &str = "account";
+
+#[async_std::test]
+async fn every_write_appends_to_the_change_log() -> Result<(), AnyHow> {
+    let pool = restore_db().await?;
+    let catalog_service = CatalogSQLService::new(pool);
+    let account = CATALOG_ACCOUNT.to_string();
+    let item_doc = catalog_service
+        .create(&account, &SqlCatalogObject::Item(fake_item()))
+        .await?;
+    catalog_service
+        .update(&account, &item_doc.id, &SqlCatalogObject::Item(fake_item()))
+        .await?;
+    let variation_doc = catalog_service
+        .create(
+            &account,
+            &SqlCatalogObject::Variation(fake_item_variation(item_doc.id)),
+        )
+        .await?;
+    catalog_service
+        .cmd(
+            &account,
+            CatalogCmd::IncreaseItemVariationUnits(IncreaseItemVariationUnitsPayload {
+                id: variation_doc.id,
+                units: 3,
+            }),
+        )
+        .await?;
+    catalog_service.delete(&account, &variation_doc.id).await?;
+    catalog_service
+        .create(&"other".to_string(), &SqlCatalogObject::Item(fake_item()))
+        .await?;
+
+    let changes = catalog_service
+        .changes(&account, &ListCatalogChangesOptions::default())
+        .await?;
+    let operations: Vec<_> = changes
+        .iter()
+        .map(|change| (change.sequence, change.id, change.operation))
+        .collect();
+    assert_eq!(
+        operations,
+        vec![
+            (1, item_doc.id, CatalogChangeOperation::Created),
+            (2, item_doc.id, CatalogChangeOperation::Updated),
+            (3, variation_doc.id, CatalogChangeOperation::Created),
+            (4, variation_doc.id, CatalogChangeOperation::Command),
+            (5, variation_doc.id, CatalogChangeOperation::Deleted),
+        ]
+    );
+    assert!(changes[3].cmd.is_some());
+
+    let since = catalog_service
+        .changes(
+            &account,
+            &ListCatalogChangesOptions {
+                since: Some(3),
+                limit: Some(1),
+            },
+        )
+        .await?;
+    assert_eq!(since.len(), 1);
+    assert_eq!(since[0].sequence, 4);
+
+    let other = catalog_service
+        .changes(&"other".to_string(), &ListCatalogChangesOptions::default())
+        .await?;
+    assert_eq!(other.len(), 1);
+    assert_eq!(other[0].sequence, 1);
+    Ok(())
+}
+
+#[async_std::test]
+async fn subscribers_receive_recorded_changes() -> Result<(), AnyHow> {
+    let pool = restore_db().await?;
+    let catalog_service = CatalogSQLService::new(pool);
+    let account = CATALOG_ACCOUNT.to_string();
+    let subscription = catalog_service.subscribe()?;
+    let other_subscription = catalog_service.clone().subscribe()?;
+
+    let item_doc = catalog_service
+        .create(&account, &SqlCatalogObject::Item(fake_item()))
+        .await?;
+    catalog_service.delete(&account, &item_doc.id).await?;
+
+    for changes in [subscription, other_subscription] {
+        let created = changes.recv().await?;
+        assert_eq!(created.sequence, 1);
+        assert_eq!(created.operation, CatalogChangeOperation::Created);
+        assert_eq!(created.document.id, item_doc.id);
+        let deleted = changes.recv().await?;
+        assert_eq!(deleted.sequence, 2);
+        assert_eq!(deleted.operation, CatalogChangeOperation::Deleted);
+        assert!(changes.is_empty());
+    }
+    Ok(())
+}
+
+#[test]
+fn backends_without_a_feed_cant_be_subscribed_to() {
+    let catalog_service = AnyCatalogService::Memory(CatalogMemoryService::new());
+    assert!(!catalog_service.limits().is_empty());
+    check_if_error_is(
+        catalog_service.subscribe().unwrap_err(),
+        CatalogError::Unsupported,
+    );
+}
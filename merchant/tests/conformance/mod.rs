@@ -0,0 +1,1484 @@
+// Behaviour every catalog backend has to share. Each case is generic over
+// the backend, `conformance_suite!` turns them into tests for one of them.
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use merchant::catalog::backend::{Account, SqlCatalogObjectDocument, SqlCatalogQueryOptions};
+use merchant::catalog::models::{
+    CatalogObject, CatalogObjectBulkDocument, CatalogObjectDocument, Control, Delivery, Image,
+    Item, ItemCategory, ItemControl, ItemDelivery, ItemMeasurmentUnits, ItemModification,
+    ItemVariation, Price, StockLocationKind, Time,
+};
+use merchant::catalog::projection::{project, Fields, DATA_FIELDS, DOCUMENT_FIELDS};
+use merchant::catalog::service::{
+    patch, CatalogCmd, CatalogColumnOrder, CatalogError, CatalogProjection, CatalogService,
+    Commander, IncreaseItemVariationUnitsAtPayload, IncreaseItemVariationUnitsPayload,
+    ListCatalogQueryOptions, StockLocationService, TransferItemVariationUnitsPayload,
+};
+use merchant::catalog::validation::Violation;
+use merchant::utils::query::{Order, OrderBy};
+use serde_json::{json, Value};
+use sqlx::types::chrono::NaiveDateTime;
+
+use crate::as_value;
+use crate::fixtures::catalog::{
+    fake_item, fake_item_control, fake_item_delivery, fake_item_modification, fake_item_variation,
+    fake_stock_location,
+};
+use crate::utils::{check_if_error_is, random_account, AnyHow, InstanceOf};
+
+type Id = u32;
+type Document = CatalogObjectDocument<Id, Account>;
+
+pub trait Backend:
+    CatalogService<Id = Id, Query = SqlCatalogQueryOptions>
+    + Commander<Account = Account, Cmd = CatalogCmd<Id>>
+    + StockLocationService
+    + CatalogProjection
+    + Sync
+{
+}
+
+impl<T> Backend for T where
+    T: CatalogService<Id = Id, Query = SqlCatalogQueryOptions>
+        + Commander<Account = Account, Cmd = CatalogCmd<Id>>
+        + StockLocationService
+        + CatalogProjection
+        + Sync
+{
+}
+
+// Builds the backend for one test, the guard is kept alive until the test
+// ends. The suites of backends that need a server are `#[ignore]`d, they run
+// with `--ignored` and fail when the server isn't there.
+#[async_trait]
+pub trait Harness {
+    type Service: Backend;
+    type Guard;
+    async fn setup() -> Result<(Self::Service, Self::Guard), AnyHow>;
+}
+
+#[macro_export]
+macro_rules! conformance_suite {
+    (@case [$(#[$attr:meta])*] $harness:ty, $case:ident) => {
+        #[async_std::test]
+        $(#[$attr])*
+        async fn $case() -> Result<(), $crate::utils::AnyHow> {
+            use $crate::conformance::Harness;
+            let (service, _guard) = <$harness>::setup().await?;
+            $crate::conformance::$case(&service).await
+        }
+    };
+    // the attributes are passed as a single token tree to repeat them per case
+    (@cases $attrs:tt $harness:ty; $($case:ident),*) => {
+        $(
+            conformance_suite!(@case $attrs $harness, $case);
+        )*
+    };
+    ($(#[$attr:meta])* $harness:ty) => {
+        conformance_suite!(
+            $(#[$attr])* $harness;
+            create_item,
+            update_item,
+            read_item,
+            check_if_exists,
+            read_item_fails_if_id_doesnt_exists,
+            delete_variation,
+            delete_item_fails_if_it_has_variations,
+            delete_fails_if_id_doesnt_exists,
+            create_item_variation,
+            create_item_variation_fails_if_not_exists_item_id,
+            update_variation,
+            update_variation_fails_if_not_exists_item_id,
+            update_fails_if_the_type_changes,
+            read_variation,
+            list_item_by_name,
+            list_item_children,
+            list_item_by_min_and_max_amount,
+            list_item_by_tags,
+            list_order_by_price,
+            list_only_returns_the_account_objects,
+            increase_item_in_variations,
+            decrease_below_zero_units_fails,
+            increase_units_at_unknown_location_fails,
+            increase_units_of_unknown_variation_fails,
+            transfer_units_between_locations,
+            transfer_fails_without_enough_units,
+            transfer_to_the_same_location_fails,
+            create_bulk,
+            create_bulk_fails_if_reference_doesnt_exists,
+            create_fails_with_every_violation,
+            update_fails_with_every_violation,
+            create_bulk_fails_with_every_violation,
+            patch_variation,
+            patch_fails_if_the_version_changed,
+            patch_fails_with_violations,
+            list_fields_matches_the_documents,
+            list_fields_keeps_the_fields_asked_for
+        );
+    };
+    ($(#[$attr:meta])* $harness:ty; $($case:ident),*) => {
+        conformance_suite!(@cases [$(#[$attr])*] $harness; $($case),*);
+    };
+}
+
+pub fn check_catalog_object_document<T: 'static>(catalog: &CatalogObjectDocument<T, Account>) {
+    assert!(
+        catalog.version.instance_of::<NaiveDateTime>(),
+        "it should be an instance of NaiveDateTime"
+    );
+    assert!(
+        catalog.id.instance_of::<T>(),
+        "it should be a instance of Id"
+    );
+    assert!(
+        catalog.account.instance_of::<String>(),
+        "the accoutn property should be an str"
+    );
+    assert!(catalog.created_at.instance_of::<NaiveDateTime>());
+}
+
+pub fn check_item_document(catalog: &SqlCatalogObjectDocument, item_object: &Item) {
+    check_catalog_object_document(catalog);
+    assert!(
+        matches!(catalog.catalog_object, CatalogObject::Item(_)),
+        "the catalog object should be an item"
+    );
+    match &catalog.catalog_object {
+        CatalogObject::Item(item) => {
+            assert!(
+                item.tags.instance_of::<Vec<String>>(),
+                "tags should be a instance of vector"
+            );
+            assert!(item.name.instance_of::<String>(), "name should be a string");
+            assert!(
+                item.description.instance_of::<String>(),
+                "description should be an string"
+            );
+            assert!(
+                item.category.instance_of::<ItemCategory>(),
+                "description should be an string"
+            );
+            // item tags
+            assert_eq!(item.tags, item_object.tags);
+            assert_eq!(item.name, item_object.name);
+            assert_eq!(item.description, item_object.description);
+            assert!(
+                item.category == item_object.category,
+                "category are distinct"
+            );
+        }
+        _ => panic!("catalog_object should be an item"),
+    }
+}
+
+pub fn check_variation_document<T: 'static, Y: 'static>(
+    catalog: &CatalogObjectDocument<T, Account>,
+    variation: &ItemVariation<Y>,
+) {
+    check_catalog_object_document(catalog);
+    assert!(
+        matches!(catalog.catalog_object, CatalogObject::Variation(_)),
+        "the catalog object should be an Variation"
+    );
+    match &catalog.catalog_object {
+        CatalogObject::Variation(v) => {
+            assert!(
+                v.images.instance_of::<Vec<Image>>(),
+                "it should be a vector of images"
+            );
+            assert!(v.item_id.instance_of::<T>(), "it should be an id");
+            assert!(
+                v.measurement_units.instance_of::<ItemMeasurmentUnits>(),
+                "it should be an id"
+            );
+            assert_eq!(v.images, variation.images);
+            // assert_eq!(v.item_id, variation.item_id);
+            assert_eq!(v.measurement_units, variation.measurement_units);
+            assert_eq!(v.name, variation.name);
+            assert_eq!(v.price, variation.price);
+            assert_eq!(v.sku, variation.sku);
+            assert_eq!(v.available_units, variation.available_units);
+            assert_eq!(v.upc, variation.upc);
+        }
+        _ => panic!("catalog_object should be an item"),
+    }
+}
+
+pub fn check_control_document<T: 'static, Y: 'static>(
+    catalog_a: &CatalogObjectDocument<T, Account>,
+    control_b: &ItemControl<Y>,
+) {
+    check_catalog_object_document(catalog_a);
+    assert!(
+        matches!(catalog_a.catalog_object, CatalogObject::Control(_)),
+        "the catalog object should be a Control"
+    );
+    assert!(control_b.item_id.instance_of::<Y>(), "it should be an id");
+    if let CatalogObject::Control(item_control_a) = &catalog_a.catalog_object {
+        match &item_control_a.control {
+            Control::Matrix(matrix_a) => {
+                if let Control::Matrix(matrix_b) = &control_b.control {
+                    let combination_a = matrix_a.to_owned();
+                    for (key_a, _) in combination_a.combinations.iter() {
+                        assert!(
+                            matrix_b.combinations.contains_key(key_a),
+                            "combinations_b dont have the requested key"
+                        );
+                    }
+
+                    assert_eq!(matrix_a.key_template, matrix_b.key_template);
+
+                    for prop in matrix_b.props.iter() {
+                        assert!(
+                            matrix_a
+                                .props
+                                .iter()
+                                .find(|x| x.name == prop.name)
+                                .is_some(),
+                            "the catalog object should be an Variation"
+                        );
+                    }
+                } else {
+                    panic!("control_b is not a matrix control");
+                }
+            }
+            _ => panic!("not supported other controls than Matrix"),
+        }
+    } else {
+        panic!("catalog_object should be an item");
+    }
+}
+
+#[allow(dead_code)]
+pub fn check_delivery_document<T: 'static + PartialEq, Y: 'static + PartialEq>(
+    delivery_a_doc: &CatalogObjectDocument<T, Account>,
+    delivery_b: &ItemDelivery<Y>,
+) where
+    ItemDelivery<T>: PartialEq<ItemDelivery<Y>>,
+{
+    check_catalog_object_document(delivery_a_doc);
+    assert!(
+        matches!(delivery_a_doc.catalog_object, CatalogObject::Variation(_)),
+        "the catalog object should be an Variation"
+    );
+    assert!(delivery_b.item_id.instance_of::<Y>(), "it should be an id");
+
+    if let CatalogObject::Delivery(ref delivery_a) = delivery_a_doc.catalog_object {
+        if delivery_a != delivery_b {
+            panic!("delivery are not the same")
+        }
+    }
+}
+
+pub fn check_modification_document<T: 'static, Y: 'static>(
+    catalog: &CatalogObjectDocument<T, Account>,
+    modification: &ItemModification<Y>,
+) {
+    check_catalog_object_document(catalog);
+    assert!(
+        matches!(catalog.catalog_object, CatalogObject::Modification(_)),
+        "the catalog object should be an Modification"
+    );
+    match &catalog.catalog_object {
+        CatalogObject::Modification(m) => {
+            assert!(
+                m.images.instance_of::<Vec<Image>>(),
+                "it should be a vector of images"
+            );
+            assert!(m.item_id.instance_of::<T>(), "it should be a valid id");
+            assert_eq!(m.images, modification.images);
+            // assert_eq!(m.item_id, modification.item_id);
+            assert_eq!(m.name, modification.name);
+            assert_eq!(m.price, modification.price);
+        }
+        _ => panic!("catalog_object should be an item"),
+    }
+}
+
+pub async fn make_item<S: Backend>(
+    service: &S,
+    account: &Account,
+    item: Item,
+) -> Result<Document, AnyHow> {
+    Ok(service.create(account, &CatalogObject::Item(item)).await?)
+}
+
+pub async fn make_variation<S: Backend>(
+    service: &S,
+    account: &Account,
+    variation: ItemVariation<Id>,
+) -> Result<Document, CatalogError> {
+    service
+        .create(account, &CatalogObject::Variation(variation))
+        .await
+}
+
+fn query(options: ListCatalogQueryOptions<Id>) -> SqlCatalogQueryOptions {
+    SqlCatalogQueryOptions {
+        limit: None,
+        order_by: None,
+        options,
+    }
+}
+
+fn by_created_at() -> OrderBy<CatalogColumnOrder> {
+    OrderBy {
+        field: CatalogColumnOrder::CreatedAt,
+        direction: Order::Asc,
+    }
+}
+
+fn fixed_price(amount: f32) -> Price {
+    Price::Fixed {
+        amount,
+        asset_name: "USD".to_string(),
+        asset_scale: 2,
+    }
+}
+
+fn available_units(document: &Document) -> i32 {
+    match &document.catalog_object {
+        CatalogObject::Variation(variation) => variation.available_units,
+        _ => panic!("catalog_object should be a variation"),
+    }
+}
+
+pub async fn create_item<S: Backend>(service: &S) -> Result<(), AnyHow> {
+    let account = random_account();
+    let entry = CatalogObject::Item(fake_item());
+    let catalog_entry_document = service.create(&account, &entry).await?;
+    check_item_document(&catalog_entry_document, entry.item().unwrap());
+    Ok(())
+}
+
+pub async fn update_item<S: Backend>(service: &S) -> Result<(), AnyHow> {
+    let account = random_account();
+    let item_old = fake_item();
+    let item_new = fake_item();
+    let item_doc = make_item(service, &account, item_old.clone()).await?;
+    check_item_document(&item_doc, &item_old);
+    let updated_catalog_item = service
+        .update(
+            &account,
+            &item_doc.id,
+            &CatalogObject::Item(item_new.clone()),
+        )
+        .await?;
+    check_item_document(&updated_catalog_item, &item_new);
+    let item_created = as_value!(updated_catalog_item.catalog_object, CatalogObject::Item).unwrap();
+    assert_ne!(item_created.name, item_old.name);
+    assert_eq!(item_created.name, item_new.name);
+    Ok(())
+}
+
+pub async fn read_item<S: Backend>(service: &S) -> Result<(), AnyHow> {
+    let account = random_account();
+    let item_doc = make_item(service, &account, fake_item()).await?;
+    let item = item_doc.catalog_object.item().unwrap();
+    check_item_document(&item_doc, item);
+    let read_catalog_item = service.read(&account, &item_doc.id).await?;
+    check_item_document(&read_catalog_item, item);
+    Ok(())
+}
+
+pub async fn check_if_exists<S: Backend>(service: &S) -> Result<(), AnyHow> {
+    let account = random_account();
+    let catalog_item_document = make_item(service, &account, fake_item()).await?;
+    assert!(
+        service.exists(&account, &catalog_item_document.id).await?,
+        "it should exists"
+    );
+    assert!(
+        !service.exists(&account, &Id::default()).await?,
+        "it should not exists"
+    );
+    assert!(
+        !service
+            .exists(&random_account(), &catalog_item_document.id)
+            .await?,
+        "it should not exists for other accounts"
+    );
+    Ok(())
+}
+
+pub async fn read_item_fails_if_id_doesnt_exists<S: Backend>(service: &S) -> Result<(), AnyHow> {
+    let account = random_account();
+    let id = Id::default();
+    let read_catalog_item = service.read(&account, &id).await;
+    check_if_error_is(
+        read_catalog_item.unwrap_err(),
+        CatalogError::CatalogEntryNotFound(id.to_string()),
+    );
+    Ok(())
+}
+
+pub async fn delete_variation<S: Backend>(service: &S) -> Result<(), AnyHow> {
+    let account = random_account();
+    let item_doc = make_item(service, &account, fake_item()).await?;
+    let variation = fake_item_variation(item_doc.id);
+    let variation_doc = make_variation(service, &account, variation.clone()).await?;
+    let deleted = service.delete(&account, &variation_doc.id).await?;
+    check_variation_document(&deleted, &variation);
+    let result = service.read(&account, &variation_doc.id).await;
+    check_if_error_is(
+        result.unwrap_err(),
+        CatalogError::CatalogEntryNotFound(variation_doc.id.to_string()),
+    );
+    // once the variations are gone the item can be removed
+    service.delete(&account, &item_doc.id).await?;
+    Ok(())
+}
+
+pub async fn delete_item_fails_if_it_has_variations<S: Backend>(service: &S) -> Result<(), AnyHow> {
+    let account = random_account();
+    let item_doc = make_item(service, &account, fake_item()).await?;
+    make_variation(service, &account, fake_item_variation(item_doc.id)).await?;
+    let result = service.delete(&account, &item_doc.id).await;
+    check_if_error_is(result.unwrap_err(), CatalogError::item_in_use(item_doc.id));
+    Ok(())
+}
+
+pub async fn delete_fails_if_id_doesnt_exists<S: Backend>(service: &S) -> Result<(), AnyHow> {
+    let account = random_account();
+    let item_doc = make_item(service, &account, fake_item()).await?;
+    let result = service.delete(&account, &Id::default()).await;
+    check_if_error_is(
+        result.unwrap_err(),
+        CatalogError::CatalogEntryNotFound(Id::default().to_string()),
+    );
+    // other accounts can't remove it either
+    let result = service.delete(&random_account(), &item_doc.id).await;
+    check_if_error_is(
+        result.unwrap_err(),
+        CatalogError::CatalogEntryNotFound(item_doc.id.to_string()),
+    );
+    Ok(())
+}
+
+pub async fn create_item_variation<S: Backend>(service: &S) -> Result<(), AnyHow> {
+    let account = random_account();
+    let item_doc = make_item(service, &account, fake_item()).await?;
+    let variation = fake_item_variation(item_doc.id);
+    let variation_doc = make_variation(service, &account, variation.clone()).await?;
+    check_variation_document(&variation_doc, &variation);
+    Ok(())
+}
+
+pub async fn create_item_variation_fails_if_not_exists_item_id<S: Backend>(
+    service: &S,
+) -> Result<(), AnyHow> {
+    let account = random_account();
+    let variation = fake_item_variation(Id::default());
+    let result = make_variation(service, &account, variation).await;
+    check_if_error_is(
+        result.unwrap_err(),
+        CatalogError::unknown_item(Id::default()),
+    );
+    Ok(())
+}
+
+pub async fn update_variation<S: Backend>(service: &S) -> Result<(), AnyHow> {
+    let account = random_account();
+    let item_doc = make_item(service, &account, fake_item()).await?;
+    let variation = fake_item_variation(item_doc.id);
+    let variation_new = fake_item_variation(item_doc.id);
+    let catalog_variation_document = make_variation(service, &account, variation.clone()).await?;
+    check_variation_document(&catalog_variation_document, &variation);
+    let updated_catalog_variation = service
+        .update(
+            &account,
+            &catalog_variation_document.id,
+            &CatalogObject::Variation(variation_new.clone()),
+        )
+        .await?;
+    check_variation_document(&updated_catalog_variation, &variation_new);
+    let variation_updated = as_value!(
+        updated_catalog_variation.catalog_object,
+        CatalogObject::Variation
+    )
+    .unwrap();
+    assert_ne!(variation_updated.name, variation.name);
+    assert_eq!(variation_updated.name, variation_new.name);
+    Ok(())
+}
+
+pub async fn update_variation_fails_if_not_exists_item_id<S: Backend>(
+    service: &S,
+) -> Result<(), AnyHow> {
+    let account = random_account();
+    let catalog_item_document = make_item(service, &account, fake_item()).await?;
+    let variation = fake_item_variation(catalog_item_document.id);
+    let variation_new = fake_item_variation(Id::default());
+    let catalog_variation_document = make_variation(service, &account, variation.clone()).await?;
+    check_variation_document(&catalog_variation_document, &variation);
+    let result = service
+        .update(
+            &account,
+            &catalog_variation_document.id,
+            &CatalogObject::Variation(variation_new),
+        )
+        .await;
+    check_if_error_is(
+        result.unwrap_err(),
+        CatalogError::unknown_item(Id::default()),
+    );
+    Ok(())
+}
+
+pub async fn update_fails_if_the_type_changes<S: Backend>(service: &S) -> Result<(), AnyHow> {
+    let account = random_account();
+    let item_doc = make_item(service, &account, fake_item()).await?;
+    let result = service
+        .update(
+            &account,
+            &item_doc.id,
+            &CatalogObject::Variation(fake_item_variation(item_doc.id)),
+        )
+        .await;
+    check_if_error_is(
+        result.unwrap_err(),
+        CatalogError::CatalogEntryNotFound(item_doc.id.to_string()),
+    );
+    Ok(())
+}
+
+pub async fn read_variation<S: Backend>(service: &S) -> Result<(), AnyHow> {
+    let account = random_account();
+    let item_doc = make_item(service, &account, fake_item()).await?;
+    let variation = fake_item_variation(item_doc.id);
+    let variation_doc = make_variation(service, &account, variation.clone()).await?;
+    check_variation_document(&variation_doc, &variation);
+    let read_catalog_variation = service.read(&account, &variation_doc.id).await?;
+    check_variation_document(&read_catalog_variation, &variation);
+    Ok(())
+}
+
+pub async fn list_item_by_name<S: Backend>(service: &S) -> Result<(), AnyHow> {
+    let account = random_account();
+    let doc = make_item(service, &account, fake_item()).await?;
+    let item = doc.catalog_object.item().unwrap();
+
+    let items_empty = service
+        .list(
+            &account,
+            &query(ListCatalogQueryOptions {
+                name: Some("None".to_string()),
+                ..Default::default()
+            }),
+        )
+        .await?;
+    assert_eq!(items_empty.len(), 0);
+    let items_found = service
+        .list(
+            &account,
+            &query(ListCatalogQueryOptions {
+                name: Some(item.name.clone()),
+                ..Default::default()
+            }),
+        )
+        .await?;
+    assert_eq!(items_found.len(), 1);
+    check_item_document(&items_found[0], item);
+    // any part of the name in any case finds it too
+    let middle: String = item.name.chars().skip(1).take(3).collect();
+    let items_found = service
+        .list(
+            &account,
+            &query(ListCatalogQueryOptions {
+                name: Some(middle.to_uppercase()),
+                ..Default::default()
+            }),
+        )
+        .await?;
+    assert_eq!(items_found.len(), 1);
+
+    // the wildcards of LIKE are looked for as they are
+    let account = random_account();
+    let mut cotton = fake_item();
+    cotton.name = "Shirt 100% cotton_tee".to_string();
+    let cotton_doc = make_item(service, &account, cotton).await?;
+    let mut other = fake_item();
+    other.name = "Shirt 1000 cotton tee".to_string();
+    make_item(service, &account, other).await?;
+    for name in ["0% c", "n_t", "%", "_"] {
+        let found = service
+            .list(
+                &account,
+                &query(ListCatalogQueryOptions {
+                    name: Some(name.to_string()),
+                    ..Default::default()
+                }),
+            )
+            .await?;
+        assert_eq!(found.len(), 1, "{}", name);
+        assert_eq!(found[0].id, cotton_doc.id);
+    }
+    Ok(())
+}
+
+pub async fn list_item_children<S: Backend>(service: &S) -> Result<(), AnyHow> {
+    let account = random_account();
+    let item_doc = make_item(service, &account, fake_item()).await?;
+    let other_doc = make_item(service, &account, fake_item()).await?;
+    let variation_doc = make_variation(service, &account, fake_item_variation(item_doc.id)).await?;
+    let control_doc = service
+        .create(
+            &account,
+            &CatalogObject::Control(fake_item_control(item_doc.id)),
+        )
+        .await?;
+    make_variation(service, &account, fake_item_variation(other_doc.id)).await?;
+
+    let found = service
+        .list(
+            &account,
+            &query(ListCatalogQueryOptions {
+                item_id: Some(item_doc.id),
+                ..Default::default()
+            }),
+        )
+        .await?;
+    let mut ids: Vec<Id> = found.iter().map(|document| document.id).collect();
+    ids.sort_unstable();
+    let mut expected = vec![variation_doc.id, control_doc.id];
+    expected.sort_unstable();
+    assert_eq!(ids, expected);
+    Ok(())
+}
+
+pub async fn list_item_by_min_and_max_amount<S: Backend>(service: &S) -> Result<(), AnyHow> {
+    let account = random_account();
+    let item_doc = make_item(service, &account, fake_item()).await?;
+    let mut variation = fake_item_variation(item_doc.id);
+    variation.price = fixed_price(2000.0);
+    let variation_document = make_variation(service, &account, variation.clone()).await?;
+    check_variation_document(&variation_document, &variation);
+    let mut variation_two = fake_item_variation(item_doc.id);
+    variation_two.price = fixed_price(5000.0);
+    let variation_document_two = make_variation(service, &account, variation_two.clone()).await?;
+    check_variation_document(&variation_document_two, &variation_two);
+
+    let found = service
+        .list(
+            &account,
+            &query(ListCatalogQueryOptions {
+                min_price: Some(5000.0),
+                ..Default::default()
+            }),
+        )
+        .await?;
+    assert_eq!(found.len(), 1);
+    check_variation_document(&found[0], &variation_two);
+
+    let found = service
+        .list(
+            &account,
+            &SqlCatalogQueryOptions {
+                order_by: Some(by_created_at()),
+                ..query(ListCatalogQueryOptions {
+                    min_price: Some(2000.0),
+                    ..Default::default()
+                })
+            },
+        )
+        .await?;
+    assert_eq!(found.len(), 2);
+    check_variation_document(&found[0], &variation);
+    check_variation_document(&found[1], &variation_two);
+
+    let found = service
+        .list(
+            &account,
+            &query(ListCatalogQueryOptions {
+                max_price: Some(2000.0),
+                ..Default::default()
+            }),
+        )
+        .await?;
+    assert_eq!(found.len(), 1);
+    check_variation_document(&found[0], &variation);
+
+    let found = service
+        .list(
+            &account,
+            &SqlCatalogQueryOptions {
+                order_by: Some(by_created_at()),
+                ..query(ListCatalogQueryOptions {
+                    max_price: Some(5000.0),
+                    ..Default::default()
+                })
+            },
+        )
+        .await?;
+    assert_eq!(found.len(), 2);
+    check_variation_document(&found[0], &variation);
+    check_variation_document(&found[1], &variation_two);
+    Ok(())
+}
+
+pub async fn list_item_by_tags<S: Backend>(service: &S) -> Result<(), AnyHow> {
+    let account = random_account();
+    let doc = make_item(service, &account, fake_item()).await?;
+    let item = doc.catalog_object.item().unwrap();
+
+    let items_empty = service
+        .list(
+            &account,
+            &query(ListCatalogQueryOptions {
+                tags: Some(vec!["not-existing".to_string()]),
+                ..Default::default()
+            }),
+        )
+        .await?;
+    assert_eq!(items_empty.len(), 0);
+    let items_found = service
+        .list(
+            &account,
+            &query(ListCatalogQueryOptions {
+                tags: Some(item.tags.clone()),
+                ..Default::default()
+            }),
+        )
+        .await?;
+    assert_eq!(items_found.len(), 1);
+    check_item_document(&items_found[0], item);
+
+    // some of the tags, in another order, still match
+    let mut some_tags = item.tags[1..].to_vec();
+    some_tags.reverse();
+    let items_found = service
+        .list(
+            &account,
+            &query(ListCatalogQueryOptions {
+                tags: Some(some_tags.clone()),
+                ..Default::default()
+            }),
+        )
+        .await?;
+    assert_eq!(items_found.len(), 1);
+    assert_eq!(items_found[0].id, doc.id);
+
+    some_tags.push("not-existing".to_string());
+    let items_empty = service
+        .list(
+            &account,
+            &query(ListCatalogQueryOptions {
+                tags: Some(some_tags),
+                ..Default::default()
+            }),
+        )
+        .await?;
+    assert_eq!(items_empty.len(), 0);
+    Ok(())
+}
+
+pub async fn list_order_by_price<S: Backend>(service: &S) -> Result<(), AnyHow> {
+    let account = random_account();
+    let item_doc = make_item(service, &account, fake_item()).await?;
+    let mut ids = vec![];
+    for amount in [300.0, 10.0, 150.0] {
+        let mut variation = fake_item_variation(item_doc.id);
+        variation.price = fixed_price(amount);
+        ids.push(make_variation(service, &account, variation).await?.id);
+    }
+
+    for (direction, expected) in [
+        (Order::Asc, vec![ids[1], ids[2], ids[0]]),
+        (Order::Desc, vec![ids[0], ids[2], ids[1]]),
+    ] {
+        let found = service
+            .list(
+                &account,
+                &SqlCatalogQueryOptions {
+                    order_by: Some(OrderBy {
+                        field: CatalogColumnOrder::Price,
+                        direction,
+                    }),
+                    ..query(ListCatalogQueryOptions {
+                        min_price: Some(1.0),
+                        ..Default::default()
+                    })
+                },
+            )
+            .await?;
+        let found: Vec<Id> = found.iter().map(|doc| doc.id).collect();
+        assert_eq!(found, expected);
+    }
+    Ok(())
+}
+
+pub async fn list_only_returns_the_account_objects<S: Backend>(service: &S) -> Result<(), AnyHow> {
+    let account = random_account();
+    let other_account = random_account();
+    let item_doc = make_item(service, &account, fake_item()).await?;
+    make_variation(service, &account, fake_item_variation(item_doc.id)).await?;
+    make_item(service, &other_account, fake_item()).await?;
+
+    let found = service
+        .list(&account, &query(ListCatalogQueryOptions::default()))
+        .await?;
+    assert_eq!(found.len(), 2);
+    assert!(found.iter().all(|doc| doc.account == account));
+    Ok(())
+}
+
+pub async fn increase_item_in_variations<S: Backend>(service: &S) -> Result<(), AnyHow> {
+    let account = random_account();
+    let doc = make_item(service, &account, fake_item()).await?;
+    let variation = fake_item_variation(doc.id);
+    let variation_document = make_variation(service, &account, variation.clone()).await?;
+    check_variation_document(&variation_document, &variation);
+
+    for (units, expected) in [
+        (10, variation.available_units + 10),
+        (-10, variation.available_units),
+    ] {
+        service
+            .cmd(
+                &account,
+                CatalogCmd::IncreaseItemVariationUnits(IncreaseItemVariationUnitsPayload {
+                    id: variation_document.id,
+                    units,
+                }),
+            )
+            .await?;
+        let read = service.read(&account, &variation_document.id).await?;
+        assert_eq!(available_units(&read), expected);
+    }
+    Ok(())
+}
+
+pub async fn decrease_below_zero_units_fails<S: Backend>(service: &S) -> Result<(), AnyHow> {
+    let account = random_account();
+    let item_doc = make_item(service, &account, fake_item()).await?;
+    let variation_doc = make_variation(service, &account, fake_item_variation(item_doc.id)).await?;
+    let result = service
+        .cmd(
+            &account,
+            CatalogCmd::IncreaseItemVariationUnits(IncreaseItemVariationUnitsPayload {
+                id: variation_doc.id,
+                units: -available_units(&variation_doc) - 1,
+            }),
+        )
+        .await;
+    check_if_error_is(
+        result.unwrap_err(),
+        CatalogError::InsufficientUnits(variation_doc.id.to_string()),
+    );
+    let read = service.read(&account, &variation_doc.id).await?;
+    assert_eq!(available_units(&read), available_units(&variation_doc));
+    Ok(())
+}
+
+pub async fn increase_units_at_unknown_location_fails<S: Backend>(
+    service: &S,
+) -> Result<(), AnyHow> {
+    let account = random_account();
+    let item_doc = make_item(service, &account, fake_item()).await?;
+    let variation_doc = make_variation(service, &account, fake_item_variation(item_doc.id)).await?;
+    let result = service
+        .cmd(
+            &account,
+            CatalogCmd::IncreaseItemVariationUnitsAt(IncreaseItemVariationUnitsAtPayload {
+                id: variation_doc.id,
+                location_id: Id::default(),
+                units: 1,
+            }),
+        )
+        .await;
+    check_if_error_is(
+        result.unwrap_err(),
+        CatalogError::CatalogEntryNotFound(Id::default().to_string()),
+    );
+    Ok(())
+}
+
+pub async fn increase_units_of_unknown_variation_fails<S: Backend>(
+    service: &S,
+) -> Result<(), AnyHow> {
+    let account = random_account();
+    let result = service
+        .cmd(
+            &account,
+            CatalogCmd::IncreaseItemVariationUnits(IncreaseItemVariationUnitsPayload {
+                id: Id::default(),
+                units: 1,
+            }),
+        )
+        .await;
+    check_if_error_is(
+        result.unwrap_err(),
+        CatalogError::CatalogEntryNotFound(Id::default().to_string()),
+    );
+    Ok(())
+}
+
+async fn stocked_variation<S: Backend>(
+    service: &S,
+    account: &Account,
+    units: i32,
+) -> Result<(Document, Id, Id), AnyHow> {
+    let item_doc = make_item(service, account, fake_item()).await?;
+    let variation_doc = make_variation(service, account, fake_item_variation(item_doc.id)).await?;
+    let warehouse = service
+        .create_location(account, &fake_stock_location(StockLocationKind::Warehouse))
+        .await?;
+    let store = service
+        .create_location(account, &fake_stock_location(StockLocationKind::Store))
+        .await?;
+    service
+        .cmd(
+            account,
+            CatalogCmd::IncreaseItemVariationUnitsAt(IncreaseItemVariationUnitsAtPayload {
+                id: variation_doc.id,
+                location_id: warehouse.id,
+                units,
+            }),
+        )
+        .await?;
+    Ok((variation_doc, warehouse.id, store.id))
+}
+
+pub async fn transfer_units_between_locations<S: Backend>(service: &S) -> Result<(), AnyHow> {
+    let account = random_account();
+    let (variation_doc, warehouse, store) = stocked_variation(service, &account, 4).await?;
+    service
+        .cmd(
+            &account,
+            CatalogCmd::TransferItemVariationUnits(TransferItemVariationUnitsPayload {
+                id: variation_doc.id,
+                from_location_id: warehouse,
+                to_location_id: store,
+                units: 3,
+            }),
+        )
+        .await?;
+
+    let mut levels: Vec<(Id, i32)> = service
+        .stock_levels(&account, &variation_doc.id)
+        .await?
+        .iter()
+        .map(|level| (level.location_id, level.units))
+        .collect();
+    levels.sort();
+    let mut expected = vec![(warehouse, 1), (store, 3)];
+    expected.sort();
+    assert_eq!(levels, expected);
+
+    // moving units around doesn't change the total
+    let read = service.read(&account, &variation_doc.id).await?;
+    assert_eq!(available_units(&read), available_units(&variation_doc) + 4);
+
+    let found = service
+        .list(
+            &account,
+            &query(ListCatalogQueryOptions {
+                available_at: Some(store),
+                ..Default::default()
+            }),
+        )
+        .await?;
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].id, variation_doc.id);
+    Ok(())
+}
+
+pub async fn transfer_fails_without_enough_units<S: Backend>(service: &S) -> Result<(), AnyHow> {
+    let account = random_account();
+    let (variation_doc, warehouse, store) = stocked_variation(service, &account, 2).await?;
+    let result = service
+        .cmd(
+            &account,
+            CatalogCmd::TransferItemVariationUnits(TransferItemVariationUnitsPayload {
+                id: variation_doc.id,
+                from_location_id: warehouse,
+                to_location_id: store,
+                units: 3,
+            }),
+        )
+        .await;
+    check_if_error_is(
+        result.unwrap_err(),
+        CatalogError::InsufficientUnits(variation_doc.id.to_string()),
+    );
+    // nothing moved
+    let levels = service.stock_levels(&account, &variation_doc.id).await?;
+    let units: i32 = levels
+        .iter()
+        .filter(|level| level.location_id == warehouse)
+        .map(|level| level.units)
+        .sum();
+    assert_eq!(units, 2);
+    Ok(())
+}
+
+pub async fn transfer_to_the_same_location_fails<S: Backend>(service: &S) -> Result<(), AnyHow> {
+    let account = random_account();
+    let (variation_doc, warehouse, _) = stocked_variation(service, &account, 2).await?;
+    let result = service
+        .cmd(
+            &account,
+            CatalogCmd::TransferItemVariationUnits(TransferItemVariationUnitsPayload {
+                id: variation_doc.id,
+                from_location_id: warehouse,
+                to_location_id: warehouse,
+                units: 1,
+            }),
+        )
+        .await;
+    check_if_error_is(result.unwrap_err(), CatalogError::invalid_transfer());
+    Ok(())
+}
+
+pub async fn create_bulk<S: Backend>(service: &S) -> Result<(), AnyHow> {
+    let account = random_account();
+    let id_ref = String::from("#item-a");
+    let item_a = fake_item();
+    let variation_a_for_a = fake_item_variation(id_ref.clone());
+    let modification_a_for_a = fake_item_modification(id_ref.clone());
+    let control_a_for_a = fake_item_control(id_ref.clone());
+
+    let ItemControl {
+        mut control,
+        item_id,
+    } = control_a_for_a;
+
+    if let Control::Matrix(ref mut control) = control {
+        let key = control.key_template.clone().replace(":color", "Red");
+        let key = key.replace(":size", "L");
+        control.combinations.entry(key).or_insert(id_ref.clone());
+    }
+
+    let control_a_for_a = ItemControl { control, item_id };
+
+    let items: Vec<CatalogObjectBulkDocument<String>> = vec![
+        CatalogObjectBulkDocument {
+            id: Some(id_ref.clone()),
+            catalog_object: CatalogObject::Item(item_a.clone()),
+        },
+        CatalogObjectBulkDocument {
+            id: None,
+            catalog_object: CatalogObject::Variation(variation_a_for_a.clone()),
+        },
+        CatalogObjectBulkDocument {
+            id: None,
+            catalog_object: CatalogObject::Modification(modification_a_for_a.clone()),
+        },
+        CatalogObjectBulkDocument {
+            id: None,
+            catalog_object: CatalogObject::Control(control_a_for_a.clone()),
+        },
+    ];
+
+    let items_docs = service.bulk_create(&account, &items).await?;
+    assert_eq!(items_docs.len(), items.len());
+
+    for item_doc in items_docs.iter() {
+        match &item_doc.catalog_object {
+            CatalogObject::Item(_) => {
+                check_item_document(item_doc, &item_a);
+            }
+            CatalogObject::Modification(_) => {
+                check_modification_document(item_doc, &modification_a_for_a);
+            }
+            CatalogObject::Variation(_) => {
+                check_variation_document(item_doc, &variation_a_for_a);
+            }
+            CatalogObject::Control(_) => {
+                check_control_document(item_doc, &control_a_for_a);
+            }
+            _ => panic!("never should flow through here"),
+        }
+    }
+    Ok(())
+}
+
+pub async fn create_bulk_fails_if_reference_doesnt_exists<S: Backend>(
+    service: &S,
+) -> Result<(), AnyHow> {
+    let account = random_account();
+    let items = vec![CatalogObjectBulkDocument {
+        id: None,
+        catalog_object: CatalogObject::Variation(fake_item_variation("#missing".to_string())),
+    }];
+    let result = service.bulk_create(&account, &items).await;
+    check_if_error_is(
+        result.unwrap_err(),
+        CatalogError::BulkReferenceNotExist("#missing".to_string()),
+    );
+    Ok(())
+}
+
+fn everything() -> SqlCatalogQueryOptions {
+    SqlCatalogQueryOptions {
+        limit: None,
+        order_by: None,
+        options: Default::default(),
+    }
+}
+
+pub async fn create_fails_with_every_violation<S: Backend>(service: &S) -> Result<(), AnyHow> {
+    let account = random_account();
+    let item_doc = make_item(service, &account, fake_item()).await?;
+    let mut variation = fake_item_variation(item_doc.id);
+    variation.sku = " ".to_string();
+    variation.available_units = -1;
+    variation.images = vec![Image { url: String::new() }];
+    let result = make_variation(service, &account, variation).await;
+    check_if_error_is(
+        result.unwrap_err(),
+        CatalogError::ValidationFailed(vec![
+            Violation::new("/data/sku", "not_blank", "must not be blank"),
+            Violation::new("/data/images/0/url", "not_blank", "must not be blank"),
+            Violation::new(
+                "/data/available_units",
+                "not_negative",
+                "must not be negative",
+            ),
+        ]),
+    );
+
+    let mut delivery = fake_item_delivery(item_doc.id);
+    delivery.delivery = Delivery::Shipping {
+        width_mm: 0,
+        length_mm: 0,
+        height_mm: 0,
+        weight_grams: 0,
+    };
+    let result = service
+        .create(&account, &CatalogObject::Delivery(delivery))
+        .await;
+    match result.unwrap_err() {
+        CatalogError::ValidationFailed(violations) => {
+            let pointers: Vec<&str> = violations
+                .iter()
+                .map(|violation| violation.pointer.as_str())
+                .collect();
+            assert_eq!(
+                pointers,
+                vec![
+                    "/data/delivery_width_mm",
+                    "/data/delivery_length_mm",
+                    "/data/delivery_height_mm",
+                    "/data/delivery_weight_grams",
+                ]
+            );
+        }
+        error => panic!("unexpected error {:?}", error),
+    }
+    assert_eq!(service.list(&account, &everything()).await?.len(), 1);
+    Ok(())
+}
+
+pub async fn update_fails_with_every_violation<S: Backend>(service: &S) -> Result<(), AnyHow> {
+    let account = random_account();
+    let item = fake_item();
+    let item_doc = make_item(service, &account, item.clone()).await?;
+    let mut invalid = item.clone();
+    invalid.name = String::new();
+    invalid.tags.push(String::new());
+    let result = service
+        .update(&account, &item_doc.id, &CatalogObject::Item(invalid))
+        .await;
+    check_if_error_is(
+        result.unwrap_err(),
+        CatalogError::ValidationFailed(vec![
+            Violation::new("/data/name", "not_blank", "must not be blank"),
+            Violation::new(
+                format!("/data/tags/{}", item.tags.len()),
+                "not_blank",
+                "must not be blank",
+            ),
+        ]),
+    );
+    let document = service.read(&account, &item_doc.id).await?;
+    assert_eq!(document.catalog_object, CatalogObject::Item(item));
+    Ok(())
+}
+
+pub async fn create_bulk_fails_with_every_violation<S: Backend>(service: &S) -> Result<(), AnyHow> {
+    let account = random_account();
+    let id_ref = String::from("#item-a");
+    let mut variation = fake_item_variation(id_ref.clone());
+    variation.price = Price::Fixed {
+        amount: -1.0,
+        asset_name: String::new(),
+        asset_scale: 2,
+    };
+    let mut control = fake_item_control(id_ref.clone());
+    if let Control::Matrix(ref mut matrix) = control.control {
+        matrix.key_template = ":color".to_string();
+    }
+    let items = vec![
+        CatalogObjectBulkDocument {
+            id: Some(id_ref.clone()),
+            catalog_object: CatalogObject::Item(fake_item()),
+        },
+        CatalogObjectBulkDocument {
+            id: None,
+            catalog_object: CatalogObject::Variation(variation),
+        },
+        CatalogObjectBulkDocument {
+            id: None,
+            catalog_object: CatalogObject::Control(control),
+        },
+    ];
+    let result = service.bulk_create(&account, &items).await;
+    check_if_error_is(
+        result.unwrap_err(),
+        CatalogError::ValidationFailed(vec![
+            Violation::new(
+                "/1/data/price_amount",
+                "not_negative",
+                "must be a positive number or zero",
+            ),
+            Violation::new("/1/data/price_asset_name", "not_blank", "must not be blank"),
+            Violation::new(
+                "/2/data/control_data/props/1/name",
+                "in_key_template",
+                "isn't part of the key template",
+            ),
+        ]),
+    );
+    // nothing is created when any object is invalid
+    assert!(service.list(&account, &everything()).await?.is_empty());
+    Ok(())
+}
+
+pub async fn patch_variation<S: Backend>(service: &S) -> Result<(), AnyHow> {
+    let account = random_account();
+    let item_doc = make_item(service, &account, fake_item()).await?;
+    let mut variation = fake_item_variation(item_doc.id);
+    variation.upc = Some("012345678905".to_string());
+    let variation_doc = make_variation(service, &account, variation.clone()).await?;
+    let changes = serde_json::json!({
+        "data": { "name": "Patched", "price_amount": 9.5, "upc": null },
+    });
+    let document = patch(service, &account, &variation_doc.id, &changes, None).await?;
+    // only the fields of the patch change
+    let mut expected = variation;
+    expected.name = "Patched".to_string();
+    expected.price = fixed_price(9.5);
+    expected.upc = None;
+    check_variation_document(&document, &expected);
+    assert_eq!(document.catalog_object, CatalogObject::Variation(expected));
+    let stored = service.read(&account, &variation_doc.id).await?;
+    assert_eq!(stored.catalog_object, document.catalog_object);
+    Ok(())
+}
+
+pub async fn patch_fails_if_the_version_changed<S: Backend>(service: &S) -> Result<(), AnyHow> {
+    let account = random_account();
+    let item_doc = make_item(service, &account, fake_item()).await?;
+    // another writer updates the object after it was read
+    let mut item = as_value!(item_doc.catalog_object.clone(), CatalogObject::Item).unwrap();
+    item.description = "Updated".to_string();
+    let updated = service
+        .update(&account, &item_doc.id, &CatalogObject::Item(item))
+        .await?;
+    assert!(updated.version > item_doc.version);
+
+    let changes = serde_json::json!({ "data": { "name": "Patched" } });
+    let result = patch(
+        service,
+        &account,
+        &item_doc.id,
+        &changes,
+        Some(&item_doc.version),
+    )
+    .await;
+    check_if_error_is(
+        result.unwrap_err(),
+        CatalogError::VersionMismatch(item_doc.id.to_string()),
+    );
+    // the version is checked by the write itself too
+    let result = service
+        .update_at_version(
+            &account,
+            &item_doc.id,
+            &item_doc.catalog_object,
+            &item_doc.version,
+        )
+        .await;
+    check_if_error_is(
+        result.unwrap_err(),
+        CatalogError::VersionMismatch(item_doc.id.to_string()),
+    );
+    let document = service.read(&account, &item_doc.id).await?;
+    assert_eq!(document.catalog_object, updated.catalog_object);
+    assert_eq!(document.version, updated.version);
+
+    let document = patch(
+        service,
+        &account,
+        &item_doc.id,
+        &changes,
+        Some(&updated.version),
+    )
+    .await?;
+    assert_eq!(
+        as_value!(document.catalog_object, CatalogObject::Item)
+            .unwrap()
+            .name,
+        "Patched"
+    );
+    assert!(document.version > updated.version);
+    Ok(())
+}
+
+pub async fn patch_fails_with_violations<S: Backend>(service: &S) -> Result<(), AnyHow> {
+    let account = random_account();
+    let item_doc = make_item(service, &account, fake_item()).await?;
+    let variation_doc = make_variation(service, &account, fake_item_variation(item_doc.id)).await?;
+    let id = variation_doc.id;
+
+    let changes = serde_json::json!({ "data": { "name": " " } });
+    let result = patch(service, &account, &id, &changes, None).await;
+    check_if_error_is(
+        result.unwrap_err(),
+        CatalogError::ValidationFailed(vec![Violation::new(
+            "/data/name",
+            "not_blank",
+            "must not be blank",
+        )]),
+    );
+    let changes = serde_json::json!({ "type": "Item" });
+    let result = patch(service, &account, &id, &changes, None).await;
+    check_if_error_is(
+        result.unwrap_err(),
+        CatalogError::ValidationFailed(vec![Violation::new(
+            "/type",
+            "immutable",
+            "the type of an object can't be changed",
+        )]),
+    );
+    // the fields keep their types
+    let changes = serde_json::json!({ "data": { "price_amount": "cheap", "sku": null } });
+    let result = patch(service, &account, &id, &changes, None).await;
+    assert!(matches!(
+        result.unwrap_err(),
+        CatalogError::ValidationFailed(violations) if violations[0].constraint == "format"
+    ));
+    let document = service.read(&account, &id).await?;
+    assert_eq!(document.catalog_object, variation_doc.catalog_object);
+
+    let result = patch(service, &account, &Id::default(), &changes, None).await;
+    check_if_error_is(
+        result.unwrap_err(),
+        CatalogError::CatalogEntryNotFound(Id::default().to_string()),
+    );
+    Ok(())
+}
+
+// prices are f32, the backends hand them out with the precision they keep
+fn f32_numbers(value: Value) -> Value {
+    match value {
+        Value::Number(number) if number.is_f64() => {
+            json!(number.as_f64().unwrap_or_default() as f32)
+        }
+        Value::Array(values) => Value::Array(values.into_iter().map(f32_numbers).collect()),
+        Value::Object(fields) => Value::Object(
+            fields
+                .into_iter()
+                .map(|(name, value)| (name, f32_numbers(value)))
+                .collect(),
+        ),
+        other => other,
+    }
+}
+
+pub async fn list_fields_matches_the_documents<S: Backend>(service: &S) -> Result<(), AnyHow> {
+    let account = random_account();
+    let mut item = fake_item();
+    item.warranty_time = Some(Time::Fixed { seconds: 3600 });
+    let item_doc = make_item(service, &account, item).await?;
+    let mut variation = fake_item_variation(item_doc.id);
+    variation.extra_attributes = Some(HashMap::from([("color".to_string(), "red".to_string())]));
+    variation.enabled = false;
+    make_variation(service, &account, variation).await?;
+    for object in [
+        CatalogObject::Modification(fake_item_modification(item_doc.id)),
+        CatalogObject::Delivery(fake_item_delivery(item_doc.id)),
+        CatalogObject::Control(fake_item_control(item_doc.id)),
+    ] {
+        service.create(&account, &object).await?;
+    }
+
+    let every_field: Vec<&str> = DOCUMENT_FIELDS
+        .iter()
+        .chain(DATA_FIELDS.iter().flat_map(|(_, fields)| fields.iter()))
+        .copied()
+        .collect();
+    for fields in [
+        vec![],
+        vec!["name", "price_amount"],
+        vec!["created_at", "version", "enabled", "warranty_time_seconds"],
+        every_field,
+    ] {
+        let fields = Fields::new(&fields)?;
+        for options in [
+            ListCatalogQueryOptions::default(),
+            ListCatalogQueryOptions {
+                tags: Some(item_doc.catalog_object.item().unwrap().tags.clone()),
+                ..Default::default()
+            },
+        ] {
+            let query = query(options);
+            let projected = service.list_fields(&account, &query, &fields).await?;
+            let documents = service
+                .list(&account, &query)
+                .await?
+                .iter()
+                .map(|document| project(document, &fields))
+                .collect::<Result<Vec<_>, _>>()?;
+            assert_eq!(
+                f32_numbers(Value::Array(projected)),
+                f32_numbers(Value::Array(documents)),
+                "{:?}",
+                fields
+            );
+        }
+    }
+    Ok(())
+}
+
+pub async fn list_fields_keeps_the_fields_asked_for<S: Backend>(service: &S) -> Result<(), AnyHow> {
+    let account = random_account();
+    let item_doc = make_item(service, &account, fake_item()).await?;
+    let mut variation = fake_item_variation(item_doc.id);
+    variation.price = fixed_price(12.5);
+    let variation_doc = make_variation(service, &account, variation.clone()).await?;
+    let fields = Fields::new(&["id", "name", "price_amount", "processing_time_seconds"])?;
+
+    let mut projected = service
+        .list_fields(&account, &query(Default::default()), &fields)
+        .await?;
+    projected.sort_by_key(|object| object["type"].to_string());
+    assert_eq!(
+        projected,
+        vec![
+            json!({
+                "id": item_doc.id,
+                "type": "Item",
+                "data": { "name": item_doc.catalog_object.item().unwrap().name },
+            }),
+            json!({
+                "id": variation_doc.id,
+                "type": "Variation",
+                "data": {
+                    "name": variation.name,
+                    "price_amount": 12.5,
+                    "processing_time_seconds": null,
+                },
+            }),
+        ]
+    );
+
+    let unknown = Fields::new(&["name", "password"]).unwrap_err();
+    assert_eq!(
+        unknown,
+        CatalogError::bad_request("the field password doesn't exist")
+    );
+    Ok(())
+}
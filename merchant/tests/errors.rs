@@ -0,0 +1,191 @@
+mod fixtures;
+mod utils;
+
+use std::error::Error;
+
+use fixtures::catalog::{fake_item, fake_item_variation};
+use merchant::catalog::backend::{CatalogSQLService, SqlCatalogObject};
+use merchant::catalog::error::Problem;
+use merchant::catalog::service::{CatalogError, CatalogService};
+use merchant::catalog::validation::Violation;
+use serde_json::json;
+use utils::{restore_db, AnyHow};
+
+const CATALOG_ACCOUNT: &str = "account";
+
+#[test]
+fn every_error_has_a_code_and_a_status() {
+    let errors = [
+        (CatalogError::database("disk I/O error"), "E_DATABASE", 500),
+        (CatalogError::DatabaseBusy, "E_DATABASE_BUSY", 503),
+        (
+            CatalogError::CatalogEntryNotFound("1".to_string()),
+            "E_NOT_FOUND",
+            404,
+        ),
+        (CatalogError::unknown_item(1), "E_BAD_REQUEST", 400),
+        (CatalogError::mapping("bad row"), "E_MAPPING", 500),
+        (
+            CatalogError::BulkReferenceNotExist("ref".to_string()),
+            "E_BULK_ACTION",
+            400,
+        ),
+        (
+            CatalogError::InsufficientUnits("1".to_string()),
+            "E_INSUFFICIENT_UNITS",
+            409,
+        ),
+        (
+            CatalogError::VersionMismatch("1".to_string()),
+            "E_VERSION_MISMATCH",
+            412,
+        ),
+        (CatalogError::Unsupported, "E_UNSUPPORTED", 501),
+        (
+            CatalogError::UnsupportedMediaType("application/merge-patch+json".to_string()),
+            "E_UNSUPPORTED_MEDIA_TYPE",
+            415,
+        ),
+        (CatalogError::ValidationFailed(vec![]), "E_VALIDATION", 422),
+    ];
+    for (error, code, status) in errors {
+        assert_eq!(error.code(), code);
+        assert_eq!(error.status(), status);
+        assert!(!error.to_string().is_empty(), "{:?} has no message", error);
+        assert!(!error.title().is_empty());
+    }
+}
+
+#[test]
+fn messages_tell_the_reason() {
+    assert_eq!(
+        CatalogError::unknown_item(7).to_string(),
+        "the item 7 doesn't exist"
+    );
+    assert_eq!(
+        CatalogError::item_in_use(7).to_string(),
+        "the item 7 still has objects that refer to it"
+    );
+    assert_eq!(
+        CatalogError::CatalogEntryNotFound("7".to_string()).to_string(),
+        "the object 7 doesn't exist"
+    );
+    // the source is for the logs only
+    assert_eq!(
+        CatalogError::database("no such table: catalog").to_string(),
+        "the database failed"
+    );
+}
+
+#[test]
+fn sources_are_chained() {
+    let error = CatalogError::mapping("unknown type: thing");
+    assert_eq!(error.source().unwrap().to_string(), "unknown type: thing");
+    assert_eq!(error, CatalogError::mapping("another source"));
+    assert!(CatalogError::unknown_item(1).source().is_none());
+}
+
+#[async_std::test]
+async fn database_errors_keep_the_sqlx_error() -> Result<(), AnyHow> {
+    let pool = restore_db().await?;
+    let catalog_service = CatalogSQLService::new(pool.clone());
+    pool.close().await;
+    let error = catalog_service
+        .read(&CATALOG_ACCOUNT.to_string(), &1)
+        .await
+        .expect_err("a closed pool");
+    assert!(matches!(error, CatalogError::DatabaseError(_)));
+    let source = error.source().expect("the sqlx error");
+    assert!(matches!(
+        source.downcast_ref::<sqlx::Error>(),
+        Some(sqlx::Error::PoolClosed)
+    ));
+    Ok(())
+}
+
+#[async_std::test]
+async fn problems_describe_the_error() -> Result<(), AnyHow> {
+    let catalog_service = CatalogSQLService::new(restore_db().await?);
+    let account = CATALOG_ACCOUNT.to_string();
+    let error = catalog_service
+        .read(&account, &4242)
+        .await
+        .expect_err("an unknown object");
+    assert_eq!(
+        serde_json::to_value(Problem::from(&error))?,
+        json!({
+            "type": "urn:merchant:error:E_NOT_FOUND",
+            "title": "Not found",
+            "status": 404,
+            "detail": "the object 4242 doesn't exist",
+            "code": "E_NOT_FOUND",
+            "id": "4242",
+        })
+    );
+
+    let mut item = fake_item();
+    item.name = " ".to_string();
+    let error = catalog_service
+        .create(&account, &SqlCatalogObject::Item(item))
+        .await
+        .expect_err("a blank name");
+    assert_eq!(
+        serde_json::to_value(Problem::from(&error))?,
+        json!({
+            "type": "urn:merchant:error:E_VALIDATION",
+            "title": "Invalid object",
+            "status": 422,
+            "detail": "the object has 1 invalid field",
+            "code": "E_VALIDATION",
+            "violations": [{
+                "pointer": "/data/name",
+                "constraint": "not_blank",
+                "message": "must not be blank",
+            }],
+        })
+    );
+
+    let error = catalog_service
+        .create(
+            &account,
+            &SqlCatalogObject::Variation(fake_item_variation(4242)),
+        )
+        .await
+        .expect_err("an unknown item");
+    let problem = Problem::from(&error);
+    assert_eq!(problem.code, "E_BAD_REQUEST");
+    assert_eq!(problem.detail, "the item 4242 doesn't exist");
+    Ok(())
+}
+
+#[test]
+fn http_errors_have_a_code_by_status() {
+    let codes = [
+        (400, "E_BAD_REQUEST"),
+        (401, "E_UNAUTHORIZED"),
+        (403, "E_FORBIDDEN"),
+        (404, "E_NOT_FOUND"),
+        (415, "E_BAD_REQUEST"),
+        (422, "E_UNPROCESSABLE"),
+        (500, "E_INTERNAL"),
+        (501, "E_UNSUPPORTED"),
+    ];
+    for (status, code) in codes {
+        let problem = Problem::for_status(status, "title", "detail");
+        assert_eq!(problem.status, status);
+        assert_eq!(problem.code, code);
+        assert_eq!(problem.kind, format!("urn:merchant:error:{}", code));
+    }
+}
+
+#[test]
+fn problems_are_read_back() -> Result<(), AnyHow> {
+    let problem = Problem::from(&CatalogError::ValidationFailed(vec![Violation::new(
+        "/0/data/sku",
+        "not_blank",
+        "must not be blank",
+    )]));
+    let body = serde_json::to_string(&problem)?;
+    assert_eq!(serde_json::from_str::<Problem>(&body)?, problem);
+    Ok(())
+}
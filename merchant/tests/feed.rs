@@ -0,0 +1,154 @@
+mod fixtures;
+mod utils;
+
+use fixtures::catalog::{fake_item, fake_item_variation};
+use merchant::catalog::backend::{CatalogSQLService, SqlCatalogObject, SqlCatalogQueryOptions};
+use merchant::catalog::feed::{feed_entries, write_rss, write_tsv, FeedOptions, TSV_COLUMNS};
+use merchant::catalog::models::{Image, Price};
+use merchant::catalog::service::CatalogService;
+use utils::{restore_db, AnyHow};
+
+const CATALOG_ACCOUNT: &str = "account";
+
+fn everything() -> SqlCatalogQueryOptions {
+    SqlCatalogQueryOptions {
+        limit: None,
+        order_by: None,
+        options: Default::default(),
+    }
+}
+
+// a shirt with a variation in stock and another sold out, a disabled item
+// and a disabled variation
+async fn fill_catalog(catalog_service: &CatalogSQLService) -> Result<(u32, u32), AnyHow> {
+    let account = CATALOG_ACCOUNT.to_string();
+    let mut item = fake_item();
+    item.name = "Shirt".to_string();
+    item.description = "Cotton & <linen>".to_string();
+    item.images = vec![Image {
+        url: "https://shop.test/shirt.png".to_string(),
+    }];
+    let item_doc = catalog_service
+        .create(&account, &SqlCatalogObject::Item(item))
+        .await?;
+    let mut in_stock = fake_item_variation(item_doc.id);
+    in_stock.name = "M".to_string();
+    in_stock.sku = "SH-M".to_string();
+    in_stock.upc = Some("0012345678905".to_string());
+    in_stock.price = Price::Fixed {
+        amount: 12.5,
+        asset_name: "USD".to_string(),
+        asset_scale: 2,
+    };
+    in_stock.images = vec![Image {
+        url: "https://shop.test/shirt-m.png".to_string(),
+    }];
+    let in_stock_doc = catalog_service
+        .create(&account, &SqlCatalogObject::Variation(in_stock))
+        .await?;
+    let mut sold_out = fake_item_variation(item_doc.id);
+    sold_out.available_units = 0;
+    catalog_service
+        .create(&account, &SqlCatalogObject::Variation(sold_out))
+        .await?;
+    let mut disabled = fake_item_variation(item_doc.id);
+    disabled.enabled = false;
+    catalog_service
+        .create(&account, &SqlCatalogObject::Variation(disabled))
+        .await?;
+
+    let mut disabled_item = fake_item();
+    disabled_item.enabled = false;
+    let disabled_item_doc = catalog_service
+        .create(&account, &SqlCatalogObject::Item(disabled_item))
+        .await?;
+    catalog_service
+        .create(
+            &account,
+            &SqlCatalogObject::Variation(fake_item_variation(disabled_item_doc.id)),
+        )
+        .await?;
+    Ok((item_doc.id, in_stock_doc.id))
+}
+
+#[async_std::test]
+async fn feeds_list_enabled_variations() -> Result<(), AnyHow> {
+    let catalog_service = CatalogSQLService::new(restore_db().await?);
+    let (item_id, variation_id) = fill_catalog(&catalog_service).await?;
+    let documents = catalog_service
+        .list(&CATALOG_ACCOUNT.to_string(), &everything())
+        .await?;
+    let options = FeedOptions {
+        link: Some("https://shop.test/products/{id}".to_string()),
+        ..FeedOptions::default()
+    };
+
+    let entries = feed_entries(&documents, &options);
+    assert_eq!(entries.len(), 2);
+    let entry = entries
+        .iter()
+        .find(|entry| entry.id == variation_id.to_string())
+        .unwrap();
+    assert_eq!(entry.title, "Shirt - M");
+    assert_eq!(entry.price, "12.50 USD");
+    assert_eq!(entry.availability, "in_stock");
+    assert_eq!(entry.gtin.as_deref(), Some("0012345678905"));
+    assert_eq!(entry.mpn, "SH-M");
+    assert_eq!(entry.item_group_id, item_id.to_string());
+    assert_eq!(
+        entry.link,
+        Some(format!("https://shop.test/products/{}", variation_id))
+    );
+    // the image of the variation first
+    assert_eq!(
+        entry.image_link.as_deref(),
+        Some("https://shop.test/shirt-m.png")
+    );
+    assert_eq!(
+        entry.additional_image_links,
+        vec!["https://shop.test/shirt.png".to_string()]
+    );
+    let sold_out = entries
+        .iter()
+        .find(|entry| entry.id != variation_id.to_string())
+        .unwrap();
+    assert_eq!(sold_out.availability, "out_of_stock");
+    assert_eq!(sold_out.gtin, None);
+    Ok(())
+}
+
+#[async_std::test]
+async fn feeds_are_written_as_rss_and_tsv() -> Result<(), AnyHow> {
+    let catalog_service = CatalogSQLService::new(restore_db().await?);
+    let (_, variation_id) = fill_catalog(&catalog_service).await?;
+    let documents = catalog_service
+        .list(&CATALOG_ACCOUNT.to_string(), &everything())
+        .await?;
+    let entries = feed_entries(&documents, &FeedOptions::default());
+
+    let rss = write_rss("account", "https://shop.test/feed.xml", &entries);
+    assert!(rss.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>"));
+    assert!(rss.contains("xmlns:g=\"http://base.google.com/ns/1.0\""));
+    assert_eq!(rss.matches("<item>").count(), 2);
+    assert!(rss.contains(&format!("<g:id>{}</g:id>", variation_id)));
+    assert!(rss.contains("<description>Cotton &amp; &lt;linen&gt;</description>"));
+    assert!(rss.contains("<g:price>12.50 USD</g:price>"));
+    assert!(rss.contains("<g:gtin>0012345678905</g:gtin>"));
+    assert!(rss.contains("<g:identifier_exists>no</g:identifier_exists>"));
+    // no link without the option
+    assert!(!rss.contains("<item>\n    <link>"));
+
+    let tsv = write_tsv(&entries);
+    let lines: Vec<Vec<&str>> = tsv.lines().map(|line| line.split('\t').collect()).collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0], TSV_COLUMNS.to_vec());
+    assert!(lines.iter().all(|line| line.len() == TSV_COLUMNS.len()));
+    let row = lines
+        .iter()
+        .find(|line| line[0] == variation_id.to_string())
+        .unwrap();
+    assert_eq!(row[2], "Cotton & <linen>");
+    assert_eq!(row[6], "12.50 USD");
+    assert_eq!(row[7], "in_stock");
+    Ok(())
+}
@@ -1,10 +1,10 @@
 use std::collections::HashMap;
 
 use fake::faker::company::en::Buzzword;
-use merchant::catalog::backend::{Id, SqlCatalogItemVariation};
 use merchant::catalog::models::{
     Control, Delivery, Item, ItemCategory, ItemControl, ItemDelivery, ItemMeasurmentUnits,
-    ItemModification, ItemVariation, MatrixControl, MatrixProp, Price,
+    ItemModification, ItemVariation, MatrixControl, MatrixProp, Price, StockLocation,
+    StockLocationKind,
 };
 
 use fake::faker::lorem::en::*;
@@ -41,6 +41,7 @@ pub fn fake_item_variation<T>(item_id: T) -> ItemVariation<T> {
         },
         sku: Buzzword().fake(),
         available_units: 10,
+        reorder_threshold: None,
         upc: None,
     }
 }
@@ -101,3 +102,12 @@ pub fn fake_item_modification<T>(id: T) -> ItemModification<T> {
         },
     }
 }
+
+pub fn fake_stock_location(kind: StockLocationKind) -> StockLocation {
+    StockLocation {
+        name: Name(EN).fake(),
+        kind,
+        address: None,
+        enabled: true,
+    }
+}
@@ -0,0 +1,299 @@
+mod fixtures;
+mod utils;
+
+use async_std::stream::StreamExt;
+use fixtures::catalog::{fake_item, fake_item_delivery, fake_item_variation};
+use merchant::catalog::any::AnyCatalogService;
+use merchant::catalog::backend::{CatalogSQLService, SqlCatalogObject};
+use merchant::catalog::memory::CatalogMemoryService;
+use merchant::catalog::models::{Actor, CatalogObject};
+use merchant::catalog::service::{CatalogAuditLog, CatalogService, ListAuditOptions};
+use merchant::graphql::{schema, CatalogSchema, GraphQLRequest};
+use serde_json::{json, Value};
+use utils::{restore_db, AnyHow};
+
+const CATALOG_ACCOUNT: &str = "account";
+
+fn request(query: &str, variables: Value) -> async_graphql::Request {
+    GraphQLRequest {
+        query: query.to_string(),
+        operation_name: None,
+        variables: Some(variables),
+    }
+    .into_request(Actor {
+        id: "graphql".to_string(),
+        claimed_id: None,
+        address: None,
+        user_agent: None,
+    })
+}
+
+async fn execute(schema: &CatalogSchema, query: &str, variables: Value) -> Value {
+    let response = schema.execute(request(query, variables)).await;
+    serde_json::to_value(&response).unwrap()
+}
+
+#[async_std::test]
+async fn items_resolve_their_children() -> Result<(), AnyHow> {
+    let catalog_service = CatalogMemoryService::new();
+    let account = CATALOG_ACCOUNT.to_string();
+    let item = catalog_service
+        .create(&account, &SqlCatalogObject::Item(fake_item()))
+        .await?;
+    let variation = catalog_service
+        .create(
+            &account,
+            &SqlCatalogObject::Variation(fake_item_variation(item.id)),
+        )
+        .await?;
+    catalog_service
+        .create(
+            &account,
+            &SqlCatalogObject::Delivery(fake_item_delivery(item.id)),
+        )
+        .await?;
+    let schema = schema(AnyCatalogService::Memory(catalog_service));
+
+    let response = execute(
+        &schema,
+        r#"query($account: String!, $id: Int!) {
+            object(account: $account, id: $id) {
+                ... on Item {
+                    id
+                    variations { id sku item { id } }
+                    modifications { id }
+                    deliveries { shipping { weightGrams } }
+                }
+            }
+        }"#,
+        json!({ "account": account, "id": item.id }),
+    )
+    .await;
+    assert_eq!(response["errors"], Value::Null, "{}", response);
+    let object = &response["data"]["object"];
+    assert_eq!(object["id"], item.id);
+    assert_eq!(object["variations"][0]["id"], variation.id);
+    assert_eq!(object["variations"][0]["item"]["id"], item.id);
+    assert_eq!(object["modifications"], json!([]));
+    assert_eq!(object["deliveries"].as_array().unwrap().len(), 1);
+    Ok(())
+}
+
+#[async_std::test]
+async fn the_catalog_is_filtered_as_the_rest_api() -> Result<(), AnyHow> {
+    let catalog_service = CatalogSQLService::new(restore_db().await?);
+    let account = CATALOG_ACCOUNT.to_string();
+    let mut shirt = fake_item();
+    shirt.tags = vec!["summer".to_string()];
+    let shirt = catalog_service
+        .create(&account, &SqlCatalogObject::Item(shirt))
+        .await?;
+    let mut coat = fake_item();
+    coat.tags = vec!["winter".to_string()];
+    catalog_service
+        .create(&account, &SqlCatalogObject::Item(coat))
+        .await?;
+    let schema = schema(AnyCatalogService::Sqlite(catalog_service));
+
+    let response = execute(
+        &schema,
+        r#"query($account: String!) {
+            catalog(account: $account, tags: ["summer"], limit: 10, orderBy: { field: CREATED_AT, direction: DESC }) {
+                ... on Item { id tags }
+            }
+        }"#,
+        json!({ "account": account }),
+    )
+    .await;
+    assert_eq!(
+        response["data"]["catalog"],
+        json!([{ "id": shirt.id, "tags": ["summer"] }]),
+        "{}",
+        response
+    );
+    Ok(())
+}
+
+#[async_std::test]
+async fn mutations_write_as_the_actor() -> Result<(), AnyHow> {
+    let catalog_service = CatalogSQLService::new(restore_db().await?);
+    let account = CATALOG_ACCOUNT.to_string();
+    let schema = schema(AnyCatalogService::Sqlite(catalog_service.clone()));
+
+    let response = execute(
+        &schema,
+        r#"mutation($account: String!, $object: JSON!) {
+            create(account: $account, object: $object) { ... on Item { id name } }
+        }"#,
+        json!({
+            "account": account,
+            "object": serde_json::to_value(SqlCatalogObject::Item(fake_item()))?,
+        }),
+    )
+    .await;
+    assert_eq!(response["errors"], Value::Null, "{}", response);
+    let id = response["data"]["create"]["id"].as_u64().unwrap() as u32;
+    catalog_service.read(&account, &id).await?;
+
+    let response = execute(
+        &schema,
+        r#"mutation($account: String!, $variation: JSON!) {
+            create(account: $account, object: $variation) { ... on Variation { id availableUnits } }
+        }"#,
+        json!({
+            "account": account,
+            "variation": serde_json::to_value(SqlCatalogObject::Variation(fake_item_variation(id)))?,
+        }),
+    )
+    .await;
+    assert_eq!(
+        response["data"]["create"]["availableUnits"], 10,
+        "{}",
+        response
+    );
+    let variation_id = response["data"]["create"]["id"].clone();
+
+    let response = execute(
+        &schema,
+        r#"mutation($account: String!, $cmd: JSON!) {
+            cmd(account: $account, cmd: $cmd)
+        }"#,
+        json!({
+            "account": account,
+            "cmd": { "type": "IncreaseItemVariationUnits", "data": { "id": variation_id, "units": 3 } },
+        }),
+    )
+    .await;
+    assert_eq!(response["data"]["cmd"], true, "{}", response);
+    let variation = catalog_service
+        .read(&account, &(variation_id.as_u64().unwrap() as u32))
+        .await?;
+    match variation.catalog_object {
+        CatalogObject::Variation(variation) => assert_eq!(variation.available_units, 13),
+        other => panic!("not a variation: {:?}", other),
+    }
+
+    let audit = catalog_service
+        .audit(&account, &ListAuditOptions::default())
+        .await?;
+    assert_eq!(audit.len(), 3);
+    assert!(audit.iter().all(|entry| entry.actor.id == "graphql"));
+    Ok(())
+}
+
+#[async_std::test]
+async fn errors_carry_the_problem() -> Result<(), AnyHow> {
+    let schema = schema(AnyCatalogService::Memory(CatalogMemoryService::new()));
+    let mut item = fake_item();
+    item.name = " ".to_string();
+
+    let response = execute(
+        &schema,
+        r#"mutation($object: JSON!) {
+            create(account: "account", object: $object) { __typename }
+        }"#,
+        json!({ "object": serde_json::to_value(SqlCatalogObject::Item(item))? }),
+    )
+    .await;
+    assert_eq!(
+        response["errors"][0]["extensions"],
+        json!({
+            "code": "E_VALIDATION",
+            "status": 422,
+            "violations": [{
+                "pointer": "/data/name",
+                "constraint": "not_blank",
+                "message": "must not be blank",
+            }],
+        })
+    );
+
+    let response = execute(
+        &schema,
+        r#"{ object(account: "account", id: 4242) { __typename } }"#,
+        json!({}),
+    )
+    .await;
+    assert_eq!(
+        response["errors"][0]["message"],
+        "the object 4242 doesn't exist"
+    );
+    assert_eq!(response["errors"][0]["extensions"]["code"], "E_NOT_FOUND");
+    assert_eq!(response["errors"][0]["extensions"]["id"], "4242");
+    Ok(())
+}
+
+#[async_std::test]
+async fn deep_or_complex_queries_are_refused() -> Result<(), AnyHow> {
+    let schema = schema(AnyCatalogService::Memory(CatalogMemoryService::new()));
+    let nested = (0..6).fold("id".to_string(), |inner, _| {
+        format!("variations {{ item {{ {} }} }}", inner)
+    });
+
+    let response = execute(
+        &schema,
+        &format!(
+            r#"{{ object(account: "account", id: 1) {{ ... on Item {{ {} }} }} }}"#,
+            nested
+        ),
+        json!({}),
+    )
+    .await;
+    assert_eq!(
+        response["errors"][0]["message"],
+        "Query is nested too deep."
+    );
+
+    let aliases: Vec<String> = (0..1000)
+        .map(|index| format!(r#"l{}: locations(account: "account") {{ id }}"#, index))
+        .collect();
+    let response = execute(&schema, &format!("{{ {} }}", aliases.join(" ")), json!({})).await;
+    assert_eq!(response["errors"][0]["message"], "Query is too complex.");
+    Ok(())
+}
+
+#[async_std::test]
+async fn subscriptions_replay_and_follow_the_changes() -> Result<(), AnyHow> {
+    let catalog_service = CatalogSQLService::new(restore_db().await?);
+    let account = CATALOG_ACCOUNT.to_string();
+    let missed = catalog_service
+        .create(&account, &SqlCatalogObject::Item(fake_item()))
+        .await?;
+    let schema = schema(AnyCatalogService::Sqlite(catalog_service.clone()));
+
+    let mut changes = schema.execute_stream(request(
+        r#"subscription($account: String!) {
+            changes(account: $account, since: 0) { sequence operation object { ... on Item { id } } }
+        }"#,
+        json!({ "account": account }),
+    ));
+    let first = serde_json::to_value(changes.next().await.unwrap())?;
+    assert_eq!(
+        first["data"]["changes"],
+        json!({ "sequence": 1, "operation": "CREATED", "object": { "id": missed.id } })
+    );
+
+    catalog_service
+        .create(&"other".to_string(), &SqlCatalogObject::Item(fake_item()))
+        .await?;
+    catalog_service.delete(&account, &missed.id).await?;
+    let second = serde_json::to_value(changes.next().await.unwrap())?;
+    assert_eq!(second["data"]["changes"]["operation"], "DELETED");
+    assert_eq!(second["data"]["changes"]["object"]["id"], missed.id);
+    Ok(())
+}
+
+#[test]
+fn the_sdl_mirrors_the_models() {
+    let sdl = schema(AnyCatalogService::Memory(CatalogMemoryService::new())).sdl();
+    for definition in [
+        "union CatalogNode = Item | Variation | Modification | Delivery | Control",
+        "type Query",
+        "type Mutation",
+        "type Subscription",
+        "enum ItemCategory",
+        "variations: [Variation!]!",
+    ] {
+        assert!(sdl.contains(definition), "{} isn't in\n{}", definition, sdl);
+    }
+}
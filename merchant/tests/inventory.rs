@@ -0,0 +1,257 @@
+mod fixtures;
+mod utils;
+
+use async_std::sync::Mutex;
+use async_trait::async_trait;
+use fixtures::catalog::{fake_item, fake_item_variation, fake_stock_location};
+use merchant::catalog::backend::{
+    Account, CatalogSQLService, Id, SqlCatalogObject, SqlCatalogQueryOptions, SqlStockAlert,
+};
+use merchant::catalog::models::{CatalogObject, StockLocationKind};
+use merchant::catalog::service::{
+    CatalogCmd, CatalogError, CatalogService, Commander, IncreaseItemVariationUnitsAtPayload,
+    IncreaseItemVariationUnitsPayload, ListCatalogQueryOptions, StockAlertHook, StockAlertService,
+    StockLocationService, TransferItemVariationUnitsPayload,
+};
+use std::sync::Arc;
+use utils::{check_if_error_is, restore_db, AnyHow};
+
+const CATALOG_ACCOUNT: &str = "account";
+
+#[async_std::test]
+async fn create_and_list_locations() -> Result<(), AnyHow> {
+    let pool = restore_db().await?;
+    let catalog_service = CatalogSQLService::new(pool);
+    let account = CATALOG_ACCOUNT.to_string();
+    let store = fake_stock_location(StockLocationKind::Store);
+    let warehouse = fake_stock_location(StockLocationKind::Warehouse);
+
+    let store_doc = catalog_service.create_location(&account, &store).await?;
+    catalog_service
+        .create_location(&account, &warehouse)
+        .await?;
+    catalog_service
+        .create_location(&"other".to_string(), &store)
+        .await?;
+
+    assert_eq!(store_doc.location, store);
+    let read_store = catalog_service
+        .read_location(&account, &store_doc.id)
+        .await?;
+    assert_eq!(read_store.location, store);
+
+    let locations = catalog_service.list_locations(&account).await?;
+    assert_eq!(locations.len(), 2);
+
+    let mut renamed = store.clone();
+    renamed.name = "Downtown".to_string();
+    let updated = catalog_service
+        .update_location(&account, &store_doc.id, &renamed)
+        .await?;
+    assert_eq!(updated.location.name, "Downtown");
+    Ok(())
+}
+
+#[async_std::test]
+async fn stock_per_location_and_transfers() -> Result<(), AnyHow> {
+    let pool = restore_db().await?;
+    let catalog_service = CatalogSQLService::new(pool);
+    let account = CATALOG_ACCOUNT.to_string();
+    let item_doc = catalog_service
+        .create(&account, &SqlCatalogObject::Item(fake_item()))
+        .await?;
+    let variation = fake_item_variation(item_doc.id);
+    let variation_doc = catalog_service
+        .create(&account, &SqlCatalogObject::Variation(variation.clone()))
+        .await?;
+    let store = catalog_service
+        .create_location(&account, &fake_stock_location(StockLocationKind::Store))
+        .await?;
+    let warehouse = catalog_service
+        .create_location(&account, &fake_stock_location(StockLocationKind::Warehouse))
+        .await?;
+
+    catalog_service
+        .cmd(
+            &account,
+            CatalogCmd::IncreaseItemVariationUnitsAt(IncreaseItemVariationUnitsAtPayload {
+                id: variation_doc.id,
+                location_id: warehouse.id,
+                units: 20,
+            }),
+        )
+        .await?;
+    catalog_service
+        .cmd(
+            &account,
+            CatalogCmd::TransferItemVariationUnits(TransferItemVariationUnitsPayload {
+                id: variation_doc.id,
+                from_location_id: warehouse.id,
+                to_location_id: store.id,
+                units: 5,
+            }),
+        )
+        .await?;
+
+    let levels = catalog_service
+        .stock_levels(&account, &variation_doc.id)
+        .await?;
+    let units_at = |location_id| {
+        levels
+            .iter()
+            .find(|level| level.location_id == location_id)
+            .map(|level| level.units)
+    };
+    assert_eq!(units_at(warehouse.id), Some(15));
+    assert_eq!(units_at(store.id), Some(5));
+
+    let read_variation = catalog_service.read(&account, &variation_doc.id).await?;
+    let read_variation =
+        as_value!(read_variation.catalog_object, CatalogObject::Variation).unwrap();
+    assert_eq!(
+        read_variation.available_units,
+        variation.available_units + 20
+    );
+
+    let result = catalog_service
+        .cmd(
+            &account,
+            CatalogCmd::TransferItemVariationUnits(TransferItemVariationUnitsPayload {
+                id: variation_doc.id,
+                from_location_id: store.id,
+                to_location_id: warehouse.id,
+                units: 6,
+            }),
+        )
+        .await;
+    check_if_error_is(
+        result.unwrap_err(),
+        CatalogError::InsufficientUnits(variation_doc.id.to_string()),
+    );
+    Ok(())
+}
+
+#[async_std::test]
+async fn list_variations_available_at_location() -> Result<(), AnyHow> {
+    let pool = restore_db().await?;
+    let catalog_service = CatalogSQLService::new(pool);
+    let account = CATALOG_ACCOUNT.to_string();
+    let item_doc = catalog_service
+        .create(&account, &SqlCatalogObject::Item(fake_item()))
+        .await?;
+    let stocked = catalog_service
+        .create(
+            &account,
+            &SqlCatalogObject::Variation(fake_item_variation(item_doc.id)),
+        )
+        .await?;
+    catalog_service
+        .create(
+            &account,
+            &SqlCatalogObject::Variation(fake_item_variation(item_doc.id)),
+        )
+        .await?;
+    let store = catalog_service
+        .create_location(&account, &fake_stock_location(StockLocationKind::Store))
+        .await?;
+    catalog_service
+        .cmd(
+            &account,
+            CatalogCmd::IncreaseItemVariationUnitsAt(IncreaseItemVariationUnitsAtPayload {
+                id: stocked.id,
+                location_id: store.id,
+                units: 3,
+            }),
+        )
+        .await?;
+
+    let query = SqlCatalogQueryOptions {
+        limit: None,
+        order_by: None,
+        options: ListCatalogQueryOptions {
+            available_at: Some(store.id),
+            ..Default::default()
+        },
+    };
+    let found = catalog_service.list(&account, &query).await?;
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].id, stocked.id);
+    Ok(())
+}
+
+#[derive(Default)]
+struct RecordingHook {
+    alerts: Mutex<Vec<SqlStockAlert>>,
+}
+
+#[async_trait]
+impl StockAlertHook<Id, Account> for RecordingHook {
+    async fn notify(&self, alert: &SqlStockAlert) {
+        self.alerts.lock().await.push(alert.clone());
+    }
+}
+
+#[async_std::test]
+async fn alert_when_units_drop_to_reorder_threshold() -> Result<(), AnyHow> {
+    let pool = restore_db().await?;
+    let hook = Arc::new(RecordingHook::default());
+    let catalog_service = CatalogSQLService::new(pool).with_alert_hook(hook.clone());
+    let account = CATALOG_ACCOUNT.to_string();
+    let item_doc = catalog_service
+        .create(&account, &SqlCatalogObject::Item(fake_item()))
+        .await?;
+    let mut variation = fake_item_variation(item_doc.id);
+    variation.available_units = 10;
+    variation.reorder_threshold = Some(5);
+    let variation_doc = catalog_service
+        .create(&account, &SqlCatalogObject::Variation(variation.clone()))
+        .await?;
+
+    let decrease = |units| {
+        CatalogCmd::IncreaseItemVariationUnits(IncreaseItemVariationUnitsPayload {
+            id: variation_doc.id,
+            units,
+        })
+    };
+    catalog_service.cmd(&account, decrease(-4)).await?;
+    assert!(catalog_service.list_alerts(&account).await?.is_empty());
+
+    catalog_service.cmd(&account, decrease(-2)).await?;
+    // already below the threshold, no new alert
+    catalog_service.cmd(&account, decrease(-1)).await?;
+    let alerts = catalog_service.list_alerts(&account).await?;
+    assert_eq!(alerts.len(), 1);
+    assert_eq!(alerts[0].variation_id, variation_doc.id);
+    assert_eq!(alerts[0].available_units, 4);
+    assert_eq!(alerts[0].reorder_threshold, 5);
+
+    variation.available_units = 2;
+    catalog_service
+        .update(
+            &account,
+            &variation_doc.id,
+            &SqlCatalogObject::Variation(variation.clone()),
+        )
+        .await?;
+    assert_eq!(catalog_service.list_alerts(&account).await?.len(), 1);
+
+    variation.available_units = 20;
+    catalog_service
+        .update(
+            &account,
+            &variation_doc.id,
+            &SqlCatalogObject::Variation(variation.clone()),
+        )
+        .await?;
+    variation.available_units = 1;
+    catalog_service
+        .update(
+            &account,
+            &variation_doc.id,
+            &SqlCatalogObject::Variation(variation),
+        )
+        .await?;
+    assert_eq!(catalog_service.list_alerts(&account).await?.len(), 2);
+    assert_eq!(hook.alerts.lock().await.len(), 2);
+    Ok(())
+}
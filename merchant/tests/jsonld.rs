@@ -0,0 +1,218 @@
+mod fixtures;
+mod utils;
+
+use fixtures::catalog::{fake_item, fake_item_control, fake_item_delivery, fake_item_variation};
+use merchant::catalog::backend::{CatalogSQLService, SqlCatalogObject, SqlCatalogQueryOptions};
+use merchant::catalog::jsonld::json_ld;
+use merchant::catalog::models::{Control, Image, MatrixProp, Price, Time};
+use merchant::catalog::service::CatalogService;
+use serde_json::json;
+use utils::{restore_db, AnyHow};
+
+const CATALOG_ACCOUNT: &str = "account";
+
+fn everything() -> SqlCatalogQueryOptions {
+    SqlCatalogQueryOptions {
+        limit: None,
+        order_by: None,
+        options: Default::default(),
+    }
+}
+
+#[async_std::test]
+async fn items_with_variations_are_product_groups() -> Result<(), AnyHow> {
+    let catalog_service = CatalogSQLService::new(restore_db().await?);
+    let account = CATALOG_ACCOUNT.to_string();
+    let mut item = fake_item();
+    item.name = "Shirt".to_string();
+    item.warranty_time = Some(Time::Fixed { seconds: 86400 });
+    item.images = vec![Image {
+        url: "https://shop.test/shirt.png".to_string(),
+    }];
+    let item_doc = catalog_service
+        .create(&account, &SqlCatalogObject::Item(item))
+        .await?;
+    let mut variation = fake_item_variation(item_doc.id);
+    variation.name = "Blue S".to_string();
+    variation.sku = "SH-BS".to_string();
+    variation.upc = Some("0012345678905".to_string());
+    variation.price = Price::Fixed {
+        amount: 12.5,
+        asset_name: "USD".to_string(),
+        asset_scale: 2,
+    };
+    let variation_doc = catalog_service
+        .create(&account, &SqlCatalogObject::Variation(variation))
+        .await?;
+    let mut disabled = fake_item_variation(item_doc.id);
+    disabled.enabled = false;
+    catalog_service
+        .create(&account, &SqlCatalogObject::Variation(disabled))
+        .await?;
+    let mut control = fake_item_control(item_doc.id);
+    if let Control::Matrix(matrix) = &mut control.control {
+        matrix.key_template = ":color-:size-:fit".to_string();
+        matrix.props.push(MatrixProp {
+            name: "fit".to_string(),
+            options: vec!["Slim".to_string(), "Loose".to_string()],
+        });
+        matrix
+            .combinations
+            .insert("Blue-S-Slim".to_string(), variation_doc.id);
+    }
+    catalog_service
+        .create(&account, &SqlCatalogObject::Control(control))
+        .await?;
+    catalog_service
+        .create(
+            &account,
+            &SqlCatalogObject::Delivery(fake_item_delivery(item_doc.id)),
+        )
+        .await?;
+    let documents = catalog_service.list(&account, &everything()).await?;
+
+    let markup = json_ld(&item_doc, &documents).unwrap();
+    assert_eq!(markup["@context"], "https://schema.org");
+    assert_eq!(markup["@type"], "ProductGroup");
+    assert_eq!(markup["productGroupID"], item_doc.id.to_string());
+    assert_eq!(markup["name"], "Shirt");
+    assert_eq!(
+        markup["variesBy"],
+        json!(["https://schema.org/color", "https://schema.org/size", "fit"])
+    );
+    assert_eq!(markup["hasVariant"].as_array().unwrap().len(), 1);
+    let variant = &markup["hasVariant"][0];
+    assert_eq!(variant["@type"], "Product");
+    assert_eq!(variant["productID"], variation_doc.id.to_string());
+    assert_eq!(variant["sku"], "SH-BS");
+    assert_eq!(variant["gtin"], "0012345678905");
+    assert_eq!(variant["color"], "Blue");
+    assert_eq!(variant["size"], "S");
+    assert_eq!(
+        variant["additionalProperty"],
+        json!([{ "@type": "PropertyValue", "name": "fit", "value": "Slim" }])
+    );
+    assert_eq!(
+        variant["weight"],
+        json!({ "@type": "QuantitativeValue", "value": 200, "unitCode": "GRM" })
+    );
+    assert_eq!(variant["depth"]["unitCode"], "MMT");
+    assert_eq!(variant["image"], json!(["https://shop.test/shirt.png"]));
+    assert_eq!(
+        variant["offers"],
+        json!({
+            "@type": "Offer",
+            "price": "12.50",
+            "priceCurrency": "USD",
+            "availability": "https://schema.org/InStock",
+            "warranty": {
+                "@type": "WarrantyPromise",
+                "durationOfWarranty": {
+                    "@type": "QuantitativeValue",
+                    "value": 86400,
+                    "unitCode": "SEC",
+                },
+            },
+        })
+    );
+
+    // a variation on its own points to its group
+    let markup = json_ld(&variation_doc, &documents).unwrap();
+    assert_eq!(markup["@type"], "Product");
+    assert_eq!(markup["color"], "Blue");
+    assert_eq!(
+        markup["isVariantOf"],
+        json!({
+            "@type": "ProductGroup",
+            "productGroupID": item_doc.id.to_string(),
+            "name": "Shirt",
+        })
+    );
+    Ok(())
+}
+
+#[async_std::test]
+async fn only_items_and_variations_are_products() -> Result<(), AnyHow> {
+    let catalog_service = CatalogSQLService::new(restore_db().await?);
+    let account = CATALOG_ACCOUNT.to_string();
+    let item_doc = catalog_service
+        .create(&account, &SqlCatalogObject::Item(fake_item()))
+        .await?;
+    let delivery_doc = catalog_service
+        .create(
+            &account,
+            &SqlCatalogObject::Delivery(fake_item_delivery(item_doc.id)),
+        )
+        .await?;
+    let documents = catalog_service.list(&account, &everything()).await?;
+
+    let markup = json_ld(&item_doc, &documents).unwrap();
+    assert_eq!(markup["@type"], "Product");
+    assert_eq!(markup["productID"], item_doc.id.to_string());
+    assert_eq!(markup["weight"]["value"], 200);
+    assert!(markup.get("offers").is_none());
+    assert!(markup.get("hasVariant").is_none());
+    assert!(json_ld(&delivery_doc, &documents).is_none());
+    Ok(())
+}
+
+#[async_std::test]
+async fn variation_options_come_from_their_key() -> Result<(), AnyHow> {
+    let catalog_service = CatalogSQLService::new(restore_db().await?);
+    let account = CATALOG_ACCOUNT.to_string();
+    let item_doc = catalog_service
+        .create(&account, &SqlCatalogObject::Item(fake_item()))
+        .await?;
+    let variation_doc = catalog_service
+        .create(
+            &account,
+            &SqlCatalogObject::Variation(fake_item_variation(item_doc.id)),
+        )
+        .await?;
+    // far too many combinations to try each of them
+    let mut props: Vec<MatrixProp> = (0..24)
+        .map(|index| MatrixProp {
+            name: format!("p{}", index),
+            options: (0..10).map(|option| format!("o{}", option)).collect(),
+        })
+        .collect();
+    // options that start like others and names that start like others
+    props.push(MatrixProp {
+        name: "p1x".to_string(),
+        options: vec!["S".to_string(), "Slim".to_string()],
+    });
+    let key_template = props
+        .iter()
+        .map(|prop| format!(":{}", prop.name))
+        .collect::<Vec<String>>()
+        .join("-");
+    let mut key = (0..24)
+        .map(|index| format!("o{}", index % 10))
+        .collect::<Vec<String>>()
+        .join("-");
+    key.push_str("-Slim");
+    let mut control = fake_item_control(item_doc.id);
+    if let Control::Matrix(matrix) = &mut control.control {
+        matrix.key_template = key_template;
+        matrix.props = props;
+        matrix.combinations.clear();
+        matrix.combinations.insert(key, variation_doc.id);
+    }
+    catalog_service
+        .create(&account, &SqlCatalogObject::Control(control))
+        .await?;
+    let documents = catalog_service.list(&account, &everything()).await?;
+
+    let markup = json_ld(&variation_doc, &documents).unwrap();
+    let properties = markup["additionalProperty"].as_array().unwrap();
+    assert_eq!(properties.len(), 25);
+    assert_eq!(
+        properties[13],
+        json!({ "@type": "PropertyValue", "name": "p13", "value": "o3" })
+    );
+    assert_eq!(
+        properties[24],
+        json!({ "@type": "PropertyValue", "name": "p1x", "value": "Slim" })
+    );
+    Ok(())
+}
@@ -0,0 +1,323 @@
+#[macro_use]
+mod conformance;
+mod fixtures;
+mod utils;
+
+use async_std::net::TcpListener;
+use async_std::sync::Mutex;
+use async_trait::async_trait;
+use conformance::Harness;
+use fixtures::catalog::{fake_item, fake_item_variation, fake_stock_location};
+use merchant::catalog::backend::{SqlCatalogObject, SqlCatalogQueryOptions};
+use merchant::catalog::matrix::{
+    CatalogMatrixService, MatrixClient, ITEM_EVENT, STOCK_LEVEL_EVENT, VARIATION_EVENT,
+};
+use merchant::catalog::models::{CatalogObject, StockLocationKind};
+use merchant::catalog::service::{
+    CatalogCmd, CatalogError, CatalogService, Commander, IncreaseItemVariationUnitsAtPayload,
+    StockLocationService,
+};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use utils::AnyHow;
+
+const CATALOG_ACCOUNT: &str = "account";
+const ACCESS_TOKEN: &str = "syt_merchant";
+const ROOM_ID: &str = "!catalog:localhost";
+
+#[derive(Clone, Default)]
+struct Homeserver {
+    // (event type, state key) -> content
+    state: Arc<Mutex<BTreeMap<(String, String), Value>>>,
+    unavailable: Arc<AtomicBool>,
+    // milliseconds every state event takes to be stored
+    latency: Arc<AtomicU64>,
+}
+
+// the objects with this name are rejected by the homeserver
+const REJECTED_NAME: &str = "rejected";
+
+fn authorized(request: &tide::Request<Homeserver>) -> tide::Result<()> {
+    let expected = format!("Bearer {}", ACCESS_TOKEN);
+    match request.header("Authorization") {
+        Some(value) if value.as_str() == expected => Ok(()),
+        _ => Err(tide::Error::from_str(401, "M_UNKNOWN_TOKEN")),
+    }
+}
+
+// just the room state endpoints of the client-server API
+async fn start_homeserver() -> Result<(MatrixClient, Homeserver), AnyHow> {
+    let homeserver = Homeserver::default();
+    let mut app = tide::with_state(homeserver.clone());
+    app.at("/_matrix/client/v3/rooms/:room_id/state").get(
+        |request: tide::Request<Homeserver>| async move {
+            authorized(&request)?;
+            let events: Vec<Value> = request
+                .state()
+                .state
+                .lock()
+                .await
+                .iter()
+                .map(|((event_type, state_key), content)| {
+                    json!({
+                        "type": event_type,
+                        "state_key": state_key,
+                        "content": content,
+                        "sender": "@merchant:localhost",
+                    })
+                })
+                .collect();
+            tide::Body::from_json(&events)
+        },
+    );
+    app.at("/_matrix/client/v3/rooms/:room_id/state/:event_type/:state_key")
+        .put(|mut request: tide::Request<Homeserver>| async move {
+            authorized(&request)?;
+            let latency = request.state().latency.load(Ordering::SeqCst);
+            async_std::task::sleep(Duration::from_millis(latency)).await;
+            let content: Value = request.body_json().await?;
+            if request.state().unavailable.load(Ordering::SeqCst)
+                || content["data"]["name"] == REJECTED_NAME
+            {
+                return Ok(tide::Response::new(502));
+            }
+            let key = (
+                request.param("event_type")?.to_string(),
+                request.param("state_key")?.to_string(),
+            );
+            request.state().state.lock().await.insert(key, content);
+            let mut response = tide::Response::new(200);
+            response.set_body(json!({ "event_id": "$event" }));
+            Ok(response)
+        });
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let url = format!("http://{}/", listener.local_addr()?);
+    async_std::task::spawn(app.listen(listener));
+    Ok((
+        MatrixClient::new(url.parse()?, ACCESS_TOKEN, ROOM_ID),
+        homeserver,
+    ))
+}
+
+struct Matrix;
+
+#[async_trait]
+impl Harness for Matrix {
+    type Service = CatalogMatrixService;
+    type Guard = Homeserver;
+
+    async fn setup() -> Result<(Self::Service, Self::Guard), AnyHow> {
+        let (client, homeserver) = start_homeserver().await?;
+        Ok((CatalogMatrixService::connect(client).await?, homeserver))
+    }
+}
+
+conformance_suite!(Matrix);
+
+#[async_std::test]
+async fn objects_are_stored_as_state_events() -> Result<(), AnyHow> {
+    let (client, homeserver) = start_homeserver().await?;
+    let catalog_service = CatalogMatrixService::connect(client).await?;
+    let account = CATALOG_ACCOUNT.to_string();
+
+    let item_doc = catalog_service
+        .create(&account, &SqlCatalogObject::Item(fake_item()))
+        .await?;
+    let variation_doc = catalog_service
+        .create(
+            &account,
+            &SqlCatalogObject::Variation(fake_item_variation(item_doc.id)),
+        )
+        .await?;
+
+    let state = homeserver.state.lock().await;
+    let item_event = &state[&(ITEM_EVENT.to_string(), item_doc.id.to_string())];
+    assert_eq!(item_event["account"], CATALOG_ACCOUNT);
+    assert_eq!(
+        item_event["data"]["name"],
+        item_doc.catalog_object.item().unwrap().name.as_str()
+    );
+    assert!(state.contains_key(&(VARIATION_EVENT.to_string(), variation_doc.id.to_string())));
+    drop(state);
+
+    catalog_service.delete(&account, &variation_doc.id).await?;
+    let state = homeserver.state.lock().await;
+    assert_eq!(
+        state[&(VARIATION_EVENT.to_string(), variation_doc.id.to_string())],
+        json!({})
+    );
+    Ok(())
+}
+
+#[async_std::test]
+async fn the_cache_is_rebuilt_from_the_room_state() -> Result<(), AnyHow> {
+    let (client, homeserver) = start_homeserver().await?;
+    let catalog_service = CatalogMatrixService::connect(client.clone()).await?;
+    let account = CATALOG_ACCOUNT.to_string();
+
+    let item_doc = catalog_service
+        .create(&account, &SqlCatalogObject::Item(fake_item()))
+        .await?;
+    let variation_doc = catalog_service
+        .create(
+            &account,
+            &SqlCatalogObject::Variation(fake_item_variation(item_doc.id)),
+        )
+        .await?;
+    let removed_doc = catalog_service
+        .create(
+            &account,
+            &SqlCatalogObject::Variation(fake_item_variation(item_doc.id)),
+        )
+        .await?;
+    catalog_service.delete(&account, &removed_doc.id).await?;
+    let store = catalog_service
+        .create_location(&account, &fake_stock_location(StockLocationKind::Store))
+        .await?;
+    catalog_service
+        .cmd(
+            &account,
+            CatalogCmd::IncreaseItemVariationUnitsAt(IncreaseItemVariationUnitsAtPayload {
+                id: variation_doc.id,
+                location_id: store.id,
+                units: 5,
+            }),
+        )
+        .await?;
+    assert!(homeserver.state.lock().await.contains_key(&(
+        STOCK_LEVEL_EVENT.to_string(),
+        format!("{}:{}", store.id, variation_doc.id)
+    )));
+
+    // a new instance only knows what is in the room
+    let restarted = CatalogMatrixService::connect(client).await?;
+    let read = restarted.read(&account, &variation_doc.id).await?;
+    match (&read.catalog_object, &variation_doc.catalog_object) {
+        (CatalogObject::Variation(read), CatalogObject::Variation(created)) => {
+            assert_eq!(read.available_units, created.available_units + 5);
+            assert_eq!(read.item_id, item_doc.id);
+        }
+        _ => panic!("catalog_object should be a variation"),
+    }
+    assert!(!restarted.exists(&account, &removed_doc.id).await?);
+    assert_eq!(
+        restarted.read_location(&account, &store.id).await?.id,
+        store.id
+    );
+    let levels = restarted.stock_levels(&account, &variation_doc.id).await?;
+    assert_eq!(levels.len(), 1);
+    assert_eq!(levels[0].units, 5);
+
+    // the objects are stored as they were, not written again
+    let read_item = restarted.read(&account, &item_doc.id).await?;
+    assert_eq!(read_item.version, item_doc.version);
+    assert_eq!(read_item.created_at, item_doc.created_at);
+    let stored_variation = catalog_service.read(&account, &variation_doc.id).await?;
+    assert_eq!(read.version, stored_variation.version);
+    assert_eq!(read.created_at, variation_doc.created_at);
+    let read_store = restarted.read_location(&account, &store.id).await?;
+    assert_eq!(read_store.version, store.version);
+    assert_eq!(read_store.created_at, store.created_at);
+    Ok(())
+}
+
+#[async_std::test]
+async fn writes_rejected_by_the_homeserver_are_undone() -> Result<(), AnyHow> {
+    let (client, homeserver) = start_homeserver().await?;
+    let catalog_service = CatalogMatrixService::connect(client).await?;
+    let account = CATALOG_ACCOUNT.to_string();
+    let item = fake_item();
+    let item_doc = catalog_service
+        .create(&account, &SqlCatalogObject::Item(item.clone()))
+        .await?;
+
+    homeserver.unavailable.store(true, Ordering::SeqCst);
+    let result = catalog_service
+        .create(&account, &SqlCatalogObject::Item(fake_item()))
+        .await;
+    assert!(matches!(
+        result.unwrap_err(),
+        CatalogError::DatabaseError(_)
+    ));
+    let result = catalog_service
+        .update(&account, &item_doc.id, &SqlCatalogObject::Item(fake_item()))
+        .await;
+    assert!(matches!(
+        result.unwrap_err(),
+        CatalogError::DatabaseError(_)
+    ));
+    let result = catalog_service.delete(&account, &item_doc.id).await;
+    assert!(matches!(
+        result.unwrap_err(),
+        CatalogError::DatabaseError(_)
+    ));
+
+    let read = catalog_service.read(&account, &item_doc.id).await?;
+    assert_eq!(read.catalog_object.item().unwrap().name, item.name);
+    let everything = catalog_service
+        .list(
+            &account,
+            &SqlCatalogQueryOptions {
+                limit: None,
+                order_by: None,
+                options: Default::default(),
+            },
+        )
+        .await?;
+    assert_eq!(everything.len(), 1);
+    Ok(())
+}
+
+#[async_std::test]
+async fn a_rejected_write_keeps_the_concurrent_ones() -> Result<(), AnyHow> {
+    let (client, homeserver) = start_homeserver().await?;
+    let catalog_service = CatalogMatrixService::connect(client).await?;
+    let account = CATALOG_ACCOUNT.to_string();
+    homeserver.latency.store(20, Ordering::SeqCst);
+
+    let writers: Vec<_> = (0..8)
+        .map(|index| {
+            let catalog_service = catalog_service.clone();
+            let account = account.clone();
+            let mut item = fake_item();
+            if index == 3 {
+                item.name = REJECTED_NAME.to_string();
+            }
+            async_std::task::spawn(async move {
+                catalog_service
+                    .create(&account, &SqlCatalogObject::Item(item))
+                    .await
+            })
+        })
+        .collect();
+    let mut created = vec![];
+    let mut rejected = 0;
+    for writer in writers {
+        match writer.await {
+            Ok(document) => created.push(document),
+            Err(CatalogError::DatabaseError(_)) => rejected += 1,
+            Err(err) => return Err(err.into()),
+        }
+    }
+    assert_eq!(rejected, 1);
+    assert_eq!(created.len(), 7);
+
+    let everything = catalog_service
+        .list(
+            &account,
+            &SqlCatalogQueryOptions {
+                limit: None,
+                order_by: None,
+                options: Default::default(),
+            },
+        )
+        .await?;
+    assert_eq!(everything.len(), 7);
+    for document in created {
+        assert!(catalog_service.exists(&account, &document.id).await?);
+    }
+    Ok(())
+}
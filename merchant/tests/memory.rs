@@ -0,0 +1,23 @@
+#[macro_use]
+mod conformance;
+mod fixtures;
+mod utils;
+
+use async_trait::async_trait;
+use conformance::Harness;
+use merchant::catalog::memory::CatalogMemoryService;
+use utils::AnyHow;
+
+struct Memory;
+
+#[async_trait]
+impl Harness for Memory {
+    type Service = CatalogMemoryService;
+    type Guard = ();
+
+    async fn setup() -> Result<(Self::Service, Self::Guard), AnyHow> {
+        Ok((CatalogMemoryService::new(), ()))
+    }
+}
+
+conformance_suite!(Memory);
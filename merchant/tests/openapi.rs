@@ -0,0 +1,617 @@
+mod fixtures;
+mod utils;
+
+use std::collections::{BTreeSet, HashMap};
+
+use fixtures::catalog::{
+    fake_item, fake_item_control, fake_item_delivery, fake_item_modification, fake_item_variation,
+    fake_stock_location,
+};
+use merchant::catalog::backend::{CatalogSQLService, SqlCatalogObject, SqlCatalogQueryOptions};
+use merchant::catalog::error::{Problem, PROBLEM_JSON};
+use merchant::catalog::models::{
+    CatalogObject, CatalogObjectBulkDocument, StockLocationKind, Time,
+};
+use merchant::catalog::projection::Fields;
+use merchant::catalog::service::{
+    CatalogArchiveService, CatalogAuditLog, CatalogChangeFeed, CatalogCmd, CatalogError,
+    CatalogProjection, CatalogRevisionService, CatalogService, Commander,
+    IncreaseItemVariationUnitsAtPayload, IncreaseItemVariationUnitsPayload, ListAuditOptions,
+    ListCatalogChangesOptions, ListRevisionsOptions, StockAlertService, StockLocationService,
+    TransferItemVariationUnitsPayload,
+};
+use merchant::catalog::spreadsheet::{import_csv, CsvImportOptions};
+use merchant::catalog::validation::Violation;
+use merchant::openapi::{openapi, openapi_path, routes};
+use merchant::webhooks::backend::WebhookSQLService;
+use merchant::webhooks::models::{WebhookEvent, WebhookSubscription};
+use merchant::webhooks::service::WebhookService;
+use serde::Serialize;
+use serde_json::{json, Value};
+use utils::{restore_db, AnyHow};
+
+const CATALOG_ACCOUNT: &str = "account";
+const OTHER_ACCOUNT: &str = "other";
+
+fn everything() -> SqlCatalogQueryOptions {
+    SqlCatalogQueryOptions {
+        limit: None,
+        order_by: None,
+        options: Default::default(),
+    }
+}
+
+// Checks a value against the subset of the OpenAPI 3.0 schemas the spec
+// uses. It is strict about objects: a property no schema declares is an
+// error, that's how a field added to a model without its schema shows up.
+struct Validator<'a> {
+    spec: &'a Value,
+}
+
+impl<'a> Validator<'a> {
+    fn resolve(&self, schema: &'a Value) -> &'a Value {
+        match schema.get("$ref").and_then(Value::as_str) {
+            Some(reference) => {
+                let name = reference.trim_start_matches("#/components/schemas/");
+                self.resolve(&self.spec["components"]["schemas"][name])
+            }
+            None => schema,
+        }
+    }
+
+    fn validate(&self, schema: &'a Value, value: &Value, at: &str) -> Result<(), String> {
+        let evaluated = self.evaluate(schema, value, at)?;
+        if let (Some(evaluated), Value::Object(object)) = (evaluated, value) {
+            if let Some(name) = object.keys().find(|name| !evaluated.contains(*name)) {
+                return Err(format!("{}/{} isn't in the schema", at, name));
+            }
+        }
+        Ok(())
+    }
+
+    // the properties of an object the schema knows of, `None` when it
+    // accepts any
+    fn evaluate(
+        &self,
+        schema: &'a Value,
+        value: &Value,
+        at: &str,
+    ) -> Result<Option<BTreeSet<String>>, String> {
+        // `nullable` can sit next to a `$ref`
+        if value.is_null() && schema["nullable"] == true {
+            return Ok(None);
+        }
+        let schema = self.resolve(schema);
+        if schema == &Value::Bool(true) {
+            return Ok(None);
+        }
+        if value.is_null() {
+            return match schema["nullable"].as_bool() {
+                Some(true) => Ok(None),
+                _ if schema.get("type").is_none() && schema.get("$ref").is_none() => {
+                    self.evaluate_subschemas(schema, value, at)
+                }
+                _ => Err(format!("{} is null", at)),
+            };
+        }
+        if let Some(kind) = schema["type"].as_str() {
+            let matches = match kind {
+                "object" => value.is_object(),
+                "array" => value.is_array(),
+                "string" => value.is_string(),
+                "integer" => value.is_i64() || value.is_u64(),
+                "number" => value.is_number(),
+                "boolean" => value.is_boolean(),
+                _ => false,
+            };
+            if !matches {
+                return Err(format!("{} isn't a {}: {}", at, kind, value));
+            }
+        }
+        if let Some(values) = schema["enum"].as_array() {
+            if !values.contains(value) {
+                return Err(format!("{} isn't one of {:?}: {}", at, values, value));
+            }
+        }
+        if let Value::Array(items) = value {
+            if let Some(item_schema) = schema.get("items") {
+                for (index, item) in items.iter().enumerate() {
+                    self.validate(item_schema, item, &format!("{}/{}", at, index))?;
+                }
+            }
+        }
+
+        let mut evaluated = None;
+        if let Value::Object(object) = value {
+            let properties = schema["properties"].as_object();
+            if properties.is_some() || schema.get("additionalProperties").is_some() {
+                evaluated = Some(BTreeSet::new());
+            }
+            for (name, property) in properties.into_iter().flatten() {
+                match object.get(name) {
+                    Some(property_value) => {
+                        self.validate(property, property_value, &format!("{}/{}", at, name))?;
+                        evaluated
+                            .get_or_insert_with(BTreeSet::new)
+                            .insert(name.clone());
+                    }
+                    // the secrets are only written
+                    None if self.resolve(property)["writeOnly"] == true => {}
+                    None => {
+                        let required = schema["required"]
+                            .as_array()
+                            .is_some_and(|required| required.contains(&json!(name)));
+                        if required {
+                            return Err(format!("{}/{} is missing", at, name));
+                        }
+                    }
+                }
+            }
+            match schema.get("additionalProperties") {
+                Some(Value::Bool(false)) | None => {}
+                Some(Value::Bool(true)) => evaluated = None,
+                Some(additional) => {
+                    for (name, property_value) in object {
+                        if !properties.is_some_and(|properties| properties.contains_key(name)) {
+                            self.validate(additional, property_value, &format!("{}/{}", at, name))?;
+                            evaluated
+                                .get_or_insert_with(BTreeSet::new)
+                                .insert(name.clone());
+                        }
+                    }
+                }
+            }
+        }
+        let subschemas = self.evaluate_subschemas(schema, value, at)?;
+        Ok(merge(evaluated, subschemas, schema, value))
+    }
+
+    fn evaluate_subschemas(
+        &self,
+        schema: &'a Value,
+        value: &Value,
+        at: &str,
+    ) -> Result<Option<BTreeSet<String>>, String> {
+        let mut evaluated: Option<BTreeSet<String>> = None;
+        let mut constrained = false;
+        for subschema in schema["allOf"].as_array().into_iter().flatten() {
+            constrained = true;
+            evaluated = union(evaluated, self.evaluate(subschema, value, at)?);
+        }
+        for (keyword, exactly_one) in [("oneOf", true), ("anyOf", false)] {
+            let subschemas = match schema[keyword].as_array() {
+                Some(subschemas) => subschemas,
+                None => continue,
+            };
+            constrained = true;
+            let mut errors = vec![];
+            let mut matched = vec![];
+            for subschema in subschemas {
+                match self.evaluate(subschema, value, at) {
+                    Ok(keys) => matched.push(keys),
+                    Err(error) => errors.push(error),
+                }
+            }
+            if matched.is_empty() || (exactly_one && matched.len() > 1) {
+                return Err(format!(
+                    "{} matches {} of the {}: {:?}",
+                    at,
+                    matched.len(),
+                    keyword,
+                    errors
+                ));
+            }
+            for keys in matched {
+                evaluated = union(evaluated, keys);
+            }
+        }
+        if constrained && evaluated.is_none() && value.is_object() {
+            return Ok(Some(BTreeSet::new()));
+        }
+        Ok(evaluated)
+    }
+}
+
+fn union(a: Option<BTreeSet<String>>, b: Option<BTreeSet<String>>) -> Option<BTreeSet<String>> {
+    match (a, b) {
+        (Some(mut a), Some(b)) => {
+            a.extend(b);
+            Some(a)
+        }
+        (a, b) => a.or(b),
+    }
+}
+
+fn merge(
+    evaluated: Option<BTreeSet<String>>,
+    subschemas: Option<BTreeSet<String>>,
+    schema: &Value,
+    value: &Value,
+) -> Option<BTreeSet<String>> {
+    // a bare `type: object` accepts anything
+    if evaluated.is_none() && subschemas.is_none() {
+        let constrained = schema.get("properties").is_some()
+            || schema.get("oneOf").is_some()
+            || schema.get("anyOf").is_some()
+            || schema.get("allOf").is_some();
+        return if constrained && value.is_object() {
+            Some(BTreeSet::new())
+        } else {
+            None
+        };
+    }
+    union(evaluated, subschemas)
+}
+
+fn json_schema<'a>(spec: &'a Value, method: &str, path: &str, kind: &str) -> &'a Value {
+    let operation = &spec["paths"][openapi_path(path)][method];
+    let content = match kind {
+        "request" => &operation["requestBody"]["content"],
+        _ => &operation["responses"]["200"]["content"],
+    };
+    let schema = &content["application/json"]["schema"];
+    assert!(
+        !schema.is_null(),
+        "no {} schema for {} {}",
+        kind,
+        method,
+        path
+    );
+    schema
+}
+
+fn check<T: Serialize>(spec: &Value, method: &str, path: &str, kind: &str, value: &T) {
+    let value = serde_json::to_value(value).unwrap();
+    let schema = json_schema(spec, method, path, kind);
+    if let Err(error) = (Validator { spec }).validate(schema, &value, "") {
+        panic!(
+            "the {} of {} {} doesn't match the spec, {}: {}",
+            kind, method, path, error, value
+        );
+    }
+}
+
+// an object of every kind but items
+fn objects<Id: Clone>(item_id: Id) -> Vec<CatalogObject<Id>> {
+    let mut variation = fake_item_variation(item_id.clone());
+    variation.reorder_threshold = Some(5);
+    variation.available_units = 10;
+    variation.extra_attributes = Some(HashMap::from([("color".to_string(), "Blue".to_string())]));
+    vec![
+        CatalogObject::Variation(variation),
+        CatalogObject::Modification(fake_item_modification(item_id.clone())),
+        CatalogObject::Delivery(fake_item_delivery(item_id.clone())),
+        CatalogObject::Control(fake_item_control(item_id)),
+    ]
+}
+
+#[async_std::test]
+async fn serde_output_matches_the_spec() -> Result<(), AnyHow> {
+    let spec = openapi();
+    let pool = restore_db().await?;
+    let catalog_service = CatalogSQLService::new(pool.clone());
+    let webhook_service = WebhookSQLService::new(pool).with_private_targets();
+    let account = CATALOG_ACCOUNT.to_string();
+
+    let mut item = fake_item();
+    item.warranty_time = Some(Time::Fixed { seconds: 60 });
+    let item_doc = catalog_service
+        .create(&account, &SqlCatalogObject::Item(item.clone()))
+        .await?;
+    check(
+        &spec,
+        "post",
+        "/catalog/:account",
+        "request",
+        &item_doc.catalog_object,
+    );
+    check(&spec, "post", "/catalog/:account", "response", &item_doc);
+    for object in objects(item_doc.id) {
+        check(&spec, "post", "/catalog/:account", "request", &object);
+        let document = catalog_service.create(&account, &object).await?;
+        check(&spec, "get", "/catalog/:account/:id", "response", &document);
+    }
+    let documents = catalog_service.list(&account, &everything()).await?;
+    assert_eq!(documents.len(), 5);
+    check(&spec, "get", "/catalog/:account", "response", &documents);
+    let fields = Fields::new(&["id", "name", "price_amount", "tags"])?;
+    let projected = catalog_service
+        .list_fields(&account, &everything(), &fields)
+        .await?;
+    check(&spec, "get", "/catalog/:account", "response", &projected);
+    let variation_id = documents
+        .iter()
+        .find(|document| matches!(document.catalog_object, CatalogObject::Variation(_)))
+        .unwrap()
+        .id;
+
+    let mut bulk = vec![CatalogObjectBulkDocument {
+        id: Some("item".to_string()),
+        catalog_object: CatalogObject::Item(item),
+    }];
+    bulk.extend(
+        objects("item".to_string())
+            .into_iter()
+            .map(|object| CatalogObjectBulkDocument {
+                id: None,
+                catalog_object: object,
+            }),
+    );
+    check(&spec, "post", "/catalog/:account/_bulk", "request", &bulk);
+
+    let store = fake_stock_location(StockLocationKind::Store);
+    check(
+        &spec,
+        "post",
+        "/catalog/:account/_locations",
+        "request",
+        &store,
+    );
+    let store = catalog_service.create_location(&account, &store).await?;
+    let warehouse = catalog_service
+        .create_location(&account, &fake_stock_location(StockLocationKind::Warehouse))
+        .await?;
+    let locations = catalog_service.list_locations(&account).await?;
+    check(
+        &spec,
+        "get",
+        "/catalog/:account/_locations",
+        "response",
+        &locations,
+    );
+    let cmds = vec![
+        CatalogCmd::IncreaseItemVariationUnitsAt(IncreaseItemVariationUnitsAtPayload {
+            id: variation_id,
+            location_id: store.id,
+            units: 4,
+        }),
+        CatalogCmd::TransferItemVariationUnits(TransferItemVariationUnitsPayload {
+            id: variation_id,
+            from_location_id: store.id,
+            to_location_id: warehouse.id,
+            units: 1,
+        }),
+        CatalogCmd::IncreaseItemVariationUnits(IncreaseItemVariationUnitsPayload {
+            id: variation_id,
+            units: -12,
+        }),
+    ];
+    for cmd in cmds {
+        check(&spec, "post", "/catalog/:account/cmd", "request", &cmd);
+        catalog_service.cmd(&account, cmd).await?;
+    }
+    let stock = catalog_service
+        .stock_levels(&account, &variation_id)
+        .await?;
+    assert!(!stock.is_empty());
+    check(
+        &spec,
+        "get",
+        "/catalog/:account/:id/_stock",
+        "response",
+        &stock,
+    );
+    let alerts = catalog_service.list_alerts(&account).await?;
+    assert!(!alerts.is_empty());
+    check(
+        &spec,
+        "get",
+        "/catalog/:account/_alerts",
+        "response",
+        &alerts,
+    );
+
+    let changes = catalog_service
+        .changes(&account, &ListCatalogChangesOptions::default())
+        .await?;
+    check(
+        &spec,
+        "get",
+        "/catalog/:account/_changes",
+        "response",
+        &changes,
+    );
+    let audit = catalog_service
+        .audit(&account, &ListAuditOptions::default())
+        .await?;
+    check(&spec, "get", "/catalog/:account/_audit", "response", &audit);
+
+    let mut renamed = item_doc.catalog_object.clone();
+    if let SqlCatalogObject::Item(item) = &mut renamed {
+        item.name = format!("{} renamed", item.name);
+    }
+    check(&spec, "put", "/catalog/:account/:id", "request", &renamed);
+    catalog_service
+        .update(&account, &item_doc.id, &renamed)
+        .await?;
+    let revisions = catalog_service
+        .list_revisions(&account, &item_doc.id)
+        .await?;
+    let path = "/catalog/:account/:id/revisions";
+    check(&spec, "get", path, "response", &revisions);
+    check(&spec, "get", path, "response", &revisions[0]);
+    let diff = catalog_service
+        .diff_revisions(&account, &item_doc.id, 1, 2)
+        .await?;
+    check(
+        &spec,
+        "get",
+        "/catalog/:account/:id/revisions/_diff",
+        "response",
+        &diff,
+    );
+
+    let archive = catalog_service.export(&account).await?;
+    check(
+        &spec,
+        "get",
+        "/catalog/:account/_export",
+        "response",
+        &archive,
+    );
+    check(
+        &spec,
+        "post",
+        "/catalog/:account/_import",
+        "request",
+        &archive,
+    );
+    let import = catalog_service
+        .import(&OTHER_ACCOUNT.to_string(), &archive)
+        .await?;
+    check(
+        &spec,
+        "post",
+        "/catalog/:account/_import",
+        "response",
+        &import,
+    );
+
+    let data =
+        "item_name,item_category,variation_name,sku,price_amount,price_asset_name,price_asset_scale
+Shirt,Shop,Shirt M,SH-M,12.5,USD,2
+Shirt,Shop,Shirt L,,twelve,USD,2
+";
+    let options = CsvImportOptions {
+        preview: true,
+        ..CsvImportOptions::default()
+    };
+    let report = import_csv(&catalog_service, &account, data, &options).await?;
+    check(&spec, "post", "/catalog/:account/_csv", "response", &report);
+
+    let subscription = WebhookSubscription {
+        url: "http://127.0.0.1:9/hook".to_string(),
+        events: vec![WebhookEvent::CatalogCreated, WebhookEvent::LowStock],
+        secret: "s3cr3t".to_string(),
+        enabled: true,
+    };
+    check(
+        &spec,
+        "post",
+        "/catalog/:account/_webhooks",
+        "request",
+        &subscription,
+    );
+    let subscription = webhook_service
+        .create_subscription(&account, &subscription)
+        .await?;
+    check(
+        &spec,
+        "post",
+        "/catalog/:account/_webhooks",
+        "response",
+        &subscription,
+    );
+    webhook_service
+        .enqueue(&account, WebhookEvent::CatalogCreated, &json!({ "id": 1 }))
+        .await?;
+    let deliveries = webhook_service
+        .list_deliveries(&account, &subscription.id)
+        .await?;
+    assert_eq!(deliveries.len(), 1);
+    let path = "/catalog/:account/_webhooks/:id/deliveries";
+    check(&spec, "get", path, "response", &deliveries);
+    Ok(())
+}
+
+#[test]
+fn query_options_are_parameters() {
+    let spec = openapi();
+    let parameters = |method: &str, path: &str| -> BTreeSet<String> {
+        spec["paths"][openapi_path(path)][method]["parameters"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|parameter| parameter["in"] == "query")
+            .map(|parameter| parameter["name"].as_str().unwrap().to_string())
+            .collect()
+    };
+    let fields =
+        |value: Value| -> BTreeSet<String> { value.as_object().unwrap().keys().cloned().collect() };
+    let path = "/catalog/:account/_changes";
+    let options = serde_json::to_value(ListCatalogChangesOptions::default()).unwrap();
+    assert_eq!(parameters("get", path), fields(options));
+    let path = "/catalog/:account/_audit";
+    let options = serde_json::to_value(ListAuditOptions::default()).unwrap();
+    assert_eq!(parameters("get", path), fields(options));
+    let path = "/catalog/:account/:id/revisions";
+    let options = serde_json::to_value(ListRevisionsOptions::default()).unwrap();
+    assert_eq!(parameters("get", path), fields(options));
+    assert!(parameters("get", "/catalog/:account").contains("fields"));
+}
+
+#[test]
+fn undeclared_properties_are_caught() {
+    let spec = openapi();
+    let validator = Validator { spec: &spec };
+    let schema = json_schema(&spec, "post", "/catalog/:account", "request");
+    let mut object =
+        serde_json::to_value(SqlCatalogObject::Variation(fake_item_variation(1))).unwrap();
+    assert_eq!(validator.validate(schema, &object, ""), Ok(()));
+    object["data"]["price"] = object["data"]["price_amount"].take();
+    object["data"]
+        .as_object_mut()
+        .unwrap()
+        .remove("price_amount");
+    assert!(validator.validate(schema, &object, "").is_err());
+    object["data"]["price_amount"] = json!(12.5);
+    object["data"]["sku"] = json!(12);
+    assert!(validator.validate(schema, &object, "").is_err());
+}
+
+// the routes `main` registers, `app.at(path)` followed by its methods
+fn served_routes() -> BTreeSet<(String, String)> {
+    let source = include_str!("../src/main.rs");
+    let mut routes = BTreeSet::new();
+    for chunk in source.split("app.at(\"").skip(1) {
+        let path = chunk.split('"').next().unwrap();
+        let chain: String = chunk
+            .split(';')
+            .next()
+            .unwrap()
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect();
+        for method in ["get", "post", "put", "patch", "delete"] {
+            if chain.contains(&format!(").{}(", method)) {
+                routes.insert((method.to_string(), path.to_string()));
+            }
+        }
+    }
+    routes
+}
+
+#[test]
+fn every_served_route_is_in_the_spec() {
+    let documented: BTreeSet<(String, String)> = routes()
+        .into_iter()
+        .map(|route| (route.method.to_string(), route.path.to_string()))
+        .collect();
+    assert_eq!(served_routes(), documented);
+}
+
+#[test]
+fn problems_match_the_spec() {
+    let spec = openapi();
+    let validator = Validator { spec: &spec };
+    let schema = &spec["paths"][openapi_path("/catalog/:account")]["post"]["responses"]["default"]
+        ["content"][PROBLEM_JSON]["schema"];
+    let violations = vec![Violation::new(
+        "/data/name",
+        "not_blank",
+        "must not be blank",
+    )];
+    let mut problems = vec![
+        Problem::from(&CatalogError::ValidationFailed(violations)),
+        Problem::from(&CatalogError::CatalogEntryNotFound("1".to_string())),
+        Problem::from(&CatalogError::database("disk I/O error")),
+        Problem::for_status(415, "Unsupported Media Type", "the body isn't json"),
+    ];
+    problems[1].instance = Some("/catalog/account/1".to_string());
+    for problem in problems {
+        let value = serde_json::to_value(&problem).unwrap();
+        if let Err(error) = validator.validate(schema, &value, "") {
+            panic!("the problem doesn't match the spec, {}: {}", error, value);
+        }
+    }
+}
@@ -0,0 +1,28 @@
+#[macro_use]
+mod conformance;
+mod fixtures;
+mod utils;
+
+use async_trait::async_trait;
+use conformance::Harness;
+use merchant::catalog::postgres::CatalogPgService;
+use utils::{restore_pg_db, AnyHow, PgTestDb};
+
+struct Postgres;
+
+#[async_trait]
+impl Harness for Postgres {
+    type Service = CatalogPgService;
+    type Guard = PgTestDb;
+
+    async fn setup() -> Result<(Self::Service, Self::Guard), AnyHow> {
+        let db = restore_pg_db().await?;
+        Ok((CatalogPgService::new(db.pool.clone()), db))
+    }
+}
+
+// cargo test --test postgres -- --ignored, with POSTGRES_URL or initdb in the path
+conformance_suite!(
+    #[ignore = "needs a Postgres server"]
+    Postgres
+);
@@ -0,0 +1,211 @@
+mod fixtures;
+mod utils;
+
+use fixtures::catalog::{fake_item, fake_item_variation};
+use merchant::catalog::backend::{CatalogSQLService, SqlCatalogObject, SqlCatalogQueryOptions};
+use merchant::catalog::feed::FeedOptions;
+use merchant::catalog::memory::CatalogMemoryService;
+use merchant::catalog::projection::ProjectionOptions;
+use merchant::catalog::service::{
+    CatalogProjection, CatalogService, DiffRevisionsOptions, ListAuditOptions,
+    ListCatalogChangesOptions, ListRevisionsOptions,
+};
+use merchant::catalog::spreadsheet::CsvImportOptions;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::de::DeserializeOwned;
+use tide::http::{Request, Url};
+use utils::{restore_db, AnyHow};
+
+const CATALOG_ACCOUNT: &str = "account";
+const QUERIES: usize = 500;
+
+const KEYS: &[&str] = &[
+    "name",
+    "tags",
+    "tags[]",
+    "tags[0]",
+    "tags[x]",
+    "max_price",
+    "min_price",
+    "available_at",
+    "in_stock",
+    "limit",
+    "order_by_field",
+    "order_by_direction",
+    "order_by",
+    "since",
+    "at",
+    "from",
+    "to",
+    "actor",
+    "preview",
+    "columns",
+    "format",
+    "link",
+    "fields",
+    "",
+    "[",
+    "]]",
+    "%",
+];
+
+const VALUES: &[&str] = &[
+    "",
+    "1",
+    "0",
+    "-1",
+    "65536",
+    "4294967296",
+    "99999999999999999999999",
+    "1.5",
+    "1e39",
+    "NaN",
+    "inf",
+    "true",
+    "false",
+    "yes",
+    "Price",
+    "CreatedAt",
+    "Asc",
+    "Desc",
+    "price",
+    "2022-01-01T00:00:00",
+    "2022-13-45",
+    "%",
+    "%zz",
+    "%00",
+    "%F0%9F%92%A5",
+    "[",
+    "a=b",
+    "a&b",
+    "'; DROP TABLE catalogs; --",
+    "🦀",
+    "Rss",
+    "Tsv",
+    "id,name,price_amount",
+    "name,,tags",
+    "id,secret",
+];
+
+// the query strings a client could send, mostly nonsense
+fn random_query(rng: &mut StdRng) -> String {
+    let pairs = rng.gen_range(0..6);
+    (0..pairs)
+        .map(|_| {
+            let key = KEYS[rng.gen_range(0..KEYS.len())];
+            let value = VALUES[rng.gen_range(0..VALUES.len())];
+            match rng.gen_range(0..4) {
+                0 => key.to_string(),
+                _ => format!("{}={}", key, value),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+// what tide does with `request.query()`
+fn parse<T: DeserializeOwned>(query: &str) -> tide::Result<T> {
+    let mut url = Url::parse("http://localhost/catalog/account").unwrap();
+    url.set_query(Some(query));
+    Request::get(url).query()
+}
+
+#[test]
+fn any_query_string_is_parsed_or_refused() {
+    let mut rng = StdRng::seed_from_u64(47);
+    for _ in 0..QUERIES {
+        let query = random_query(&mut rng);
+        // a query that can't be read is the client's mistake
+        let refused = |status: tide::StatusCode| {
+            assert!(status.is_client_error(), "{} for {:?}", status, query)
+        };
+        macro_rules! check {
+            ($($options:ty),*) => {
+                $(if let Err(err) = parse::<$options>(&query) {
+                    refused(err.status());
+                })*
+            };
+        }
+        check!(
+            SqlCatalogQueryOptions,
+            ListRevisionsOptions,
+            DiffRevisionsOptions,
+            ListAuditOptions,
+            ListCatalogChangesOptions,
+            CsvImportOptions,
+            FeedOptions,
+            ProjectionOptions
+        );
+    }
+}
+
+#[test]
+fn list_options_are_read_from_the_query() -> Result<(), AnyHow> {
+    let query: SqlCatalogQueryOptions =
+        parse("name=shirt&max_price=10.5&in_stock=true&limit=5&order_by_field=Price&order_by_direction=Desc")?;
+    assert_eq!(query.options.name.as_deref(), Some("shirt"));
+    assert_eq!(query.options.max_price, Some(10.5));
+    assert_eq!(query.options.in_stock, Some(true));
+    assert_eq!(query.limit, Some(5));
+    assert!(query.order_by.is_some());
+
+    for query in ["max_price=cheap", "limit=-1", "in_stock=maybe"] {
+        let err = parse::<SqlCatalogQueryOptions>(query).expect_err(query);
+        assert_eq!(err.status(), tide::StatusCode::BadRequest);
+    }
+    Ok(())
+}
+
+#[async_std::test]
+async fn every_parsed_query_can_be_listed() -> Result<(), AnyHow> {
+    let sqlite = CatalogSQLService::new(restore_db().await?);
+    let memory = CatalogMemoryService::new();
+    let account = CATALOG_ACCOUNT.to_string();
+    let item = sqlite
+        .create(&account, &SqlCatalogObject::Item(fake_item()))
+        .await?;
+    sqlite
+        .create(
+            &account,
+            &SqlCatalogObject::Variation(fake_item_variation(item.id)),
+        )
+        .await?;
+    let item = memory
+        .create(&account, &SqlCatalogObject::Item(fake_item()))
+        .await?;
+    memory
+        .create(
+            &account,
+            &SqlCatalogObject::Variation(fake_item_variation(item.id)),
+        )
+        .await?;
+
+    let mut rng = StdRng::seed_from_u64(47);
+    for _ in 0..QUERIES {
+        let query = random_query(&mut rng);
+        let options = match parse::<SqlCatalogQueryOptions>(&query) {
+            Ok(options) => options,
+            Err(_) => continue,
+        };
+        if let Err(err) = sqlite.list(&account, &options).await {
+            panic!("sqlite can't list {:?}: {:?}", query, err);
+        }
+        if let Err(err) = memory.list(&account, &options).await {
+            panic!("memory can't list {:?}: {:?}", query, err);
+        }
+        let fields = match parse::<ProjectionOptions>(&query) {
+            Ok(ProjectionOptions {
+                fields: Some(fields),
+            }) => fields,
+            _ => continue,
+        };
+        if let Err(err) = sqlite.list_fields(&account, &options, &fields).await {
+            panic!("sqlite can't project {:?}: {:?}", query, err);
+        }
+        if let Err(err) = memory.list_fields(&account, &options, &fields).await {
+            panic!("memory can't project {:?}: {:?}", query, err);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,176 @@
+mod utils;
+
+use merchant::catalog::backend::{CatalogSQLService, SqlCatalogQueryOptions};
+use merchant::catalog::service::{CatalogColumnOrder, ListCatalogQueryOptions};
+use merchant::utils::query::{Order, OrderBy};
+use utils::{restore_db, AnyHow};
+
+const CATALOG_ACCOUNT: &str = "account";
+
+fn query(
+    options: ListCatalogQueryOptions<u32>,
+    order_by: Option<CatalogColumnOrder>,
+) -> SqlCatalogQueryOptions {
+    SqlCatalogQueryOptions {
+        limit: None,
+        order_by: order_by.map(|field| OrderBy {
+            field,
+            direction: Order::Asc,
+        }),
+        options,
+    }
+}
+
+// virtual tables answer their own constraints, json_each reads the json of
+// the row and catalog_names matches through its full text index
+fn assert_no_scans(plan: &[String]) {
+    assert!(
+        plan.iter()
+            .all(|step| !step.starts_with("SCAN") || step.contains("VIRTUAL TABLE")),
+        "full table scan in {:#?}",
+        plan
+    );
+}
+
+#[async_std::test]
+async fn list_searches_the_account_index() -> Result<(), AnyHow> {
+    let catalog_service = CatalogSQLService::new(restore_db().await?);
+    let account = CATALOG_ACCOUNT.to_string();
+
+    let plan = catalog_service
+        .explain_list(&account, &query(Default::default(), None))
+        .await?;
+
+    assert_no_scans(&plan);
+    assert!(plan[0].starts_with("SEARCH catalogs USING INDEX catalogs_account"));
+    // the fields of each type are found by primary key
+    for table in [
+        "catalog_items",
+        "catalog_variations",
+        "catalog_modifications",
+        "catalog_deliveries",
+        "catalog_controls",
+    ] {
+        assert!(plan.iter().any(
+            |step| step.starts_with(&format!("SEARCH {} USING INDEX", table))
+                && step.ends_with("(id=?)")
+        ));
+    }
+    Ok(())
+}
+
+#[async_std::test]
+async fn list_by_creation_is_sorted_by_the_index() -> Result<(), AnyHow> {
+    let catalog_service = CatalogSQLService::new(restore_db().await?);
+    let account = CATALOG_ACCOUNT.to_string();
+
+    let plan = catalog_service
+        .explain_list(
+            &account,
+            &query(Default::default(), Some(CatalogColumnOrder::CreatedAt)),
+        )
+        .await?;
+
+    assert_no_scans(&plan);
+    assert!(plan[0].contains("catalogs_account_created_at"));
+    assert!(!plan.iter().any(|step| step.contains("TEMP B-TREE")));
+    Ok(())
+}
+
+#[async_std::test]
+async fn list_filters_never_scan() -> Result<(), AnyHow> {
+    let catalog_service = CatalogSQLService::new(restore_db().await?);
+    let account = CATALOG_ACCOUNT.to_string();
+    let filters = || {
+        [
+            ListCatalogQueryOptions {
+                name: Some("shirt".to_string()),
+                ..Default::default()
+            },
+            ListCatalogQueryOptions {
+                item_id: Some(1),
+                ..Default::default()
+            },
+            ListCatalogQueryOptions {
+                tags: Some(vec!["summer".to_string()]),
+                ..Default::default()
+            },
+            ListCatalogQueryOptions {
+                min_price: Some(10.0),
+                max_price: Some(20.0),
+                ..Default::default()
+            },
+            ListCatalogQueryOptions {
+                in_stock: Some(true),
+                ..Default::default()
+            },
+            ListCatalogQueryOptions {
+                available_at: Some(1),
+                ..Default::default()
+            },
+        ]
+    };
+
+    for by_price in [false, true] {
+        for options in filters() {
+            let plan = catalog_service
+                .explain_list(
+                    &account,
+                    &query(options, by_price.then_some(CatalogColumnOrder::Price)),
+                )
+                .await?;
+            assert_no_scans(&plan);
+        }
+    }
+    Ok(())
+}
+
+#[async_std::test]
+async fn list_filters_use_their_indexes() -> Result<(), AnyHow> {
+    let catalog_service = CatalogSQLService::new(restore_db().await?);
+    let account = CATALOG_ACCOUNT.to_string();
+    let cases = [
+        (
+            ListCatalogQueryOptions {
+                min_price: Some(10.0),
+                max_price: Some(20.0),
+                ..Default::default()
+            },
+            vec!["catalog_variations_price (price_amount>? AND price_amount<?)"],
+        ),
+        (
+            ListCatalogQueryOptions {
+                name: Some("shirt".to_string()),
+                ..Default::default()
+            },
+            vec!["catalog_names VIRTUAL TABLE INDEX 0:M1"],
+        ),
+        (
+            ListCatalogQueryOptions {
+                item_id: Some(1),
+                ..Default::default()
+            },
+            vec![
+                "catalog_variations_item (item_id=?)",
+                "catalog_modifications_item (item_id=?)",
+                "catalog_deliveries_item (item_id=?)",
+                "catalog_controls_item (item_id=?)",
+            ],
+        ),
+    ];
+
+    for (options, indexes) in cases {
+        let plan = catalog_service
+            .explain_list(&account, &query(options, None))
+            .await?;
+        for index in indexes {
+            assert!(
+                plan.iter().any(|step| step.ends_with(index)),
+                "{} not used in {:#?}",
+                index,
+                plan
+            );
+        }
+    }
+    Ok(())
+}
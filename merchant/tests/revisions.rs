@@ -0,0 +1,174 @@
+mod fixtures;
+mod utils;
+
+use fixtures::catalog::{fake_item, fake_item_variation};
+use merchant::catalog::backend::{CatalogSQLService, SqlCatalogObject};
+use merchant::catalog::models::CatalogObject;
+use merchant::catalog::service::{
+    patch, CatalogCmd, CatalogError, CatalogRevisionService, CatalogService, Commander,
+    IncreaseItemVariationUnitsPayload,
+};
+use serde_json::json;
+use utils::{check_if_error_is, restore_db, AnyHow};
+
+const CATALOG_ACCOUNT: &str = "account";
+
+#[async_std::test]
+async fn every_write_keeps_a_revision() -> Result<(), AnyHow> {
+    let pool = restore_db().await?;
+    let catalog_service = CatalogSQLService::new(pool);
+    let account = CATALOG_ACCOUNT.to_string();
+    let item = fake_item();
+    let item_doc = catalog_service
+        .create(&account, &SqlCatalogObject::Item(item.clone()))
+        .await?;
+    let mut renamed = item.clone();
+    renamed.name = format!("{} renamed", item.name);
+    catalog_service
+        .update(
+            &account,
+            &item_doc.id,
+            &SqlCatalogObject::Item(renamed.clone()),
+        )
+        .await?;
+
+    let revisions = catalog_service
+        .list_revisions(&account, &item_doc.id)
+        .await?;
+    assert_eq!(revisions.len(), 2);
+    assert_eq!(revisions[0].revision, 1);
+    assert_eq!(
+        revisions[0].document.catalog_object,
+        CatalogObject::Item(item.clone())
+    );
+    assert_eq!(
+        revisions[1].document.catalog_object,
+        CatalogObject::Item(renamed)
+    );
+
+    let first = catalog_service
+        .read_revision(&account, &item_doc.id, 1)
+        .await?;
+    assert_eq!(first.document.catalog_object, CatalogObject::Item(item));
+
+    let current = catalog_service
+        .read_revision_at(&account, &item_doc.id, &revisions[1].created_at)
+        .await?;
+    assert_eq!(current.revision, 2);
+
+    let result = catalog_service
+        .list_revisions(&"other".to_string(), &item_doc.id)
+        .await?;
+    assert!(result.is_empty());
+    let result = catalog_service
+        .read_revision(&account, &item_doc.id, 3)
+        .await;
+    check_if_error_is(
+        result.unwrap_err(),
+        CatalogError::CatalogEntryNotFound(item_doc.id.to_string()),
+    );
+    Ok(())
+}
+
+#[async_std::test]
+async fn diff_between_revisions() -> Result<(), AnyHow> {
+    let pool = restore_db().await?;
+    let catalog_service = CatalogSQLService::new(pool);
+    let account = CATALOG_ACCOUNT.to_string();
+    let item_doc = catalog_service
+        .create(&account, &SqlCatalogObject::Item(fake_item()))
+        .await?;
+    let mut variation = fake_item_variation(item_doc.id);
+    variation.available_units = 10;
+    let variation_doc = catalog_service
+        .create(&account, &SqlCatalogObject::Variation(variation))
+        .await?;
+    catalog_service
+        .cmd(
+            &account,
+            CatalogCmd::IncreaseItemVariationUnits(IncreaseItemVariationUnitsPayload {
+                id: variation_doc.id,
+                units: 5,
+            }),
+        )
+        .await?;
+
+    let diff = catalog_service
+        .diff_revisions(&account, &variation_doc.id, 1, 2)
+        .await?;
+    assert_eq!(diff.changes.len(), 1);
+    assert_eq!(diff.changes[0].path, "/data/available_units");
+    assert_eq!(diff.changes[0].before, Some(10.into()));
+    assert_eq!(diff.changes[0].after, Some(15.into()));
+
+    let diff = catalog_service
+        .diff_revisions(&account, &variation_doc.id, 2, 2)
+        .await?;
+    assert!(diff.changes.is_empty());
+    Ok(())
+}
+
+#[async_std::test]
+async fn patches_are_revisions() -> Result<(), AnyHow> {
+    let pool = restore_db().await?;
+    let catalog_service = CatalogSQLService::new(pool);
+    let account = CATALOG_ACCOUNT.to_string();
+    let item_doc = catalog_service
+        .create(&account, &SqlCatalogObject::Item(fake_item()))
+        .await?;
+    let variation_doc = catalog_service
+        .create(
+            &account,
+            &SqlCatalogObject::Variation(fake_item_variation(item_doc.id)),
+        )
+        .await?;
+    let changes = json!({ "data": { "price_amount": 12.5 } });
+    patch(
+        &catalog_service,
+        &account,
+        &variation_doc.id,
+        &changes,
+        None,
+    )
+    .await?;
+
+    let diff = catalog_service
+        .diff_revisions(&account, &variation_doc.id, 1, 2)
+        .await?;
+    assert_eq!(diff.changes.len(), 1);
+    assert_eq!(diff.changes[0].path, "/data/price_amount");
+    assert_eq!(diff.changes[0].after, Some(12.5.into()));
+    Ok(())
+}
+
+#[async_std::test]
+async fn restore_edited_and_deleted_objects() -> Result<(), AnyHow> {
+    let pool = restore_db().await?;
+    let catalog_service = CatalogSQLService::new(pool);
+    let account = CATALOG_ACCOUNT.to_string();
+    let item = fake_item();
+    let item_doc = catalog_service
+        .create(&account, &SqlCatalogObject::Item(item.clone()))
+        .await?;
+    catalog_service
+        .update(&account, &item_doc.id, &SqlCatalogObject::Item(fake_item()))
+        .await?;
+
+    let restored = catalog_service
+        .restore_revision(&account, &item_doc.id, 1)
+        .await?;
+    assert_eq!(restored.catalog_object, CatalogObject::Item(item.clone()));
+    let revisions = catalog_service
+        .list_revisions(&account, &item_doc.id)
+        .await?;
+    assert_eq!(revisions.len(), 3);
+
+    catalog_service.delete(&account, &item_doc.id).await?;
+    let restored = catalog_service
+        .restore_revision(&account, &item_doc.id, 2)
+        .await?;
+    assert_eq!(restored.id, item_doc.id);
+    let read = catalog_service.read(&account, &item_doc.id).await?;
+    assert_eq!(read.catalog_object, revisions[1].document.catalog_object);
+    Ok(())
+}
@@ -0,0 +1,175 @@
+mod fixtures;
+mod utils;
+
+use std::collections::HashMap;
+
+use fixtures::catalog::{
+    fake_item, fake_item_control, fake_item_delivery, fake_item_modification, fake_item_variation,
+};
+use merchant::catalog::backend::{CatalogSQLService, Id, SqlCatalogObject};
+use merchant::catalog::models::Time;
+use merchant::catalog::service::{CatalogError, CatalogService};
+use sqlx::SqlitePool;
+use utils::{check_if_error_is, migrate, restore_db_before, AnyHow};
+
+const CATALOG_ACCOUNT: &str = "account";
+const NORMALIZED_CATALOG: i64 = 20261018150000;
+
+// writes the object the way it was stored in the `*_data` columns
+async fn insert_legacy(
+    pool: &SqlitePool,
+    id: Id,
+    catalog_object: &SqlCatalogObject,
+) -> Result<(), AnyHow> {
+    let column = match catalog_object {
+        SqlCatalogObject::Item(_) => "item_data",
+        SqlCatalogObject::Variation(_) => "item_variation_data",
+        SqlCatalogObject::Modification(_) => "item_modification_data",
+        SqlCatalogObject::Delivery(_) => "item_delivery_data",
+        SqlCatalogObject::Control(_) => "item_control_data",
+    };
+    let data = serde_json::to_value(catalog_object)?["data"].take();
+    sqlx::query(
+        format!(
+            "INSERT INTO catalogs (id, account, type_entry, {}) VALUES (?, ?, ?, ?)",
+            column
+        )
+        .as_str(),
+    )
+    .bind(id)
+    .bind(CATALOG_ACCOUNT)
+    .bind(catalog_object.to_string())
+    .bind(data.to_string())
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+#[async_std::test]
+async fn objects_are_moved_to_their_tables() -> Result<(), AnyHow> {
+    let pool = restore_db_before(NORMALIZED_CATALOG).await?;
+    let mut item = fake_item();
+    item.warranty_time = Some(Time::Fixed { seconds: 3600 });
+    let mut variation = fake_item_variation(1);
+    variation.processing_time = Some(Time::Fixed { seconds: 60 });
+    variation.upc = Some("012345678905".to_string());
+    variation.reorder_threshold = Some(2);
+    variation.extra_attributes = Some(HashMap::from([("color".to_string(), "red".to_string())]));
+    let objects = [
+        (1, SqlCatalogObject::Item(item)),
+        (2, SqlCatalogObject::Variation(variation)),
+        (3, SqlCatalogObject::Modification(fake_item_modification(1))),
+        (4, SqlCatalogObject::Delivery(fake_item_delivery(1))),
+        (5, SqlCatalogObject::Control(fake_item_control(1))),
+    ];
+    for (id, catalog_object) in objects.iter() {
+        insert_legacy(&pool, *id, catalog_object).await?;
+    }
+    // its item was removed when references weren't checked
+    insert_legacy(
+        &pool,
+        6,
+        &SqlCatalogObject::Variation(fake_item_variation(7)),
+    )
+    .await?;
+
+    migrate(&pool).await?;
+
+    let catalog_service = CatalogSQLService::new(pool.clone());
+    let account = CATALOG_ACCOUNT.to_string();
+    for (id, catalog_object) in objects.iter() {
+        let document = catalog_service.read(&account, id).await?;
+        assert_eq!(&document.catalog_object, catalog_object);
+    }
+    let result = catalog_service.read(&account, &6).await;
+    check_if_error_is(
+        result.unwrap_err(),
+        CatalogError::CatalogEntryNotFound(6.to_string()),
+    );
+    // the orphan is kept aside instead of lost
+    let (type_entry, item_id): (String, i64) = sqlx::query_as(
+        "SELECT type_entry, json_extract(data, '$.item_id') FROM catalog_orphans WHERE id = 6",
+    )
+    .fetch_one(&pool)
+    .await?;
+    assert_eq!(type_entry, "Variation");
+    assert_eq!(item_id, 7);
+    Ok(())
+}
+
+#[async_std::test]
+async fn children_without_an_item_id_are_quarantined() -> Result<(), AnyHow> {
+    let pool = restore_db_before(NORMALIZED_CATALOG).await?;
+    insert_legacy(&pool, 1, &SqlCatalogObject::Item(fake_item())).await?;
+    let mut missing = serde_json::to_value(fake_item_variation(1))?;
+    missing.as_object_mut().unwrap().remove("item_id");
+    let mut null = serde_json::to_value(fake_item_variation(1))?;
+    null["item_id"] = serde_json::Value::Null;
+    for (id, data) in [(2, missing), (3, null)] {
+        sqlx::query(
+            "INSERT INTO catalogs (id, account, type_entry, item_variation_data)
+            VALUES (?, ?, 'Variation', ?)",
+        )
+        .bind(id)
+        .bind(CATALOG_ACCOUNT)
+        .bind(data.to_string())
+        .execute(&pool)
+        .await?;
+    }
+
+    migrate(&pool).await?;
+
+    let orphans: Vec<(i64,)> = sqlx::query_as("SELECT id FROM catalog_orphans ORDER BY id")
+        .fetch_all(&pool)
+        .await?;
+    assert_eq!(orphans, vec![(2,), (3,)]);
+    let catalog_service = CatalogSQLService::new(pool);
+    assert!(
+        catalog_service
+            .exists(&CATALOG_ACCOUNT.to_string(), &1)
+            .await?
+    );
+    Ok(())
+}
+
+#[async_std::test]
+async fn children_need_an_existing_item() -> Result<(), AnyHow> {
+    let pool = restore_db_before(i64::MAX).await?;
+    sqlx::query("INSERT INTO catalogs (id, account, type_entry) VALUES (1, ?, 'Variation')")
+        .bind(CATALOG_ACCOUNT)
+        .execute(&pool)
+        .await?;
+
+    let result = sqlx::query(
+        "INSERT INTO catalog_variations (id, item_id, name, sku, images, enabled, measurement_units, available_units, price_type, price_amount, price_asset_name, price_asset_scale)
+        VALUES (1, 2, 'name', 'sku', '[]', TRUE, 'Units', 0, 'Fixed', 1.0, 'USD', 2)",
+    )
+    .execute(&pool)
+    .await;
+    assert!(result.is_err());
+    Ok(())
+}
+
+#[async_std::test]
+async fn removing_an_object_removes_its_fields() -> Result<(), AnyHow> {
+    let pool = restore_db_before(i64::MAX).await?;
+    let catalog_service = CatalogSQLService::new(pool.clone());
+    let account = CATALOG_ACCOUNT.to_string();
+    let item_doc = catalog_service
+        .create(&account, &SqlCatalogObject::Item(fake_item()))
+        .await?;
+    let variation_doc = catalog_service
+        .create(
+            &account,
+            &SqlCatalogObject::Variation(fake_item_variation(item_doc.id)),
+        )
+        .await?;
+
+    catalog_service.delete(&account, &variation_doc.id).await?;
+
+    let (count,): (i64,) = sqlx::query_as("SELECT COUNT(1) FROM catalog_variations")
+        .fetch_one(&pool)
+        .await?;
+    assert_eq!(count, 0);
+    Ok(())
+}
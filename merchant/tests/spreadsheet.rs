@@ -0,0 +1,223 @@
+mod fixtures;
+mod utils;
+
+use fixtures::catalog::{fake_item, fake_item_variation};
+use merchant::catalog::backend::{CatalogSQLService, SqlCatalogObject, SqlCatalogQueryOptions};
+use merchant::catalog::models::{CatalogObject, ItemVariation, Price};
+use merchant::catalog::service::CatalogService;
+use merchant::catalog::spreadsheet::{import_csv, write_csv, CsvImportOptions, CsvRowError};
+use std::collections::HashMap;
+use utils::{restore_db, AnyHow};
+
+const CATALOG_ACCOUNT: &str = "account";
+const OTHER_ACCOUNT: &str = "other";
+
+fn everything() -> SqlCatalogQueryOptions {
+    SqlCatalogQueryOptions {
+        limit: None,
+        order_by: None,
+        options: Default::default(),
+    }
+}
+
+fn variations(objects: &[CatalogObject<u32>]) -> Vec<&ItemVariation<u32>> {
+    let mut variations: Vec<&ItemVariation<u32>> = objects
+        .iter()
+        .filter_map(|object| match object {
+            CatalogObject::Variation(variation) => Some(variation),
+            _ => None,
+        })
+        .collect();
+    variations.sort_by(|a, b| a.sku.cmp(&b.sku));
+    variations
+}
+
+#[async_std::test]
+async fn exported_sheets_import_into_another_account() -> Result<(), AnyHow> {
+    let catalog_service = CatalogSQLService::new(restore_db().await?);
+    let account = CATALOG_ACCOUNT.to_string();
+    let item_doc = catalog_service
+        .create(&account, &SqlCatalogObject::Item(fake_item()))
+        .await?;
+    let mut variation = fake_item_variation(item_doc.id);
+    variation.extra_attributes = Some(HashMap::from([
+        ("color".to_string(), "blue, dark".to_string()),
+        ("size".to_string(), "M".to_string()),
+    ]));
+    variation.reorder_threshold = Some(3);
+    catalog_service
+        .create(&account, &SqlCatalogObject::Variation(variation))
+        .await?;
+    catalog_service
+        .create(
+            &account,
+            &SqlCatalogObject::Variation(fake_item_variation(item_doc.id)),
+        )
+        .await?;
+    // without variations
+    catalog_service
+        .create(&account, &SqlCatalogObject::Item(fake_item()))
+        .await?;
+
+    let documents = catalog_service.list(&account, &everything()).await?;
+    let data = write_csv(&documents)?;
+    let header = data.lines().next().unwrap();
+    assert!(header.ends_with("price_asset_scale,extra_attributes.color,extra_attributes.size"));
+    assert_eq!(data.lines().count(), 4);
+
+    let other = OTHER_ACCOUNT.to_string();
+    let report = import_csv(
+        &catalog_service,
+        &other,
+        &data,
+        &CsvImportOptions::default(),
+    )
+    .await?;
+    assert!(report.sheet.errors.is_empty(), "{:?}", report.sheet.errors);
+    assert!(report.sheet.ignored_columns.is_empty());
+    assert_eq!(report.created.len(), 4);
+
+    let imported = catalog_service.list(&other, &everything()).await?;
+    let exported: Vec<CatalogObject<u32>> = documents
+        .into_iter()
+        .map(|document| document.catalog_object)
+        .collect();
+    let imported: Vec<CatalogObject<u32>> = imported
+        .into_iter()
+        .map(|document| document.catalog_object)
+        .collect();
+    for (imported, exported) in variations(&imported).into_iter().zip(variations(&exported)) {
+        assert_eq!(imported.sku, exported.sku);
+        assert_eq!(imported.price, exported.price);
+        assert_eq!(imported.extra_attributes, exported.extra_attributes);
+        assert_eq!(imported.reorder_threshold, exported.reorder_threshold);
+        assert_eq!(imported.available_units, exported.available_units);
+    }
+    let mut exported_items: Vec<_> = exported.iter().filter_map(|object| object.item()).collect();
+    let mut imported_items: Vec<_> = imported.iter().filter_map(|object| object.item()).collect();
+    exported_items.sort_by(|a, b| a.name.cmp(&b.name));
+    imported_items.sort_by(|a, b| a.name.cmp(&b.name));
+    assert_eq!(imported_items, exported_items);
+    Ok(())
+}
+
+#[async_std::test]
+async fn rows_with_errors_stop_the_import() -> Result<(), AnyHow> {
+    let catalog_service = CatalogSQLService::new(restore_db().await?);
+    let account = CATALOG_ACCOUNT.to_string();
+    let data =
+        "item_name,item_category,variation_name,sku,price_amount,price_asset_name,price_asset_scale
+Shirt,Shop,Shirt M,SH-M,12.5,USD,2
+Shirt,Shop,Shirt L,,twelve,USD,2
+Mug,Kitchen,Mug,MG-1,4,USD,2
+";
+
+    let report = import_csv(
+        &catalog_service,
+        &account,
+        data,
+        &CsvImportOptions::default(),
+    )
+    .await?;
+    assert_eq!(
+        report.sheet.errors,
+        vec![
+            CsvRowError {
+                line: 3,
+                column: Some("sku".to_string()),
+                message: "is required".to_string(),
+            },
+            CsvRowError {
+                line: 3,
+                column: Some("price_amount".to_string()),
+                message: "\"twelve\" is not a valid number".to_string(),
+            },
+            CsvRowError {
+                line: 4,
+                column: Some("item_category".to_string()),
+                message: "\"Kitchen\" is not a known value".to_string(),
+            },
+        ]
+    );
+    assert!(report.created.is_empty());
+    assert!(catalog_service
+        .list(&account, &everything())
+        .await?
+        .is_empty());
+    Ok(())
+}
+
+#[async_std::test]
+async fn previews_create_nothing() -> Result<(), AnyHow> {
+    let catalog_service = CatalogSQLService::new(restore_db().await?);
+    let account = CATALOG_ACCOUNT.to_string();
+    let data = "item_name,variation_name,sku,price_amount,price_asset_name,price_asset_scale,notes
+Shirt,Shirt M,SH-M,12.5,USD,2,first
+Shirt,Shirt L,SH-L,13,USD,2,
+";
+    let options = CsvImportOptions {
+        preview: true,
+        columns: None,
+    };
+
+    let report = import_csv(&catalog_service, &account, data, &options).await?;
+    assert!(report.preview);
+    assert!(report.sheet.errors.is_empty());
+    assert_eq!(report.sheet.ignored_columns, vec!["notes".to_string()]);
+    // the rows of the same item share it
+    assert_eq!(report.sheet.documents.len(), 3);
+    assert!(report.created.is_empty());
+    assert!(catalog_service
+        .list(&account, &everything())
+        .await?
+        .is_empty());
+    Ok(())
+}
+
+#[async_std::test]
+async fn headers_are_mapped_to_columns() -> Result<(), AnyHow> {
+    let catalog_service = CatalogSQLService::new(restore_db().await?);
+    let account = CATALOG_ACCOUNT.to_string();
+    let data = "Product,Option,Code,Price,Currency,Decimals,Stock,Material
+Shirt,Shirt M,SH-M,12.5,USD,2,8,cotton
+";
+    let options = CsvImportOptions {
+        preview: false,
+        columns: Some(
+            "Product:item_name, Option:variation_name, Code:sku, Price:price_amount, \
+            Currency:price_asset_name, Decimals:price_asset_scale, Stock:available_units, \
+            Material:extra_attributes.material"
+                .to_string(),
+        ),
+    };
+
+    let report = import_csv(&catalog_service, &account, data, &options).await?;
+    assert!(report.sheet.errors.is_empty(), "{:?}", report.sheet.errors);
+    assert_eq!(report.created.len(), 2);
+    let variation = report
+        .created
+        .iter()
+        .find_map(|document| match &document.catalog_object {
+            CatalogObject::Variation(variation) => Some(variation),
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(variation.sku, "SH-M");
+    assert_eq!(variation.available_units, 8);
+    assert_eq!(
+        variation.price,
+        Price::Fixed {
+            amount: 12.5,
+            asset_name: "USD".to_string(),
+            asset_scale: 2,
+        }
+    );
+    assert_eq!(
+        variation.extra_attributes,
+        Some(HashMap::from([(
+            "material".to_string(),
+            "cotton".to_string()
+        )]))
+    );
+    Ok(())
+}
@@ -0,0 +1,205 @@
+mod fixtures;
+mod utils;
+
+use fixtures::catalog::{fake_item, fake_item_variation, fake_stock_location};
+use merchant::catalog::backend::{CatalogSQLService, SqlCatalogObject};
+use merchant::catalog::models::{CatalogObject, StockLocationKind};
+use merchant::catalog::service::{
+    CatalogAuditLog, CatalogChangeFeed, CatalogCmd, CatalogError, CatalogService, Commander,
+    IncreaseItemVariationUnitsAtPayload, ListAuditOptions, ListCatalogChangesOptions,
+    StockLocationService,
+};
+use merchant::utils::sqlite::{BusyRetryPolicy, SqliteConfig};
+use sqlx::{Connection, SqliteConnection};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use utils::{check_if_error_is, migrate, AnyHow};
+
+const CATALOG_ACCOUNT: &str = "account";
+
+// a database file removed with its WAL files on drop, the pool needs a file
+// to share a database between connections
+struct SqliteFile {
+    path: PathBuf,
+}
+
+impl SqliteFile {
+    fn new() -> Self {
+        let path = std::env::temp_dir().join(format!("merchant-{}.db", rand::random::<u32>()));
+        Self { path }
+    }
+
+    fn url(&self) -> String {
+        format!("sqlite://{}?mode=rwc", self.path.display())
+    }
+}
+
+impl Drop for SqliteFile {
+    fn drop(&mut self) {
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", self.path.display(), suffix));
+        }
+    }
+}
+
+// another process writing, it keeps the write lock until it's dropped
+async fn lock(file: &SqliteFile) -> Result<SqliteConnection, AnyHow> {
+    let mut conn = SqliteConnection::connect(&file.url()).await?;
+    sqlx::query("BEGIN IMMEDIATE").execute(&mut conn).await?;
+    Ok(conn)
+}
+
+#[async_std::test]
+async fn the_database_is_opened_with_the_config() -> Result<(), AnyHow> {
+    let file = SqliteFile::new();
+    let config = SqliteConfig {
+        max_connections: 3,
+        ..SqliteConfig::default()
+    };
+    let pool = config.connect(&file.url()).await?;
+
+    let (journal_mode,): (String,) = sqlx::query_as("PRAGMA journal_mode")
+        .fetch_one(&pool)
+        .await?;
+    assert_eq!(journal_mode, "wal");
+    let (synchronous,): (i32,) = sqlx::query_as("PRAGMA synchronous")
+        .fetch_one(&pool)
+        .await?;
+    // NORMAL
+    assert_eq!(synchronous, 1);
+
+    // the connections of an in-memory database would each see a different one
+    let memory = config.connect("sqlite::memory:").await?;
+    let _conn = memory.acquire().await?;
+    assert!(memory.try_acquire().is_none());
+    Ok(())
+}
+
+#[async_std::test]
+async fn concurrent_writers_all_succeed() -> Result<(), AnyHow> {
+    let file = SqliteFile::new();
+    let config = SqliteConfig {
+        max_connections: 8,
+        ..SqliteConfig::default()
+    };
+    let pool = config.connect(&file.url()).await?;
+    migrate(&pool).await?;
+    let catalog_service = Arc::new(CatalogSQLService::new(pool));
+    let account = CATALOG_ACCOUNT.to_string();
+
+    let item_doc = catalog_service
+        .create(&account, &SqlCatalogObject::Item(fake_item()))
+        .await?;
+    let variation_doc = catalog_service
+        .create(
+            &account,
+            &SqlCatalogObject::Variation(fake_item_variation(item_doc.id)),
+        )
+        .await?;
+    let store = catalog_service
+        .create_location(&account, &fake_stock_location(StockLocationKind::Store))
+        .await?;
+
+    // every command reads the references before writing, the transactions
+    // that lose the race for the write lock have to start over
+    let writers: Vec<_> = (0..16)
+        .map(|_| {
+            let catalog_service = catalog_service.clone();
+            let account = account.clone();
+            let (id, location_id) = (variation_doc.id, store.id);
+            async_std::task::spawn(async move {
+                catalog_service
+                    .cmd(
+                        &account,
+                        CatalogCmd::IncreaseItemVariationUnitsAt(
+                            IncreaseItemVariationUnitsAtPayload {
+                                id,
+                                location_id,
+                                units: 1,
+                            },
+                        ),
+                    )
+                    .await
+            })
+        })
+        .collect();
+    for writer in writers {
+        writer.await?;
+    }
+
+    let levels = catalog_service
+        .stock_levels(&account, &variation_doc.id)
+        .await?;
+    assert_eq!(levels[0].units, 16);
+    let read = catalog_service.read(&account, &variation_doc.id).await?;
+    match (&read.catalog_object, &variation_doc.catalog_object) {
+        (CatalogObject::Variation(read), CatalogObject::Variation(created)) => {
+            assert_eq!(read.available_units, created.available_units + 16);
+        }
+        _ => panic!("catalog_object should be a variation"),
+    }
+    Ok(())
+}
+
+#[async_std::test]
+async fn writes_are_retried_while_the_database_is_locked() -> Result<(), AnyHow> {
+    let file = SqliteFile::new();
+    let config = SqliteConfig {
+        busy_timeout: Duration::ZERO,
+        ..SqliteConfig::default()
+    };
+    let pool = config.connect(&file.url()).await?;
+    migrate(&pool).await?;
+    let catalog_service = CatalogSQLService::new(pool).with_busy_retry(BusyRetryPolicy {
+        max_attempts: 50,
+        base_delay: Duration::from_millis(10),
+        max_delay: Duration::from_millis(20),
+    });
+    let account = CATALOG_ACCOUNT.to_string();
+
+    let mut writer = lock(&file).await?;
+    async_std::task::spawn(async move {
+        async_std::task::sleep(Duration::from_millis(100)).await;
+        sqlx::query("ROLLBACK").execute(&mut writer).await
+    });
+
+    let item_doc = catalog_service
+        .create(&account, &SqlCatalogObject::Item(fake_item()))
+        .await?;
+    assert!(catalog_service.exists(&account, &item_doc.id).await?);
+    // the change and the audit entry are written once with the object
+    let changes = catalog_service
+        .changes(&account, &ListCatalogChangesOptions::default())
+        .await?;
+    assert_eq!(changes.len(), 1);
+    let audit = catalog_service
+        .audit(&account, &ListAuditOptions::default())
+        .await?;
+    assert_eq!(audit.len(), 1);
+    Ok(())
+}
+
+#[async_std::test]
+async fn writes_give_up_when_the_database_stays_locked() -> Result<(), AnyHow> {
+    let file = SqliteFile::new();
+    let config = SqliteConfig {
+        busy_timeout: Duration::ZERO,
+        ..SqliteConfig::default()
+    };
+    let pool = config.connect(&file.url()).await?;
+    migrate(&pool).await?;
+    let catalog_service = CatalogSQLService::new(pool).with_busy_retry(BusyRetryPolicy {
+        max_attempts: 2,
+        base_delay: Duration::from_millis(1),
+        max_delay: Duration::from_millis(1),
+    });
+    let account = CATALOG_ACCOUNT.to_string();
+
+    let _writer = lock(&file).await?;
+    let result = catalog_service
+        .create(&account, &SqlCatalogObject::Item(fake_item()))
+        .await;
+    check_if_error_is(result.unwrap_err(), CatalogError::DatabaseBusy);
+    Ok(())
+}
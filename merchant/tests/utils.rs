@@ -1,8 +1,16 @@
+#![allow(dead_code)]
+
 use merchant::catalog::service::CatalogError;
-use sqlx::{migrate::Migrator, sqlite::SqlitePoolOptions, SqlitePool as Pool};
+use rand::Rng;
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use sqlx::{migrate::Migrator, sqlite::SqlitePoolOptions, PgPool, SqlitePool as Pool};
 use std::any::{Any, TypeId};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
 
 static MIGRATOR: Migrator = sqlx::migrate!();
+static PG_MIGRATOR: Migrator = sqlx::migrate!("./migrations_postgres");
 pub type AnyHow = Box<dyn std::error::Error>;
 
 pub async fn restore_db() -> Result<Pool, AnyHow> {
@@ -11,8 +19,121 @@ pub async fn restore_db() -> Result<Pool, AnyHow> {
     Ok(pool)
 }
 
+// a database with only the migrations older than `version`, to test upgrades
+pub async fn restore_db_before(version: i64) -> Result<Pool, AnyHow> {
+    let pool = get_conn().await?;
+    let migrator = Migrator {
+        migrations: MIGRATOR
+            .iter()
+            .filter(|migration| migration.version < version)
+            .cloned()
+            .collect::<Vec<_>>()
+            .into(),
+        ignore_missing: false,
+    };
+    migrator.run(&pool).await?;
+    Ok(pool)
+}
+
+pub async fn migrate(pool: &Pool) -> Result<(), AnyHow> {
+    MIGRATOR.run(pool).await?;
+    Ok(())
+}
+
 async fn get_conn() -> Result<Pool, AnyHow> {
-    Ok(SqlitePoolOptions::new().connect("sqlite::memory:").await?)
+    // every connection to `sqlite::memory:` opens a different database
+    Ok(SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await?)
+}
+
+// A Postgres database for the tests. When `POSTGRES_URL` is set that server
+// is used, otherwise a throwaway cluster is started with `initdb`/`pg_ctl`
+// and removed on drop. The tests fail when neither is available.
+pub struct PgTestDb {
+    pub pool: PgPool,
+    cluster: Option<PathBuf>,
+}
+
+impl Drop for PgTestDb {
+    fn drop(&mut self) {
+        if let Some(dir) = &self.cluster {
+            let _ = Command::new("pg_ctl")
+                .arg("-D")
+                .arg(dir)
+                .args(["-m", "immediate", "stop"])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status();
+            let _ = std::fs::remove_dir_all(dir);
+        }
+    }
+}
+
+pub async fn restore_pg_db() -> Result<PgTestDb, AnyHow> {
+    let (options, cluster) = match std::env::var("POSTGRES_URL") {
+        Ok(url) => (url.parse::<PgConnectOptions>()?, None),
+        Err(_) => match start_pg_cluster() {
+            Some((options, dir)) => (options, Some(dir)),
+            None => return Err("no Postgres available, set POSTGRES_URL or install initdb".into()),
+        },
+    };
+    let pool = PgPoolOptions::new()
+        .max_connections(4)
+        .connect_with(options)
+        .await?;
+    PG_MIGRATOR.run(&pool).await?;
+    Ok(PgTestDb { pool, cluster })
+}
+
+fn start_pg_cluster() -> Option<(PgConnectOptions, PathBuf)> {
+    let port = TcpListener::bind("127.0.0.1:0")
+        .ok()?
+        .local_addr()
+        .ok()?
+        .port();
+    let dir = std::env::temp_dir().join(format!("merchant-pg-{}", rand::thread_rng().gen::<u64>()));
+    let initialized = Command::new("initdb")
+        .arg("-D")
+        .arg(&dir)
+        .args(["-U", "postgres", "--auth=trust"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false);
+    let started = initialized
+        && Command::new("pg_ctl")
+            .arg("-D")
+            .arg(&dir)
+            .arg("-o")
+            .arg(format!(
+                "-p {} -c listen_addresses=127.0.0.1 -k {}",
+                port,
+                dir.display()
+            ))
+            .args(["-w", "start"])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map(|status| status.success())
+            .unwrap_or(false);
+    if !started {
+        let _ = std::fs::remove_dir_all(&dir);
+        return None;
+    }
+    let options = PgConnectOptions::new()
+        .host("127.0.0.1")
+        .port(port)
+        .username("postgres")
+        .database("postgres");
+    Some((options, dir))
+}
+
+// accounts are random so tests sharing a `POSTGRES_URL` don't see each other
+pub fn random_account() -> String {
+    format!("account-{}", rand::thread_rng().gen::<u64>())
 }
 
 pub trait InstanceOf
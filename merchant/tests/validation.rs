@@ -0,0 +1,176 @@
+mod fixtures;
+
+use std::collections::{BTreeSet, HashMap};
+
+use fixtures::catalog::{
+    fake_item, fake_item_control, fake_item_delivery, fake_item_modification, fake_item_variation,
+};
+use merchant::catalog::models::{
+    CatalogObject, Control, Delivery, Image, MatrixControl, MatrixProp, Price,
+};
+use merchant::catalog::validation::violations;
+use schemars::schema_for;
+use serde_json::Value;
+
+// every rule breakable in a single object, with its constraint in the schema
+fn invalid_objects() -> Vec<CatalogObject<u32>> {
+    let image = Image { url: String::new() };
+    let price = Price::Fixed {
+        amount: -1.0,
+        asset_name: " ".to_string(),
+        asset_scale: -1,
+    };
+
+    let mut item = fake_item();
+    item.name = " ".to_string();
+    item.tags = vec!["summer".to_string(), String::new()];
+    item.images = vec![image.clone()];
+
+    let mut variation = fake_item_variation(1);
+    variation.name = String::new();
+    variation.sku = " ".to_string();
+    variation.upc = Some("12a".to_string());
+    variation.images = vec![image.clone()];
+    variation.available_units = -1;
+    variation.reorder_threshold = Some(-1);
+    variation.price = price.clone();
+    variation.extra_attributes = Some(HashMap::from([(" ".to_string(), "red".to_string())]));
+
+    let mut modification = fake_item_modification(1);
+    modification.name = String::new();
+    modification.images = vec![image];
+    modification.price = price;
+
+    let mut delivery = fake_item_delivery(1);
+    delivery.delivery = Delivery::Shipping {
+        width_mm: 0,
+        length_mm: 0,
+        height_mm: -1,
+        weight_grams: 0,
+    };
+
+    let mut without_props = fake_item_control(1);
+    without_props.control = Control::Matrix(MatrixControl {
+        key_template: " ".to_string(),
+        props: vec![],
+        combinations: HashMap::new(),
+    });
+
+    let mut blank_prop = fake_item_control(1);
+    blank_prop.control = Control::Matrix(MatrixControl {
+        key_template: "size".to_string(),
+        props: vec![MatrixProp {
+            name: String::new(),
+            options: vec![],
+        }],
+        combinations: HashMap::from([(" ".to_string(), 1)]),
+    });
+
+    vec![
+        CatalogObject::Item(item),
+        CatalogObject::Variation(variation),
+        CatalogObject::Modification(modification),
+        CatalogObject::Delivery(delivery),
+        CatalogObject::Control(without_props),
+        CatalogObject::Control(blank_prop),
+    ]
+}
+
+// the schemas a value can be checked against, through references and unions
+fn expand<'a>(root: &'a Value, schema: &'a Value, schemas: &mut Vec<&'a Value>) {
+    if let Some(reference) = schema["$ref"].as_str() {
+        let name = reference.trim_start_matches("#/definitions/");
+        return expand(root, &root["definitions"][name], schemas);
+    }
+    schemas.push(schema);
+    for union in ["anyOf", "oneOf", "allOf"] {
+        for branch in schema[union].as_array().into_iter().flatten() {
+            expand(root, branch, schemas);
+        }
+    }
+}
+
+// the schemas of the field a pointer of a violation points to
+fn resolve<'a>(root: &'a Value, object_type: &str, pointer: &str) -> Vec<&'a Value> {
+    let mut schemas = vec![];
+    expand(root, root, &mut schemas);
+    // the variant of the object, its tag is an enum of a single name
+    schemas.retain(|schema| {
+        schema["properties"]["type"]["enum"]
+            .as_array()
+            .is_some_and(|names| names.contains(&Value::from(object_type)))
+    });
+
+    for token in pointer.split('/').skip(1) {
+        let mut children = vec![];
+        for schema in schemas {
+            let child = match &schema["properties"][token] {
+                Value::Null if token.parse::<usize>().is_ok() => &schema["items"],
+                Value::Null => &schema["propertyNames"],
+                child => child,
+            };
+            if !child.is_null() {
+                expand(root, child, &mut children);
+            }
+        }
+        assert!(!children.is_empty(), "{} isn't in the schema", pointer);
+        schemas = children;
+    }
+    schemas
+}
+
+fn has_constraint(schema: &Value, constraint: &str) -> bool {
+    match constraint {
+        "not_blank" => schema["pattern"] == r"\S",
+        "digits" => schema["pattern"] == "^[0-9]+$",
+        "not_negative" => schema["minimum"] == 0.0,
+        "positive" => schema["minimum"] == 1.0,
+        "not_empty" => schema["minItems"] == 1,
+        _ => false,
+    }
+}
+
+// a rule spanning several fields has no constraint of a single one
+const CROSS_FIELD: &[&str] = &["in_key_template"];
+
+#[test]
+fn every_rule_has_a_constraint_in_the_schema() {
+    let root = serde_json::to_value(schema_for!(CatalogObject<u32>)).unwrap();
+    let mut constraints = BTreeSet::new();
+
+    for object in invalid_objects() {
+        let object_type = object.to_string();
+        let violations = violations(&object, "");
+        assert!(!violations.is_empty(), "{} is valid", object_type);
+        for violation in violations {
+            constraints.insert(violation.constraint.clone());
+            if CROSS_FIELD.contains(&violation.constraint.as_str()) {
+                continue;
+            }
+            let schemas = resolve(&root, &object_type, &violation.pointer);
+            assert!(
+                schemas
+                    .iter()
+                    .any(|schema| has_constraint(schema, &violation.constraint)),
+                "{} of {} has no {} constraint in {:#?}",
+                violation.pointer,
+                object_type,
+                violation.constraint,
+                schemas
+            );
+        }
+    }
+
+    // the objects break every rule there is
+    assert_eq!(
+        constraints,
+        BTreeSet::from([
+            "digits".to_string(),
+            "in_key_template".to_string(),
+            "not_blank".to_string(),
+            "not_empty".to_string(),
+            "not_negative".to_string(),
+            "positive".to_string(),
+        ])
+    );
+}
@@ -0,0 +1,361 @@
+mod fixtures;
+mod utils;
+
+use async_std::net::TcpListener;
+use async_std::sync::Mutex;
+use fixtures::catalog::{fake_item, fake_item_variation};
+use merchant::catalog::backend::{CatalogSQLService, SqlCatalogObject};
+use merchant::catalog::service::{
+    CatalogCmd, CatalogError, CatalogService, Commander, IncreaseItemVariationUnitsPayload,
+};
+use merchant::utils::sqlite::BusyRetryPolicy;
+use merchant::webhooks::backend::WebhookSQLService;
+use merchant::webhooks::models::{WebhookDeliveryStatus, WebhookEvent, WebhookSubscription};
+use merchant::webhooks::service::WebhookService;
+use merchant::webhooks::worker::{
+    sign, verify, WebhookWorker, SIGNATURE_HEADER, SIGNATURE_TOLERANCE, TIMESTAMP_HEADER,
+};
+use std::sync::Arc;
+use std::time::Duration;
+use utils::{restore_db, AnyHow};
+
+const CATALOG_ACCOUNT: &str = "account";
+const SECRET: &str = "s3cr3t";
+
+#[derive(Clone, Default)]
+struct Receiver {
+    // (timestamp, signature, body) of every request, including the failed ones
+    requests: Arc<Mutex<Vec<(i64, String, String)>>>,
+}
+
+// local stand-in for the subscriber, it fails the first request
+async fn start_receiver() -> Result<(String, Receiver), AnyHow> {
+    let receiver = Receiver::default();
+    let mut app = tide::with_state(receiver.clone());
+    app.at("/hook")
+        .post(|mut request: tide::Request<Receiver>| async move {
+            let body = request.body_string().await?;
+            let signature = request
+                .header(SIGNATURE_HEADER)
+                .map(|value| value.as_str().to_string())
+                .unwrap_or_default();
+            let timestamp = request
+                .header(TIMESTAMP_HEADER)
+                .and_then(|value| value.as_str().parse().ok())
+                .unwrap_or_default();
+            let mut requests = request.state().requests.lock().await;
+            requests.push((timestamp, signature, body));
+            Ok(tide::Response::new(if requests.len() == 1 {
+                500
+            } else {
+                200
+            }))
+        });
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let url = format!("http://{}/hook", listener.local_addr()?);
+    async_std::task::spawn(app.listen(listener));
+    Ok((url, receiver))
+}
+
+fn immediate_retries() -> BusyRetryPolicy {
+    BusyRetryPolicy {
+        max_attempts: 3,
+        base_delay: Duration::from_secs(0),
+        max_delay: Duration::from_secs(0),
+    }
+}
+
+#[async_std::test]
+async fn deliver_signed_payloads_with_retries() -> Result<(), AnyHow> {
+    let pool = restore_db().await?;
+    let webhook_service = WebhookSQLService::new(pool.clone()).with_private_targets();
+    let catalog_service = CatalogSQLService::new(pool);
+    let account = CATALOG_ACCOUNT.to_string();
+    let (url, receiver) = start_receiver().await?;
+    let subscription = webhook_service
+        .create_subscription(
+            &account,
+            &WebhookSubscription {
+                url,
+                events: vec![WebhookEvent::CatalogCreated],
+                secret: SECRET.to_string(),
+                enabled: true,
+            },
+        )
+        .await?;
+    let worker = WebhookWorker::new(webhook_service.clone(), immediate_retries());
+
+    let item_doc = catalog_service
+        .create(&account, &SqlCatalogObject::Item(fake_item()))
+        .await?;
+    catalog_service
+        .update(&account, &item_doc.id, &SqlCatalogObject::Item(fake_item()))
+        .await?;
+    assert_eq!(worker.catch_up(&catalog_service).await?, 2);
+    assert_eq!(worker.catch_up(&catalog_service).await?, 0);
+
+    // only the creation matches the subscription
+    assert_eq!(worker.deliver_pending().await?, 1);
+    let deliveries = webhook_service
+        .list_deliveries(&account, &subscription.id)
+        .await?;
+    assert_eq!(deliveries.len(), 1);
+    assert_eq!(deliveries[0].status, WebhookDeliveryStatus::Pending);
+    assert_eq!(deliveries[0].attempts, 1);
+    assert_eq!(deliveries[0].response_status, Some(500));
+
+    assert_eq!(worker.deliver_pending().await?, 1);
+    assert_eq!(worker.deliver_pending().await?, 0);
+    let deliveries = webhook_service
+        .list_deliveries(&account, &subscription.id)
+        .await?;
+    assert_eq!(deliveries[0].status, WebhookDeliveryStatus::Delivered);
+    assert_eq!(deliveries[0].attempts, 2);
+    assert_eq!(deliveries[0].response_status, Some(200));
+    assert_eq!(deliveries[0].event, WebhookEvent::CatalogCreated);
+
+    let requests = receiver.requests.lock().await;
+    assert_eq!(requests.len(), 2);
+    let (timestamp, signature, body) = &requests[1];
+    assert_eq!(
+        signature,
+        &format!("sha256={}", sign(SECRET, *timestamp, body.as_bytes()))
+    );
+    assert!(verify(
+        SECRET,
+        *timestamp,
+        body.as_bytes(),
+        signature,
+        *timestamp
+    ));
+    // replayed past the tolerance or with another timestamp
+    let late = *timestamp + SIGNATURE_TOLERANCE.as_secs() as i64 + 1;
+    assert!(!verify(
+        SECRET,
+        *timestamp,
+        body.as_bytes(),
+        signature,
+        late
+    ));
+    assert!(!verify(SECRET, late, body.as_bytes(), signature, late));
+    let payload: serde_json::Value = serde_json::from_str(body)?;
+    assert_eq!(payload["id"], item_doc.id);
+    Ok(())
+}
+
+#[async_std::test]
+async fn give_up_after_max_attempts() -> Result<(), AnyHow> {
+    let pool = restore_db().await?;
+    let webhook_service = WebhookSQLService::new(pool).with_private_targets();
+    let account = CATALOG_ACCOUNT.to_string();
+    let subscription = webhook_service
+        .create_subscription(
+            &account,
+            &WebhookSubscription {
+                // nothing listens on the discard port
+                url: "http://127.0.0.1:9/hook".to_string(),
+                events: vec![],
+                secret: SECRET.to_string(),
+                enabled: true,
+            },
+        )
+        .await?;
+    webhook_service
+        .enqueue(
+            &account,
+            WebhookEvent::InventoryChanged,
+            &serde_json::json!({}),
+        )
+        .await?;
+    let worker = WebhookWorker::new(webhook_service.clone(), immediate_retries());
+
+    for _ in 0..3 {
+        assert_eq!(worker.deliver_pending().await?, 1);
+    }
+    assert_eq!(worker.deliver_pending().await?, 0);
+    let deliveries = webhook_service
+        .list_deliveries(&account, &subscription.id)
+        .await?;
+    assert_eq!(deliveries[0].status, WebhookDeliveryStatus::Failed);
+    assert_eq!(deliveries[0].attempts, 3);
+    assert!(deliveries[0].error.is_some());
+    Ok(())
+}
+
+#[async_std::test]
+async fn low_stock_alerts_are_queued() -> Result<(), AnyHow> {
+    let pool = restore_db().await?;
+    let webhook_service = WebhookSQLService::new(pool.clone()).with_private_targets();
+    let catalog_service = CatalogSQLService::new(pool);
+    let account = CATALOG_ACCOUNT.to_string();
+    let subscription = webhook_service
+        .create_subscription(
+            &account,
+            &WebhookSubscription {
+                url: "http://127.0.0.1:9/hook".to_string(),
+                events: vec![WebhookEvent::LowStock],
+                secret: SECRET.to_string(),
+                enabled: true,
+            },
+        )
+        .await?;
+    let item_doc = catalog_service
+        .create(&account, &SqlCatalogObject::Item(fake_item()))
+        .await?;
+    let mut variation = fake_item_variation(item_doc.id);
+    variation.available_units = 10;
+    variation.reorder_threshold = Some(5);
+    let variation_doc = catalog_service
+        .create(&account, &SqlCatalogObject::Variation(variation))
+        .await?;
+
+    catalog_service
+        .cmd(
+            &account,
+            CatalogCmd::IncreaseItemVariationUnits(IncreaseItemVariationUnitsPayload {
+                id: variation_doc.id,
+                units: -6,
+            }),
+        )
+        .await?;
+    let worker = WebhookWorker::new(webhook_service.clone(), immediate_retries());
+    assert_eq!(worker.catch_up(&catalog_service).await?, 3);
+
+    // queued along the change that ran the variation low
+    let deliveries = webhook_service
+        .list_deliveries(&account, &subscription.id)
+        .await?;
+    assert_eq!(deliveries.len(), 1);
+    assert_eq!(deliveries[0].event, WebhookEvent::LowStock);
+    assert_eq!(deliveries[0].payload["variation_id"], variation_doc.id);
+    Ok(())
+}
+
+fn subscription_to(url: &str) -> WebhookSubscription {
+    WebhookSubscription {
+        url: url.to_string(),
+        events: vec![],
+        secret: SECRET.to_string(),
+        enabled: true,
+    }
+}
+
+#[async_std::test]
+async fn catch_up_on_the_changes_made_while_no_worker_ran() -> Result<(), AnyHow> {
+    let pool = restore_db().await?;
+    let webhook_service = WebhookSQLService::new(pool.clone()).with_private_targets();
+    let catalog_service = CatalogSQLService::new(pool);
+    let account = CATALOG_ACCOUNT.to_string();
+
+    // the changes made before the subscription aren't queued
+    catalog_service
+        .create(&account, &SqlCatalogObject::Item(fake_item()))
+        .await?;
+    let subscription = webhook_service
+        .create_subscription(&account, &subscription_to("http://127.0.0.1:9/hook"))
+        .await?;
+    let worker = WebhookWorker::new(webhook_service.clone(), immediate_retries());
+    assert_eq!(worker.catch_up(&catalog_service).await?, 0);
+
+    let item_doc = catalog_service
+        .create(&account, &SqlCatalogObject::Item(fake_item()))
+        .await?;
+    assert_eq!(worker.catch_up(&catalog_service).await?, 1);
+
+    // a worker started later goes on from where the previous one stopped
+    catalog_service.delete(&account, &item_doc.id).await?;
+    let restarted = WebhookWorker::new(webhook_service.clone(), immediate_retries());
+    assert_eq!(restarted.catch_up(&catalog_service).await?, 1);
+    assert_eq!(restarted.catch_up(&catalog_service).await?, 0);
+
+    let deliveries = webhook_service
+        .list_deliveries(&account, &subscription.id)
+        .await?;
+    assert_eq!(deliveries.len(), 2);
+    Ok(())
+}
+
+#[async_std::test]
+async fn subscriptions_need_an_http_url() -> Result<(), AnyHow> {
+    let pool = restore_db().await?;
+    let webhook_service = WebhookSQLService::new(pool).with_private_targets();
+    let account = CATALOG_ACCOUNT.to_string();
+    for url in ["not a url", "ftp://127.0.0.1/hook", "file:///etc/passwd"] {
+        let error = webhook_service
+            .create_subscription(&account, &subscription_to(url))
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(error, CatalogError::ValidationFailed(_)),
+            "{} was accepted",
+            url
+        );
+    }
+    assert!(webhook_service
+        .list_subscriptions(&account)
+        .await?
+        .is_empty());
+    Ok(())
+}
+
+#[async_std::test]
+async fn subscriptions_cant_target_private_addresses() -> Result<(), AnyHow> {
+    let pool = restore_db().await?;
+    let webhook_service = WebhookSQLService::new(pool);
+    let account = CATALOG_ACCOUNT.to_string();
+    for url in [
+        "http://127.0.0.1:9/hook",
+        "http://localhost/hook",
+        "http://169.254.169.254/latest/meta-data",
+        "http://10.0.0.1/hook",
+        "http://192.168.1.1/hook",
+        "http://100.64.0.1/hook",
+        "http://[::1]/hook",
+        "http://[fd00::1]/hook",
+        "http://[::ffff:127.0.0.1]/hook",
+    ] {
+        let error = webhook_service
+            .create_subscription(&account, &subscription_to(url))
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(error, CatalogError::ValidationFailed(_)),
+            "{} was accepted",
+            url
+        );
+    }
+    webhook_service
+        .create_subscription(&account, &subscription_to("https://93.184.216.34/hook"))
+        .await?;
+    Ok(())
+}
+
+#[async_std::test]
+async fn deliveries_to_private_addresses_are_refused() -> Result<(), AnyHow> {
+    let pool = restore_db().await?;
+    let account = CATALOG_ACCOUNT.to_string();
+    let (url, receiver) = start_receiver().await?;
+    // registered while private targets were allowed
+    let subscription = WebhookSQLService::new(pool.clone())
+        .with_private_targets()
+        .create_subscription(&account, &subscription_to(&url))
+        .await?;
+    let webhook_service = WebhookSQLService::new(pool);
+    webhook_service
+        .enqueue(
+            &account,
+            WebhookEvent::InventoryChanged,
+            &serde_json::json!({}),
+        )
+        .await?;
+    let worker = WebhookWorker::new(webhook_service.clone(), immediate_retries());
+
+    assert_eq!(worker.deliver_pending().await?, 1);
+    let deliveries = webhook_service
+        .list_deliveries(&account, &subscription.id)
+        .await?;
+    assert_eq!(deliveries[0].attempts, 1);
+    assert!(deliveries[0].error.is_some());
+    assert!(receiver.requests.lock().await.is_empty());
+    Ok(())
+}